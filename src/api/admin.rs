@@ -0,0 +1,216 @@
+use std::future::{ready, Ready};
+
+use actix_cors::Cors;
+use actix_web::{dev::Payload, http::header, web::Data, FromRequest, HttpRequest};
+
+use super::api_key::API_KEY_HEADER;
+use super::error::ApiError;
+use super::request_id::REQUEST_ID_HEADER;
+use super::server::ApiServerData;
+
+// extractor for routes under /admin, the request must carry the
+// configured admin token as a bearer token, admin routes are
+// disabled when no token is configured
+pub struct AdminGuard;
+
+impl FromRequest for AdminGuard {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(check_admin_token(req))
+    }
+}
+
+fn check_admin_token(req: &HttpRequest) -> Result<AdminGuard, ApiError> {
+    let token = req
+        .app_data::<Data<ApiServerData>>()
+        .and_then(|data| data.config.admin_token.clone())
+        .ok_or_else(|| ApiError::new("admin routes are disabled", 403))?;
+
+    let provided = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(provided) if token_eq(provided.as_bytes(), token.as_bytes()) => Ok(AdminGuard),
+        _ => Err(ApiError::new("missing or invalid admin token", 401)),
+    }
+}
+
+// compare without returning early so timing does not leak the token
+pub(super) fn token_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// CORS for /admin routes, only the configured origins are allowed and
+// requests from any other origin are rejected rather than passed on
+pub fn admin_cors(origins: &[String]) -> Cors {
+    origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .block_on_origin_mismatch(true)
+        .allowed_methods(vec!["GET", "POST", "DELETE"])
+        .allowed_headers(vec![
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::HeaderName::from_static(REQUEST_ID_HEADER),
+            header::HeaderName::from_static(API_KEY_HEADER),
+        ])
+        .expose_headers(vec![header::HeaderName::from_static(REQUEST_ID_HEADER)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::{
+        api::{
+            router::{register_admin_routes, register_all_routes},
+            server::{public_cors, TEST_ADMIN_TOKEN},
+        },
+        core::blockchain::Blockchain,
+        crypto::private_key::PrivateKey,
+        network::tx_pool::TxPool,
+        vm::validator::BlockValidator,
+    };
+    use actix_web::{
+        http::{Method, StatusCode},
+        test, App,
+    };
+    use std::sync::{Arc, Mutex};
+
+    const ADMIN_ORIGIN: &str = "https://admin.example";
+
+    fn app_data(admin_token: Option<&str>) -> Data<ApiServerData> {
+        let mut config = test_config();
+        config.admin_token = admin_token.map(str::to_string);
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            Arc::new(Mutex::new(
+                Blockchain::new_with_genesis_in_memory().unwrap(),
+            )),
+            config,
+        );
+        data
+    }
+
+    #[actix_web::test]
+    async fn test_admin_guard() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data(Some(TEST_ADMIN_TOKEN)))
+                .service(register_admin_routes()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list")
+            .insert_header((header::AUTHORIZATION, "Bearer wrong-token"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {TEST_ADMIN_TOKEN}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // no configured token disables admin routes
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data(None))
+                .service(register_admin_routes()),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {TEST_ADMIN_TOKEN}")))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_admin_cors_independent_of_public() {
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data(Some(TEST_ADMIN_TOKEN)))
+                .service(register_admin_routes().wrap(admin_cors(&[ADMIN_ORIGIN.to_string()])))
+                .service(register_all_routes().wrap(public_cors())),
+        )
+        .await;
+
+        // public routes accept any origin
+        let req = test::TestRequest::get()
+            .uri("/chain/height")
+            .insert_header((header::ORIGIN, "https://other.example"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "*"
+        );
+
+        // admin routes only accept the configured origin
+        let auth = (header::AUTHORIZATION, format!("Bearer {TEST_ADMIN_TOKEN}"));
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list")
+            .insert_header((header::ORIGIN, "https://other.example"))
+            .insert_header(auth.clone())
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_ne!(res.status(), StatusCode::OK);
+        assert!(res
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list")
+            .insert_header((header::ORIGIN, ADMIN_ORIGIN))
+            .insert_header(auth)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            ADMIN_ORIGIN
+        );
+
+        // the api key header is allowed when an api key is configured
+        let req = test::TestRequest::default()
+            .method(Method::OPTIONS)
+            .uri("/admin/state/rich-list")
+            .insert_header((header::ORIGIN, ADMIN_ORIGIN))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "GET"))
+            .insert_header((header::ACCESS_CONTROL_REQUEST_HEADERS, API_KEY_HEADER))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let allowed = res
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_HEADERS)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        assert!(allowed.contains(API_KEY_HEADER), "{allowed}");
+    }
+}
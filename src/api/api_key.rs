@@ -0,0 +1,157 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::Next,
+    web::Data,
+    Error, Responder,
+};
+
+use super::admin::token_eq;
+use super::error::ApiError;
+use super::server::ApiServerData;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// paths served without an api key by default
+pub fn default_api_key_exempt() -> Vec<String> {
+    vec!["/health".to_string()]
+}
+
+// when an api key is configured every request must send it in the
+// X-Api-Key header, except exempt paths and CORS preflight requests
+pub async fn api_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    // without server data the configured key is unknown, reject rather
+    // than let the request through unchecked
+    let config = match req.app_data::<Data<ApiServerData>>() {
+        Some(data) => &data.config,
+        None => {
+            let (req, _) = req.into_parts();
+            let res = ApiError::new("api server data is not configured", 500).respond_to(&req);
+            return Ok(ServiceResponse::new(req, res).map_into_right_body());
+        }
+    };
+
+    let key = match &config.api_key {
+        Some(key) => key,
+        None => return Ok(next.call(req).await?.map_into_left_body()),
+    };
+
+    let exempt = req.method() == Method::OPTIONS
+        || config.api_key_exempt.iter().any(|path| path == req.path());
+
+    let provided = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok());
+
+    let allowed = match provided {
+        Some(provided) => token_eq(provided.as_bytes(), key.as_bytes()),
+        None => false,
+    };
+
+    if exempt || allowed {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+
+    let (req, _) = req.into_parts();
+    let res = ApiError::new("missing or invalid api key", 401).respond_to(&req);
+    Ok(ServiceResponse::new(req, res).map_into_right_body())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::{
+        api::router::register_all_routes, core::blockchain::Blockchain,
+        crypto::private_key::PrivateKey, network::tx_pool::TxPool, vm::validator::BlockValidator,
+    };
+    use actix_web::{http::StatusCode, middleware::from_fn, test, App};
+    use std::sync::{Arc, Mutex};
+
+    const API_KEY: &str = "test-api-key";
+
+    #[actix_web::test]
+    async fn test_api_key_required() {
+        let mut config = test_config();
+        config.api_key = Some(API_KEY.to_string());
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            Arc::new(Mutex::new(
+                Blockchain::new_with_genesis_in_memory().unwrap(),
+            )),
+            config,
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(data)
+                .service(register_all_routes())
+                .wrap(from_fn(api_key)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/chain/height").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/chain/height")
+            .insert_header((API_KEY_HEADER, "wrong-key"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let req = test::TestRequest::get()
+            .uri("/chain/height")
+            .insert_header((API_KEY_HEADER, API_KEY))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // exempt path stays open
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_api_key_not_configured() {
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+                    Arc::new(Mutex::new(
+                        Blockchain::new_with_genesis_in_memory().unwrap(),
+                    )),
+                    test_config(),
+                ))
+                .service(register_all_routes())
+                .wrap(from_fn(api_key)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/chain/height").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_api_key_missing_server_data() {
+        let app = test::init_service(
+            App::new()
+                .route("/health", actix_web::web::get().to(|| async { "ok" }))
+                .wrap(from_fn(api_key)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
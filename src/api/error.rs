@@ -3,9 +3,18 @@ use std::{
     fmt::{self, Display},
 };
 
-use actix_web::{body::BoxBody, http::StatusCode, web::Json, HttpResponse, Responder};
+use actix_web::{
+    body::BoxBody,
+    error::{InternalError, JsonPayloadError},
+    http::StatusCode,
+    web::{Json, JsonConfig},
+    HttpRequest, HttpResponse, Responder, ResponseError,
+};
 use serde_json::json;
 
+use super::request_id::request_id_of;
+use crate::network::error::NetworkError;
+
 #[derive(Debug)]
 pub struct ApiError {
     pub message: String,
@@ -38,6 +47,16 @@ impl ApiError {
     }
 }
 
+// handler errors from the RpcController, status from the error variant
+impl From<NetworkError> for ApiError {
+    fn from(value: NetworkError) -> Self {
+        Self {
+            message: value.to_string(),
+            status: value.status(),
+        }
+    }
+}
+
 impl Default for ApiError {
     fn default() -> Self {
         Self::new("There was an error", 400)
@@ -54,13 +73,127 @@ impl Display for ApiError {
 
 impl Responder for ApiError {
     type Body = BoxBody;
-    fn respond_to(self, _req: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+    fn respond_to(self, req: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
+        let data = match request_id_of(req) {
+            Some(id) => json!({"error": self.message, "request_id": id}),
+            None => json!({"error": self.message}),
+        };
+        HttpResponse::build(self.status).json(data)
+    }
+}
+
+// used when ApiError is returned from an extractor
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse<BoxBody> {
         HttpResponse::build(self.status).json(json!({"error": self.message}))
     }
 }
 
+// Json<T> extractor config used by the server, replaces actix's bare 400
+pub fn json_config() -> JsonConfig {
+    JsonConfig::default().error_handler(json_error_handler)
+}
+
+// body which is not JSON or does not match the request struct, the parse
+// detail is returned with the status code so clients can fix the request
+fn json_error_handler(err: JsonPayloadError, req: &HttpRequest) -> actix_web::Error {
+    let (status, detail) = match &err {
+        JsonPayloadError::ContentType => (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "content type must be application/json".to_string(),
+        ),
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            (StatusCode::PAYLOAD_TOO_LARGE, err.to_string())
+        }
+        JsonPayloadError::Deserialize(e) => (StatusCode::BAD_REQUEST, e.to_string()),
+        _ => (StatusCode::BAD_REQUEST, err.to_string()),
+    };
+
+    let mut data = json!({
+        "error": format!("invalid JSON: {detail}"),
+        "code": status.as_u16(),
+    });
+    if let Some(id) = request_id_of(req) {
+        data["request_id"] = json!(id);
+    }
+
+    InternalError::from_response(err, HttpResponse::build(status).json(data)).into()
+}
+
 pub type ApiResult<T> = Result<T, ApiError>;
 
 pub trait ApiModel {
     fn save<T>(&self) -> ApiResult<T>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes::account::register_account_routes;
+    use crate::api::server::test_config;
+    use crate::api::server::ApiServerData;
+    use crate::core::blockchain::Blockchain;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::network::tx_pool::TxPool;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{http::header, test, App};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    #[actix_web::test]
+    async fn test_invalid_json_body() {
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+                    Arc::new(Mutex::new(
+                        Blockchain::new_with_genesis_in_memory().unwrap(),
+                    )),
+                    test_config(),
+                ))
+                .app_data(json_config())
+                .service(register_account_routes()),
+        )
+        .await;
+
+        // not JSON at all
+        let req = test::TestRequest::post()
+            .uri("/account/txs")
+            .insert_header(header::ContentType::json())
+            .set_payload("address=abc")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["code"], 400);
+        let error = body["error"].as_str().unwrap();
+        assert!(error.starts_with("invalid JSON: expected value"), "{error}");
+
+        // JSON with a field of the wrong type
+        let req = test::TestRequest::post()
+            .uri("/account/txs")
+            .set_json(json!({"address": 42}))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body: Value = test::read_body_json(res).await;
+        let error = body["error"].as_str().unwrap();
+        assert!(error.contains("invalid type: integer `42`"), "{error}");
+
+        // JSON sent without a JSON content type
+        let req = test::TestRequest::post()
+            .uri("/account/txs")
+            .insert_header(header::ContentType::plaintext())
+            .set_payload(r#"{"address": "abc"}"#)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let body: Value = test::read_body_json(res).await;
+        assert_eq!(body["code"], 415);
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+// keys kept at most, the oldest key is dropped first once full even
+// before it expires
+pub const DEFAULT_IDEMPOTENCY_CAPACITY: usize = 10_000;
+
+// outcome of reserving an idempotency key
+#[derive(Debug, PartialEq)]
+pub enum Reservation {
+    // key was unused, the caller handles the request and then completes
+    // or releases the key
+    Reserved,
+    // a request with the key is still being handled
+    InFlight,
+    // response returned for the first request made with the key
+    Done(Value),
+}
+
+// time bounded map of idempotency keys to the response returned for
+// the first request made with that key, expired keys are pruned when
+// a key is reserved
+pub struct IdempotencyCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<IdempotencyEntries>,
+}
+
+#[derive(Default)]
+struct IdempotencyEntries {
+    // None while the first request with the key is in flight
+    responses: HashMap<String, (Instant, Option<Value>)>,
+    // reservation order, oldest first, used to prune expired keys
+    order: VecDeque<(String, Instant)>,
+}
+
+impl IdempotencyEntries {
+    // remove key if its entry was reserved at reserved_at, a key
+    // reserved again after being dropped has a newer entry
+    fn remove_if_reserved_at(&mut self, key: &str, reserved_at: Instant) {
+        if self.responses.get(key).map(|(at, _)| *at) == Some(reserved_at) {
+            self.responses.remove(key);
+        }
+    }
+}
+
+impl IdempotencyCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: Mutex::new(IdempotencyEntries::default()),
+        }
+    }
+
+    // check and reserve key under one lock so two requests with the same
+    // key can't both be handled
+    pub fn reserve(&self, key: &str) -> Reservation {
+        let mut entries = self.entries.lock().unwrap();
+
+        while let Some((oldest, reserved_at)) = entries.order.front().cloned() {
+            if reserved_at.elapsed() < self.ttl {
+                break;
+            }
+            entries.order.pop_front();
+            entries.remove_if_reserved_at(&oldest, reserved_at);
+        }
+
+        match entries.responses.get(key) {
+            Some((_, Some(value))) => return Reservation::Done(value.clone()),
+            Some((_, None)) => return Reservation::InFlight,
+            None => {}
+        }
+
+        let now = Instant::now();
+        entries.responses.insert(key.to_string(), (now, None));
+        entries.order.push_back((key.to_string(), now));
+
+        while entries.responses.len() > self.capacity {
+            let Some((oldest, reserved_at)) = entries.order.pop_front() else {
+                break;
+            };
+            entries.remove_if_reserved_at(&oldest, reserved_at);
+        }
+        Reservation::Reserved
+    }
+
+    // store the response for a reserved key
+    pub fn complete(&self, key: &str, value: Value) {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some((_, response)) = entries.responses.get_mut(key) {
+            *response = Some(value);
+        }
+    }
+
+    // drop a reserved key without a response so the request can be
+    // retried with it
+    pub fn release(&self, key: &str) {
+        let mut entries = self.entries.lock().unwrap();
+        if matches!(entries.responses.get(key), Some((_, None))) {
+            entries.responses.remove(key);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().responses.len()
+    }
+}
+
+impl Default for IdempotencyCache {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(300), DEFAULT_IDEMPOTENCY_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::thread;
+
+    #[test]
+    fn test_idempotency_cache_ttl() {
+        let cache = IdempotencyCache::new(Duration::from_millis(50), 10);
+
+        assert_eq!(cache.reserve("key-1"), Reservation::Reserved);
+        assert_eq!(cache.reserve("key-1"), Reservation::InFlight);
+        cache.complete("key-1", json!({ "hash": "abc" }));
+        assert_eq!(
+            cache.reserve("key-1"),
+            Reservation::Done(json!({ "hash": "abc" }))
+        );
+
+        thread::sleep(Duration::from_millis(60));
+
+        // expired keys are pruned on next reserve
+        assert_eq!(cache.reserve("key-2"), Reservation::Reserved);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.reserve("key-1"), Reservation::Reserved);
+    }
+
+    #[test]
+    fn test_idempotency_cache_release_and_capacity() {
+        let cache = IdempotencyCache::new(Duration::from_secs(60), 2);
+
+        // a failed request frees its key for a retry
+        assert_eq!(cache.reserve("key-1"), Reservation::Reserved);
+        cache.release("key-1");
+        assert_eq!(cache.reserve("key-1"), Reservation::Reserved);
+
+        // completed keys are not released
+        cache.complete("key-1", json!({}));
+        cache.release("key-1");
+        assert_eq!(cache.reserve("key-1"), Reservation::Done(json!({})));
+
+        // the oldest key is dropped once full
+        assert_eq!(cache.reserve("key-2"), Reservation::Reserved);
+        assert_eq!(cache.reserve("key-3"), Reservation::Reserved);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.reserve("key-1"), Reservation::Reserved);
+    }
+}
@@ -1,4 +1,8 @@
+pub mod admin;
+pub mod api_key;
 pub mod error;
+pub mod idempotency;
+pub mod request_id;
 pub mod router;
 pub mod routes;
 pub mod server;
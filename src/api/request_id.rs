@@ -0,0 +1,146 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    middleware::Next,
+    Error, HttpMessage, HttpRequest,
+};
+use log::info;
+
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// longest request id accepted from a client, longer ids are replaced
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+// correlation id of a request, stored in the request extensions
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+// request id of the request being handled on the current task, used to
+// correlate RPC handling with the API request which sent it
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+pub fn request_id_of(req: &HttpRequest) -> Option<String> {
+    req.extensions().get::<RequestId>().map(|id| id.0.clone())
+}
+
+// assign each request an id, a valid id sent by the client is kept so
+// requests can be traced across services, the id is logged and returned
+// in the X-Request-Id response header
+pub async fn request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| is_valid_id(id))
+        .map(|id| id.to_string())
+        .unwrap_or_else(new_request_id);
+
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    req.extensions_mut().insert(RequestId(id.clone()));
+
+    let mut res = REQUEST_ID.scope(id.clone(), next.call(req)).await?;
+
+    log_request(format!(
+        "request_id={id} {method} {path} status: {}",
+        res.status().as_u16()
+    ));
+
+    // SAFETY: id is ascii, checked or generated above
+    res.headers_mut().insert(
+        HeaderName::from_static(REQUEST_ID_HEADER),
+        HeaderValue::from_str(&id).unwrap(),
+    );
+
+    Ok(res)
+}
+
+fn log_request(line: String) {
+    #[cfg(test)]
+    tests::capture(&line);
+    info!("{line}");
+}
+
+fn new_request_id() -> String {
+    format!("{:016x}", rand::random::<u64>())
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{get, middleware::from_fn, test, App, HttpResponse};
+    use std::cell::RefCell;
+
+    // request lines logged on this thread, each test runs its service on
+    // its own thread so tests only see their own lines
+    thread_local! {
+        static CAPTURED: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    }
+
+    pub(super) fn capture(line: &str) {
+        CAPTURED.with(|captured| captured.borrow_mut().push(line.to_string()));
+    }
+
+    fn captured() -> Vec<String> {
+        CAPTURED.with(|captured| captured.borrow().clone())
+    }
+
+    #[get("/ping")]
+    async fn ping() -> HttpResponse {
+        let id = current_request_id().unwrap_or_default();
+        HttpResponse::Ok().body(id)
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_header_and_log() {
+        let app = test::init_service(App::new().wrap(from_fn(request_id)).service(ping)).await;
+
+        let req = test::TestRequest::get().uri("/ping").to_request();
+        let res = test::call_service(&app, req).await;
+        let id = res
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(id.len(), 16);
+
+        // handler sees the same id through the task local
+        let body = test::read_body(res).await;
+        assert_eq!(body, id.as_bytes());
+
+        let lines = captured();
+        assert_eq!(lines.len(), 1);
+        assert!(lines
+            .iter()
+            .any(|line| line.contains(&format!("request_id={id}"))));
+
+        // id sent by the client is kept
+        let req = test::TestRequest::get()
+            .uri("/ping")
+            .insert_header((REQUEST_ID_HEADER, "client-id-1"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.headers().get(REQUEST_ID_HEADER).unwrap(), "client-id-1");
+        assert!(captured()[1].starts_with("request_id=client-id-1 GET /ping"));
+    }
+}
@@ -1,12 +1,36 @@
 use actix_web::{web::scope, Scope};
 
-use crate::api::routes::block::register_block_routes;
+use crate::api::routes::block::{mine_block, register_block_routes};
 
-use super::routes::{chain::register_chain_routes, transaction::register_transaction_routes};
+use super::routes::{
+    account::register_account_routes,
+    chain::register_chain_routes,
+    debug::register_debug_routes,
+    header::register_header_routes,
+    health::register_health_routes,
+    metrics::register_metrics_routes,
+    state::{register_state_admin_routes, register_state_routes},
+    transaction::register_transaction_routes,
+    ws::register_ws_routes,
+};
 
 pub fn register_all_routes() -> Scope {
     scope("")
         .service(register_block_routes())
         .service(register_transaction_routes())
         .service(register_chain_routes())
+        .service(register_header_routes())
+        .service(register_account_routes())
+        .service(register_state_routes())
+        .service(register_metrics_routes())
+        .service(register_debug_routes())
+        .service(register_health_routes())
+        .service(register_ws_routes())
+}
+
+// routes under /admin, handlers require AdminGuard
+pub fn register_admin_routes() -> Scope {
+    scope("/admin")
+        .service(register_state_admin_routes())
+        .service(mine_block)
 }
@@ -0,0 +1,329 @@
+use std::error::Error;
+
+use actix_web::web::{Data, Json};
+use actix_web::{post, web::scope, HttpRequest, HttpResponse, Responder, Scope};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::api::error::ApiError;
+use crate::api::server::ApiServerData;
+use crate::api::util::to_bytes;
+use crate::core::encoding::HexEncoding;
+use crate::crypto::address::Address;
+use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetAccountTxsReq {
+    // hex encoded address, optionally 0x prefixed and checksummed
+    pub address: String,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetNextNonceReq {
+    // hex encoded address, optionally 0x prefixed and checksummed
+    pub address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetAccountProofReq {
+    // hex encoded address, optionally 0x prefixed and checksummed
+    pub address: String,
+}
+
+#[post("/txs")]
+pub async fn get_account_txs(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetAccountTxsReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetAccountTxs,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Transactions(txs) => {
+            let address = Address::decode(&body.address)?;
+            let format = app.config.address_format;
+
+            let mut json = vec![];
+            for tx in txs {
+                json.push(json!({
+                    "hash": tx.hash()?.to_string(),
+                    "category": tx.category(&address),
+                    "sender": tx.sender.encode(format),
+                    "receiver": tx.receiver.encode(format),
+                    "tx": tx.to_hex()?,
+                }));
+            }
+
+            let data = json!({ "address": address.encode(format), "txs": json });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+// nonce for the next transaction from an address, pending mem_pool
+// transactions are counted so a wallet can send several in a row
+#[post("/next-nonce")]
+pub async fn get_next_nonce(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetNextNonceReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetNextNonce,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Nonce(nonce) => {
+            let data = json!({ "address": body.address, "next_nonce": nonce });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+// account balance with a proof against the tip block state root, the
+// proof is hex encoded borsh of Vec<ProofNode> for verify_account_proof
+#[post("/proof")]
+pub async fn get_account_proof(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetAccountProofReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetAccountProof,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::AccountProof(proof) => {
+            let data = json!({
+                "address": body.address,
+                "balance": proof.balance,
+                "height": proof.height,
+                "state_root": proof.state_root.to_string(),
+                "proof": hex::encode(borsh::to_vec(&proof.proof)?),
+            });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+pub fn register_account_routes() -> Scope {
+    scope("/account")
+        .service(get_account_txs)
+        .service(get_next_nonce)
+        .service(get_account_proof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::block::Block;
+    use crate::core::blockchain::Blockchain;
+    use crate::core::header::random_header;
+    use crate::core::transaction::Transaction;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::crypto::utils::random_hash;
+    use crate::network::tx_pool::TxPool;
+    use crate::state::account::Account;
+    use crate::state::root::{verify_account_proof, ProofNode};
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{test, App};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    #[actix_web::test]
+    async fn test_get_next_nonce() {
+        let key = PrivateKey::new();
+        let sender = key.address();
+        let receiver = PrivateKey::new().address();
+        let signed_tx = |nonce: u64| {
+            let mut tx =
+                Transaction::new_transfer(receiver.clone(), sender.clone(), random_hash(), &[], 1)
+                    .unwrap();
+            tx.nonce = nonce;
+            tx.sign(&key).unwrap();
+            tx
+        };
+
+        // nonces 1 and 2 are committed, 3 and 4 are pending
+        let mut chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let prev_hash = *chain.last_block().unwrap().hash();
+        let block = Block::new(
+            random_header(1, prev_hash),
+            vec![signed_tx(1), signed_tx(2)],
+        )
+        .unwrap();
+        chain.add_block(block).unwrap();
+
+        let mut mem_pool = TxPool::new();
+        mem_pool.set_last_nonce(&sender, 2);
+        mem_pool.add(signed_tx(3));
+        mem_pool.add(signed_tx(4));
+        assert_eq!(mem_pool.highest_pending_nonce(&sender), Some(4));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(mem_pool)),
+                    Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+                    Arc::new(Mutex::new(chain)),
+                    test_config(),
+                ))
+                .service(register_account_routes()),
+        )
+        .await;
+
+        let next_nonce = |address: String| {
+            test::TestRequest::post()
+                .uri("/account/next-nonce")
+                .set_json(GetNextNonceReq { address })
+                .to_request()
+        };
+
+        let res: Value =
+            test::call_and_read_body_json(&app, next_nonce(sender.to_hex().unwrap())).await;
+        assert_eq!(res["data"]["next_nonce"], 5);
+
+        // an address which never sent a transaction starts at 1
+        let res: Value =
+            test::call_and_read_body_json(&app, next_nonce(receiver.to_hex().unwrap())).await;
+        assert_eq!(res["data"]["next_nonce"], 1);
+    }
+
+    #[actix_web::test]
+    async fn test_get_account_proof() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let (funded, other) = (Address::new(&[7_u8; 20]), Address::new(&[8_u8; 20]));
+        for (address, balance) in [(&funded, 100), (&other, 5)] {
+            chain
+                .state()
+                .set_account(address, &Account { balance })
+                .unwrap();
+        }
+
+        // block reward changes state so the tip header commits to it
+        let key = PrivateKey::new();
+        let validator = BlockValidator::new(key.clone(), 10);
+        chain
+            .state()
+            .set_account(&key.address(), &Account { balance: 0 })
+            .unwrap();
+        let chain = Arc::new(Mutex::new(chain));
+        let block = validator
+            .propose_block(&chain.lock().unwrap(), vec![])
+            .unwrap();
+        let state_root = block.header().state_root;
+        chain.lock().unwrap().commit_block(block).unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    Arc::new(Mutex::new(validator)),
+                    chain,
+                    test_config(),
+                ))
+                .service(register_account_routes()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/account/proof")
+            .set_json(GetAccountProofReq {
+                address: funded.to_hex().unwrap(),
+            })
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let data = &res["data"];
+        assert_eq!(data["balance"], 100);
+        assert_eq!(data["height"], 1);
+        assert_eq!(data["state_root"], state_root.to_string());
+
+        let bytes = hex::decode(data["proof"].as_str().unwrap()).unwrap();
+        let proof: Vec<ProofNode> = borsh::from_slice(&bytes).unwrap();
+        let account = Account { balance: 100 };
+        assert!(verify_account_proof(&state_root, &funded, &account, &proof));
+
+        // the proof only holds for the proven account and balance
+        assert!(!verify_account_proof(
+            &state_root,
+            &funded,
+            &Account { balance: 101 },
+            &proof
+        ));
+        assert!(!verify_account_proof(&state_root, &other, &account, &proof));
+        assert!(!verify_account_proof(
+            &state_root,
+            &other,
+            &Account { balance: 5 },
+            &proof
+        ));
+
+        // unknown accounts have no proof
+        let req = test::TestRequest::post()
+            .uri("/account/proof")
+            .set_json(GetAccountProofReq {
+                address: Address::new(&[9_u8; 20]).to_hex().unwrap(),
+            })
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(res["error"].is_string());
+    }
+}
@@ -7,9 +7,12 @@ use serde::{Deserialize, Serialize};
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
 use serde_json::{json, Value};
 
+use crate::api::admin::AdminGuard;
+use crate::api::error::ApiError;
 use crate::api::server::ApiServerData;
 use crate::api::util::to_bytes;
-use crate::core::encoding::HexEncoding;
+use crate::core::block::Block;
+use crate::core::encoding::{ByteEncoding, HexEncoding};
 use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -34,15 +37,22 @@ pub async fn get_block(
     let rpc = RPC {
         header: RpcHeader::GetBlock,
         payload: bytes,
+        id: None,
     };
 
-    let res = handler.handle_client_rpc(&rpc)?;
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
 
     let data = match res {
         RpcResponse::Block(block) => {
             // TODO: Make block json format
             let block_json = block.to_hex()?;
-            let data = json!({ "block": block_json });
+            let producer = block
+                .producer()?
+                .map(|address| address.encode(app.config.address_format));
+            let data = json!({ "block": block_json, "producer": producer });
             json!({ "data": data })
         }
         RpcResponse::Generic(string) => json!({ "error": string }),
@@ -68,9 +78,13 @@ pub async fn get_block_header(
     let rpc = RPC {
         header: RpcHeader::GetBlockHeader,
         payload: bytes,
+        id: None,
     };
 
-    let res = handler.handle_client_rpc(&rpc)?;
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
 
     let data = match res {
         RpcResponse::Header(header) => {
@@ -88,21 +102,120 @@ pub async fn get_block_header(
 }
 
 #[get("/last")]
-pub async fn get_last_block(app: Data<ApiServerData>) -> Result<HttpResponse, Box<dyn Error>> {
+pub async fn get_last_block(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+) -> Result<HttpResponse, Box<dyn Error>> {
     let handler = app.rpc_controller.clone();
 
     let rpc = RPC {
         header: RpcHeader::GetLastBlock,
         payload: vec![],
+        id: None,
     };
 
-    let res = handler.handle_client_rpc(&rpc)?;
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
 
     let data = match res {
         RpcResponse::Block(block) => {
             // TODO: Make json format
             let json = block.to_hex()?;
-            let data = json!({ "block": json });
+            let producer = block
+                .producer()?
+                .map(|address| address.encode(app.config.address_format));
+            let data = json!({ "block": json, "producer": producer });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SubmitBlockReq {
+    // hex encoded signed block
+    pub block: String,
+}
+
+#[post("/submit")]
+pub async fn submit_block(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<SubmitBlockReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let block = match Block::from_hex(&body.block) {
+        Ok(block) => block,
+        Err(e) => {
+            let data = json!({ "error": format!("unable to decode block, {e}") });
+            return Ok(HttpResponse::Ok().json(data));
+        }
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::CommitBlock,
+        payload: block.to_bytes()?,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Ack(Some(hash)) => {
+            let data = json!({ "hash": hash.to_string() });
+            return Ok(HttpResponse::Accepted().json(json!({ "data": data })));
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+// admin only, produce a block from the mem_pool when the node
+// uses manual block production
+#[post("/mine")]
+pub async fn mine_block(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    _admin: AdminGuard,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    if !app.config.manual_mining {
+        return Ok(ApiError::new("manual block production is disabled", 403).respond_to(&req));
+    }
+
+    let handler = app.rpc_controller.clone();
+
+    let rpc = RPC {
+        header: RpcHeader::MineBlock,
+        payload: vec![],
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Block(block) => {
+            let data = json!({
+                "hash": block.hash().to_string(),
+                "height": block.height(),
+                "num_txs": block.num_txs(),
+                "producer": block
+                    .producer()?
+                    .map(|address| address.encode(app.config.address_format)),
+            });
             json!({ "data": data })
         }
         RpcResponse::Generic(string) => json!({ "error": string }),
@@ -117,4 +230,210 @@ pub fn register_block_routes() -> Scope {
         .service(get_block)
         .service(get_block_header)
         .service(get_last_block)
+        .service(submit_block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::blockchain::Blockchain;
+    use crate::core::header::random_header;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::network::tx_pool::TxPool;
+    use crate::vm::validator::BlockValidator;
+    use crate::{
+        api::{router::register_admin_routes, server::TEST_ADMIN_TOKEN},
+        state::account::Account,
+    };
+    use actix_web::{http::StatusCode, test, App};
+    use std::sync::{Arc, Mutex};
+
+    fn submit_req(block: &Block) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/block/submit")
+            .set_json(SubmitBlockReq {
+                block: block.to_hex().unwrap(),
+            })
+    }
+
+    #[actix_web::test]
+    async fn test_submit_block() {
+        let miner_key = PrivateKey::new();
+        let miner = BlockValidator::new(miner_key.clone(), 10);
+
+        let mut node_validator = BlockValidator::new(PrivateKey::new(), 10);
+        node_validator.add_validator(miner_key.address());
+
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let app_data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(node_validator)),
+            chain.clone(),
+            test_config(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data)
+                .service(register_block_routes()),
+        )
+        .await;
+
+        // block built by external miner on top of the node's tip
+        let block = miner.propose_block(&chain.lock().unwrap(), vec![]).unwrap();
+
+        let res = test::call_service(&app, submit_req(&block).to_request()).await;
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+        let res: Value = test::read_body_json(res).await;
+        assert_eq!(res["data"]["hash"], block.hash().to_string());
+        assert_eq!(chain.lock().unwrap().height(), 1);
+        assert_eq!(
+            chain.lock().unwrap().last_block().unwrap().hash(),
+            block.hash()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_submit_block_rejected() {
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let app_data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            chain.clone(),
+            test_config(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data)
+                .service(register_block_routes()),
+        )
+        .await;
+
+        // signer is not in the node's validator set
+        let outsider = BlockValidator::new(PrivateKey::new(), 10);
+        let block = outsider
+            .propose_block(&chain.lock().unwrap(), vec![])
+            .unwrap();
+
+        let res: Value = test::call_and_read_body_json(&app, submit_req(&block).to_request()).await;
+        assert!(res["error"].is_string());
+
+        // block which does not link to the tip
+        let genesis = chain.lock().unwrap().last_block().unwrap();
+        let mut block = Block::new(random_header(5, genesis.hash().clone()), vec![]).unwrap();
+        block.sign(&PrivateKey::new()).unwrap();
+
+        let res: Value = test::call_and_read_body_json(&app, submit_req(&block).to_request()).await;
+        assert!(res["error"].is_string());
+
+        assert_eq!(chain.lock().unwrap().height(), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_get_block_not_found_status() {
+        let app_data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            Arc::new(Mutex::new(
+                Blockchain::new_with_genesis_in_memory().unwrap(),
+            )),
+            test_config(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(app_data)
+                .service(register_block_routes()),
+        )
+        .await;
+
+        let get_req = |height: &str| {
+            test::TestRequest::post()
+                .uri("/block/get")
+                .set_json(GetBlockReq {
+                    height: Some(height.to_string()),
+                    hash: None,
+                })
+                .to_request()
+        };
+
+        let res = test::call_service(&app, get_req("0")).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = test::call_service(&app, get_req("5")).await;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        let res: Value = test::read_body_json(res).await;
+        assert!(res["error"].is_string());
+        assert!(res["error"]
+            .as_str()
+            .unwrap()
+            .contains("requested height 5 exceeds chain height 0"));
+
+        let res = test::call_service(&app, get_req("not-a-height")).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_manual_mine_route() {
+        let producer = PrivateKey::new();
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        chain
+            .lock()
+            .unwrap()
+            .state()
+            .set_account(&producer.address(), &Account { balance: 0 })
+            .unwrap();
+
+        let mut config = test_config();
+        config.manual_mining = true;
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(BlockValidator::new(producer, 10))),
+            chain.clone(),
+            config,
+        );
+
+        let app =
+            test::init_service(App::new().app_data(data).service(register_admin_routes())).await;
+        let mine_req = || {
+            test::TestRequest::post()
+                .uri("/admin/mine")
+                .insert_header(("Authorization", format!("Bearer {TEST_ADMIN_TOKEN}")))
+                .to_request()
+        };
+
+        // nothing is produced until the route is hit
+        assert_eq!(chain.lock().unwrap().height(), 0);
+
+        let res = test::call_service(&app, mine_req()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let res: Value = test::read_body_json(res).await;
+        assert_eq!(res["data"]["height"], 1);
+        assert_eq!(chain.lock().unwrap().height(), 1);
+
+        test::call_service(&app, mine_req()).await;
+        assert_eq!(chain.lock().unwrap().height(), 2);
+
+        // route is disabled unless block production is manual
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+                    chain.clone(),
+                    test_config(),
+                ))
+                .service(register_admin_routes()),
+        )
+        .await;
+        let res = test::call_service(&app, mine_req()).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        assert_eq!(chain.lock().unwrap().height(), 2);
+    }
 }
@@ -7,23 +7,62 @@ use log::debug;
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
 use serde_json::{json, Value};
 
+use crate::api::error::ApiError;
 use crate::api::server::ApiServerData;
 use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
 
 #[get("/height")]
-pub async fn get_chain_height(app: Data<ApiServerData>) -> Result<HttpResponse, Box<dyn Error>> {
+pub async fn get_chain_height(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+) -> Result<HttpResponse, Box<dyn Error>> {
     let handler = app.rpc_controller.clone();
 
     let rpc = RPC {
-        header: RpcHeader::GetLastBlock,
+        header: RpcHeader::GetChainHeight,
         payload: vec![],
+        id: None,
     };
 
-    let res = handler.handle_client_rpc(&rpc)?;
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Height(height) => {
+            let data = json!({ "height": height });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+// total coin supply from state, genesis allocations plus rewards less burns
+#[get("/supply")]
+pub async fn get_supply(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let rpc = RPC {
+        header: RpcHeader::GetSupply,
+        payload: vec![],
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
 
     let data = match res {
-        RpcResponse::Block(block) => {
-            let data = json!({ "height": block.header().height });
+        RpcResponse::Supply(supply) => {
+            let data = json!({ "supply": supply });
             json!({ "data": data })
         }
         RpcResponse::Generic(string) => json!({ "error": string }),
@@ -34,5 +73,95 @@ pub async fn get_chain_height(app: Data<ApiServerData>) -> Result<HttpResponse,
 }
 
 pub fn register_chain_routes() -> Scope {
-    scope("/chain").service(get_chain_height)
+    scope("/chain")
+        .service(get_chain_height)
+        .service(get_supply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::blockchain::Blockchain;
+    use crate::crypto::address::Address;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::network::tx_pool::TxPool;
+    use crate::state::account::Account;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{http::StatusCode, test, App};
+    use std::sync::{Arc, Mutex};
+
+    async fn call_route(chain: Arc<Mutex<Blockchain>>, uri: &str) -> (StatusCode, Value) {
+        let validator = Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10)));
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    validator,
+                    chain,
+                    test_config(),
+                ))
+                .service(register_chain_routes()),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri(uri).to_request();
+        let res = test::call_service(&app, req).await;
+        let status = res.status();
+        (status, test::read_body_json(res).await)
+    }
+
+    #[actix_web::test]
+    async fn test_get_chain_height_genesis_only() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+
+        let (status, res) = call_route(Arc::new(Mutex::new(chain)), "/chain/height").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res["data"]["height"], 0);
+    }
+
+    #[actix_web::test]
+    async fn test_get_chain_height_storage_error() {
+        // store without a genesis block, tip can not be read
+        let chain = Blockchain::new_in_memory().unwrap();
+
+        let (status, res) = call_route(Arc::new(Mutex::new(chain)), "/chain/height").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(res["error"].is_string());
+        assert!(res["data"].is_null());
+    }
+
+    #[actix_web::test]
+    async fn test_get_supply() {
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let producer = PrivateKey::new();
+        let validator = BlockValidator::new(producer.clone(), 10);
+
+        {
+            let mut chain = chain.lock().unwrap();
+            // genesis allocations
+            for (i, balance) in [(1_u8, 1000), (2, 250)] {
+                chain
+                    .state()
+                    .set_account(&Address::new(&[i; 20]), &Account { balance })
+                    .unwrap();
+            }
+            chain
+                .state()
+                .set_account(&producer.address(), &Account { balance: 0 })
+                .unwrap();
+
+            for _ in 0..3 {
+                let block = validator.propose_block(&chain, vec![]).unwrap();
+                chain.commit_block(block).unwrap();
+            }
+        }
+
+        // three empty blocks each pay the block reward of 50
+        let (status, res) = call_route(chain, "/chain/supply").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res["data"]["supply"], 1000 + 250 + 3 * 50);
+    }
 }
@@ -0,0 +1,414 @@
+use std::error::Error;
+
+use std::collections::BTreeMap;
+
+use actix_web::web::{Data, Json};
+use actix_web::{get, post, web::scope, HttpRequest, HttpResponse, Responder, Scope};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::api::error::ApiError;
+use crate::api::server::ApiServerData;
+use crate::core::block::Block;
+use crate::core::encoding::{ByteEncoding, HexEncoding};
+use crate::core::height::BlockHeight;
+use crate::crypto::address::{Address, AddressFormat};
+use crate::crypto::hash::Hash;
+use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
+use crate::state::snapshot::{AccountDiff, SnapshotAccount, StateSnapshot};
+
+use super::block::SubmitBlockReq;
+
+// computed root next to the one in the block header
+fn root_json(computed: &Hash, header: &Hash) -> serde_json::Value {
+    json!({
+        "computed": computed.to_string(),
+        "header": header.to_string(),
+        "matches": computed == header,
+    })
+}
+
+// dev only, dry run a block built on the tip and report each tx result
+// and the roots it produces, state is left unchanged
+#[post("/execute-block")]
+pub async fn execute_block(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<SubmitBlockReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    if !app.config.debug_routes {
+        return Ok(ApiError::new("debug routes are disabled", 403).respond_to(&req));
+    }
+
+    let handler = app.rpc_controller.clone();
+
+    let block = match Block::from_hex(&body.block) {
+        Ok(block) => block,
+        Err(e) => {
+            let data = json!({ "error": format!("unable to decode block, {e}") });
+            return Ok(HttpResponse::Ok().json(data));
+        }
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::ExecuteBlock,
+        payload: block.to_bytes()?,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Execution(execution) => {
+            let header = block.header();
+            let txs: Vec<_> = execution
+                .txs
+                .iter()
+                .map(|tx| {
+                    json!({
+                        "hash": tx.hash.map(|hash| hash.to_string()),
+                        "gas_used": tx.gas_used,
+                        "error": tx.error,
+                    })
+                })
+                .collect();
+
+            let data = json!({
+                "hash": block.hash().to_string(),
+                "height": block.height(),
+                "txs": txs,
+                "state_root": root_json(&execution.state_root, &header.state_root),
+                "tx_root": root_json(&execution.tx_root, &header.tx_root),
+                "poh": root_json(&execution.poh, &header.poh),
+            });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SnapshotAccountReq {
+    pub address: String,
+    pub balance: u64,
+    pub nonce: u64,
+}
+
+// snapshot as returned by /debug/state-snapshot
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StateSnapshotReq {
+    pub height: BlockHeight,
+    pub accounts: Vec<SnapshotAccountReq>,
+}
+
+impl StateSnapshotReq {
+    fn from_snapshot(snapshot: &StateSnapshot, format: AddressFormat) -> Self {
+        let accounts = snapshot
+            .accounts
+            .iter()
+            .map(|(address, account)| SnapshotAccountReq {
+                address: address.encode(format),
+                balance: account.balance,
+                nonce: account.nonce,
+            })
+            .collect();
+
+        Self {
+            height: snapshot.height,
+            accounts,
+        }
+    }
+
+    fn to_snapshot(&self) -> Result<StateSnapshot, String> {
+        let mut accounts = BTreeMap::new();
+        for account in &self.accounts {
+            let address = Address::decode(&account.address)
+                .map_err(|e| format!("invalid address: {}, {e}", account.address))?;
+            let entry = SnapshotAccount {
+                balance: account.balance,
+                nonce: account.nonce,
+            };
+            if accounts.insert(address, entry).is_some() {
+                return Err(format!("duplicate address: {}", account.address));
+            }
+        }
+
+        Ok(StateSnapshot {
+            height: self.height,
+            accounts,
+        })
+    }
+}
+
+fn account_json(account: &SnapshotAccount) -> serde_json::Value {
+    json!({ "balance": account.balance, "nonce": account.nonce })
+}
+
+fn diff_json(diff: &AccountDiff, format: AddressFormat) -> serde_json::Value {
+    let (kind, before, after) = match diff {
+        AccountDiff::Added { after, .. } => ("added", None, Some(after)),
+        AccountDiff::Removed { before, .. } => ("removed", Some(before), None),
+        AccountDiff::Changed { before, after, .. } => ("changed", Some(before), Some(after)),
+    };
+
+    json!({
+        "address": diff.address().encode(format),
+        "kind": kind,
+        "before": before.map(account_json),
+        "after": after.map(account_json),
+    })
+}
+
+// dev only, every account with its balance and nonce, the response data
+// can be uploaded to /debug/state-diff on another node
+#[get("/state-snapshot")]
+pub async fn get_state_snapshot(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    if !app.config.debug_routes {
+        return Ok(ApiError::new("debug routes are disabled", 403).respond_to(&req));
+    }
+
+    let handler = app.rpc_controller.clone();
+
+    let rpc = RPC {
+        header: RpcHeader::GetStateSnapshot,
+        payload: vec![],
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Snapshot(snapshot) => {
+            let snapshot = StateSnapshotReq::from_snapshot(&snapshot, app.config.address_format);
+            json!({ "data": snapshot })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+// dev only, accounts which differ between the state of this node and the
+// uploaded snapshot, before is this node and after is the snapshot
+#[post("/state-diff")]
+pub async fn diff_state(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<StateSnapshotReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    if !app.config.debug_routes {
+        return Ok(ApiError::new("debug routes are disabled", 403).respond_to(&req));
+    }
+
+    let handler = app.rpc_controller.clone();
+
+    let snapshot = match body.to_snapshot() {
+        Ok(snapshot) => snapshot,
+        Err(e) => {
+            let data = json!({ "error": format!("unable to decode snapshot, {e}") });
+            return Ok(HttpResponse::Ok().json(data));
+        }
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::DiffState,
+        payload: snapshot.to_bytes()?,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::StateDiff(diffs) => {
+            let format = app.config.address_format;
+            let diffs: Vec<_> = diffs.iter().map(|diff| diff_json(diff, format)).collect();
+            json!({ "data": { "height": snapshot.height, "diffs": diffs } })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+pub fn register_debug_routes() -> Scope {
+    scope("/debug")
+        .service(execute_block)
+        .service(get_state_snapshot)
+        .service(diff_state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::blockchain::Blockchain;
+    use crate::crypto::address::random_sender_receiver;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::network::tx_pool::TxPool;
+    use crate::state::account::Account;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{http::StatusCode, test, App};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    #[actix_web::test]
+    async fn test_execute_block_roots_match_header() {
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let validator = BlockValidator::new(PrivateKey::new(), 10);
+        let block = validator
+            .propose_block(&chain.lock().unwrap(), vec![])
+            .unwrap();
+        let state_root = chain.lock().unwrap().state().gen_state_root().unwrap();
+
+        let mut config = test_config();
+        config.debug_routes = true;
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(validator)),
+            chain.clone(),
+            config,
+        );
+        let app =
+            test::init_service(App::new().app_data(data).service(register_debug_routes())).await;
+
+        let req = test::TestRequest::post()
+            .uri("/debug/execute-block")
+            .set_json(SubmitBlockReq {
+                block: block.to_hex().unwrap(),
+            })
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+
+        let data = &res["data"];
+        // block reward and gas reward txs
+        let txs = data["txs"].as_array().unwrap();
+        assert_eq!(txs.len(), 2);
+        assert!(txs.iter().all(|tx| tx["error"].is_null()));
+        for root in ["state_root", "tx_root", "poh"] {
+            assert_eq!(data[root]["matches"], true, "{root}");
+        }
+
+        // the block was only dry run
+        let chain = chain.lock().unwrap();
+        assert_eq!(chain.height(), 0);
+        assert_eq!(chain.state().gen_state_root().unwrap(), state_root);
+    }
+
+    #[actix_web::test]
+    async fn test_state_diff_against_snapshot() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let (a, b) = random_sender_receiver();
+        chain
+            .state()
+            .set_account(&a, &Account { balance: 100 })
+            .unwrap();
+        chain
+            .state()
+            .set_account(&b, &Account { balance: 50 })
+            .unwrap();
+
+        let mut config = test_config();
+        config.debug_routes = true;
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            Arc::new(Mutex::new(chain)),
+            config,
+        );
+        let app =
+            test::init_service(App::new().app_data(data).service(register_debug_routes())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/debug/state-snapshot")
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let mut snapshot: StateSnapshotReq = serde_json::from_value(res["data"].clone()).unwrap();
+
+        // unchanged snapshot has no diffs
+        let req = test::TestRequest::post()
+            .uri("/debug/state-diff")
+            .set_json(&snapshot)
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"]["diffs"], json!([]));
+
+        let account = snapshot
+            .accounts
+            .iter_mut()
+            .find(|account| Address::decode(&account.address).unwrap() == b)
+            .unwrap();
+        account.balance = 75;
+
+        let req = test::TestRequest::post()
+            .uri("/debug/state-diff")
+            .set_json(&snapshot)
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let diffs = res["data"]["diffs"].as_array().unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0]["kind"], "changed");
+        assert_eq!(
+            Address::decode(diffs[0]["address"].as_str().unwrap()).unwrap(),
+            b
+        );
+        assert_eq!(diffs[0]["before"], json!({ "balance": 50, "nonce": 0 }));
+        assert_eq!(diffs[0]["after"], json!({ "balance": 75, "nonce": 0 }));
+    }
+
+    #[actix_web::test]
+    async fn test_execute_block_disabled() {
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let validator = BlockValidator::new(PrivateKey::new(), 10);
+        let block = validator
+            .propose_block(&chain.lock().unwrap(), vec![])
+            .unwrap();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    Arc::new(Mutex::new(validator)),
+                    chain,
+                    test_config(),
+                ))
+                .service(register_debug_routes()),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/debug/execute-block")
+            .set_json(SubmitBlockReq {
+                block: block.to_hex().unwrap(),
+            })
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+        let req = test::TestRequest::get()
+            .uri("/debug/state-snapshot")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,64 @@
+use std::error::Error;
+
+use actix_web::web::{Data, Json};
+use actix_web::{post, web::scope, HttpRequest, HttpResponse, Responder, Scope};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::api::error::ApiError;
+use crate::api::server::ApiServerData;
+use crate::api::util::to_bytes;
+use crate::core::{encoding::HexEncoding, height::BlockHeight};
+use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
+
+// inclusive range of block heights
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetHeadersReq {
+    pub from: BlockHeight,
+    pub to: BlockHeight,
+}
+
+#[post("/range")]
+pub async fn get_headers_range(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetHeadersReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetHeaders,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Headers(headers) => {
+            let mut json = vec![];
+            for header in headers {
+                json.push(header.to_hex()?);
+            }
+
+            let data = json!({ "headers": json });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+pub fn register_header_routes() -> Scope {
+    scope("/headers").service(get_headers_range)
+}
@@ -0,0 +1,12 @@
+use actix_web::{get, web::scope, HttpResponse, Scope};
+use serde_json::json;
+
+// liveness check for load balancers, does not touch the chain
+#[get("")]
+pub async fn get_health() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "data": { "status": "ok" } }))
+}
+
+pub fn register_health_routes() -> Scope {
+    scope("/health").service(get_health)
+}
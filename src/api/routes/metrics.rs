@@ -0,0 +1,114 @@
+use std::error::Error;
+
+use actix_web::web::Data;
+use actix_web::{get, web::scope, HttpRequest, HttpResponse, Responder, Scope};
+use serde_json::json;
+
+use crate::api::error::ApiError;
+use crate::api::server::ApiServerData;
+use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
+
+// block production counters of this node's validator and counts
+// of mempool events since the node started
+#[get("")]
+pub async fn get_metrics(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let rpc = RPC {
+        header: RpcHeader::GetMetrics,
+        payload: vec![],
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Metrics(blocks, mempool) => {
+            let data = json!({
+                "blocks_proposed": blocks.proposed,
+                "blocks_accepted": blocks.accepted,
+                "blocks_rejected": blocks.rejected,
+                "mempool_added": mempool.added,
+                "mempool_replaced": mempool.replaced,
+                "mempool_evicted": mempool.evicted,
+                "mempool_confirmed": mempool.confirmed,
+            });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+pub fn register_metrics_routes() -> Scope {
+    scope("/metrics").service(get_metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::blockchain::Blockchain;
+    use crate::core::transaction::random_signed_tx;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::network::tx_pool::TxPool;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{test, App};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    #[actix_web::test]
+    async fn test_get_metrics() {
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let validator = BlockValidator::new(PrivateKey::new(), 10);
+        {
+            let mut chain = chain.lock().unwrap();
+            let block = validator.propose_block(&chain, vec![]).unwrap();
+            let stale = validator.propose_block(&chain, vec![]).unwrap();
+            validator.add_proposed_block(&mut chain, block).unwrap();
+            assert!(validator.add_proposed_block(&mut chain, stale).is_err());
+        }
+
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    mem_pool.clone(),
+                    Arc::new(Mutex::new(validator)),
+                    chain,
+                    test_config(),
+                ))
+                .service(register_metrics_routes()),
+        )
+        .await;
+        mem_pool.lock().unwrap().add(random_signed_tx());
+
+        // mempool events are counted on a background thread
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let res = loop {
+            let req = test::TestRequest::get().uri("/metrics").to_request();
+            let res: Value = test::call_and_read_body_json(&app, req).await;
+            if res["data"]["mempool_added"] == 1 || Instant::now() > deadline {
+                break res;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        };
+
+        assert_eq!(res["data"]["blocks_proposed"], 2);
+        assert_eq!(res["data"]["blocks_accepted"], 1);
+        assert_eq!(res["data"]["blocks_rejected"], 1);
+        assert_eq!(res["data"]["mempool_added"], 1);
+        assert_eq!(res["data"]["mempool_confirmed"], 0);
+    }
+}
@@ -1,3 +1,10 @@
+pub mod account;
 pub mod block;
 pub mod chain;
+pub mod debug;
+pub mod header;
+pub mod health;
+pub mod metrics;
+pub mod state;
 pub mod transaction;
+pub mod ws;
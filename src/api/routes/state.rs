@@ -0,0 +1,387 @@
+use std::error::Error;
+
+use actix_web::web::{Data, Query};
+use actix_web::{get, web::scope, HttpRequest, HttpResponse, Responder, Scope};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::api::admin::AdminGuard;
+use crate::api::error::ApiError;
+use crate::api::server::ApiServerData;
+use crate::api::util::to_bytes;
+use crate::core::height::BlockHeight;
+use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetStateRootReq {
+    pub height: BlockHeight,
+}
+
+#[get("/root")]
+pub async fn get_state_root(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    query: Query<GetStateRootReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&query.into_inner()) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetStateRoot,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::StateRoot(height, state_root) => {
+            let data = json!({ "height": height, "state_root": state_root.to_string() });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetRichListReq {
+    // only accounts with a balance above min are listed
+    pub min: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+// admin only, listing every account is too expensive to expose publicly
+#[get("/rich-list")]
+pub async fn get_rich_list(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    query: Query<GetRichListReq>,
+    _admin: AdminGuard,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&query.into_inner()) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetRichList,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Accounts(accounts) => {
+            let mut data = vec![];
+            for (address, balance) in accounts {
+                data.push(json!({ "address": address.encode(app.config.address_format), "balance": balance }));
+            }
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetStateDumpReq {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+}
+
+// admin only, accounts in address order, paged with offset and limit,
+// limit is capped by max_dump_accounts in the api config
+#[get("/dump")]
+pub async fn get_state_dump(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    query: Query<GetStateDumpReq>,
+    _admin: AdminGuard,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let max = app.config.max_dump_accounts;
+    let mut dump_req = query.into_inner();
+    dump_req.limit = Some(dump_req.limit.unwrap_or(max).min(max));
+
+    let bytes = match to_bytes(&dump_req) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetStateDump,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Accounts(accounts) => {
+            let mut data = vec![];
+            for (address, balance) in accounts {
+                data.push(json!({ "address": address.encode(app.config.address_format), "balance": balance }));
+            }
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+pub fn register_state_routes() -> Scope {
+    scope("/state").service(get_state_root)
+}
+
+// mounted under /admin
+pub fn register_state_admin_routes() -> Scope {
+    scope("/state")
+        .service(get_rich_list)
+        .service(get_state_dump)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::router::register_admin_routes;
+    use crate::api::server::{test_config, TEST_ADMIN_TOKEN};
+    use crate::core::block::Block;
+    use crate::core::blockchain::Blockchain;
+    use crate::core::encoding::HexEncoding;
+    use crate::core::header::Header;
+    use crate::crypto::address::{Address, AddressFormat};
+    use crate::crypto::private_key::PrivateKey;
+    use crate::crypto::utils::random_hash;
+    use crate::network::tx_pool::TxPool;
+    use crate::state::account::Account;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{test, App};
+    use serde_json::Value;
+    use std::sync::{Arc, Mutex};
+
+    fn admin_auth() -> (&'static str, String) {
+        ("authorization", format!("Bearer {TEST_ADMIN_TOKEN}"))
+    }
+
+    #[actix_web::test]
+    async fn test_get_state_root_by_height() {
+        let mut chain = Blockchain::new_with_genesis_in_memory().unwrap();
+
+        for height in 1..=4 {
+            let prev_hash = chain.last_block().unwrap().hash().clone();
+            let (poh, tx_root, state_root) = (random_hash(), random_hash(), random_hash());
+            let blockhash =
+                Header::gen_blockhash(BlockHeight(height), prev_hash, poh, tx_root, state_root)
+                    .unwrap();
+            let header = Header::new(
+                BlockHeight(height),
+                blockhash,
+                poh,
+                tx_root,
+                state_root,
+                prev_hash,
+            );
+            chain
+                .add_block(Block::new(header, vec![]).unwrap())
+                .unwrap();
+        }
+
+        let headers: Vec<Header> = BlockHeight::GENESIS
+            .range_to(BlockHeight(4))
+            .map(|height| chain.get_header_by_height(height).unwrap())
+            .collect();
+
+        let validator = Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10)));
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    validator,
+                    Arc::new(Mutex::new(chain)),
+                    test_config(),
+                ))
+                .service(register_state_routes()),
+        )
+        .await;
+
+        for header in headers {
+            let req = test::TestRequest::get()
+                .uri(&format!("/state/root?height={}", header.height()))
+                .to_request();
+            let res: Value = test::call_and_read_body_json(&app, req).await;
+
+            assert_eq!(res["data"]["height"], header.height().value());
+            assert_eq!(res["data"]["state_root"], header.state_root.to_string());
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/state/root?height=5")
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert!(res["error"].is_string());
+    }
+
+    #[actix_web::test]
+    async fn test_get_rich_list() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let addresses: Vec<Address> = (1..=4_u8).map(|i| Address::new(&[i; 20])).collect();
+        for (i, address) in addresses.iter().enumerate() {
+            chain
+                .state()
+                .set_account(
+                    address,
+                    &Account {
+                        balance: i as u64 * 100,
+                    },
+                )
+                .unwrap();
+        }
+
+        let validator = Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10)));
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    validator,
+                    Arc::new(Mutex::new(chain)),
+                    test_config(),
+                ))
+                .service(register_admin_routes()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list?min=100&limit=1")
+            .insert_header(admin_auth())
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+
+        let data = res["data"].as_array().unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0]["address"], addresses[3].to_hex().unwrap());
+        assert_eq!(data[0]["balance"], 300);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list?min=100")
+            .insert_header(admin_auth())
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"].as_array().unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_get_state_dump_paged() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let accounts: Vec<(Address, Account)> = (1..=50_u8)
+            .map(|i| (Address::new(&[i; 20]), Account { balance: i as u64 }))
+            .collect();
+        for (address, account) in &accounts {
+            chain.state().set_account(address, account).unwrap();
+        }
+
+        // pages from storage match the full account list
+        let all = chain.state().accounts_page(0, usize::MAX).unwrap();
+        assert_eq!(all.len(), 50);
+        let page = chain.state().accounts_page(20, 20).unwrap();
+        assert_eq!(page, all[20..40].to_vec());
+        assert_eq!(chain.state().accounts_page(40, 20).unwrap().len(), 10);
+        assert!(chain.state().accounts_page(50, 20).unwrap().is_empty());
+
+        let validator = Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10)));
+        let mut config = test_config();
+        config.max_dump_accounts = 20;
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            validator,
+            Arc::new(Mutex::new(chain)),
+            config,
+        );
+        let app =
+            test::init_service(App::new().app_data(data).service(register_admin_routes())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/dump?offset=5&limit=10")
+            .insert_header(admin_auth())
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        let page = res["data"].as_array().unwrap();
+        assert_eq!(page.len(), 10);
+        for (item, (address, account)) in page.iter().zip(&all[5..15]) {
+            assert_eq!(item["address"], address.to_hex().unwrap());
+            assert_eq!(item["balance"], account.balance);
+        }
+
+        // limit is capped by max_dump_accounts
+        let req = test::TestRequest::get()
+            .uri("/admin/state/dump?limit=100")
+            .insert_header(admin_auth())
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"].as_array().unwrap().len(), 20);
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/dump?offset=40")
+            .insert_header(admin_auth())
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(res["data"].as_array().unwrap().len(), 10);
+    }
+
+    #[actix_web::test]
+    async fn test_rich_list_address_format() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let address = Address::new(&[0xab; 20]);
+        chain
+            .state()
+            .set_account(&address, &Account { balance: 500 })
+            .unwrap();
+
+        let validator = Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10)));
+        let format = AddressFormat::parse("checksum").unwrap();
+        let mut config = test_config();
+        config.address_format = format;
+        let data = ApiServerData::new_test(
+            Arc::new(Mutex::new(TxPool::new())),
+            validator,
+            Arc::new(Mutex::new(chain)),
+            config,
+        );
+        let app =
+            test::init_service(App::new().app_data(data).service(register_admin_routes())).await;
+
+        let req = test::TestRequest::get()
+            .uri("/admin/state/rich-list?min=100")
+            .insert_header(admin_auth())
+            .to_request();
+        let res: Value = test::call_and_read_body_json(&app, req).await;
+
+        let encoded = "0xABababaBaBAbAbaBAbaBABabAbAbabAbabaBaBAb";
+        let data = res["data"].as_array().unwrap();
+        assert!(data.iter().any(|item| item["address"] == encoded));
+        assert_eq!(Address::decode(encoded).unwrap(), address);
+    }
+}
@@ -8,19 +8,25 @@ use serde::{Deserialize, Serialize};
 use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Responder};
 use serde_json::{json, Value};
 
+use crate::api::error::ApiError;
+use crate::api::idempotency::{Reservation, IDEMPOTENCY_KEY_HEADER};
 use crate::api::server::ApiServerData;
 use crate::api::util::to_bytes;
 use crate::core::encoding::ByteEncoding;
 use crate::core::transaction::Transaction;
 use crate::crypto::address::random_sender_receiver;
-use crate::crypto::utils::random_hash;
 use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct GetTxReq {
     pub hash: String,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct GetTxsReq {
+    pub hashes: Vec<String>,
+}
+
 #[post("/get")]
 pub async fn get_tx(
     req: HttpRequest,
@@ -37,14 +43,120 @@ pub async fn get_tx(
     let rpc = RPC {
         header: RpcHeader::GetTx,
         payload: bytes,
+        id: None,
     };
 
-    let res = handler.handle_client_rpc(&rpc)?;
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
 
     let data = match res {
-        RpcResponse::Transaction(tx) => {
-            let tx_json = tx.data_str();
-            let data = json!({ "tx": tx_json });
+        RpcResponse::TxStatus(status) => {
+            let format = app.config.address_format;
+            let data = json!({
+                "tx": status.tx.data_str(),
+                "hash": body.hash,
+                "sender": status.tx.sender.encode(format),
+                "receiver": status.tx.receiver.encode(format),
+                "block_height": status.block_height,
+                "confirmations": status.confirmations,
+            });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+#[post("/receipt")]
+pub async fn get_tx_receipt(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetTxReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetTx,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::TxStatus(status) => {
+            let state = match (status.block_height, status.replaced_by) {
+                (Some(_), _) => "confirmed",
+                (None, Some(_)) => "replaced",
+                (None, None) => "pending",
+            };
+            let data = json!({
+                "hash": body.hash,
+                "status": state,
+                "block_height": status.block_height,
+                "confirmations": status.confirmations,
+                "replaced_by": status.replaced_by.map(|hash| hash.to_string()),
+            });
+            json!({ "data": data })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
+// summary of the block which included the tx
+#[post("/block")]
+pub async fn get_tx_block(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetTxReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetTxBlock,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::Block(block) => {
+            let producer = block
+                .producer()?
+                .map(|address| address.encode(app.config.address_format));
+            let data = json!({
+                "tx_hash": body.hash,
+                "height": block.height(),
+                "hash": block.hash().to_string(),
+                "prev_hash": block.prev_hash().to_string(),
+                "timestamp": block.header().timestamp,
+                "tx_count": block.txs().len(),
+                "producer": producer,
+            });
             json!({ "data": data })
         }
         RpcResponse::Generic(string) => json!({ "error": string }),
@@ -54,6 +166,55 @@ pub async fn get_tx(
     Ok(HttpResponse::Ok().json(data))
 }
 
+// several txs in one request, hashes not found are listed in missing
+#[post("/batch-get")]
+pub async fn get_txs(
+    req: HttpRequest,
+    app: Data<ApiServerData>,
+    body: Json<GetTxsReq>,
+) -> Result<HttpResponse, Box<dyn Error>> {
+    let handler = app.rpc_controller.clone();
+
+    let bytes = match to_bytes(&body) {
+        Ok(b) => b,
+        Err(e) => return Ok(e.respond_to(&req)),
+    };
+
+    let rpc = RPC {
+        header: RpcHeader::GetTxs,
+        payload: bytes,
+        id: None,
+    };
+
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok(ApiError::from(e).respond_to(&req)),
+    };
+
+    let data = match res {
+        RpcResponse::TxBatch(batch) => {
+            let format = app.config.address_format;
+            let mut txs = vec![];
+            for status in batch.found {
+                let hash = status.tx.hash()?.to_string();
+                txs.push(json!({
+                    "tx": status.tx.data_str(),
+                    "hash": hash,
+                    "sender": status.tx.sender.encode(format),
+                    "receiver": status.tx.receiver.encode(format),
+                    "block_height": status.block_height,
+                    "confirmations": status.confirmations,
+                }));
+            }
+            json!({ "data": { "txs": txs, "missing": batch.missing } })
+        }
+        RpcResponse::Generic(string) => json!({ "error": string }),
+        _ => json!({"error":"incorrect response from RPC handler"}),
+    };
+
+    Ok(HttpResponse::Ok().json(data))
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct NewTxReq {
     pub value: String,
@@ -65,41 +226,391 @@ pub async fn new_tx(
     app: Data<ApiServerData>,
     body: Json<NewTxReq>,
 ) -> Result<HttpResponse, Box<dyn Error>> {
+    // a retried request with a known idempotency key gets the
+    // original response instead of submitting the tx again
+    let idempotency_key = req
+        .headers()
+        .get(IDEMPOTENCY_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+
+    let Some(key) = idempotency_key else {
+        return submit_new_tx(&req, &app, &body).map(|(res, _)| res);
+    };
+
+    match app.idempotency.reserve(&key) {
+        Reservation::Reserved => {}
+        Reservation::InFlight => {
+            let msg = format!("a request with idempotency key: {key} is in progress");
+            return Ok(ApiError::new(&msg, 409).respond_to(&req));
+        }
+        Reservation::Done(data) => {
+            debug!("returning cached response for idempotency key: {key}");
+            return Ok(HttpResponse::Ok().json(data));
+        }
+    }
+
+    // only a submitted tx keeps the key, a failed request can be retried
+    let res = submit_new_tx(&req, &app, &body);
+    match &res {
+        Ok((_, Some(data))) => app.idempotency.complete(&key, data.clone()),
+        _ => app.idempotency.release(&key),
+    }
+    res.map(|(res, _)| res)
+}
+
+// submit a tx built from body, the response data is also returned
+// when the tx was accepted
+fn submit_new_tx(
+    req: &HttpRequest,
+    app: &ApiServerData,
+    body: &NewTxReq,
+) -> Result<(HttpResponse, Option<Value>), Box<dyn Error>> {
     let handler = app.rpc_controller.clone();
 
-    let bytes = match to_bytes(&body) {
+    let bytes = match to_bytes(body) {
         Ok(b) => b,
-        Err(e) => return Ok(e.respond_to(&req)),
+        Err(e) => return Ok((e.respond_to(req), None)),
     };
 
     // TODO: Tx should be completed and signed by client
     let (sender, receiver) = random_sender_receiver();
-    let hash = random_hash();
-    let new_tx = Transaction::new_transfer(sender, receiver, hash, &bytes, 9)?;
 
-    debug!("NEW TX REQ :{new_tx:?}",);
+    // anchor the tx to the current tip, unknown anchors are rejected
+    let last_block = RPC {
+        header: RpcHeader::GetLastBlock,
+        payload: vec![],
+        id: None,
+    };
+    let hash = match handler.handle_client_rpc(&last_block) {
+        Ok(RpcResponse::Block(block)) => block.hash().clone(),
+        Ok(_) => {
+            let data = json!({"error":"incorrect response from RPC handler"});
+            return Ok((HttpResponse::Ok().json(data), None));
+        }
+        Err(e) => return Ok((ApiError::from(e).respond_to(req), None)),
+    };
+    let tx = Transaction::new_transfer(sender, receiver, hash, &bytes, 9)?;
+
+    debug!("NEW TX REQ :{tx:?}",);
 
     let rpc = RPC {
         header: RpcHeader::NewTx,
-        payload: new_tx.to_bytes()?,
+        payload: tx.to_bytes()?,
+        id: None,
     };
 
-    let res = handler.handle_client_rpc(&rpc)?;
+    let res = match handler.handle_client_rpc(&rpc) {
+        Ok(res) => res,
+        Err(e) => return Ok((ApiError::from(e).respond_to(req), None)),
+    };
 
-    let data = match res {
+    match res {
         RpcResponse::Transaction(tx) => {
             let tx_json = tx.data_str();
+            let hash = tx.hash()?.to_string();
 
-            let data = json!({ "tx": tx_json });
-            json!({ "data": data })
+            let format = app.config.address_format;
+            let data = json!({
+                "tx": tx_json,
+                "hash": hash,
+                "sender": tx.sender.encode(format),
+                "receiver": tx.receiver.encode(format),
+            });
+            let data = json!({ "data": data });
+            Ok((HttpResponse::Ok().json(&data), Some(data)))
         }
-        RpcResponse::Generic(string) => json!({ "error": string }),
-        _ => json!({"error":"incorrect response from RPC handler"}),
-    };
-
-    Ok(HttpResponse::Ok().json(data))
+        RpcResponse::Generic(string) => {
+            Ok((HttpResponse::Ok().json(json!({ "error": string })), None))
+        }
+        _ => {
+            let data = json!({"error":"incorrect response from RPC handler"});
+            Ok((HttpResponse::Ok().json(data), None))
+        }
+    }
 }
 
 pub fn register_transaction_routes() -> Scope {
-    scope("/tx").service(get_tx).service(new_tx)
+    scope("/tx")
+        .service(get_tx)
+        .service(get_tx_receipt)
+        .service(get_tx_block)
+        .service(get_txs)
+        .service(new_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::block::Block;
+    use crate::core::blockchain::Blockchain;
+    use crate::core::header::random_header;
+    use crate::core::transaction::random_signed_tx;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::crypto::{hash::Hash, utils::random_hash};
+    use crate::network::tx_pool::{MempoolEvent, TxPool};
+    use crate::rpc::handlers::transaction::MAX_BATCH_TXS;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{test, App};
+    use std::sync::{Arc, Mutex};
+
+    fn setup_app_data(mem_pool: Arc<Mutex<TxPool>>, chain: Blockchain) -> Data<ApiServerData> {
+        let validator = Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10)));
+
+        ApiServerData::new_test(
+            mem_pool,
+            validator,
+            Arc::new(Mutex::new(chain)),
+            test_config(),
+        )
+    }
+
+    // chain with one block per entry of txs from height 1, returns the
+    // added blocks
+    fn chain_with_blocks(txs: Vec<Vec<Transaction>>) -> (Blockchain, Vec<Block>) {
+        let mut chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let mut blocks = vec![];
+        for (height, txs) in (1..).zip(txs) {
+            let prev_hash = chain.last_block().unwrap().hash().clone();
+            let block = Block::new(random_header(height, prev_hash), txs).unwrap();
+            chain.add_block(block.clone()).unwrap();
+            blocks.push(block);
+        }
+        (chain, blocks)
+    }
+
+    // pool holding one pending tx, with the hash of the tx
+    fn pool_with_pending_tx() -> (Arc<Mutex<TxPool>>, String) {
+        let pending = random_signed_tx();
+        let pending_hash = pending.hash().unwrap().to_string();
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+        mem_pool.lock().unwrap().add(pending);
+        (mem_pool, pending_hash)
+    }
+
+    #[actix_web::test]
+    async fn test_new_tx_idempotency_key() {
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+        let data = setup_app_data(
+            mem_pool.clone(),
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        );
+        let app = test::init_service(
+            App::new()
+                .app_data(data.clone())
+                .service(register_transaction_routes()),
+        )
+        .await;
+
+        let keyed_req = |key: &str| {
+            test::TestRequest::post()
+                .uri("/tx/new")
+                .insert_header((IDEMPOTENCY_KEY_HEADER, key.to_string()))
+                .set_json(NewTxReq {
+                    value: "hello".to_string(),
+                })
+                .to_request()
+        };
+
+        // a key still held by another request is not submitted twice
+        data.idempotency.reserve("in-flight-key");
+        let res = test::call_service(&app, keyed_req("in-flight-key")).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::CONFLICT);
+        assert_eq!(mem_pool.lock().unwrap().len(), 0);
+
+        let first: Value = test::call_and_read_body_json(&app, keyed_req("retry-key-1")).await;
+        let second: Value = test::call_and_read_body_json(&app, keyed_req("retry-key-1")).await;
+
+        assert_eq!(mem_pool.lock().unwrap().len(), 1);
+        assert!(first["data"]["hash"].is_string());
+        assert_eq!(first, second);
+
+        // a request without a key is always submitted
+        let req = test::TestRequest::post()
+            .uri("/tx/new")
+            .set_json(NewTxReq {
+                value: "hello".to_string(),
+            })
+            .to_request();
+        let _: Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(mem_pool.lock().unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_get_tx_confirmations() {
+        // tx included at height 2, tip at height 4
+        let tx = random_signed_tx();
+        let hash = tx.hash().unwrap().to_string();
+        let (chain, _) = chain_with_blocks(vec![vec![], vec![tx], vec![], vec![]]);
+        let (mem_pool, pending_hash) = pool_with_pending_tx();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(setup_app_data(mem_pool, chain))
+                .service(register_transaction_routes()),
+        )
+        .await;
+
+        let req = |uri: &str, hash: &str| {
+            test::TestRequest::post()
+                .uri(uri)
+                .set_json(GetTxReq {
+                    hash: hash.to_string(),
+                })
+                .to_request()
+        };
+
+        let res: Value = test::call_and_read_body_json(&app, req("/tx/get", &hash)).await;
+        assert_eq!(res["data"]["block_height"], 2);
+        assert_eq!(res["data"]["confirmations"], 3);
+
+        let res: Value = test::call_and_read_body_json(&app, req("/tx/receipt", &hash)).await;
+        assert_eq!(res["data"]["status"], "confirmed");
+        assert_eq!(res["data"]["confirmations"], 3);
+
+        let res: Value =
+            test::call_and_read_body_json(&app, req("/tx/receipt", &pending_hash)).await;
+        assert_eq!(res["data"]["status"], "pending");
+        assert!(res["data"]["block_height"].is_null());
+        assert_eq!(res["data"]["confirmations"], 0);
+    }
+
+    #[actix_web::test]
+    async fn test_get_tx_block() {
+        // tx included at height 2 of 3
+        let tx = random_signed_tx();
+        let hash = tx.hash().unwrap().to_string();
+        let (chain, blocks) = chain_with_blocks(vec![vec![], vec![random_signed_tx(), tx], vec![]]);
+        let including = blocks[1].clone();
+        assert_eq!(
+            chain.get_block_for_tx(&hash).unwrap().hash(),
+            including.hash()
+        );
+        let (mem_pool, pending_hash) = pool_with_pending_tx();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(setup_app_data(mem_pool, chain))
+                .service(register_transaction_routes()),
+        )
+        .await;
+
+        let req = |hash: &str| {
+            test::TestRequest::post()
+                .uri("/tx/block")
+                .set_json(GetTxReq {
+                    hash: hash.to_string(),
+                })
+                .to_request()
+        };
+
+        let res: Value = test::call_and_read_body_json(&app, req(&hash)).await;
+        assert_eq!(res["data"]["height"], 2);
+        assert_eq!(res["data"]["hash"], including.hash().to_string());
+        assert_eq!(res["data"]["tx_count"], 2);
+
+        // pending txs are not in a block yet
+        let res = test::call_service(&app, req(&pending_hash)).await;
+        assert_eq!(res.status(), actix_web::http::StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_get_txs_batch() {
+        let included = random_signed_tx();
+        let included_hash = included.hash().unwrap().to_string();
+        let (chain, _) = chain_with_blocks(vec![vec![included]]);
+        let (mem_pool, pending_hash) = pool_with_pending_tx();
+
+        let missing_hash = random_signed_tx().hash().unwrap().to_string();
+
+        let app = test::init_service(
+            App::new()
+                .app_data(setup_app_data(mem_pool, chain))
+                .service(register_transaction_routes()),
+        )
+        .await;
+
+        let req = |hashes: Vec<String>| {
+            test::TestRequest::post()
+                .uri("/tx/batch-get")
+                .set_json(GetTxsReq { hashes })
+                .to_request()
+        };
+
+        let hashes = vec![
+            included_hash.clone(),
+            missing_hash.clone(),
+            pending_hash.clone(),
+        ];
+        let res: Value = test::call_and_read_body_json(&app, req(hashes)).await;
+        let txs = res["data"]["txs"].as_array().unwrap();
+        assert_eq!(txs.len(), 2);
+        assert_eq!(txs[0]["hash"], included_hash);
+        assert_eq!(txs[0]["block_height"], 1);
+        assert_eq!(txs[1]["hash"], pending_hash);
+        assert_eq!(txs[1]["confirmations"], 0);
+        assert_eq!(res["data"]["missing"], json!([missing_hash]));
+
+        // requests over the cap are rejected
+        let hashes = vec![missing_hash; MAX_BATCH_TXS + 1];
+        let res = test::call_service(&app, req(hashes)).await;
+        assert!(res.status().is_client_error());
+    }
+
+    #[actix_web::test]
+    async fn test_replaced_tx_status() {
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+        let key = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        let signed = |gas_price: u64| {
+            let mut tx =
+                Transaction::new_transfer(receiver.clone(), key.address(), random_hash(), &[], 5)
+                    .unwrap();
+            tx.nonce = 1;
+            tx.gas_price = gas_price;
+            tx.sign(&key).unwrap();
+            tx
+        };
+
+        // same sender and nonce with a higher fee replaces the first tx
+        let (first, bump) = (signed(1), signed(2));
+        let (first_hash, bump_hash) = (first.hash().unwrap(), bump.hash().unwrap());
+        let mut events = mem_pool.lock().unwrap().subscribe();
+        mem_pool.lock().unwrap().add(first);
+        mem_pool.lock().unwrap().add(bump);
+        assert!(matches!(events.try_recv(), Ok(MempoolEvent::Added(_))));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(MempoolEvent::Replaced { old_hash, new_hash })
+                if old_hash == first_hash && new_hash == bump_hash
+        ));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(setup_app_data(
+                    mem_pool,
+                    Blockchain::new_with_genesis_in_memory().unwrap(),
+                ))
+                .service(register_transaction_routes()),
+        )
+        .await;
+        let req = |hash: &Hash| {
+            test::TestRequest::post()
+                .uri("/tx/receipt")
+                .set_json(GetTxReq {
+                    hash: hash.to_string(),
+                })
+                .to_request()
+        };
+
+        let res: Value = test::call_and_read_body_json(&app, req(&first_hash)).await;
+        assert_eq!(res["data"]["status"], "replaced");
+        assert_eq!(res["data"]["replaced_by"], bump_hash.to_string());
+
+        let res: Value = test::call_and_read_body_json(&app, req(&bump_hash)).await;
+        assert_eq!(res["data"]["status"], "pending");
+        assert!(res["data"]["replaced_by"].is_null());
+    }
 }
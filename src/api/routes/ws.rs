@@ -0,0 +1,141 @@
+use actix_web::web::{Data, Payload};
+use actix_web::{get, web::scope, HttpRequest, HttpResponse, Scope};
+use actix_ws::Message;
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::api::server::ApiServerData;
+use crate::crypto::hash::Hash;
+use crate::network::tx_pool::MempoolEvent;
+
+// stream mempool events to the client as JSON text messages, a client
+// too slow to keep up misses the oldest events
+#[get("/mempool")]
+pub async fn mempool_events(
+    req: HttpRequest,
+    body: Payload,
+    app: Data<ApiServerData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (res, mut session, mut messages) = actix_ws::handle(&req, body)?;
+    let mut events = app.rpc_controller.subscribe_mempool();
+
+    actix_web::rt::spawn(async move {
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if session.text(mempool_event_json(&event).to_string()).await.is_err() {
+                            return;
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("mempool websocket missed {missed} events")
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                msg = messages.recv() => match msg {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(reason))) => {
+                        debug!("mempool websocket closed by client");
+                        let _ = session.close(reason).await;
+                        return;
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => return,
+                },
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(res)
+}
+
+// transactions are sent by hash only
+pub fn mempool_event_json(event: &MempoolEvent) -> Value {
+    let hex = |hash: &Option<Hash>| hash.as_ref().map(|hash| hash.to_string());
+    match event {
+        MempoolEvent::Added(tx) => json!({ "event": "added", "hash": hex(&tx.hash) }),
+        MempoolEvent::Replaced { old_hash, new_hash } => json!({
+            "event": "replaced",
+            "old_hash": old_hash.to_string(),
+            "new_hash": new_hash.to_string(),
+        }),
+        MempoolEvent::Evicted(tx) => json!({ "event": "evicted", "hash": hex(&tx.hash) }),
+        MempoolEvent::Confirmed(tx) => json!({ "event": "confirmed", "hash": hex(&tx.hash) }),
+    }
+}
+
+pub fn register_ws_routes() -> Scope {
+    scope("/ws").service(mempool_events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::server::test_config;
+    use crate::core::blockchain::Blockchain;
+    use crate::core::transaction::random_signed_tx;
+    use crate::crypto::private_key::PrivateKey;
+    use crate::crypto::utils::random_hash;
+    use crate::network::tx_pool::TxPool;
+    use crate::vm::validator::BlockValidator;
+    use actix_web::{http::StatusCode, test, App};
+    use std::sync::{Arc, Mutex};
+
+    #[actix_web::test]
+    async fn test_mempool_websocket_upgrade() {
+        let app = test::init_service(
+            App::new()
+                .app_data(ApiServerData::new_test(
+                    Arc::new(Mutex::new(TxPool::new())),
+                    Arc::new(Mutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+                    Arc::new(Mutex::new(
+                        Blockchain::new_with_genesis_in_memory().unwrap(),
+                    )),
+                    test_config(),
+                ))
+                .service(register_ws_routes()),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/ws/mempool")
+            .insert_header(("upgrade", "websocket"))
+            .insert_header(("connection", "upgrade"))
+            .insert_header(("sec-websocket-version", "13"))
+            .insert_header(("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::SWITCHING_PROTOCOLS);
+
+        // a plain request is not upgraded
+        let req = test::TestRequest::get().uri("/ws/mempool").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_mempool_event_json() {
+        let tx = random_signed_tx();
+        let hash = tx.hash.unwrap();
+
+        let value = mempool_event_json(&MempoolEvent::Added(tx));
+        assert_eq!(value["event"], "added");
+        assert_eq!(value["hash"], hash.to_string());
+
+        let new_hash = random_hash();
+        let value = mempool_event_json(&MempoolEvent::Replaced {
+            old_hash: hash,
+            new_hash,
+        });
+        assert_eq!(value["event"], "replaced");
+        assert_eq!(value["old_hash"], hash.to_string());
+        assert_eq!(value["new_hash"], new_hash.to_string());
+    }
+}
@@ -1,6 +1,6 @@
 use actix_cors::Cors;
-use actix_web::dev::Server;
-use actix_web::middleware::Logger;
+use actix_web::dev::{Server, ServerHandle};
+use actix_web::middleware::{from_fn, Logger};
 use actix_web::{http::header, web, App, HttpServer, Scope};
 use std::net::SocketAddr;
 use std::sync::{Arc, Mutex as StdMutex};
@@ -16,9 +16,16 @@ use log::{error, info, warn};
 use tokio::net::TcpListener;
 use tokio::sync::Mutex;
 
+use crate::crypto::address::AddressFormat;
 use crate::rpc::controller::RpcController;
+use crate::rpc::handlers::state::MAX_STATE_DUMP;
 
-use super::router::register_all_routes;
+use super::admin::admin_cors;
+use super::api_key::{api_key, default_api_key_exempt, API_KEY_HEADER};
+use super::error::json_config;
+use super::idempotency::{IdempotencyCache, DEFAULT_IDEMPOTENCY_CAPACITY};
+use super::request_id::{request_id, REQUEST_ID_HEADER};
+use super::router::{register_admin_routes, register_all_routes};
 
 pub type GenericError = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, GenericError>;
@@ -30,14 +37,58 @@ pub type BoxBody = http_body_util::combinators::BoxBody<Bytes, hyper::Error>;
 // pub static POST_DATA: &str = r#"{"original": "data"}"#;
 // pub static URL: &str = "http://127.0.0.1:1337/json_api";
 
+// access log line with the request id of each request
+pub const DEFAULT_LOG_FORMAT: &str = r#"%a "%r" %s %b %T request_id=%{x-request-id}o"#;
+
+// open connections accepted by each worker, same as the actix default
+pub const DEFAULT_MAX_CONNECTIONS: usize = 25_000;
+
+#[derive(Clone)]
 pub struct ApiServerConfig {
     api_addr: String,
+    // how long an idempotency key is remembered for tx submission
+    pub idempotency_ttl: time::Duration,
+    // bearer token required by /admin routes, None disables them
+    pub admin_token: Option<String>,
+    // origins allowed to call /admin routes from a browser
+    pub admin_origins: Vec<String>,
+    // actix Logger format used for access logs
+    pub log_format: String,
+    // most accounts returned by one /state/dump request
+    pub max_dump_accounts: usize,
+    // enables /admin/mine, set when the node uses manual block production
+    pub manual_mining: bool,
+    // how addresses are written in JSON responses
+    pub address_format: AddressFormat,
+    // enables /debug routes, for development nodes only
+    pub debug_routes: bool,
+    // worker threads serving requests, defaults to one per cpu
+    pub workers: usize,
+    // connections each worker accepts before it stops accepting more
+    pub max_connections: usize,
+    // key required in the X-Api-Key header of every request, None
+    // leaves the api open
+    pub api_key: Option<String>,
+    // paths served without the api key
+    pub api_key_exempt: Vec<String>,
 }
 
 impl Default for ApiServerConfig {
     fn default() -> Self {
         ApiServerConfig {
             api_addr: "0.0.0.0:6000".to_string(),
+            idempotency_ttl: time::Duration::from_secs(300),
+            admin_token: None,
+            admin_origins: vec![],
+            log_format: DEFAULT_LOG_FORMAT.to_string(),
+            max_dump_accounts: MAX_STATE_DUMP,
+            manual_mining: false,
+            address_format: AddressFormat::default(),
+            debug_routes: false,
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            api_key: None,
+            api_key_exempt: default_api_key_exempt(),
         }
     }
 }
@@ -45,46 +96,243 @@ impl Default for ApiServerConfig {
 pub struct ApiServerData {
     pub config: ApiServerConfig,
     pub rpc_controller: Arc<RpcController>,
+    pub idempotency: IdempotencyCache,
+}
+
+#[cfg(test)]
+pub const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+
+// default config with TEST_ADMIN_TOKEN set, used in route tests
+#[cfg(test)]
+pub fn test_config() -> ApiServerConfig {
+    ApiServerConfig {
+        admin_token: Some(TEST_ADMIN_TOKEN.to_string()),
+        ..ApiServerConfig::default()
+    }
+}
+
+#[cfg(test)]
+impl ApiServerData {
+    // app data backed by an RpcController with a TcpController bound
+    // to a random local port, used in route tests
+    pub fn new_test(
+        mem_pool: Arc<StdMutex<crate::network::tx_pool::TxPool>>,
+        validator: Arc<StdMutex<crate::vm::validator::BlockValidator>>,
+        chain: Arc<StdMutex<crate::core::blockchain::Blockchain>>,
+        config: ApiServerConfig,
+    ) -> Data<Self> {
+        use crate::network::{
+            tcp::{TcpConfig, TcpController},
+            types::ArcMut,
+        };
+        use std::sync::mpsc::channel;
+
+        let (tx, _rx) = channel();
+        let tcp = TcpController::new(
+            "127.0.0.1:0".parse().unwrap(),
+            TcpConfig::default(),
+            ArcMut::new(tx).inner,
+        )
+        .unwrap();
+        let rpc_controller =
+            RpcController::new(mem_pool, validator, chain, Arc::new(StdMutex::new(tcp)));
+
+        Data::new(Self {
+            config,
+            rpc_controller: Arc::new(rpc_controller),
+            idempotency: IdempotencyCache::default(),
+        })
+    }
+}
+
+// permissive CORS for the public API
+pub fn public_cors() -> Cors {
+    Cors::default()
+        .allow_any_origin()
+        .send_wildcard()
+        .allowed_methods(vec!["GET", "POST", "OPTIONS", "DELETE"])
+        .allowed_headers(vec![
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::HeaderName::from_static("idempotency-key"),
+            header::HeaderName::from_static(REQUEST_ID_HEADER),
+            header::HeaderName::from_static(API_KEY_HEADER),
+        ])
+        .expose_headers(vec![header::HeaderName::from_static(REQUEST_ID_HEADER)])
 }
 
 pub struct ApiServer {
     // router: Arc<Mutex<HttpRouter>>,
     config: ApiServerConfig,
     data: Data<ApiServerData>,
+    // set once the server is started
+    handle: StdMutex<Option<ServerHandle>>,
+    addrs: StdMutex<Vec<SocketAddr>>,
 }
 
 impl ApiServer {
     pub fn new(config: ApiServerConfig, rpc_controller: Arc<RpcController>) -> Self {
         let data = Data::new(ApiServerData {
-            config: ApiServerConfig::default(),
+            config: config.clone(),
             rpc_controller,
+            idempotency: IdempotencyCache::new(
+                config.idempotency_ttl,
+                DEFAULT_IDEMPOTENCY_CAPACITY,
+            ),
         });
 
-        Self { data, config }
+        Self {
+            data,
+            config,
+            handle: StdMutex::new(None),
+            addrs: StdMutex::new(vec![]),
+        }
+    }
+
+    pub fn workers(&self) -> usize {
+        self.config.workers
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.config.max_connections
+    }
+
+    // handle of the running server, None until start is called
+    pub fn handle(&self) -> Option<ServerHandle> {
+        self.handle.lock().unwrap().clone()
+    }
+
+    // addresses the server is bound to, empty until start is called
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.addrs.lock().unwrap().clone()
+    }
+
+    // stop accepting connections, a graceful stop waits for in flight
+    // requests to finish
+    pub async fn stop(&self, graceful: bool) {
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            handle.stop(graceful).await;
+        }
     }
 
     pub async fn start(&self) -> Result<Server> {
         let api_addr = self.config.api_addr.to_string();
         let data = self.data.clone();
+        let log_format = self.config.log_format.clone();
+        let admin_origins = self.config.admin_origins.clone();
+        if self.config.workers == 0 || self.config.max_connections == 0 {
+            return Err("api server workers and max_connections must be above 0".into());
+        }
         let server = HttpServer::new(move || {
-            let cors = Cors::default()
-                .allow_any_origin()
-                .send_wildcard()
-                .allowed_methods(vec!["GET", "POST", "OPTIONS", "DELETE"])
-                .allowed_headers(vec![
-                    header::CONTENT_TYPE,
-                    header::AUTHORIZATION,
-                    header::ACCEPT,
-                ]);
-
+            // admin scope is registered first, the public scope
+            // matches every path
             App::new()
                 .app_data(data.clone())
-                .service(register_all_routes())
-                .wrap(Logger::default())
-                .wrap(cors)
+                .app_data(json_config())
+                .service(register_admin_routes().wrap(admin_cors(&admin_origins)))
+                .service(register_all_routes().wrap(public_cors()))
+                .wrap(from_fn(api_key))
+                .wrap(Logger::new(&log_format))
+                .wrap(from_fn(request_id))
         })
-        .bind(api_addr.to_string())?
-        .run();
+        .workers(self.config.workers)
+        .max_connections(self.config.max_connections)
+        .bind(api_addr.to_string())?;
+
+        *self.addrs.lock().unwrap() = server.addrs();
+        let server = server.run();
+        *self.handle.lock().unwrap() = Some(server.handle());
         Ok(server)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::blockchain::Blockchain, crypto::private_key::PrivateKey, network::tx_pool::TxPool,
+        vm::validator::BlockValidator,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[actix_web::test]
+    async fn test_stop_server() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let data = ApiServerData::new_test(
+            Arc::new(StdMutex::new(TxPool::new())),
+            Arc::new(StdMutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            Arc::new(StdMutex::new(chain)),
+            test_config(),
+        );
+        let config = ApiServerConfig {
+            api_addr: "127.0.0.1:0".to_string(),
+            ..ApiServerConfig::default()
+        };
+        let server = ApiServer::new(config, data.rpc_controller.clone());
+        actix_web::rt::spawn(server.start().await.unwrap());
+        let addr = server.addrs()[0];
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /chain/height HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut res = String::new();
+        stream.read_to_string(&mut res).await.unwrap();
+        assert!(res.starts_with("HTTP/1.1 200"));
+
+        server.stop(true).await;
+        assert!(server.handle().is_none());
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_server_connection_limits() {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        let data = ApiServerData::new_test(
+            Arc::new(StdMutex::new(TxPool::new())),
+            Arc::new(StdMutex::new(BlockValidator::new(PrivateKey::new(), 10))),
+            Arc::new(StdMutex::new(chain)),
+            test_config(),
+        );
+        let config = ApiServerConfig {
+            api_addr: "127.0.0.1:0".to_string(),
+            workers: 2,
+            max_connections: 16,
+            ..ApiServerConfig::default()
+        };
+        let server = ApiServer::new(config.clone(), data.rpc_controller.clone());
+        assert_eq!(server.workers(), 2);
+        assert_eq!(server.max_connections(), 16);
+
+        actix_web::rt::spawn(server.start().await.unwrap());
+        let addr = server.addrs()[0];
+        for _ in 0..3 {
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(
+                    b"GET /chain/height HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            let mut res = String::new();
+            stream.read_to_string(&mut res).await.unwrap();
+            assert!(res.starts_with("HTTP/1.1 200"));
+        }
+        server.stop(true).await;
+
+        // zero workers would panic inside actix
+        let server = ApiServer::new(
+            ApiServerConfig {
+                workers: 0,
+                ..config
+            },
+            data.rpc_controller.clone(),
+        );
+        assert!(server.start().await.is_err());
+    }
+}
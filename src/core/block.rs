@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs::File;
 use std::io::Read;
@@ -7,8 +7,10 @@ use std::path::PathBuf;
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use log::info;
+use rayon::{prelude::*, ThreadPool};
 use serde::{Deserialize, Serialize};
 
+use crate::crypto::address::Address;
 use crate::crypto::public_key::PublicKeyBytes;
 use crate::crypto::signature::SignatureBytes;
 use crate::crypto::{
@@ -19,7 +21,9 @@ use super::storage::DbBlockStorage;
 use super::{
     encoding::{ByteEncoding, HexEncoding},
     error::CoreError,
+    genesis::GenesisConfig,
     header::Header,
+    height::BlockHeight,
     manager::BlockManager,
     storage::{BlockStorage, MemoryBlockStorage},
     transaction::Transaction,
@@ -34,6 +38,12 @@ pub struct Block {
 }
 
 impl Block {
+    // deterministic genesis block, empty tx root, empty state root
+    // and zeroed prev hash, same config gives the same block
+    pub fn genesis(config: &GenesisConfig) -> Result<Self, CoreError> {
+        Self::new(config.header()?, vec![])
+    }
+
     pub fn new(header: Header, txs: Vec<Transaction>) -> Result<Self, CoreError> {
         let mut b = Self {
             header,
@@ -42,6 +52,9 @@ impl Block {
             signature: None,
         };
 
+        Transaction::check_signed(&txs)?;
+        Self::check_duplicate_txs(&txs)?;
+
         for tx in &txs {
             b.add_transaction(tx.clone())?;
         }
@@ -53,6 +66,25 @@ impl Block {
         &self.header
     }
 
+    pub fn signer(&self) -> Option<&PublicKeyBytes> {
+        self.signer.as_ref()
+    }
+
+    // address of the validator that signed the block, None when unsigned
+    pub fn producer(&self) -> Result<Option<Address>, CoreError> {
+        match &self.signer {
+            Some(key_bytes) => {
+                let key = PublicKey::from_bytes(&key_bytes.to_bytes()?)?;
+                Ok(Some(key.address()?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn signature(&self) -> Option<&SignatureBytes> {
+        self.signature.as_ref()
+    }
+
     pub fn num_txs(&self) -> usize {
         self.transactions.len()
     }
@@ -83,6 +115,24 @@ impl Block {
             tx.verify()?
         }
 
+        self.verify_signature()
+    }
+
+    // same checks as verify, transaction signatures are verified on pool
+    pub fn verify_parallel(&self, pool: &ThreadPool) -> Result<(), CoreError> {
+        if self.signature.is_none() {
+            return Err(CoreError::Block(
+                "no signature exists for block".to_string(),
+            ));
+        }
+
+        pool.install(|| self.transactions.par_iter().try_for_each(|tx| tx.verify()))?;
+
+        self.verify_signature()
+    }
+
+    // verify block signature only, transaction signatures are not checked
+    pub fn verify_signature(&self) -> Result<(), CoreError> {
         match (&self.signer, &self.signature) {
             (Some(key_bytes), Some(sig_bytes)) => {
                 let key = PublicKey::from_bytes(&key_bytes.to_bytes()?)?;
@@ -107,14 +157,30 @@ impl Block {
         &self.header.blockhash
     }
 
-    pub fn height(&self) -> usize {
-        self.header.height as usize
+    pub fn height(&self) -> BlockHeight {
+        self.header.height
     }
 
     pub fn txs(&self) -> &[Transaction] {
         &self.transactions
     }
 
+    // returns error if any two transactions share the same content hash
+    pub fn check_duplicate_txs(txs: &[Transaction]) -> Result<(), CoreError> {
+        let mut seen = HashSet::new();
+
+        for tx in txs {
+            let hash = tx.content_hash()?;
+            if !seen.insert(hash) {
+                return Err(CoreError::Block(format!(
+                    "duplicate transaction with hash: {hash} in block"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
     // ---
     // Private Methods
     // ---
@@ -142,12 +208,21 @@ impl Block {
     // get data to be hashed for the block
     fn hashable_data(&self) -> Result<Vec<u8>, CoreError> {
         let mut data = vec![];
-        data.extend_from_slice(&&self.header.hashable_data());
+        data.extend_from_slice(&self.header.hashable_data());
         data.extend_from_slice(&self.txs_bytes()?);
         Ok(data)
     }
 }
 
+impl Block {
+    // header is the first field of the borsh encoded block, decode
+    // only the header and leave signature and transactions unread
+    pub fn header_from_bytes(data: &[u8]) -> Result<Header, CoreError> {
+        let mut reader = data;
+        Header::deserialize(&mut reader).map_err(|e| CoreError::Parsing(e.to_string()))
+    }
+}
+
 impl ByteEncoding<Block> for Block {
     fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
         match borsh::to_vec(self) {
@@ -198,6 +273,25 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_header_from_bytes_skips_txs() {
+        let txs = vec![random_signed_tx(), random_signed_tx()];
+        let mut block = Block::new(random_header(1, random_hash()), txs).unwrap();
+        block.sign(&PrivateKey::new()).unwrap();
+        let bytes = block.to_bytes().unwrap();
+
+        let header = Block::header_from_bytes(&bytes).unwrap();
+        assert_eq!(&header, block.header());
+
+        // transactions are never read, only the header prefix is needed
+        let header_len = borsh::to_vec(block.header()).unwrap().len();
+        assert!(Block::from_bytes(&bytes[..header_len]).is_err());
+        assert_eq!(
+            Block::header_from_bytes(&bytes[..header_len]).unwrap(),
+            header
+        );
+    }
+
     #[test]
     fn test_sign_block() {
         let header = random_header(0, random_hash());
@@ -277,6 +371,46 @@ mod tests {
         assert_eq!(res, msg);
     }
 
+    #[test]
+    fn test_signature_covers_header() {
+        let private_key = PrivateKey::new();
+        let mut block = Block::new(random_header(1, random_hash()), vec![]).unwrap();
+        block.sign(&private_key).unwrap();
+        assert!(block.verify().is_ok());
+
+        let mut tampered = block.clone();
+        tampered.header.state_root = random_hash();
+        assert_eq!(
+            tampered.verify().unwrap_err().to_string(),
+            "invalid signature"
+        );
+
+        let mut tampered = block.clone();
+        tampered.header.height += 1;
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = block;
+        tampered.header.timestamp += 1;
+        assert!(tampered.verify().is_err());
+    }
+
+    #[test]
+    fn test_block_rejects_duplicate_txs() {
+        let header = random_header(1, random_hash());
+        let tx = random_signed_tx();
+
+        let res = Block::new(header.clone(), vec![tx.clone(), tx.clone()]);
+        assert!(res.is_err());
+
+        let block = Block {
+            header,
+            signer: None,
+            signature: None,
+            transactions: vec![tx.clone(), random_signed_tx(), tx],
+        };
+        assert!(Block::check_duplicate_txs(block.txs()).is_err());
+    }
+
     #[test]
     fn test_block_byte_parsing() {
         let header = random_header(1, random_hash());
@@ -1,23 +1,53 @@
-use log::info;
+use std::collections::BTreeMap;
+use std::io::{ErrorKind, Read, Write};
+use std::time::Instant;
+
+use log::{error, warn};
 
 use crate::{
-    crypto::hash::Hash,
-    state::manager::StateManager,
-    vm::{runtime::ValidatorRuntime, types::RuntimeExecData},
+    crypto::{address::Address, hash::Hash},
+    state::{manager::StateManager, storage::PendingBlock},
+    vm::runtime::ValidatorRuntime,
 };
 
 use super::{
-    block::{random_block, Block},
+    block::Block,
+    encoding::ByteEncoding,
     error::CoreError,
-    header::{random_header, Header},
+    genesis::GenesisConfig,
+    header::Header,
+    height::BlockHeight,
     manager::BlockManager,
-    storage::BlockStorage,
+    transaction::Transaction,
+    view::{ChainView, SharedChainView},
 };
 
+// max headers returned by a single get_header_range call
+pub const MAX_HEADER_RANGE: usize = 500;
+
+// largest block record read from an import stream
+pub const MAX_BLOCK_RECORD_SIZE: usize = 16 * 1024 * 1024;
+
+// prefix of the leader seed preimage so it never equals another hash of
+// the same block data
+const LEADER_SEED_TAG: &[u8] = b"orion-leader-seed";
+
+// height and hash of the last block in the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainTip {
+    pub height: BlockHeight,
+    pub hash: Hash,
+}
+
 pub struct Blockchain {
     block_manager: BlockManager,
     state_manager: StateManager,
     runtime: ValidatorRuntime,
+    batch_state_commits: bool,
+    view: SharedChainView,
+    // local time the first block at the tip height was added, None
+    // when the tip was loaded from storage
+    tip_received: Option<Instant>,
 }
 
 impl Blockchain {
@@ -30,26 +60,74 @@ impl Blockchain {
             block_manager: BlockManager::new(block_storage_path),
             state_manager: StateManager::new(state_storage_path),
             runtime: ValidatorRuntime::new(),
+            batch_state_commits: true,
+            view: ChainView::new_shared(),
+            tip_received: None,
         };
 
-        bc.add_block_without_validation(genesis_block)?;
+        // only a fresh store gets the genesis block, an existing store
+        // must have been created from the same genesis
+        match bc.block_manager.genesis() {
+            None => bc.add_block_without_validation(genesis_block)?,
+            Some(stored) => {
+                if stored.hash() != genesis_block.hash() {
+                    return Err(CoreError::Block(format!(
+                        "genesis block in storage: {} does not match provided genesis: {}",
+                        stored.hash(),
+                        genesis_block.hash()
+                    )));
+                }
+
+                if let Some(last) = bc.block_manager.last() {
+                    bc.update_view(last);
+                }
+            }
+        }
+
+        bc.recover_pending_block()?;
 
         Ok(bc)
     }
 
     pub fn commit_block(&mut self, block: Block) -> Result<(), CoreError> {
-        let state = self.state();
-        for tx in block.txs() {
-            let exec_data = RuntimeExecData::new(tx, state);
-            self.runtime.execute(exec_data)?;
+        if self.batch_state_commits {
+            return self.apply_block(block);
         }
 
+        self.runtime
+            .execute_block(block.txs(), self.state(), self.batch_state_commits)?;
+
         // Add the block to the chain
         self.add_block(block)?;
 
         Ok(())
     }
 
+    // execute block and add it to the chain, the block state is written
+    // with a pending block marker before the block is stored so a node
+    // stopped between the two writes is made consistent on restart
+    pub fn apply_block(&mut self, block: Block) -> Result<(), CoreError> {
+        if self.has_block(block.height()) {
+            return Err(CoreError::Block(
+                "blockchain already contains block".to_string(),
+            ));
+        }
+
+        let pending = PendingBlock {
+            height: block.height(),
+            hash: *block.hash(),
+        };
+        self.runtime.stage_block(block.txs(), self.state())?;
+        self.state_manager.commit_block_batch(&pending)?;
+
+        if let Err(e) = self.add_block(block) {
+            self.state_manager.rollback_block()?;
+            return Err(e);
+        }
+
+        self.state_manager.finish_block()
+    }
+
     pub fn add_block(&mut self, block: Block) -> Result<(), CoreError> {
         if self.has_block(block.height()) {
             return Err(CoreError::Block(
@@ -57,64 +135,409 @@ impl Blockchain {
             ));
         }
 
-        self.block_manager.add(block)
+        self.add_block_without_validation(block)
     }
 
-    pub fn height(&self) -> usize {
+    // replace the tip with a block at the same height on the same parent,
+    // chosen by fork choice, state is not touched same as add_block,
+    // returns the replaced block
+    pub fn replace_tip(&mut self, block: Block) -> Result<Block, CoreError> {
+        let tip = self.last_block().ok_or(CoreError::Block(
+            "unable to get last block from chain".to_string(),
+        ))?;
+
+        if block.height() == BlockHeight::GENESIS
+            || block.height() != tip.height()
+            || block.header().prev_hash() != tip.header().prev_hash()
+        {
+            return Err(CoreError::Block(format!(
+                "block: {} does not compete with the chain tip at height: {}",
+                block.hash(),
+                tip.height()
+            )));
+        }
+
+        warn!(
+            "replacing chain tip: {} at height: {} with block: {}",
+            tip.hash(),
+            tip.height(),
+            block.hash()
+        );
+        self.block_manager.replace_tip(&tip, block.clone())?;
+
+        match self.view.write() {
+            Ok(mut view) => view.replace_last(block),
+            Err(e) => error!("unable to write ChainView in Blockchain.replace_tip, {e}"),
+        }
+
+        Ok(tip)
+    }
+
+    // when this node received the tip height, a replaced tip keeps the
+    // time of the block it replaced
+    pub fn tip_received(&self) -> Option<Instant> {
+        self.tip_received
+    }
+
+    pub fn height(&self) -> BlockHeight {
         let manager = &self.block_manager;
         manager.height()
     }
 
-    pub fn has_block(&self, height: usize) -> bool {
-        height <= self.height() as usize
+    pub fn has_block(&self, height: BlockHeight) -> bool {
+        height <= self.height()
     }
 
     pub fn last_block(&self) -> Option<Block> {
         self.block_manager.last()
     }
 
-    pub fn get_block_by_height(&self, index: usize) -> Option<Block> {
-        self.block_manager.get_block_by_height(index)
+    // find an included transaction by hash, returns the transaction
+    // and the height of the block which included it
+    pub fn get_tx(&self, tx_hash: &str) -> Option<(Transaction, BlockHeight)> {
+        let block = self.get_block_for_tx(tx_hash)?;
+
+        block
+            .txs()
+            .iter()
+            .find(|tx| tx.hash.map(|hash| hash.to_string()).as_deref() == Some(tx_hash))
+            .map(|tx| (tx.clone(), block.height()))
+    }
+
+    // block which included the transaction, found through the tx index
+    // and checked to hold a transaction with tx_hash
+    pub fn get_block_for_tx(&self, tx_hash: &str) -> Option<Block> {
+        let height = self.block_manager.get_tx_height(tx_hash)?;
+
+        self.get_block_by_height(height).filter(|block| {
+            block
+                .txs()
+                .iter()
+                .any(|tx| tx.hash.map(|hash| hash.to_string()).as_deref() == Some(tx_hash))
+        })
+    }
+
+    // rebuild the tx and sender nonce indexes from every stored block,
+    // used when an index is missing entries or was created after blocks
+    // were stored, returns the number of blocks indexed
+    pub fn reindex(&mut self) -> Result<usize, CoreError> {
+        self.block_manager.clear_tx_index()?;
+
+        let mut indexed = 0;
+        for height in BlockHeight::GENESIS.range_to(self.height()) {
+            let block = self.get_block_by_height(height).ok_or_else(|| {
+                CoreError::Block(format!("unable to reindex, no block at height: {height}"))
+            })?;
+            self.block_manager.index_txs(&block)?;
+            indexed += 1;
+        }
+
+        Ok(indexed)
+    }
+
+    // header of the last block, transactions are not decoded
+    pub fn tip_header(&self) -> Option<Header> {
+        self.get_header_by_height(self.height())
+    }
+
+    pub fn tip(&self) -> Option<ChainTip> {
+        self.tip_header().map(|header| ChainTip {
+            height: header.height(),
+            hash: header.hash().clone(),
+        })
+    }
+
+    pub fn get_block_by_height(&self, height: BlockHeight) -> Option<Block> {
+        self.block_manager.get_block_by_height(height)
     }
 
     pub fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
         self.block_manager.get_block_by_hash(hash)
     }
 
-    pub fn get_prev_block_hash(&self, block_height: usize) -> Option<Hash> {
+    // most recent transactions involving address, newest first
+    pub fn get_account_txs(&self, address: &Address, limit: usize) -> Vec<Transaction> {
+        let mut txs = vec![];
+
+        for height in BlockHeight::GENESIS.range_to(self.height()).rev() {
+            let block = match self.get_block_by_height(height) {
+                Some(block) => block,
+                None => continue,
+            };
+
+            for tx in block.txs().iter().rev() {
+                if tx.category(address).is_some() {
+                    txs.push(tx.clone());
+                    if txs.len() >= limit {
+                        return txs;
+                    }
+                }
+            }
+        }
+
+        txs
+    }
+
+    // seed used to choose the leader of height, derived only from chain
+    // data so every node computes the same seed, the height is mixed in so
+    // the seed changes every height, this is not unbiasable randomness,
+    // the producer of the previous block can grind its block hash, eg. by
+    // reordering txs, to pick the next leader
+    pub fn leader_seed(&self, height: BlockHeight) -> Result<Hash, CoreError> {
+        let prev = height
+            .prev()
+            .and_then(|prev| self.get_header_by_height(prev))
+            .ok_or_else(|| {
+                CoreError::Block(format!("no block before height: {height} to seed leader"))
+            })?;
+
+        let mut buf = LEADER_SEED_TAG.to_vec();
+        buf.extend_from_slice(&prev.hash()[..]);
+        buf.extend_from_slice(&height.value().to_le_bytes());
+
+        Ok(Hash::sha256(&buf)?)
+    }
+
+    // highest nonce of transactions sent by address in the chain, 0 when
+    // it has sent none, read from the sender nonce index
+    pub fn last_nonce(&self, address: &Address) -> u64 {
+        self.block_manager.sender_nonce(address).unwrap_or(0)
+    }
+
+    // last_nonce of every sender in the chain
+    pub fn last_nonces(&self) -> Result<BTreeMap<Address, u64>, CoreError> {
+        self.block_manager.sender_nonces()
+    }
+
+    pub fn get_header_by_height(&self, height: BlockHeight) -> Option<Header> {
+        self.block_manager.get_header_by_height(height)
+    }
+
+    pub fn get_header_by_hash(&self, hash: &str) -> Option<Header> {
+        self.block_manager.get_header_by_hash(hash)
+    }
+
+    // headers from start upward, at most count and never more than
+    // MAX_HEADER_RANGE, stops at the first missing height
+    pub fn get_header_range(&self, start: BlockHeight, count: usize) -> Vec<Header> {
+        let count = count.min(MAX_HEADER_RANGE) as u64;
+
+        (start.value()..start.value().saturating_add(count))
+            .map(BlockHeight)
+            .map_while(|height| self.get_header_by_height(height))
+            .collect()
+    }
+
+    // hashes of the last count blocks, newest first
+    pub fn recent_blockhashes(&self, count: usize) -> Vec<Hash> {
+        let height = self.height();
+        let from = height.next().saturating_sub(count as u64);
+
+        from.range_to(height)
+            .rev()
+            .filter_map(|height| self.get_header_by_height(height))
+            .map(|header| header.blockhash)
+            .collect()
+    }
+
+    pub fn get_prev_block_hash(&self, block_height: BlockHeight) -> Option<Hash> {
         self.get_block_by_height(block_height)
             .map(|b| b.header.prev_hash())
     }
 
+    // clear backups left behind by a block which never finished
+    // committing, returns the number of backups removed
+    pub fn prune_backups(&self) -> Result<usize, CoreError> {
+        // backups of a pending block are needed to finish or roll it back
+        self.recover_pending_block()?;
+
+        let count = self.state_manager.backup_count()?;
+        if count > 0 {
+            self.state_manager.clear_backups()?;
+        }
+        Ok(count)
+    }
+
+    // write every block from genesis to tip as a u32 LE length followed
+    // by the serialized block, returns the number of blocks written
+    pub fn export_to_writer<W: Write>(&self, w: &mut W) -> Result<usize, CoreError> {
+        let height = self.height();
+        for height in BlockHeight::GENESIS.range_to(height) {
+            let block = self.get_block_by_height(height).ok_or_else(|| {
+                CoreError::Block(format!("block at height: {height} not found in storage"))
+            })?;
+            let bytes = block.to_bytes()?;
+            w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            w.write_all(&bytes)?;
+        }
+        w.flush()?;
+
+        Ok(height.value() as usize + 1)
+    }
+
+    // walk the stored chain from genesis to tip re-checking each block's
+    // link to its parent, blockhash, tx root, PoH and signatures, the
+    // error names the height of the first inconsistent block
+    pub fn validate_full_chain(&self) -> Result<(), CoreError> {
+        let mut prev_hash: Option<Hash> = None;
+        for height in BlockHeight::GENESIS.range_to(self.height()) {
+            let block = self.get_block_by_height(height).ok_or_else(|| {
+                CoreError::Block(format!("block at height: {height} not found in storage"))
+            })?;
+
+            Self::validate_stored_block(&block, height, prev_hash.as_ref()).map_err(|e| {
+                CoreError::Block(format!("chain is invalid at height: {height}, {e}"))
+            })?;
+
+            prev_hash = Some(block.hash().clone());
+        }
+
+        Ok(())
+    }
+
+    // compact block and state storage
+    pub fn compact(&self) -> Result<(), CoreError> {
+        self.block_manager.compact()?;
+        self.state_manager.compact()
+    }
+
     pub fn state(&self) -> &StateManager {
         &self.state_manager
     }
 
+    // shared read only snapshot of the chain tip, can be read
+    // without holding the Blockchain lock
+    pub fn view(&self) -> SharedChainView {
+        self.view.clone()
+    }
+
+    // when enabled account writes for a block are flushed to
+    // state storage in a single batch after all txs execute
+    pub fn set_batch_state_commits(&mut self, batch: bool) {
+        self.batch_state_commits = batch;
+    }
+
+    // cap on the amount an account may transfer in one block, see
+    // GenesisConfig.max_block_spend
+    pub fn set_spend_cap(&mut self, cap: Option<u64>) {
+        self.runtime.set_spend_cap(cap);
+    }
+
+    // runtime blocks are committed with
+    pub fn runtime(&self) -> &ValidatorRuntime {
+        &self.runtime
+    }
+
     // ---
     // Private Methods
     // ---
 
+    // a pending block left by apply_block is kept if it was stored,
+    // otherwise its state is rolled back
+    fn recover_pending_block(&self) -> Result<(), CoreError> {
+        let Some(pending) = self.state_manager.pending_block()? else {
+            return Ok(());
+        };
+
+        let stored = self.block_manager.get_block_by_height(pending.height);
+        if stored.is_some_and(|block| block.hash() == &pending.hash) {
+            warn!(
+                "completing block at height: {} left pending in state",
+                pending.height
+            );
+            self.state_manager.finish_block()
+        } else {
+            warn!(
+                "rolling back state of block at height: {} which was not stored",
+                pending.height
+            );
+            self.state_manager.rollback_block()
+        }
+    }
+
     fn add_block_without_validation(&mut self, block: Block) -> Result<(), CoreError> {
         let manager = &mut self.block_manager;
 
-        manager.add(block)
+        manager.add(block.clone())?;
+
+        self.update_view(block);
+        self.tip_received = Some(Instant::now());
+
+        Ok(())
+    }
+
+    // genesis has no parent and is not signed, every other block must
+    // link to prev_hash and carry a valid signature
+    fn validate_stored_block(
+        block: &Block,
+        height: BlockHeight,
+        prev_hash: Option<&Hash>,
+    ) -> Result<(), CoreError> {
+        let header = block.header();
+        if block.height() != height {
+            return Err(CoreError::Block(format!(
+                "block is stored with height: {}",
+                block.height()
+            )));
+        }
+
+        if header.poh != Header::gen_poh(block.txs())? {
+            return Err(CoreError::Block("proof of history is invalid".to_string()));
+        }
+
+        if header.tx_root != Header::gen_tx_root(block.txs())? {
+            return Err(CoreError::Block("transaction root is invalid".to_string()));
+        }
+
+        let prev_hash = match prev_hash {
+            Some(prev_hash) => prev_hash,
+            None => return Ok(()),
+        };
+
+        if header.prev_hash() != *prev_hash {
+            return Err(CoreError::Block(format!(
+                "previous block hash: {} does not match: {prev_hash}",
+                header.prev_hash()
+            )));
+        }
+
+        let blockhash = Header::gen_blockhash(
+            height,
+            *prev_hash,
+            header.poh,
+            header.tx_root,
+            header.state_root,
+        )?;
+        if header.hash() != blockhash {
+            return Err(CoreError::Block(format!(
+                "blockhash: {} does not match header contents",
+                header.hash()
+            )));
+        }
+
+        block.verify()
+    }
+
+    fn update_view(&self, block: Block) {
+        match self.view.write() {
+            Ok(mut view) => view.push(block),
+            Err(e) => error!("unable to write ChainView in Blockchain.update_view, {e}"),
+        }
     }
 
     // ---
     // Used for testing and development
     // ---
 
-    pub fn new_with_genesis() -> Result<Self, CoreError> {
-        let genesis_hash = Hash::new(&[0_u8; 32]).unwrap();
-        let block = random_block(random_header(0, genesis_hash));
+    pub fn new_with_genesis(genesis: &GenesisConfig) -> Result<Self, CoreError> {
+        let block = Block::genesis(genesis)?;
         let mut bc = Self::default();
         bc.add_block_without_validation(block).unwrap();
         Ok(bc)
     }
 
     pub fn new_with_genesis_in_memory() -> Result<Self, CoreError> {
-        let genesis_hash = Hash::new(&[0_u8; 32]).unwrap();
-        let block = random_block(random_header(0, genesis_hash));
+        let block = Block::genesis(&GenesisConfig::default())?;
         let mut bc = Self::new_in_memory()?;
         bc.add_block_without_validation(block).unwrap();
         Ok(bc)
@@ -125,18 +548,62 @@ impl Blockchain {
             block_manager: BlockManager::new_in_memory(),
             state_manager: StateManager::new_in_memory(),
             runtime: ValidatorRuntime::new(),
+            batch_state_commits: true,
+            view: ChainView::new_shared(),
+            tip_received: None,
         };
 
         Ok(bc)
     }
 }
 
+// read one length prefixed block, None when the stream ends cleanly
+// on a record boundary
+pub(crate) fn read_block_record<R: Read>(r: &mut R) -> Result<Option<Vec<u8>>, CoreError> {
+    let mut len_buf = [0_u8; 4];
+    let mut read = 0;
+    while read < len_buf.len() {
+        match r.read(&mut len_buf[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => {
+                return Err(CoreError::Parsing(
+                    "truncated block length in import stream".to_string(),
+                ))
+            }
+            Ok(n) => read += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // checked before allocating so a corrupt prefix can not claim 4GiB
+    let len = u32::from_le_bytes(len_buf) as usize;
+    if len > MAX_BLOCK_RECORD_SIZE {
+        return Err(CoreError::Parsing(format!(
+            "block length: {len} in import stream is larger than: {MAX_BLOCK_RECORD_SIZE}"
+        )));
+    }
+
+    let mut bytes = vec![0_u8; len];
+    r.read_exact(&mut bytes).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => {
+            CoreError::Parsing("truncated block in import stream".to_string())
+        }
+        _ => e.into(),
+    })?;
+
+    Ok(Some(bytes))
+}
+
 impl Default for Blockchain {
     fn default() -> Self {
         Self {
             block_manager: BlockManager::default(),
             state_manager: StateManager::default(),
             runtime: ValidatorRuntime::new(),
+            batch_state_commits: true,
+            view: ChainView::new_shared(),
+            tip_received: None,
         }
     }
 }
@@ -152,9 +619,11 @@ mod tests {
         core::{
             block::{random_block, random_signed_block},
             header::random_header,
+            transaction::{random_signed_tx, TransferData},
         },
-        crypto::{hash::Hash, utils::random_hash},
+        crypto::{address::Address, hash::Hash, private_key::PrivateKey, utils::random_hash},
         logger_init,
+        state::account::Account,
     };
 
     use super::*;
@@ -169,7 +638,7 @@ mod tests {
     #[test]
     fn test_add_block() {
         let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
-        let genesis_block = bc.get_block_by_height(0).unwrap();
+        let genesis_block = bc.get_block_by_height(BlockHeight::GENESIS).unwrap();
         let genesis_header = genesis_block.header().clone();
 
         // check cannot re-add existing block
@@ -193,7 +662,7 @@ mod tests {
 
         // assert_eq!(bc.height(), 1);
 
-        let new_height = bc.height() + 1;
+        let new_height = bc.height().value() + 1;
         let last_block = bc.last_block();
         let last_block = last_block.unwrap();
         let new_header_2 = random_header(new_height, last_block.hash().clone());
@@ -208,13 +677,13 @@ mod tests {
     fn test_has_block() {
         let bc = Blockchain::new_with_genesis_in_memory().unwrap();
 
-        assert!(bc.has_block(0));
+        assert!(bc.has_block(BlockHeight::GENESIS));
     }
 
     #[test]
     fn test_get_header() {
         let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
-        let genesis_block = bc.get_block_by_height(0).unwrap();
+        let genesis_block = bc.get_block_by_height(BlockHeight::GENESIS).unwrap();
 
         let mut headers: Vec<Header> = vec![];
         let mut prev_header: Header = random_header(1, genesis_block.hash().clone());
@@ -243,12 +712,344 @@ mod tests {
 
         let last_block = bc.last_block().unwrap();
 
-        let block = bc
-            .get_block_by_height(last_block.height() as usize)
-            .unwrap();
+        let block = bc.get_block_by_height(last_block.height()).unwrap();
 
         // let last_block = blocks.last().unwrap();
 
         assert_eq!(last_block.hash(), block.hash());
     }
+
+    #[test]
+    fn test_get_header_range() {
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+        let mut prev_hash = bc
+            .get_block_by_height(BlockHeight::GENESIS)
+            .unwrap()
+            .hash()
+            .clone();
+
+        for height in 1..MAX_HEADER_RANGE as u64 + 10 {
+            let block = random_signed_block(random_header(height, prev_hash));
+            prev_hash = block.hash().clone();
+            bc.add_block(block).unwrap();
+        }
+
+        let headers = bc.get_header_range(BlockHeight(5), 20);
+        assert_eq!(headers.len(), 20);
+        assert_eq!(headers[0].height, 5);
+        for pair in headers.windows(2) {
+            assert_eq!(pair[1].prev_blockhash, pair[0].blockhash);
+            assert_eq!(pair[1].height, pair[0].height + 1);
+        }
+
+        // count is capped
+        let headers = bc.get_header_range(BlockHeight(0), MAX_HEADER_RANGE * 2);
+        assert_eq!(headers.len(), MAX_HEADER_RANGE);
+        assert_eq!(headers.last().unwrap().height, MAX_HEADER_RANGE as u64 - 1);
+
+        // range stops at the chain tip
+        let headers = bc.get_header_range(bc.height() - 2, 10);
+        assert_eq!(headers.len(), 3);
+        assert!(bc.get_header_range(bc.height() + 1, 10).is_empty());
+    }
+
+    #[test]
+    fn test_reopen_db_chain_keeps_genesis() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.db");
+        let block_path = dir.path().join("chain.db");
+        let state_path = state_path.to_str().unwrap();
+        let block_path = block_path.to_str().unwrap();
+        let genesis = Block::genesis(&GenesisConfig::default()).unwrap();
+
+        let block = {
+            let mut bc = Blockchain::new(state_path, block_path, genesis.clone()).unwrap();
+            let block = random_signed_block(random_header(1, genesis.hash().clone()));
+            bc.add_block(block.clone()).unwrap();
+            block
+        };
+
+        let bc = Blockchain::new(state_path, block_path, genesis.clone()).unwrap();
+        assert_eq!(bc.height(), 1);
+        assert_eq!(
+            bc.get_block_by_height(BlockHeight::GENESIS).unwrap().hash(),
+            genesis.hash()
+        );
+        assert_eq!(bc.last_block().unwrap().hash(), block.hash());
+        drop(bc);
+
+        let other = Block::genesis(&GenesisConfig::new(1_700_000_000, 1)).unwrap();
+        let res = Blockchain::new(state_path, block_path, other);
+        assert!(matches!(res, Err(CoreError::Block(_))));
+    }
+
+    #[test]
+    fn test_commit_block_clears_backups() {
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+        let genesis = bc.last_block().unwrap();
+
+        let address = Address::new(&[1_u8; 20]);
+        bc.state()
+            .set_account(&address, &Account { balance: 10 })
+            .unwrap();
+        bc.state().backup_account(&address).unwrap();
+        bc.state()
+            .backup_account(&Address::new(&[2_u8; 20]))
+            .unwrap();
+        assert_eq!(bc.state().backup_count().unwrap(), 2);
+
+        let block = random_signed_block(random_header(1, genesis.hash().clone()));
+        bc.commit_block(block).unwrap();
+        assert_eq!(bc.state().backup_count().unwrap(), 0);
+
+        // orphaned backups are removed by a prune
+        bc.state().backup_account(&address).unwrap();
+        assert_eq!(bc.prune_backups().unwrap(), 1);
+        assert_eq!(bc.state().backup_count().unwrap(), 0);
+        assert_eq!(bc.prune_backups().unwrap(), 0);
+    }
+
+    #[test]
+    fn test_tip_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.db");
+        let block_path = dir.path().join("chain.db");
+        let genesis = Block::genesis(&GenesisConfig::default()).unwrap();
+        let mut bc = Blockchain::new(
+            state_path.to_str().unwrap(),
+            block_path.to_str().unwrap(),
+            genesis.clone(),
+        )
+        .unwrap();
+        assert_eq!(bc.tip_header().unwrap(), *genesis.header());
+
+        let txs = vec![random_signed_tx(), random_signed_tx()];
+        let mut block = Block::new(random_header(1, genesis.hash().clone()), txs).unwrap();
+        block.sign(&PrivateKey::new()).unwrap();
+        bc.add_block(block).unwrap();
+
+        let last_block = bc.last_block().unwrap();
+        assert_eq!(bc.tip_header().unwrap(), *last_block.header());
+        assert_eq!(bc.tip().unwrap().hash, *last_block.hash());
+    }
+
+    #[test]
+    fn test_reindex() {
+        let dir = tempfile::tempdir().unwrap();
+        let genesis = Block::genesis(&GenesisConfig::default()).unwrap();
+        let mut bc = Blockchain::new(
+            dir.path().join("state.db").to_str().unwrap(),
+            dir.path().join("chain.db").to_str().unwrap(),
+            genesis.clone(),
+        )
+        .unwrap();
+
+        let mut hashes = vec![];
+        let mut senders = vec![];
+        let mut prev_hash = genesis.hash().clone();
+        for height in 1..=3 {
+            let txs = vec![random_signed_tx(), random_signed_tx()];
+            hashes.extend(txs.iter().map(|tx| tx.hash.unwrap().to_string()));
+            senders.extend(txs.iter().map(|tx| (tx.sender.clone(), tx.nonce)));
+            let block = Block::new(random_header(height, prev_hash), txs).unwrap();
+            prev_hash = block.hash().clone();
+            bc.add_block(block).unwrap();
+        }
+
+        bc.block_manager.clear_tx_index().unwrap();
+        assert!(hashes.iter().all(|hash| bc.get_tx(hash).is_none()));
+        assert!(bc.last_nonces().unwrap().is_empty());
+
+        assert_eq!(bc.reindex().unwrap(), 4);
+        for (sender, nonce) in senders.iter() {
+            assert_eq!(bc.last_nonce(sender), *nonce);
+        }
+        for (i, hash) in hashes.iter().enumerate() {
+            let (tx, height) = bc.get_tx(hash).unwrap();
+            assert_eq!(tx.hash.unwrap().to_string(), *hash);
+            assert_eq!(height, BlockHeight(i as u64 / 2 + 1));
+        }
+    }
+
+    // signed block without txs whose header is consistent with prev_hash
+    fn linked_block(height: u64, prev_hash: Hash, key: &PrivateKey) -> Block {
+        let height = BlockHeight(height);
+        let poh = Header::gen_poh(&[]).unwrap();
+        let tx_root = Header::gen_tx_root(&[]).unwrap();
+        let state_root = random_hash();
+        let blockhash = Header::gen_blockhash(height, prev_hash, poh, tx_root, state_root).unwrap();
+        let header = Header::new(height, blockhash, poh, tx_root, state_root, prev_hash);
+        let mut block = Block::new(header, vec![]).unwrap();
+        block.sign(key).unwrap();
+        block
+    }
+
+    #[test]
+    fn test_validate_full_chain() {
+        let key = PrivateKey::new();
+
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+        for height in 1..=5 {
+            let prev_hash = *bc.last_block().unwrap().hash();
+            bc.add_block(linked_block(height, prev_hash, &key)).unwrap();
+        }
+        assert!(bc.validate_full_chain().is_ok());
+
+        // block 3 points at an unknown parent, blocks after it link to it
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+        for height in 1..=5 {
+            let prev_hash = match height {
+                3 => random_hash(),
+                _ => *bc.last_block().unwrap().hash(),
+            };
+            bc.add_block(linked_block(height, prev_hash, &key)).unwrap();
+        }
+
+        let err = bc.validate_full_chain().unwrap_err().to_string();
+        assert!(err.contains("invalid at height: 3"), "{err}");
+    }
+
+    #[test]
+    fn test_leader_seed() {
+        let key = PrivateKey::new();
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+        let mut copy = Blockchain::new_with_genesis_in_memory().unwrap();
+        for height in 1..=3 {
+            let prev_hash = *bc.last_block().unwrap().hash();
+            let block = linked_block(height, prev_hash, &key);
+            bc.add_block(block.clone()).unwrap();
+            copy.add_block(block).unwrap();
+        }
+
+        // reproducible by any node holding the same blocks
+        let seeds: Vec<Hash> = (1..=4)
+            .map(|height| bc.leader_seed(BlockHeight(height)).unwrap())
+            .collect();
+        for (height, seed) in (1..=4).zip(&seeds) {
+            assert_eq!(&copy.leader_seed(BlockHeight(height)).unwrap(), seed);
+        }
+
+        // a new seed every height
+        let unique: std::collections::HashSet<Hash> = seeds.iter().copied().collect();
+        assert_eq!(unique.len(), seeds.len());
+
+        // genesis has no leader and the tip + 2 has no parent yet
+        assert!(bc.leader_seed(BlockHeight::GENESIS).is_err());
+        assert!(bc.leader_seed(BlockHeight(5)).is_err());
+    }
+
+    #[test]
+    fn test_apply_block_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let state_path = dir.path().join("state.db");
+        let block_path = dir.path().join("chain.db");
+        let (state_path, block_path) = (state_path.to_str().unwrap(), block_path.to_str().unwrap());
+        let genesis = Block::genesis(&GenesisConfig::default()).unwrap();
+        let open = || Blockchain::new(state_path, block_path, genesis.clone()).unwrap();
+
+        let (from, to) = (Address::new(&[1_u8; 20]), Address::new(&[2_u8; 20]));
+        let key = PrivateKey::new();
+        let transfer_block = |bc: &Blockchain, amount: u64| {
+            let data = TransferData {
+                from: from.clone(),
+                to: to.clone(),
+                amount,
+            };
+            let mut tx = Transaction::new_transfer(
+                to.clone(),
+                from.clone(),
+                random_hash(),
+                &data.to_bytes().unwrap(),
+                1,
+            )
+            .unwrap();
+            tx.sign(&key).unwrap();
+            let header = random_header(
+                bc.height().value() + 1,
+                bc.last_block().unwrap().hash().clone(),
+            );
+            let mut block = Block::new(header, vec![tx]).unwrap();
+            block.sign(&key).unwrap();
+            block
+        };
+        let balances = |bc: &Blockchain| {
+            (
+                bc.state().get_account(&from).unwrap().balance,
+                bc.state().get_account(&to).unwrap().balance,
+            )
+        };
+
+        let mut bc = open();
+        bc.state()
+            .set_account(&from, &Account { balance: 1_000 })
+            .unwrap();
+        bc.state()
+            .set_account(&to, &Account { balance: 0 })
+            .unwrap();
+        bc.apply_block(transfer_block(&bc, 100)).unwrap();
+        assert_eq!(bc.height(), 1);
+        assert_eq!(balances(&bc), (899, 100));
+        assert_eq!(bc.state().pending_block().unwrap(), None);
+        assert_eq!(bc.state().backup_count().unwrap(), 0);
+
+        // stopped after the block state is written, before the block is stored
+        let block = transfer_block(&bc, 50);
+        bc.runtime.stage_block(block.txs(), bc.state()).unwrap();
+        bc.state()
+            .commit_block_batch(&PendingBlock {
+                height: block.height(),
+                hash: *block.hash(),
+            })
+            .unwrap();
+        assert_eq!(balances(&bc), (848, 150));
+        drop(bc);
+
+        // the partial block is discarded
+        let bc = open();
+        assert_eq!(bc.height(), 1);
+        assert_eq!(balances(&bc), (899, 100));
+        assert_eq!(bc.state().pending_block().unwrap(), None);
+        assert_eq!(bc.state().backup_count().unwrap(), 0);
+
+        // stopped after the block is stored, before the marker is cleared
+        let mut bc = bc;
+        let block = transfer_block(&bc, 50);
+        bc.runtime.stage_block(block.txs(), bc.state()).unwrap();
+        bc.state()
+            .commit_block_batch(&PendingBlock {
+                height: block.height(),
+                hash: *block.hash(),
+            })
+            .unwrap();
+        bc.add_block(block).unwrap();
+        drop(bc);
+
+        // a stored block keeps its state
+        let bc = open();
+        assert_eq!(bc.height(), 2);
+        assert_eq!(balances(&bc), (848, 150));
+        assert_eq!(bc.state().pending_block().unwrap(), None);
+        assert_eq!(bc.state().backup_count().unwrap(), 0);
+
+        // a stale backup is not restored when a pending block rolls back
+        bc.state().backup_account(&from).unwrap();
+        bc.state()
+            .set_account(&from, &Account { balance: 2_000 })
+            .unwrap();
+        let block = transfer_block(&bc, 50);
+        bc.runtime.stage_block(block.txs(), bc.state()).unwrap();
+        bc.state()
+            .commit_block_batch(&PendingBlock {
+                height: block.height(),
+                hash: *block.hash(),
+            })
+            .unwrap();
+        assert_eq!(balances(&bc), (1_949, 200));
+
+        // prune recovers the pending block before clearing backups
+        assert_eq!(bc.prune_backups().unwrap(), 0);
+        assert_eq!(bc.height(), 2);
+        assert_eq!(balances(&bc), (2_000, 150));
+        assert_eq!(bc.state().pending_block().unwrap(), None);
+    }
 }
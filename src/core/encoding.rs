@@ -6,6 +6,67 @@ use super::error::CoreError;
 pub trait ByteEncoding<T> {
     fn to_bytes(&self) -> Result<Vec<u8>, CoreError>;
     fn from_bytes(data: &[u8]) -> Result<T, CoreError>;
+
+    // u32 LE item count followed by each item as a u32 LE length
+    // prefix and its bytes, can be read back without borsh
+    fn encode_vec(items: &[T]) -> Result<Vec<u8>, CoreError>
+    where
+        T: ByteEncoding<T>,
+    {
+        let count = u32::try_from(items.len())
+            .map_err(|_| CoreError::Parsing("too many items to encode".to_string()))?;
+
+        let mut data = count.to_le_bytes().to_vec();
+        for item in items {
+            let bytes = item.to_bytes()?;
+            let len = u32::try_from(bytes.len())
+                .map_err(|_| CoreError::Parsing("item too large to encode".to_string()))?;
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(&bytes);
+        }
+        Ok(data)
+    }
+
+    // inverse of encode_vec, errors on truncated or trailing bytes
+    fn decode_vec(data: &[u8]) -> Result<Vec<T>, CoreError> {
+        let mut reader = data;
+        let count = read_u32(&mut reader)? as usize;
+
+        // every item needs at least its length prefix
+        if count > reader.len() / 4 {
+            return Err(CoreError::Parsing(format!(
+                "item count: {count} exceeds data length"
+            )));
+        }
+
+        let mut items = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = read_u32(&mut reader)? as usize;
+            if len > reader.len() {
+                return Err(CoreError::Parsing("truncated item in vec".to_string()));
+            }
+            let (bytes, rest) = reader.split_at(len);
+            items.push(Self::from_bytes(bytes)?);
+            reader = rest;
+        }
+
+        if !reader.is_empty() {
+            return Err(CoreError::Parsing(format!(
+                "{} trailing bytes after vec",
+                reader.len()
+            )));
+        }
+        Ok(items)
+    }
+}
+
+fn read_u32(reader: &mut &[u8]) -> Result<u32, CoreError> {
+    if reader.len() < 4 {
+        return Err(CoreError::Parsing("truncated length prefix".to_string()));
+    }
+    let (prefix, rest) = reader.split_at(4);
+    *reader = rest;
+    Ok(u32::from_le_bytes(prefix.try_into().unwrap()))
 }
 
 pub trait HexEncoding<T> {
@@ -17,3 +78,29 @@ pub trait JsonEncoding<T> {
     fn to_json(&self) -> Result<Value, CoreError>;
     fn from_json(data: Value) -> Result<T, CoreError>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transaction::{random_signed_tx, Transaction};
+
+    #[test]
+    fn test_encode_decode_vec() {
+        let txs: Vec<Transaction> = (0..5).map(|_| random_signed_tx()).collect();
+
+        let data = Transaction::encode_vec(&txs).unwrap();
+        assert_eq!(&data[..4], &5_u32.to_le_bytes());
+
+        let decoded = Transaction::decode_vec(&data).unwrap();
+        assert_eq!(decoded, txs);
+
+        let empty = Transaction::encode_vec(&[]).unwrap();
+        assert!(Transaction::decode_vec(&empty).unwrap().is_empty());
+
+        // truncated and trailing data are rejected
+        assert!(Transaction::decode_vec(&data[..data.len() - 1]).is_err());
+        let mut trailing = data.clone();
+        trailing.push(0);
+        assert!(Transaction::decode_vec(&trailing).is_err());
+    }
+}
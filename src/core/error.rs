@@ -14,10 +14,21 @@ pub enum CoreError {
     Block(String),
     CryptoError(String),
     State(String),
+    Vm(String),
+    Io(String),
 }
 
 impl Error for CoreError {}
 
+impl CoreError {
+    // io errors may succeed when retried, state errors are kept out as
+    // most come from the state itself, eg. a missing account, and fail
+    // the same way every time
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Io(_))
+    }
+}
+
 impl Display for CoreError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -27,6 +38,8 @@ impl Display for CoreError {
             Self::Block(msg) => write!(f, "{}", msg),
             Self::CryptoError(msg) => write!(f, "{}", msg),
             Self::State(msg) => write!(f, "{}", msg),
+            Self::Vm(msg) => write!(f, "{}", msg),
+            Self::Io(msg) => write!(f, "{}", msg),
         }
     }
 }
@@ -60,6 +73,18 @@ impl From<io::ErrorKind> for CoreError {
     }
 }
 
+impl From<io::Error> for CoreError {
+    fn from(value: io::Error) -> Self {
+        CoreError::Io(format!("{value}"))
+    }
+}
+
+impl From<serde_json::Error> for CoreError {
+    fn from(value: serde_json::Error) -> Self {
+        CoreError::Parsing(format!("{value}"))
+    }
+}
+
 impl From<Box<dyn Error>> for CoreError {
     fn from(value: Box<dyn Error>) -> Self {
         CoreError::Parsing(format!("{value}"))
@@ -76,6 +101,8 @@ impl Responder for CoreError {
             Self::Block(msg) => msg,
             Self::CryptoError(msg) => msg,
             Self::State(msg) => msg,
+            Self::Vm(msg) => msg,
+            Self::Io(msg) => msg,
         };
 
         let status = StatusCode::from_u16(403).unwrap_or(StatusCode::BAD_REQUEST);
@@ -0,0 +1,146 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use super::{encoding::ByteEncoding, error::CoreError, header::Header, height::BlockHeight};
+use crate::{
+    crypto::{hash::Hash, scheme::SignatureScheme},
+    state::root::StateRootCache,
+};
+
+// values used to build the genesis block, nodes started with
+// the same config produce the same genesis hash
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct GenesisConfig {
+    pub timestamp: u64,
+    pub version: u8,
+    // schemes blocks and transactions on the chain may be signed with
+    #[serde(default = "default_allowed_schemes")]
+    pub allowed_schemes: Vec<SignatureScheme>,
+    // most an account may transfer out in a single block, intended for
+    // dev and test networks, None leaves transfers uncapped
+    #[serde(default)]
+    pub max_block_spend: Option<u64>,
+}
+
+fn default_allowed_schemes() -> Vec<SignatureScheme> {
+    vec![SignatureScheme::Secp256k1]
+}
+
+impl GenesisConfig {
+    pub fn new(timestamp: u64, version: u8) -> Self {
+        Self {
+            timestamp,
+            version,
+            allowed_schemes: default_allowed_schemes(),
+            max_block_spend: None,
+        }
+    }
+
+    // load config from a JSON file, eg. {"timestamp": 0, "version": 1}
+    pub fn from_file(path: &Path) -> Result<Self, CoreError> {
+        let data = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn header(&self) -> Result<Header, CoreError> {
+        let prev_blockhash = Hash::new(&[0_u8; 32])?;
+        // genesis has no transactions and no accounts, its roots are the
+        // canonical empty roots
+        let poh = Header::empty_poh()?;
+        let tx_root = Header::empty_tx_root()?;
+        let state_root = StateRootCache::empty_root()?;
+
+        // timestamp and version are not part of gen_blockhash so
+        // include them in the genesis hash directly
+        let mut buf = Header::gen_blockhash(
+            BlockHeight::GENESIS,
+            prev_blockhash.clone(),
+            poh.clone(),
+            tx_root.clone(),
+            state_root.clone(),
+        )?
+        .to_bytes()?;
+        buf.extend_from_slice(&self.timestamp.to_le_bytes());
+        buf.push(self.version);
+
+        Ok(Header {
+            version: self.version,
+            blockhash: Hash::sha256(&buf)?,
+            prev_blockhash,
+            height: BlockHeight::GENESIS,
+            timestamp: self.timestamp,
+            tx_root,
+            state_root,
+            poh,
+        })
+    }
+}
+
+impl Default for GenesisConfig {
+    fn default() -> Self {
+        Self {
+            timestamp: 0,
+            version: 1,
+            allowed_schemes: default_allowed_schemes(),
+            max_block_spend: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::block::Block;
+
+    #[test]
+    fn test_genesis_hash_from_config() {
+        let config = GenesisConfig::new(1_700_000_000, 1);
+
+        let genesis_1 = Block::genesis(&config).unwrap();
+        let genesis_2 = Block::genesis(&config.clone()).unwrap();
+        assert_eq!(genesis_1.hash(), genesis_2.hash());
+        assert_eq!(genesis_1.to_bytes().unwrap(), genesis_2.to_bytes().unwrap());
+        assert_eq!(genesis_1.header().timestamp, 1_700_000_000);
+
+        // roots are those of an empty block, not random
+        let header = genesis_1.header();
+        assert_eq!(header.prev_blockhash, Hash::new(&[0_u8; 32]).unwrap());
+        assert_eq!(header.tx_root, Header::gen_tx_root(&[]).unwrap());
+        assert_eq!(header.poh, Header::gen_poh(&[]).unwrap());
+        assert_eq!(header.state_root, Hash::sha256(&[]).unwrap());
+        assert_eq!(header.state_root, StateRootCache::empty_root().unwrap());
+        assert_eq!(genesis_1.num_txs(), 0);
+
+        let other_timestamp = Block::genesis(&GenesisConfig::new(1_700_000_001, 1)).unwrap();
+        assert_ne!(genesis_1.hash(), other_timestamp.hash());
+
+        let other_version = Block::genesis(&GenesisConfig::new(1_700_000_000, 2)).unwrap();
+        assert_ne!(genesis_1.hash(), other_version.hash());
+    }
+
+    #[test]
+    fn test_genesis_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let missing = GenesisConfig::from_file(&dir.path().join("missing.json"));
+        assert!(matches!(missing, Err(CoreError::Io(_))));
+
+        let path = dir.path().join("genesis.json");
+        fs::write(&path, r#"{"timestamp": 1700000000, "version": 2}"#).unwrap();
+        let config = GenesisConfig::from_file(&path).unwrap();
+        assert_eq!(config, GenesisConfig::new(1_700_000_000, 2));
+
+        fs::write(
+            &path,
+            r#"{"timestamp": 1700000000, "version": 2, "allowed_schemes": ["Ed25519"]}"#,
+        )
+        .unwrap();
+        let config = GenesisConfig::from_file(&path).unwrap();
+        assert_eq!(config.allowed_schemes, vec![SignatureScheme::Ed25519]);
+
+        fs::write(&path, "not json").unwrap();
+        let invalid = GenesisConfig::from_file(&path);
+        assert!(matches!(invalid, Err(CoreError::Parsing(_))));
+    }
+}
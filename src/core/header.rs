@@ -7,6 +7,7 @@ use super::{
     block::Block,
     encoding::{ByteEncoding, HexEncoding},
     error::CoreError,
+    height::BlockHeight,
     transaction::Transaction,
     util::timestamp,
 };
@@ -20,7 +21,7 @@ pub struct Header {
     pub version: u8,
     pub blockhash: Hash,
     pub prev_blockhash: Hash,
-    pub height: usize,
+    pub height: BlockHeight,
     pub timestamp: u64,
     pub tx_root: Hash,
     pub state_root: Hash,
@@ -29,7 +30,7 @@ pub struct Header {
 
 impl Header {
     pub fn new(
-        height: usize,
+        height: BlockHeight,
         blockhash: Hash,
         poh: Hash,
         tx_root: Hash,
@@ -51,7 +52,7 @@ impl Header {
         }
     }
 
-    pub fn height(&self) -> usize {
+    pub fn height(&self) -> BlockHeight {
         self.height
     }
 
@@ -63,13 +64,30 @@ impl Header {
         self.blockhash.clone()
     }
 
+    // consensus fields covered by the block signature, in a fixed order
+    // with integers as little endian
     pub fn hashable_data(&self) -> Vec<u8> {
-        vec![]
+        let mut buf = vec![self.version];
+        buf.extend_from_slice(&self.height.value().to_le_bytes());
+        buf.extend_from_slice(&self.timestamp.to_le_bytes());
+
+        for hash in [
+            &self.blockhash,
+            &self.prev_blockhash,
+            &self.tx_root,
+            &self.state_root,
+            &self.poh,
+        ] {
+            // SAFETY: Hash to_bytes only copies the inner array
+            buf.extend_from_slice(&hash.to_bytes().unwrap());
+        }
+
+        buf
     }
 
     // Static Hashing Methods
     pub fn gen_blockhash(
-        block_height: usize,
+        block_height: BlockHeight,
         prev_blockhash: Hash,
         poh: Hash,
         tx_root: Hash,
@@ -77,7 +95,7 @@ impl Header {
     ) -> Result<Hash, CoreError> {
         let mut buf = vec![];
 
-        buf.extend_from_slice(&block_height.to_le_bytes().to_vec());
+        buf.extend_from_slice(&block_height.value().to_le_bytes());
         buf.extend_from_slice(&prev_blockhash.to_bytes()?);
         buf.extend_from_slice(&poh.to_bytes()?);
         buf.extend_from_slice(&tx_root.to_bytes()?);
@@ -86,9 +104,25 @@ impl Header {
         Ok(Hash::sha256(&buf)?)
     }
 
+    // tx_root of a block without transactions, sha256 of no bytes, only
+    // the genesis block has an empty transaction list
+    pub fn empty_tx_root() -> Result<Hash, CoreError> {
+        Ok(Hash::sha256(&[])?)
+    }
+
+    // poh of a block without transactions, the zero hash
+    pub fn empty_poh() -> Result<Hash, CoreError> {
+        Ok(Hash::new(&[0_u8; 32])?)
+    }
+
     pub fn gen_tx_root(txs: &[Transaction]) -> Result<Hash, CoreError> {
+        Transaction::check_signed(txs)?;
+        Self::tx_root(txs)
+    }
+
+    fn tx_root(txs: &[Transaction]) -> Result<Hash, CoreError> {
         let hash: Hash = match txs.len() {
-            0 => Hash::sha256(&[])?,
+            0 => Self::empty_tx_root()?,
             1 => {
                 let mut buf: Vec<u8> = vec![];
                 let tx1_bytes = &txs[0].hash()?.to_bytes()?;
@@ -105,13 +139,17 @@ impl Header {
                 buf.extend_from_slice(&tx2_bytes);
                 return Ok(Hash::sha256(&buf)?);
             }
-            _ => return Self::gen_tx_root(&txs[..txs.len() - 2]),
+            _ => return Self::tx_root(&txs[..txs.len() - 2]),
         };
 
         Ok(hash)
     }
 
     pub fn gen_poh(txs: &[Transaction]) -> Result<Hash, CoreError> {
+        if txs.is_empty() {
+            return Self::empty_poh();
+        }
+
         let mut hasher = Hasher::new();
 
         for tx in txs {
@@ -174,6 +212,34 @@ mod tests {
         crypto::{hash::Hash, public_key::PublicKey, signature::Signature, utils::random_hash},
     };
 
+    #[test]
+    fn test_empty_roots() {
+        // pinned, changing either changes the genesis hash
+        assert_eq!(
+            Header::gen_tx_root(&[]).unwrap().to_string(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            Header::gen_poh(&[]).unwrap().to_string(),
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(
+            Header::gen_tx_root(&[]).unwrap(),
+            Header::empty_tx_root().unwrap()
+        );
+        assert_eq!(Header::gen_poh(&[]).unwrap(), Header::empty_poh().unwrap());
+
+        let tx = random_signed_tx();
+        assert_ne!(
+            Header::gen_poh(&[tx.clone()]).unwrap(),
+            Header::empty_poh().unwrap()
+        );
+        assert_ne!(
+            Header::gen_tx_root(&[tx]).unwrap(),
+            Header::empty_tx_root().unwrap()
+        );
+    }
+
     #[test]
     fn test_header_parse_bytes() {
         let header = random_header(0, random_hash());
@@ -327,7 +393,7 @@ mod tests {
     }
 }
 
-pub fn random_header(height: usize, prev_hash: Hash) -> Header {
+pub fn random_header(height: u64, prev_hash: Hash) -> Header {
     let hash = random_hash();
     let prev_hash = prev_hash;
     let timestamp = timestamp(SystemTime::now());
@@ -338,7 +404,7 @@ pub fn random_header(height: usize, prev_hash: Hash) -> Header {
         version,
         blockhash: hash,
         prev_blockhash: prev_hash,
-        height,
+        height: BlockHeight(height),
         timestamp,
         tx_root: random_hash,
         state_root: random_hash,
@@ -0,0 +1,192 @@
+use std::{
+    fmt::{self, Display},
+    ops::{Add, AddAssign, Sub},
+};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use super::{encoding::ByteEncoding, error::CoreError};
+
+// height of a block in the chain, the genesis block is at height 0,
+// borsh and JSON encode it as a bare u64
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    BorshSerialize,
+    BorshDeserialize,
+    Serialize,
+    Deserialize,
+)]
+#[serde(transparent)]
+pub struct BlockHeight(pub u64);
+
+impl BlockHeight {
+    pub const GENESIS: BlockHeight = BlockHeight(0);
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+
+    pub fn next(&self) -> BlockHeight {
+        BlockHeight(self.0 + 1)
+    }
+
+    // None for the genesis block
+    pub fn prev(&self) -> Option<BlockHeight> {
+        self.0.checked_sub(1).map(BlockHeight)
+    }
+
+    pub fn saturating_sub(&self, count: u64) -> BlockHeight {
+        BlockHeight(self.0.saturating_sub(count))
+    }
+
+    // blocks from self up to and including end
+    pub fn range_to(&self, end: BlockHeight) -> impl DoubleEndedIterator<Item = BlockHeight> {
+        (self.0..=end.0).map(BlockHeight)
+    }
+}
+
+impl Add<u64> for BlockHeight {
+    type Output = BlockHeight;
+
+    fn add(self, rhs: u64) -> BlockHeight {
+        BlockHeight(self.0 + rhs)
+    }
+}
+
+impl AddAssign<u64> for BlockHeight {
+    fn add_assign(&mut self, rhs: u64) {
+        self.0 += rhs;
+    }
+}
+
+impl Sub<u64> for BlockHeight {
+    type Output = BlockHeight;
+
+    fn sub(self, rhs: u64) -> BlockHeight {
+        BlockHeight(self.0 - rhs)
+    }
+}
+
+// number of blocks between two heights
+impl Sub<BlockHeight> for BlockHeight {
+    type Output = u64;
+
+    fn sub(self, rhs: BlockHeight) -> u64 {
+        self.0 - rhs.0
+    }
+}
+
+impl PartialEq<u64> for BlockHeight {
+    fn eq(&self, other: &u64) -> bool {
+        self.0 == *other
+    }
+}
+
+impl From<u64> for BlockHeight {
+    fn from(value: u64) -> Self {
+        BlockHeight(value)
+    }
+}
+
+impl From<usize> for BlockHeight {
+    fn from(value: usize) -> Self {
+        BlockHeight(value as u64)
+    }
+}
+
+impl From<BlockHeight> for u64 {
+    fn from(value: BlockHeight) -> Self {
+        value.0
+    }
+}
+
+impl Display for BlockHeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// u64 big endian so byte order sorts the same as heights
+impl ByteEncoding<BlockHeight> for BlockHeight {
+    fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
+        Ok(self.0.to_be_bytes().to_vec())
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<BlockHeight, CoreError> {
+        let bytes: [u8; 8] = data.try_into().map_err(|_| {
+            CoreError::Parsing(format!(
+                "block height must be 8 bytes, found {}",
+                data.len()
+            ))
+        })?;
+        Ok(BlockHeight(u64::from_be_bytes(bytes)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_height_increment() {
+        let mut height = BlockHeight::GENESIS;
+        assert_eq!(height.prev(), None);
+
+        height += 1;
+        assert_eq!(height, BlockHeight(1));
+        assert_eq!(height.next(), 2);
+        assert_eq!(height + 4, 5);
+        assert_eq!(height.prev(), Some(BlockHeight::GENESIS));
+        assert_eq!(BlockHeight(10) - BlockHeight(3), 7);
+        assert_eq!(BlockHeight(2).saturating_sub(5), BlockHeight::GENESIS);
+        assert_eq!(
+            BlockHeight(2).range_to(BlockHeight(4)).collect::<Vec<_>>(),
+            vec![BlockHeight(2), BlockHeight(3), BlockHeight(4)]
+        );
+    }
+
+    #[test]
+    fn test_height_ordering() {
+        assert!(BlockHeight(1) < BlockHeight(2));
+        assert!(BlockHeight(256) > BlockHeight(255));
+        assert_eq!(BlockHeight(3).max(BlockHeight(7)), BlockHeight(7));
+
+        let mut heights = vec![BlockHeight(9), BlockHeight(0), BlockHeight(4)];
+        heights.sort();
+        assert_eq!(
+            heights,
+            vec![BlockHeight(0), BlockHeight(4), BlockHeight(9)]
+        );
+    }
+
+    #[test]
+    fn test_height_byte_round_trip() {
+        for height in [0, 1, 255, 256, u64::MAX] {
+            let height = BlockHeight(height);
+            let bytes = height.to_bytes().unwrap();
+            assert_eq!(bytes.len(), 8);
+            assert_eq!(BlockHeight::from_bytes(&bytes).unwrap(), height);
+        }
+
+        // byte order matches height order
+        assert!(BlockHeight(255).to_bytes().unwrap() < BlockHeight(256).to_bytes().unwrap());
+
+        // borsh and JSON are unchanged from the bare integer
+        assert_eq!(
+            borsh::to_vec(&BlockHeight(7)).unwrap(),
+            borsh::to_vec(&7u64).unwrap()
+        );
+        assert_eq!(serde_json::to_string(&BlockHeight(7)).unwrap(), "7");
+
+        assert!(BlockHeight::from_bytes(&[1, 2, 3]).is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fs::{File, OpenOptions};
@@ -10,6 +11,7 @@ use log::{error, info, warn};
 use rocksdb::IteratorMode;
 use serde::{Deserialize, Serialize};
 
+use crate::crypto::address::Address;
 use crate::crypto::public_key::PublicKeyBytes;
 use crate::crypto::signature::SignatureBytes;
 use crate::crypto::{
@@ -22,6 +24,7 @@ use super::{
     encoding::{ByteEncoding, HexEncoding},
     error::CoreError,
     header::Header,
+    height::BlockHeight,
     storage::{BlockStorage, MemoryBlockStorage},
     transaction::Transaction,
 };
@@ -62,7 +65,7 @@ impl BlockManager {
         self.store.put(&block)
     }
 
-    pub fn get_block_by_height(&self, height: usize) -> Option<Block> {
+    pub fn get_block_by_height(&self, height: BlockHeight) -> Option<Block> {
         let hash = match self.store.height_to_hash(height) {
             Some(hash) => hash,
             None => {
@@ -87,16 +90,14 @@ impl BlockManager {
         }
     }
 
-    pub fn get_header_by_height(&self, height: usize) -> Option<Header> {
-        match self.get_block_by_height(height) {
-            Some(block) => Some(block.header().clone()),
-            None => None,
-        }
+    pub fn get_header_by_height(&self, height: BlockHeight) -> Option<Header> {
+        let hash = self.store.height_to_hash(height)?;
+        self.get_header_by_hash(&hash)
     }
     pub fn get_header_by_hash(&self, hash: &str) -> Option<Header> {
-        match self.get_block_by_hash(hash) {
-            Some(block) => Some(block.header().clone()),
-            None => None,
+        match self.store.get_header(hash) {
+            Ok(header) => Some(header),
+            Err(_) => None,
         }
     }
 
@@ -110,15 +111,47 @@ impl BlockManager {
         }
     }
 
-    pub fn has_block(&self, height: usize) -> bool {
+    // genesis block if the store already holds one
+    pub fn genesis(&self) -> Option<Block> {
+        self.store.last_block_height()?;
+        let hash = self.store.height_to_hash(BlockHeight::GENESIS)?;
+        self.get_block_by_hash(&hash)
+    }
+
+    pub fn get_tx_height(&self, tx_hash: &str) -> Option<BlockHeight> {
+        self.store.tx_height(tx_hash)
+    }
+
+    pub fn sender_nonce(&self, sender: &Address) -> Option<u64> {
+        self.store.sender_nonce(sender)
+    }
+
+    pub fn sender_nonces(&self) -> Result<BTreeMap<Address, u64>, CoreError> {
+        self.store.sender_nonces()
+    }
+
+    pub fn replace_tip(&mut self, tip: &Block, block: Block) -> Result<(), CoreError> {
+        self.store.replace_tip(tip, &block)
+    }
+
+    pub fn index_txs(&mut self, block: &Block) -> Result<(), CoreError> {
+        self.store.index_txs(block)
+    }
+
+    pub fn clear_tx_index(&mut self) -> Result<(), CoreError> {
+        self.store.clear_tx_index()
+    }
+
+    pub fn has_block(&self, height: BlockHeight) -> bool {
         height <= self.height()
     }
 
-    pub fn height(&self) -> usize {
-        match self.store.last_block_height() {
-            Some(height) => height,
-            None => 0,
-        }
+    pub fn compact(&self) -> Result<(), CoreError> {
+        self.store.compact()
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        self.store.last_block_height().unwrap_or_default()
     }
 
     // ---
@@ -2,8 +2,11 @@ pub mod block;
 pub mod blockchain;
 pub mod encoding;
 pub mod error;
+pub mod genesis;
 pub mod header;
+pub mod height;
 pub mod manager;
 pub mod storage;
 pub mod transaction;
 pub mod util;
+pub mod view;
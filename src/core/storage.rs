@@ -1,73 +1,254 @@
-use super::{block::Block, encoding::HexEncoding, error::CoreError};
-use crate::{core::encoding::ByteEncoding, crypto::hash::Hash};
+use super::{
+    block::Block, encoding::HexEncoding, error::CoreError, header::Header, height::BlockHeight,
+};
+use crate::{
+    core::encoding::ByteEncoding,
+    crypto::{address::Address, hash::Hash},
+};
+use borsh::{BorshDeserialize, BorshSerialize};
 use log::{error, warn};
 use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, WriteBatch, DB};
 use std::str::FromStr;
-use std::{collections::HashMap, iter::Map};
+use std::{
+    collections::{BTreeMap, HashMap},
+    iter::Map,
+};
 
 pub trait BlockStorage: Send + Sync {
     fn put(&mut self, block: &Block) -> Result<(), CoreError>;
     fn get(&self, hash: &str) -> Result<Block, CoreError>;
-    fn height_to_hash(&self, height: usize) -> Option<String>;
-    fn last_block_height(&self) -> Option<usize>;
+    // header of the block without decoding its transactions
+    fn get_header(&self, hash: &str) -> Result<Header, CoreError>;
+    fn height_to_hash(&self, height: BlockHeight) -> Option<String>;
+    fn last_block_height(&self) -> Option<BlockHeight>;
+    // height of the block which included the transaction
+    fn tx_height(&self, tx_hash: &str) -> Option<BlockHeight>;
+    // highest nonce sent by sender in the stored blocks
+    fn sender_nonce(&self, sender: &Address) -> Option<u64>;
+    // sender_nonce of every sender which sent a transaction
+    fn sender_nonces(&self) -> Result<BTreeMap<Address, u64>, CoreError>;
+    // index the txs of block by hash to its height and raise the nonce of
+    // its senders, put indexes every block so this is only needed to
+    // rebuild the indexes
+    fn index_txs(&mut self, block: &Block) -> Result<(), CoreError>;
+    // remove every entry from the tx and sender nonce indexes
+    fn clear_tx_index(&mut self) -> Result<(), CoreError>;
+    // store block in place of tip, the last block put, the record and
+    // tx index entries of tip are removed and the sender nonces it raised
+    // are restored before block is indexed
+    fn replace_tip(&mut self, tip: &Block, block: &Block) -> Result<(), CoreError>;
+    // persist any buffered writes
+    fn flush(&self) -> Result<(), CoreError> {
+        Ok(())
+    }
+    // compact storage to drop deleted entries
+    fn compact(&self) -> Result<(), CoreError> {
+        Ok(())
+    }
+}
+
+// sender nonces from before the tip was indexed, None when the sender
+// had no nonce, only the tip can be replaced so only its undo is kept
+#[derive(BorshSerialize, BorshDeserialize, Default)]
+struct NonceUndo {
+    tip: String,
+    nonces: Vec<(Address, Option<u64>)>,
+}
+
+impl NonceUndo {
+    fn check_tip(&self, tip: &Block) -> Result<(), CoreError> {
+        if self.tip != tip.hash().to_hex()? {
+            return Err(CoreError::Block(format!(
+                "block: {} is not the indexed chain tip",
+                tip.hash()
+            )));
+        }
+        Ok(())
+    }
+}
+
+// highest nonce of every sender in block
+fn block_sender_nonces(block: &Block) -> BTreeMap<Address, u64> {
+    let mut nonces = BTreeMap::new();
+    for tx in block.txs() {
+        let nonce = nonces.entry(tx.sender.clone()).or_insert(tx.nonce);
+        *nonce = tx.nonce.max(*nonce);
+    }
+    nonces
 }
 
 pub struct MemoryBlockStorage {
     store: HashMap<String, Block>,
-    height_to_hash: HashMap<usize, String>,
-    last_block_height: usize,
+    height_to_hash: HashMap<BlockHeight, String>,
+    tx_index: HashMap<String, BlockHeight>,
+    nonce_index: BTreeMap<Address, u64>,
+    nonce_undo: NonceUndo,
+    last_block_height: BlockHeight,
 }
 impl MemoryBlockStorage {
     pub fn new() -> Self {
         Self {
             store: HashMap::new(),
-            last_block_height: 0,
+            last_block_height: BlockHeight::GENESIS,
             height_to_hash: HashMap::new(),
+            tx_index: HashMap::new(),
+            nonce_index: BTreeMap::new(),
+            nonce_undo: NonceUndo::default(),
         }
     }
 
     pub fn new_boxed() -> Box<Self> {
         Box::new(MemoryBlockStorage::new())
     }
+
+    // every key into store and height_to_hash comes from here so
+    // put and get always agree on the key for a hash
+    fn block_key(hash: &Hash) -> Result<String, CoreError> {
+        hash.to_hex()
+    }
+
+    fn get_block(&self, hash: &str) -> Result<&Block, CoreError> {
+        let key = Hash::from_hex(hash).and_then(|hash| Self::block_key(&hash));
+        match key.ok().and_then(|key| self.store.get(&key)) {
+            Some(block) => Ok(block),
+            None => Err(CoreError::Block(format!(
+                "block with hash: {hash} not found"
+            ))),
+        }
+    }
 }
 
 impl BlockStorage for MemoryBlockStorage {
     fn put(&mut self, block: &Block) -> Result<(), CoreError> {
+        let key = Self::block_key(block.hash())?;
         self.last_block_height = block.height();
-        self.height_to_hash
-            .insert(block.height(), block.hash().to_hex()?);
-        self.store.insert(block.hash().to_string(), block.clone());
+        self.height_to_hash.insert(block.height(), key.clone());
+        self.index_txs(block)?;
+        self.store.insert(key, block.clone());
         Ok(())
     }
 
     fn get(&self, hash: &str) -> Result<Block, CoreError> {
-        match self.store.get(hash) {
-            Some(block) => Ok(block.clone()),
-            None => Err(CoreError::Block(format!(
-                "block with hash: {hash} to found"
-            ))),
-        }
+        self.get_block(hash).cloned()
     }
 
-    fn height_to_hash(&self, height: usize) -> Option<String> {
+    fn get_header(&self, hash: &str) -> Result<Header, CoreError> {
+        self.get_block(hash).map(|block| block.header().clone())
+    }
+
+    fn height_to_hash(&self, height: BlockHeight) -> Option<String> {
         self.height_to_hash.get(&height).cloned()
     }
 
-    fn last_block_height(&self) -> Option<usize> {
+    fn last_block_height(&self) -> Option<BlockHeight> {
         Some(self.last_block_height)
     }
+
+    fn tx_height(&self, tx_hash: &str) -> Option<BlockHeight> {
+        self.tx_index.get(tx_hash).copied()
+    }
+
+    fn sender_nonce(&self, sender: &Address) -> Option<u64> {
+        self.nonce_index.get(sender).copied()
+    }
+
+    fn sender_nonces(&self) -> Result<BTreeMap<Address, u64>, CoreError> {
+        Ok(self.nonce_index.clone())
+    }
+
+    fn index_txs(&mut self, block: &Block) -> Result<(), CoreError> {
+        for tx in block.txs() {
+            if let Some(hash) = &tx.hash {
+                self.tx_index.insert(hash.to_string(), block.height());
+            }
+        }
+
+        let mut undo = NonceUndo {
+            tip: block.hash().to_hex()?,
+            nonces: vec![],
+        };
+        for (sender, nonce) in block_sender_nonces(block) {
+            let prev = self.nonce_index.get(&sender).copied();
+            self.nonce_index
+                .insert(sender.clone(), prev.map_or(nonce, |prev| prev.max(nonce)));
+            undo.nonces.push((sender, prev));
+        }
+        self.nonce_undo = undo;
+        Ok(())
+    }
+
+    fn clear_tx_index(&mut self) -> Result<(), CoreError> {
+        self.tx_index.clear();
+        self.nonce_index.clear();
+        self.nonce_undo = NonceUndo::default();
+        Ok(())
+    }
+
+    fn replace_tip(&mut self, tip: &Block, block: &Block) -> Result<(), CoreError> {
+        self.nonce_undo.check_tip(tip)?;
+        for (sender, prev) in std::mem::take(&mut self.nonce_undo.nonces) {
+            match prev {
+                Some(nonce) => self.nonce_index.insert(sender, nonce),
+                None => self.nonce_index.remove(&sender),
+            };
+        }
+        for tx in tip.txs() {
+            if let Some(hash) = &tx.hash {
+                self.tx_index.remove(&hash.to_string());
+            }
+        }
+        self.store.remove(&Self::block_key(tip.hash())?);
+        self.put(block)
+    }
+}
+
+// format version byte written before every stored block, bump when
+// the Block layout changes so old records fail with a clear error,
+// version 2 added gas_price to transactions
+pub const BLOCK_FORMAT_VERSION: u8 = 2;
+
+fn encode_block_record(block: &Block) -> Result<Vec<u8>, CoreError> {
+    let mut record = vec![BLOCK_FORMAT_VERSION];
+    record.extend_from_slice(&block.to_bytes()?);
+    Ok(record)
+}
+
+// strip and check the format version, returns the encoded block
+fn block_record_data(record: &[u8]) -> Result<&[u8], CoreError> {
+    match record.split_first() {
+        Some((&BLOCK_FORMAT_VERSION, data)) => Ok(data),
+        Some((version, _)) => Err(CoreError::Block(format!(
+            "unknown block format version: {version}, expected version: {BLOCK_FORMAT_VERSION}"
+        ))),
+        None => Err(CoreError::Block(
+            "empty block record in storage".to_string(),
+        )),
+    }
+}
+
+// key of the tip NonceUndo in the nonce index, every other key is an
+// encoded address
+const NONCE_UNDO_KEY: &[u8] = b"nonce_undo";
+
+// heights are keyed big endian so keys sort in height order
+fn height_key(height: BlockHeight) -> Result<Vec<u8>, CoreError> {
+    height.to_bytes()
 }
 
 pub struct DbBlockStorage {
     db: DB,
     block_cf: String,
     height_to_hash_cf: String,
+    tx_index_cf: String,
+    nonce_index_cf: String,
 }
 
 impl DbBlockStorage {
     pub fn new(path: &str) -> Self {
         let block_cf = "block_cf".to_string();
         let height_to_hash_cf = "height_to_hash_cf".to_string();
+        let tx_index_cf = "tx_index_cf".to_string();
+        let nonce_index_cf = "nonce_index_cf".to_string();
 
         let mut options = Options::default();
         options.create_if_missing(true);
@@ -76,19 +257,64 @@ impl DbBlockStorage {
         let block_cf_descriptor = ColumnFamilyDescriptor::new(&block_cf, Options::default());
         let height_cf_descriptor =
             ColumnFamilyDescriptor::new(&height_to_hash_cf, Options::default());
+        let tx_index_cf_descriptor = ColumnFamilyDescriptor::new(&tx_index_cf, Options::default());
+        let nonce_index_cf_descriptor =
+            ColumnFamilyDescriptor::new(&nonce_index_cf, Options::default());
 
         let db = DB::open_cf_descriptors(
             &options,
             path,
-            vec![block_cf_descriptor, height_cf_descriptor],
+            vec![
+                block_cf_descriptor,
+                height_cf_descriptor,
+                tx_index_cf_descriptor,
+                nonce_index_cf_descriptor,
+            ],
         )
         .expect("Unable to open DB with column families");
 
-        Self {
+        let storage = Self {
             db,
             block_cf,
             height_to_hash_cf,
+            tx_index_cf,
+            nonce_index_cf,
+        };
+        if let Err(e) = storage.migrate_height_keys() {
+            error!("unable to migrate height keys in DbBlockStorage, {e}");
         }
+        storage
+    }
+
+    // heights were once stored as decimal strings, which do not sort
+    // numerically, rewrite them as big endian keys
+    fn migrate_height_keys(&self) -> Result<(), CoreError> {
+        let height_cf = self.get_cf_handle(&self.height_to_hash_cf).ok_or_else(|| {
+            CoreError::Block("unable to get height column family from db".to_string())
+        })?;
+
+        let mut batch = WriteBatch::default();
+        for item in self.db.iterator_cf(height_cf, IteratorMode::Start) {
+            let (key, hash) = item.map_err(|e| CoreError::Block(e.to_string()))?;
+            // big endian keys start with a zero byte below 2^56
+            if key.first() == Some(&0) || !key.iter().all(u8::is_ascii_digit) {
+                continue;
+            }
+            let height = std::str::from_utf8(&key)
+                .ok()
+                .and_then(|key| u64::from_str(key).ok())
+                .ok_or_else(|| CoreError::Block("invalid legacy height key".to_string()))?;
+            batch.delete_cf(height_cf, &key);
+            batch.put_cf(height_cf, height_key(BlockHeight(height))?, hash);
+        }
+
+        if !batch.is_empty() {
+            warn!("migrating {} block height keys", batch.len() / 2);
+            self.db
+                .write(batch)
+                .map_err(|e| CoreError::Block(format!("unable to migrate height keys, {e}")))?;
+        }
+        Ok(())
     }
 
     pub fn new_boxed(path: &str) -> Box<Self> {
@@ -98,12 +324,69 @@ impl DbBlockStorage {
     fn get_cf_handle(&self, name: &str) -> Option<&ColumnFamily> {
         self.db.cf_handle(name)
     }
-}
 
-impl BlockStorage for DbBlockStorage {
-    fn put(&mut self, block: &Block) -> Result<(), CoreError> {
-        let mut batch = WriteBatch::default();
+    fn batch_tx_index(&self, batch: &mut WriteBatch, block: &Block) -> Result<(), CoreError> {
+        let tx_index_cf = self.get_cf_handle(&self.tx_index_cf).ok_or_else(|| {
+            CoreError::Block("unable to get tx index column family from db".to_string())
+        })?;
+        for tx in block.txs() {
+            if let Some(hash) = &tx.hash {
+                batch.put_cf(tx_index_cf, hash.to_string(), block.height().to_bytes()?);
+            }
+        }
+        Ok(())
+    }
 
+    fn nonce_index_handle(&self) -> Result<&ColumnFamily, CoreError> {
+        self.get_cf_handle(&self.nonce_index_cf).ok_or_else(|| {
+            CoreError::Block("unable to get nonce index column family from db".to_string())
+        })
+    }
+
+    fn read_sender_nonce(&self, sender: &Address) -> Result<Option<u64>, CoreError> {
+        let bytes = self
+            .db
+            .get_cf(self.nonce_index_handle()?, sender.to_bytes()?)
+            .map_err(|e| CoreError::Block(format!("unable to read nonce index, {e}")))?;
+        bytes.map(|bytes| decode_nonce(&bytes)).transpose()
+    }
+
+    // raise the nonce of the senders of block and record the undo for
+    // block, restored holds nonces written earlier in the same batch
+    fn batch_nonce_index(
+        &self,
+        batch: &mut WriteBatch,
+        block: &Block,
+        restored: &BTreeMap<Address, Option<u64>>,
+    ) -> Result<(), CoreError> {
+        let nonce_index_cf = self.nonce_index_handle()?;
+
+        let mut undo = NonceUndo {
+            tip: block.hash().to_hex()?,
+            nonces: vec![],
+        };
+        for (sender, nonce) in block_sender_nonces(block) {
+            let prev = match restored.get(&sender) {
+                Some(prev) => *prev,
+                None => self.read_sender_nonce(&sender)?,
+            };
+            let nonce = prev.map_or(nonce, |prev| prev.max(nonce));
+            batch.put_cf(nonce_index_cf, sender.to_bytes()?, nonce.to_be_bytes());
+            undo.nonces.push((sender, prev));
+        }
+
+        let undo = borsh::to_vec(&undo).map_err(|e| CoreError::Parsing(e.to_string()))?;
+        batch.put_cf(nonce_index_cf, NONCE_UNDO_KEY, undo);
+        Ok(())
+    }
+
+    // block record, height key and indexes of block
+    fn batch_block(
+        &self,
+        batch: &mut WriteBatch,
+        block: &Block,
+        restored: &BTreeMap<Address, Option<u64>>,
+    ) -> Result<(), CoreError> {
         let block_cf = self.get_cf_handle(&self.block_cf).ok_or_else(|| {
             CoreError::Block("unable to get block column family from db".to_string())
         })?;
@@ -113,15 +396,44 @@ impl BlockStorage for DbBlockStorage {
         })?;
 
         // Store block by hash in block_cf
-        batch.put_cf(block_cf, block.hash().to_hex()?, block.to_bytes()?);
+        batch.put_cf(
+            block_cf,
+            block.hash().to_hex()?,
+            encode_block_record(block)?,
+        );
 
-        let block_height = block.height();
         batch.put_cf(
             height_cf,
-            block_height.to_string(),
+            height_key(block.height())?,
             block.hash().to_bytes()?,
         );
 
+        // Index transactions by hash to the block height
+        self.batch_tx_index(batch, block)?;
+        self.batch_nonce_index(batch, block, restored)
+    }
+}
+
+fn decode_nonce(bytes: &[u8]) -> Result<u64, CoreError> {
+    bytes
+        .try_into()
+        .map(u64::from_be_bytes)
+        .map_err(|_| CoreError::Block("invalid nonce in nonce index".to_string()))
+}
+
+impl Drop for DbBlockStorage {
+    fn drop(&mut self) {
+        if let Err(e) = BlockStorage::flush(self) {
+            error!("unable to flush DbBlockStorage on drop, {e}");
+        }
+    }
+}
+
+impl BlockStorage for DbBlockStorage {
+    fn put(&mut self, block: &Block) -> Result<(), CoreError> {
+        let mut batch = WriteBatch::default();
+        self.batch_block(&mut batch, block, &BTreeMap::new())?;
+
         // Write batch
         self.db.write(batch).unwrap();
 
@@ -135,7 +447,7 @@ impl BlockStorage for DbBlockStorage {
 
         match self.db.get_cf(block_cf, hash) {
             Ok(res) => match res {
-                Some(bytes) => Ok(Block::from_bytes(&bytes)?),
+                Some(bytes) => Ok(Block::from_bytes(block_record_data(&bytes)?)?),
                 None => Err(CoreError::Block(format!(
                     "block not found with hash: {hash}"
                 ))),
@@ -144,7 +456,21 @@ impl BlockStorage for DbBlockStorage {
         }
     }
 
-    fn height_to_hash(&self, height: usize) -> Option<String> {
+    fn get_header(&self, hash: &str) -> Result<Header, CoreError> {
+        let block_cf = self.get_cf_handle(&self.block_cf).ok_or_else(|| {
+            CoreError::Block("unable to get block column family from db".to_string())
+        })?;
+
+        match self.db.get_pinned_cf(block_cf, hash) {
+            Ok(Some(bytes)) => Block::header_from_bytes(block_record_data(&bytes)?),
+            Ok(None) => Err(CoreError::Block(format!(
+                "block not found with hash: {hash}"
+            ))),
+            Err(e) => Err(CoreError::Block(e.to_string())),
+        }
+    }
+
+    fn height_to_hash(&self, height: BlockHeight) -> Option<String> {
         let height_to_hash_cf = match self.get_cf_handle(&self.height_to_hash_cf) {
             Some(cf) => cf,
             None => {
@@ -153,7 +479,15 @@ impl BlockStorage for DbBlockStorage {
             }
         };
 
-        match self.db.get_cf(height_to_hash_cf, height.to_string()) {
+        let key = match height_key(height) {
+            Ok(key) => key,
+            Err(e) => {
+                error!("unable to encode height key in height_to_hash, {e}");
+                return None;
+            }
+        };
+
+        match self.db.get_cf(height_to_hash_cf, key) {
             Ok(Some(hash_bytes)) => match Hash::from_bytes(&hash_bytes) {
                 Ok(hash) => Some(hash.to_hex().unwrap()),
                 Err(_) => {
@@ -172,7 +506,7 @@ impl BlockStorage for DbBlockStorage {
         }
     }
 
-    fn last_block_height(&self) -> Option<usize> {
+    fn last_block_height(&self) -> Option<BlockHeight> {
         let height_to_hash_cf = match self.get_cf_handle(&self.height_to_hash_cf) {
             Some(cf) => cf,
             None => {
@@ -184,24 +518,180 @@ impl BlockStorage for DbBlockStorage {
         let mut iter = self.db.iterator_cf(height_to_hash_cf, IteratorMode::End);
 
         if let Some(Ok((key, _))) = iter.next() {
-            let key_str = String::from_utf8(key.to_vec()).ok()?;
-            // Convert the string key to usize
-            let height = usize::from_str(&key_str).ok();
-            height
+            BlockHeight::from_bytes(&key).ok()
         } else {
             error!("no blocks found in database");
 
             None // No blocks in the database
         }
     }
+
+    // sync the WAL and flush memtables of every column family to disk
+    fn flush(&self) -> Result<(), CoreError> {
+        self.db
+            .flush_wal(true)
+            .map_err(|e| CoreError::Block(format!("unable to flush WAL, {e}")))?;
+
+        for name in [
+            &self.block_cf,
+            &self.height_to_hash_cf,
+            &self.tx_index_cf,
+            &self.nonce_index_cf,
+        ] {
+            let cf = self.get_cf_handle(name).ok_or_else(|| {
+                CoreError::Block(format!("unable to get column family: {name} from db"))
+            })?;
+            self.db
+                .flush_cf(cf)
+                .map_err(|e| CoreError::Block(format!("unable to flush {name}, {e}")))?;
+        }
+
+        Ok(())
+    }
+
+    // compact the full key range of every column family
+    fn compact(&self) -> Result<(), CoreError> {
+        for name in [
+            &self.block_cf,
+            &self.height_to_hash_cf,
+            &self.tx_index_cf,
+            &self.nonce_index_cf,
+        ] {
+            let cf = self.get_cf_handle(name).ok_or_else(|| {
+                CoreError::Block(format!("unable to get column family: {name} from db"))
+            })?;
+            self.db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+
+        Ok(())
+    }
+
+    fn tx_height(&self, tx_hash: &str) -> Option<BlockHeight> {
+        let tx_index_cf = match self.get_cf_handle(&self.tx_index_cf) {
+            Some(cf) => cf,
+            None => {
+                error!("unable to get ColumnFamily handle in tx_height");
+                return None;
+            }
+        };
+
+        match self.db.get_cf(tx_index_cf, tx_hash) {
+            Ok(Some(bytes)) => BlockHeight::from_bytes(&bytes).ok(),
+            Ok(None) => None,
+            Err(e) => {
+                error!("error getting height from tx_index_cf, {e}");
+                None
+            }
+        }
+    }
+
+    fn sender_nonce(&self, sender: &Address) -> Option<u64> {
+        match self.read_sender_nonce(sender) {
+            Ok(nonce) => nonce,
+            Err(e) => {
+                error!("error getting nonce from nonce_index_cf, {e}");
+                None
+            }
+        }
+    }
+
+    fn sender_nonces(&self) -> Result<BTreeMap<Address, u64>, CoreError> {
+        let mut nonces = BTreeMap::new();
+        for item in self
+            .db
+            .iterator_cf(self.nonce_index_handle()?, IteratorMode::Start)
+        {
+            let (key, value) =
+                item.map_err(|e| CoreError::Block(format!("unable to read nonce index, {e}")))?;
+            if &*key == NONCE_UNDO_KEY {
+                continue;
+            }
+            nonces.insert(Address::from_bytes(&key)?, decode_nonce(&value)?);
+        }
+        Ok(nonces)
+    }
+
+    fn index_txs(&mut self, block: &Block) -> Result<(), CoreError> {
+        let mut batch = WriteBatch::default();
+        self.batch_tx_index(&mut batch, block)?;
+        self.batch_nonce_index(&mut batch, block, &BTreeMap::new())?;
+        self.db
+            .write(batch)
+            .map_err(|e| CoreError::Block(format!("unable to write tx index, {e}")))
+    }
+
+    fn clear_tx_index(&mut self) -> Result<(), CoreError> {
+        let tx_index_cf = self.get_cf_handle(&self.tx_index_cf).ok_or_else(|| {
+            CoreError::Block("unable to get tx index column family from db".to_string())
+        })?;
+
+        let mut batch = WriteBatch::default();
+        for cf in [tx_index_cf, self.nonce_index_handle()?] {
+            for item in self.db.iterator_cf(cf, IteratorMode::Start) {
+                let (key, _) =
+                    item.map_err(|e| CoreError::Block(format!("unable to read tx index, {e}")))?;
+                batch.delete_cf(cf, key);
+            }
+        }
+
+        self.db
+            .write(batch)
+            .map_err(|e| CoreError::Block(format!("unable to clear tx index, {e}")))
+    }
+
+    fn replace_tip(&mut self, tip: &Block, block: &Block) -> Result<(), CoreError> {
+        let nonce_index_cf = self.nonce_index_handle()?;
+        let undo = self
+            .db
+            .get_cf(nonce_index_cf, NONCE_UNDO_KEY)
+            .map_err(|e| CoreError::Block(format!("unable to read nonce index, {e}")))?
+            .map(|bytes| borsh::from_slice::<NonceUndo>(&bytes))
+            .transpose()
+            .map_err(|e| CoreError::Parsing(e.to_string()))?
+            .unwrap_or_default();
+        undo.check_tip(tip)?;
+
+        let block_cf = self.get_cf_handle(&self.block_cf).ok_or_else(|| {
+            CoreError::Block("unable to get block column family from db".to_string())
+        })?;
+        let tx_index_cf = self.get_cf_handle(&self.tx_index_cf).ok_or_else(|| {
+            CoreError::Block("unable to get tx index column family from db".to_string())
+        })?;
+
+        // batch writes apply in order so entries shared with block are
+        // deleted and then written again
+        let mut batch = WriteBatch::default();
+        batch.delete_cf(block_cf, tip.hash().to_hex()?);
+        for tx in tip.txs() {
+            if let Some(hash) = &tx.hash {
+                batch.delete_cf(tx_index_cf, hash.to_string());
+            }
+        }
+        for (sender, prev) in &undo.nonces {
+            match prev {
+                Some(nonce) => {
+                    batch.put_cf(nonce_index_cf, sender.to_bytes()?, nonce.to_be_bytes())
+                }
+                None => batch.delete_cf(nonce_index_cf, sender.to_bytes()?),
+            }
+        }
+        let restored = undo.nonces.into_iter().collect();
+        self.batch_block(&mut batch, block, &restored)?;
+
+        self.db
+            .write(batch)
+            .map_err(|e| CoreError::Block(format!("unable to replace chain tip, {e}")))
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::block::random_block;
+    use crate::core::block::Block;
     use crate::core::header::random_header;
-    use crate::core::{block::Block, header::Header}; // Adjust the import path based on your project structure
+    use crate::core::transaction::random_tx;
+    use crate::crypto::private_key::PrivateKey;
     use crate::crypto::utils::random_hash; // Adjust the import path based on your project structure
     use tempfile::tempdir;
 
@@ -213,14 +703,34 @@ mod tests {
         let block = random_block(random_header);
         assert!(storage.put(&block).is_ok());
 
-        assert_eq!(storage.last_block_height(), Some(1));
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(1)));
         assert_eq!(
-            storage.height_to_hash(1),
+            storage.height_to_hash(BlockHeight(1)),
             Some(block.hash().to_hex().unwrap())
         );
         assert_eq!(storage.get(&block.hash().to_hex().unwrap()).unwrap(), block);
     }
 
+    #[test]
+    fn test_in_mem_key_round_trip() {
+        let mut storage = MemoryBlockStorage::new();
+
+        for height in 0..3 {
+            let block = random_block(random_header(height, random_hash()));
+            storage.put(&block).unwrap();
+
+            let hash = storage.height_to_hash(BlockHeight(height)).unwrap();
+            assert_eq!(storage.get(&hash).unwrap(), block);
+            assert_eq!(&storage.get_header(&hash).unwrap(), block.header());
+
+            // hex case of the requested hash does not matter
+            assert_eq!(storage.get(&hash.to_uppercase()).unwrap(), block);
+        }
+
+        assert!(storage.get("not a hash").is_err());
+        assert!(storage.get(&random_hash().to_hex().unwrap()).is_err());
+    }
+
     #[test]
     fn test_in_mem_get_block() {
         let mut storage = MemoryBlockStorage::new();
@@ -236,6 +746,41 @@ mod tests {
         assert!(non_existent_block.is_err());
     }
 
+    #[test]
+    fn test_db_block_format_version() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let mut storage = DbBlockStorage::new(db_path);
+
+        let block = random_block(random_header(0, random_hash()));
+        storage.put(&block).unwrap();
+        let hash = block.hash().to_hex().unwrap();
+
+        let block_cf = storage.get_cf_handle(&storage.block_cf).unwrap();
+        let mut record = storage.db.get_cf(block_cf, &hash).unwrap().unwrap();
+        assert_eq!(record[0], BLOCK_FORMAT_VERSION);
+
+        // records written before transactions had a gas price and by
+        // an unknown future format
+        for version in [1, BLOCK_FORMAT_VERSION + 1] {
+            record[0] = version;
+            storage.db.put_cf(block_cf, &hash, &record).unwrap();
+
+            for err in [
+                storage.get(&hash).unwrap_err(),
+                storage.get_header(&hash).unwrap_err(),
+            ] {
+                match err {
+                    CoreError::Block(msg) => assert!(
+                        msg.contains(&format!("unknown block format version: {version}")),
+                        "{msg}"
+                    ),
+                    e => panic!("expected block format error, got {e}"),
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_in_mem_height_to_hash() {
         let mut storage = MemoryBlockStorage::new();
@@ -249,21 +794,21 @@ mod tests {
         storage.put(&block2).unwrap();
 
         assert_eq!(
-            storage.height_to_hash(1),
+            storage.height_to_hash(BlockHeight(1)),
             Some(block1.hash().to_hex().unwrap())
         );
         assert_eq!(
-            storage.height_to_hash(2),
+            storage.height_to_hash(BlockHeight(2)),
             Some(block2.hash().to_hex().unwrap())
         );
-        assert_eq!(storage.height_to_hash(3), None);
+        assert_eq!(storage.height_to_hash(BlockHeight(3)), None);
     }
 
     #[test]
     fn test_in_mem_last_block_height() {
         let mut storage = MemoryBlockStorage::new();
 
-        assert_eq!(storage.last_block_height(), Some(0)); // Initially no blocks, so height should
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(0))); // Initially no blocks, so height should
 
         let random_header_1 = random_header(1, random_hash());
         let random_header_2 = random_header(2, random_hash());
@@ -271,10 +816,10 @@ mod tests {
         let block2 = random_block(random_header_2);
 
         storage.put(&block1).unwrap();
-        assert_eq!(storage.last_block_height(), Some(1));
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(1)));
 
         storage.put(&block2).unwrap();
-        assert_eq!(storage.last_block_height(), Some(2));
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(2)));
     }
 
     // DB Storage Tests
@@ -289,9 +834,9 @@ mod tests {
         let block = random_block(random_header);
         assert!(storage.put(&block).is_ok());
 
-        assert_eq!(storage.last_block_height(), Some(1));
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(1)));
         assert_eq!(
-            storage.height_to_hash(1),
+            storage.height_to_hash(BlockHeight(1)),
             Some(block.hash().to_hex().unwrap())
         );
         assert_eq!(storage.get(&block.hash().to_hex().unwrap()).unwrap(), block);
@@ -329,14 +874,14 @@ mod tests {
         storage.put(&block2).unwrap();
 
         assert_eq!(
-            storage.height_to_hash(1),
+            storage.height_to_hash(BlockHeight(1)),
             Some(block1.hash().to_hex().unwrap())
         );
         assert_eq!(
-            storage.height_to_hash(2),
+            storage.height_to_hash(BlockHeight(2)),
             Some(block2.hash().to_hex().unwrap())
         );
-        assert_eq!(storage.height_to_hash(3), None);
+        assert_eq!(storage.height_to_hash(BlockHeight(3)), None);
     }
 
     #[test]
@@ -353,9 +898,164 @@ mod tests {
         let block2 = random_block(random_header_2);
 
         storage.put(&block1).unwrap();
-        assert_eq!(storage.last_block_height(), Some(1));
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(1)));
 
         storage.put(&block2).unwrap();
-        assert_eq!(storage.last_block_height(), Some(2));
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(2)));
+    }
+
+    #[test]
+    fn test_db_writes_durable_after_drop() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+
+        let blocks: Vec<Block> = (1..=5)
+            .map(|height| random_block(random_header(height, random_hash())))
+            .collect();
+
+        let mut storage = DbBlockStorage::new(db_path);
+        for block in blocks.iter() {
+            storage.put(block).unwrap();
+        }
+        drop(storage);
+
+        // reopening the same path succeeds once the lock is released
+        let storage = DbBlockStorage::new(db_path);
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(5)));
+        for block in blocks.iter() {
+            let hash = block.hash().to_hex().unwrap();
+            assert_eq!(storage.height_to_hash(block.height()), Some(hash.clone()));
+            assert_eq!(&storage.get(&hash).unwrap(), block);
+        }
+    }
+
+    #[test]
+    fn test_db_last_block_height_past_ten() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+
+        let blocks: Vec<Block> = (1..=12)
+            .map(|height| random_block(random_header(height, random_hash())))
+            .collect();
+
+        let mut storage = DbBlockStorage::new(db_path);
+        for block in blocks.iter() {
+            storage.put(block).unwrap();
+        }
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(12)));
+        drop(storage);
+
+        let storage = DbBlockStorage::new(db_path);
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(12)));
+        assert_eq!(
+            storage.height_to_hash(BlockHeight(10)),
+            Some(blocks[9].hash().to_hex().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_db_migrates_decimal_height_keys() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+
+        let blocks: Vec<Block> = (9..=11)
+            .map(|height| random_block(random_header(height, random_hash())))
+            .collect();
+
+        let mut storage = DbBlockStorage::new(db_path);
+        for block in blocks.iter() {
+            storage.put(block).unwrap();
+        }
+
+        // rewrite keys in the old decimal format
+        let height_cf = storage.get_cf_handle(&storage.height_to_hash_cf).unwrap();
+        for block in blocks.iter() {
+            let key = height_key(block.height()).unwrap();
+            let hash = storage.db.get_cf(height_cf, &key).unwrap().unwrap();
+            storage.db.delete_cf(height_cf, key).unwrap();
+            storage
+                .db
+                .put_cf(height_cf, block.height().to_string(), hash)
+                .unwrap();
+        }
+        drop(storage);
+
+        let storage = DbBlockStorage::new(db_path);
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(11)));
+        for block in blocks.iter() {
+            assert_eq!(
+                storage.height_to_hash(block.height()),
+                Some(block.hash().to_hex().unwrap())
+            );
+        }
+    }
+
+    fn nonce_block(height: u64, txs: &[(&Address, u64)]) -> Block {
+        let txs = txs
+            .iter()
+            .map(|(sender, nonce)| {
+                let mut tx = random_tx();
+                tx.sender = (*sender).clone();
+                tx.nonce = *nonce;
+                tx.sign(&PrivateKey::new()).unwrap();
+                tx
+            })
+            .collect();
+        Block::new(random_header(height, random_hash()), txs).unwrap()
+    }
+
+    fn check_replace_tip(storage: &mut dyn BlockStorage) {
+        let a = PrivateKey::new().address();
+        let b = PrivateKey::new().address();
+        let c = PrivateKey::new().address();
+
+        let first = nonce_block(1, &[(&a, 1)]);
+        let tip = nonce_block(2, &[(&a, 3), (&a, 2), (&b, 1)]);
+        storage.put(&first).unwrap();
+        storage.put(&tip).unwrap();
+        assert_eq!(storage.sender_nonce(&a), Some(3));
+        assert_eq!(storage.sender_nonce(&b), Some(1));
+
+        // only the last block put can be replaced
+        let block = nonce_block(2, &[(&c, 5)]);
+        assert!(storage.replace_tip(&first, &block).is_err());
+
+        storage.replace_tip(&tip, &block).unwrap();
+        assert_eq!(storage.last_block_height(), Some(BlockHeight(2)));
+        assert_eq!(storage.get(&block.hash().to_hex().unwrap()).unwrap(), block);
+        assert!(storage.get(&tip.hash().to_hex().unwrap()).is_err());
+        for tx in tip.txs() {
+            assert_eq!(storage.tx_height(&tx.hash.unwrap().to_string()), None);
+        }
+        for tx in block.txs() {
+            let hash = tx.hash.unwrap().to_string();
+            assert_eq!(storage.tx_height(&hash), Some(BlockHeight(2)));
+        }
+        assert_eq!(storage.sender_nonce(&a), Some(1));
+        assert_eq!(storage.sender_nonce(&b), None);
+        assert_eq!(
+            storage.sender_nonces().unwrap(),
+            BTreeMap::from([(a.clone(), 1), (c.clone(), 5)])
+        );
+
+        storage.clear_tx_index().unwrap();
+        assert!(storage.sender_nonces().unwrap().is_empty());
+        storage.index_txs(&first).unwrap();
+        storage.index_txs(&block).unwrap();
+        assert_eq!(
+            storage.sender_nonces().unwrap(),
+            BTreeMap::from([(a, 1), (c, 5)])
+        );
+    }
+
+    #[test]
+    fn test_in_mem_replace_tip() {
+        check_replace_tip(&mut MemoryBlockStorage::new());
+    }
+
+    #[test]
+    fn test_db_replace_tip() {
+        let temp_dir = tempdir().unwrap();
+        check_replace_tip(&mut DbBlockStorage::new(temp_dir.path().to_str().unwrap()));
     }
 }
@@ -20,6 +20,9 @@ use super::{
     error::CoreError,
 };
 
+// gas price of transactions created without one
+pub const DEFAULT_GAS_PRICE: u64 = 1;
+
 #[serde_as]
 #[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
 pub struct Transaction {
@@ -29,7 +32,14 @@ pub struct Transaction {
     pub sender: Address,
     pub blockhash: Hash,
     pub hash: Option<Hash>,
+    // most gas the transaction may use
     pub gas_limit: u64,
+    // paid per unit of gas used, also the priority of the transaction
+    // in the mem_pool, higher prices are taken first
+    pub gas_price: u64,
+    // per sender sequence number, first transaction from an account is 1,
+    // 0 means the transaction is not nonce ordered eg. rewards
+    pub nonce: u64,
     pub signature: Option<SignatureBytes>,
     pub signer: Option<PublicKeyBytes>,
 }
@@ -49,6 +59,7 @@ impl Transaction {
         data: &[u8],
         gas_limit: u64,
     ) -> Result<Self, CoreError> {
+        Self::check_data(&tx_type, data)?;
         let data = data.to_vec();
 
         Ok(Self {
@@ -58,6 +69,8 @@ impl Transaction {
             sender,
             blockhash,
             gas_limit,
+            gas_price: DEFAULT_GAS_PRICE,
+            nonce: 0,
             signature: None,
             signer: None,
             hash: None,
@@ -78,12 +91,50 @@ impl Transaction {
             data: data.to_vec(),
             blockhash,
             gas_limit,
+            gas_price: DEFAULT_GAS_PRICE,
+            nonce: 0,
             signature: None,
             signer: None,
             hash: None,
         })
     }
 
+    // returns error naming the first transaction without a hash, a
+    // transaction only gets its hash once it is signed
+    pub fn check_signed(txs: &[Transaction]) -> Result<(), CoreError> {
+        match txs.iter().position(|tx| tx.hash.is_none()) {
+            Some(index) => Err(CoreError::Transaction(format!(
+                "transaction at index: {index} has no hash, transactions must be signed before inclusion in a block"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    // returns error if data does not decode into the payload for tx_type
+    pub fn check_data(tx_type: &TxType, data: &[u8]) -> Result<(), CoreError> {
+        let res = match tx_type {
+            TxType::Transfer => TransferData::from_bytes(data).map(|_| ()),
+            TxType::SmartContract => SmartContractData::from_bytes(data).map(|_| ()),
+            TxType::BlockReward | TxType::GasReward => {
+                BlockRewardData::from_bytes(data).map(|_| ())
+            }
+        };
+
+        res.map_err(|e| {
+            CoreError::Transaction(format!("transaction data does not match tx type, {e}"))
+        })
+    }
+
+    // fee paid for gas_used units of gas
+    pub fn fee(&self, gas_used: u64) -> u64 {
+        gas_used.saturating_mul(self.gas_price)
+    }
+
+    // fee if the whole gas limit is used, reserved from the sender up front
+    pub fn max_fee(&self) -> u64 {
+        self.fee(self.gas_limit)
+    }
+
     pub fn hash(&self) -> Result<Hash, CoreError> {
         match self.hash {
             Some(d) => Ok(d),
@@ -91,6 +142,30 @@ impl Transaction {
         }
     }
 
+    // hash of the signed content of the transaction, two transactions
+    // with the same content hash are duplicates
+    pub fn content_hash(&self) -> Result<Hash, CoreError> {
+        Ok(Hash::sha256(&self.hashable_data())?)
+    }
+
+    // returns None if the transaction does not involve the address
+    pub fn category(&self, address: &Address) -> Option<TxCategory> {
+        match self.tx_type {
+            TxType::BlockReward if &self.receiver == address => Some(TxCategory::Reward),
+            TxType::GasReward if &self.receiver == address => Some(TxCategory::Fee),
+            TxType::Transfer | TxType::SmartContract => {
+                if &self.sender == address {
+                    Some(TxCategory::Sent)
+                } else if &self.receiver == address {
+                    Some(TxCategory::Received)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
     pub fn signature(&self) -> Result<SignatureBytes, CoreError> {
         match &self.signature {
             Some(d) => Ok(d.clone()),
@@ -129,6 +204,13 @@ impl Transaction {
 
         // Include the block hash
         buf.extend_from_slice(&self.blockhash.to_bytes().unwrap());
+
+        // Include the gas limit and price
+        buf.extend_from_slice(&self.gas_limit.to_be_bytes());
+        buf.extend_from_slice(&self.gas_price.to_be_bytes());
+
+        // Include the sender nonce
+        buf.extend_from_slice(&self.nonce.to_be_bytes());
         buf
     }
 
@@ -220,6 +302,16 @@ impl HexEncoding<Transaction> for Transaction {
     }
 }
 
+// category of a transaction relative to an address, used by wallets
+// to display account history
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TxCategory {
+    Sent,
+    Received,
+    Reward,
+    Fee,
+}
+
 #[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
 pub enum TxType {
     Transfer,
@@ -314,7 +406,9 @@ impl ByteEncoding<BlockRewardData> for BlockRewardData {
 
 #[cfg(test)]
 mod tests {
-    use crate::crypto::{address::random_sender_receiver, utils::random_hash};
+    use crate::crypto::{
+        address::random_sender_receiver, scheme::SignatureScheme, utils::random_hash,
+    };
 
     use super::*;
     #[test]
@@ -391,6 +485,65 @@ mod tests {
         assert_eq!(tx_2_pub_key.to_bytes().unwrap(), pub_key)
     }
 
+    #[test]
+    fn test_transaction_decodes_legacy_signature_bytes() {
+        // layout written before signature schemes, signature and signer
+        // were plain fixed size byte arrays
+        #[derive(BorshSerialize)]
+        struct LegacyTransaction {
+            tx_type: TxType,
+            data: Vec<u8>,
+            receiver: Address,
+            sender: Address,
+            blockhash: Hash,
+            hash: Option<Hash>,
+            gas_limit: u64,
+            gas_price: u64,
+            nonce: u64,
+            signature: Option<[u8; 64]>,
+            signer: Option<[u8; 33]>,
+        }
+
+        let priv_key = PrivateKey::new();
+        let (sender, receiver) = random_sender_receiver();
+        let mut tx = Transaction::new_transfer(sender, receiver, random_hash(), b"", 3).unwrap();
+        tx.sign(&priv_key).unwrap();
+
+        let legacy = LegacyTransaction {
+            tx_type: tx.tx_type.clone(),
+            data: tx.data.clone(),
+            receiver: tx.receiver.clone(),
+            sender: tx.sender.clone(),
+            blockhash: tx.blockhash.clone(),
+            hash: tx.hash.clone(),
+            gas_limit: tx.gas_limit,
+            gas_price: tx.gas_price,
+            nonce: tx.nonce,
+            signature: tx
+                .signature
+                .as_ref()
+                .map(|sig| sig.signature().try_into().unwrap()),
+            signer: tx.signer.as_ref().map(|key| key.key().try_into().unwrap()),
+        };
+        let legacy_bytes = borsh::to_vec(&legacy).unwrap();
+
+        // secp256k1 transactions still encode to the legacy layout
+        assert_eq!(borsh::to_vec(&tx).unwrap(), legacy_bytes);
+
+        let decoded = Transaction::try_from_slice(&legacy_bytes).unwrap();
+        assert_eq!(decoded, tx);
+        assert!(decoded.verify().is_ok());
+
+        // other schemes are tagged and round trip
+        let mut ed_tx = tx.clone();
+        ed_tx.signature =
+            Some(SignatureBytes::new_with_scheme(SignatureScheme::Ed25519, &[7; 64]).unwrap());
+        ed_tx.signer =
+            Some(PublicKeyBytes::new_with_scheme(SignatureScheme::Ed25519, &[9; 32]).unwrap());
+        let ed_bytes = borsh::to_vec(&ed_tx).unwrap();
+        assert_eq!(Transaction::try_from_slice(&ed_bytes).unwrap(), ed_tx);
+    }
+
     #[test]
     fn test_transaction_parse_hex() {
         let priv_key = PrivateKey::new();
@@ -431,6 +584,95 @@ mod tests {
 
         assert_eq!(tx_2_hash, tx_2_hash);
     }
+
+    #[test]
+    fn test_new_checks_data_matches_type() {
+        let (sender, receiver) = random_sender_receiver();
+        let hash = random_hash();
+
+        let transfer = TransferData {
+            to: receiver.clone(),
+            from: sender.clone(),
+            amount: 5,
+        }
+        .to_bytes()
+        .unwrap();
+        let contract = SmartContractData {
+            contract_address: receiver.clone(),
+            method: "call".to_string(),
+            params: vec![1, 2, 3],
+        }
+        .to_bytes()
+        .unwrap();
+        let reward = BlockRewardData {
+            to: receiver.clone(),
+            amount: 5,
+        }
+        .to_bytes()
+        .unwrap();
+
+        let new = |tx_type: TxType, data: &[u8]| {
+            Transaction::new(tx_type, hash, receiver.clone(), sender.clone(), data, 1)
+        };
+
+        assert!(new(TxType::Transfer, &transfer).is_ok());
+        assert!(new(TxType::SmartContract, &contract).is_ok());
+        assert!(new(TxType::BlockReward, &reward).is_ok());
+        assert!(new(TxType::GasReward, &reward).is_ok());
+
+        for (tx_type, data) in [
+            (TxType::Transfer, reward.as_slice()),
+            (TxType::SmartContract, b"data".as_slice()),
+            (TxType::BlockReward, transfer.as_slice()),
+            (TxType::GasReward, &[]),
+        ] {
+            assert!(matches!(new(tx_type, data), Err(CoreError::Transaction(_))));
+        }
+    }
+
+    #[test]
+    fn test_transaction_category() {
+        let (sender, receiver) = random_sender_receiver();
+        let (other, _) = random_sender_receiver();
+        let hash = random_hash();
+
+        let transfer =
+            Transaction::new_transfer(receiver.clone(), sender.clone(), hash, b"data", 1).unwrap();
+        assert_eq!(transfer.category(&sender), Some(TxCategory::Sent));
+        assert_eq!(transfer.category(&receiver), Some(TxCategory::Received));
+        assert_eq!(transfer.category(&other), None);
+
+        let reward_data = BlockRewardData {
+            to: receiver.clone(),
+            amount: 1,
+        }
+        .to_bytes()
+        .unwrap();
+
+        let reward = Transaction::new(
+            TxType::BlockReward,
+            hash,
+            receiver.clone(),
+            receiver.clone(),
+            &reward_data,
+            0,
+        )
+        .unwrap();
+        assert_eq!(reward.category(&receiver), Some(TxCategory::Reward));
+        assert_eq!(reward.category(&sender), None);
+
+        let fee = Transaction::new(
+            TxType::GasReward,
+            hash,
+            receiver.clone(),
+            receiver.clone(),
+            &reward_data,
+            0,
+        )
+        .unwrap();
+        assert_eq!(fee.category(&receiver), Some(TxCategory::Fee));
+        assert_eq!(fee.category(&sender), None);
+    }
 }
 
 pub fn random_tx() -> Transaction {
@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+
+use super::{block::Block, height::BlockHeight};
+
+// number of most recent blocks kept in the view
+pub const CHAIN_VIEW_CAPACITY: usize = 128;
+
+// read only snapshot of the chain tip, updated each time a block is added
+// to the Blockchain, readers use the RwLock so they never contend
+// on the Blockchain write mutex
+#[derive(Debug, Clone, Default)]
+pub struct ChainView {
+    height: BlockHeight,
+    recent: VecDeque<Block>,
+}
+
+impl ChainView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_shared() -> SharedChainView {
+        Arc::new(RwLock::new(Self::new()))
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        self.height
+    }
+
+    pub fn last_block(&self) -> Option<Block> {
+        self.recent.back().cloned()
+    }
+
+    // returns None if block is not within the recent window,
+    // callers should fall back to chain storage
+    pub fn get_block_by_height(&self, height: BlockHeight) -> Option<Block> {
+        let first = self.recent.front()?.height();
+        if height < first {
+            return None;
+        }
+        self.recent
+            .get((height - first) as usize)
+            .filter(|block| block.height() == height)
+            .cloned()
+    }
+
+    pub fn get_block_by_hash(&self, hash: &str) -> Option<Block> {
+        self.recent
+            .iter()
+            .rev()
+            .find(|block| block.hash().to_string() == hash)
+            .cloned()
+    }
+
+    pub fn push(&mut self, block: Block) {
+        self.height = block.height();
+        self.recent.push_back(block);
+
+        while self.recent.len() > CHAIN_VIEW_CAPACITY {
+            self.recent.pop_front();
+        }
+    }
+
+    // swap the last block for another at the same height
+    pub fn replace_last(&mut self, block: Block) {
+        if self.last_block().map(|last| last.height()) == Some(block.height()) {
+            self.recent.pop_back();
+        }
+        self.push(block);
+    }
+}
+
+pub type SharedChainView = Arc<RwLock<ChainView>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{
+        block::{random_block, random_signed_block},
+        blockchain::Blockchain,
+        header::random_header,
+    };
+    use crate::crypto::utils::random_hash;
+    use crate::lock;
+    use std::sync::{mpsc::channel, Mutex};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_chain_view_window() {
+        let mut view = ChainView::new();
+
+        for height in 0..CHAIN_VIEW_CAPACITY as u64 + 10 {
+            view.push(random_block(random_header(height, random_hash())));
+        }
+
+        assert_eq!(view.height(), CHAIN_VIEW_CAPACITY as u64 + 9);
+        assert_eq!(
+            view.last_block().unwrap().height(),
+            CHAIN_VIEW_CAPACITY as u64 + 9
+        );
+
+        // oldest blocks have fallen out of the window
+        assert!(view.get_block_by_height(BlockHeight::GENESIS).is_none());
+
+        let block = view.get_block_by_height(BlockHeight(20)).unwrap();
+        assert_eq!(block.height(), 20);
+
+        let found = view.get_block_by_hash(&block.hash().to_string()).unwrap();
+        assert_eq!(found.height(), 20);
+    }
+
+    #[test]
+    fn test_chain_view_reads_while_chain_locked() {
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+        let genesis = bc.last_block().unwrap();
+        bc.add_block(random_signed_block(random_header(
+            1,
+            genesis.hash().clone(),
+        )))
+        .unwrap();
+
+        let view = bc.view();
+        let chain = Arc::new(Mutex::new(bc));
+
+        // simulate long running write holding the chain lock
+        let _write_guard = lock!(chain);
+
+        let (tx, rx) = channel();
+        for _ in 0..4 {
+            let view = view.clone();
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let view = view.read().unwrap();
+                let height = view.last_block().map(|b| b.height());
+                let genesis = view
+                    .get_block_by_height(BlockHeight::GENESIS)
+                    .map(|b| b.height());
+                tx.send((view.height(), height, genesis)).unwrap();
+            });
+        }
+
+        for _ in 0..4 {
+            let res = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(
+                res,
+                (
+                    BlockHeight(1),
+                    Some(BlockHeight(1)),
+                    Some(BlockHeight::GENESIS)
+                )
+            );
+        }
+    }
+}
@@ -35,6 +35,85 @@ impl Address {
         }
         Self { inner: bytes }
     }
+
+    // address as written in JSON output, see AddressFormat
+    pub fn encode(&self, format: AddressFormat) -> String {
+        let hex = hex::encode(self.inner);
+        let hex = if format.checksum {
+            checksum_hex(&hex)
+        } else {
+            hex
+        };
+
+        if format.prefix {
+            format!("0x{hex}")
+        } else {
+            hex
+        }
+    }
+
+    // parses any output of encode, the 0x prefix is optional and a mixed
+    // case address must carry a valid checksum
+    pub fn decode(data: &str) -> Result<Address, CoreError> {
+        let hex = data.strip_prefix("0x").unwrap_or(data);
+        let lower = hex.to_ascii_lowercase();
+        if lower.len() != 40 {
+            return Err(CoreError::Parsing(format!(
+                "address: {data} is not 20 hex encoded bytes"
+            )));
+        }
+
+        let mixed_case = hex.chars().any(|c| c.is_ascii_lowercase())
+            && hex.chars().any(|c| c.is_ascii_uppercase());
+        if mixed_case && checksum_hex(&lower) != hex {
+            return Err(CoreError::Parsing(format!(
+                "address: {data} has an invalid checksum"
+            )));
+        }
+
+        Self::from_hex(&lower)
+    }
+}
+
+// how addresses are written in JSON output, plain lower case hex by
+// default, prefix adds 0x so an address can't be mistaken for a hash,
+// checksum upper cases letters so a mistyped address is detected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AddressFormat {
+    pub prefix: bool,
+    pub checksum: bool,
+}
+
+impl AddressFormat {
+    // parses ORION_ADDRESS_FORMAT values: hex, prefixed or checksum
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "hex" => Some(Self::default()),
+            "prefixed" => Some(Self {
+                prefix: true,
+                checksum: false,
+            }),
+            "checksum" => Some(Self {
+                prefix: true,
+                checksum: true,
+            }),
+            _ => None,
+        }
+    }
+}
+
+// a letter of the lower case hex is upper cased when the nibble at the
+// same position of its sha256 digest is 8 or more
+fn checksum_hex(lower: &str) -> String {
+    let digest = sha256::digest(lower.as_bytes());
+    lower
+        .chars()
+        .zip(digest.chars())
+        .map(|(c, d)| match d.to_digit(16) {
+            Some(n) if n >= 8 => c.to_ascii_uppercase(),
+            _ => c,
+        })
+        .collect()
 }
 
 impl ByteEncoding<Address> for Address {
@@ -125,6 +204,31 @@ mod tests {
             addr_4.to_hex().unwrap()
         );
     }
+
+    #[test]
+    fn test_address_format() {
+        let address = PrivateKey::new().pub_key().address().unwrap();
+
+        let plain = address.encode(AddressFormat::default());
+        assert_eq!(plain, address.to_hex().unwrap());
+
+        let format = AddressFormat::parse("checksum").unwrap();
+        let encoded = address.encode(format);
+        assert!(encoded.starts_with("0x"));
+        assert_eq!(encoded[2..].to_ascii_lowercase(), plain);
+        assert_eq!(Address::decode(&encoded).unwrap(), address);
+        assert_eq!(Address::decode(&plain).unwrap(), address);
+
+        let fixed = Address::new(&[0xab; 20]);
+        let encoded = fixed.encode(format);
+        assert_eq!(encoded, "0xABababaBaBAbAbaBAbaBABabAbAbabAbabaBaBAb");
+        assert_eq!(Address::decode(&encoded).unwrap(), fixed);
+        // flipping the case of one letter breaks the checksum
+        assert!(Address::decode("0xaBababaBaBAbAbaBAbaBABabAbAbabAbabaBaBAb").is_err());
+
+        assert!(Address::decode("0x1234").is_err());
+        assert!(AddressFormat::parse("base58").is_none());
+    }
 }
 
 pub fn random_sender_receiver() -> (Address, Address) {
@@ -3,5 +3,6 @@ pub mod error;
 pub mod hash;
 pub mod private_key;
 pub mod public_key;
+pub mod scheme;
 pub mod signature;
 pub mod utils;
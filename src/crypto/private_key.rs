@@ -2,6 +2,8 @@ use crate::core::{
     encoding::{ByteEncoding, HexEncoding},
     error::CoreError,
 };
+use bip32::{DerivationPath, XPrv};
+use bip39::Mnemonic;
 use ecdsa::{
     elliptic_curve::rand_core::OsRng, signature::Signer, Signature as ECDASignature, SigningKey,
     VerifyingKey,
@@ -10,11 +12,21 @@ use k256::Secp256k1;
 use pem::{encode, parse, Pem};
 use serde::{Deserialize, Serialize};
 use std::io::Write;
-use std::{fmt::Display, fs::File};
-use std::{io::Read, path::Path};
+use std::path::Path;
+use std::{
+    fmt::Display,
+    fs::{self, File},
+};
 
 use super::{address::Address, error::CryptoError, public_key::PublicKey, signature::Signature};
 
+// BIP44 path used to derive keys from a mnemonic, the key index
+// is appended as the last component
+const MNEMONIC_DERIVATION_PATH: &str = "m/44'/60'/0'/0";
+
+// number of words in a generated mnemonic
+const MNEMONIC_WORD_COUNT: usize = 24;
+
 #[derive(Clone)]
 pub struct PrivateKey {
     key: SigningKey<Secp256k1>,
@@ -27,6 +39,37 @@ impl PrivateKey {
         }
     }
 
+    // deterministic key for tests, production keys always come from OsRng
+    #[cfg(test)]
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        use rand_chacha::{rand_core::SeedableRng, ChaCha20Rng};
+
+        Self {
+            key: SigningKey::random(&mut ChaCha20Rng::from_seed(seed)),
+        }
+    }
+
+    // derive key at index from a BIP39 mnemonic, the same phrase
+    // and index always give the same key
+    pub fn from_mnemonic(phrase: &str, index: u32) -> Result<Self, CoreError> {
+        let mnemonic = Mnemonic::parse(phrase).map_err(|e| CoreError::Parsing(e.to_string()))?;
+        let seed = mnemonic.to_seed("");
+
+        let path: DerivationPath = format!("{MNEMONIC_DERIVATION_PATH}/{index}")
+            .parse()
+            .map_err(|e: bip32::Error| CoreError::Parsing(e.to_string()))?;
+        let xprv =
+            XPrv::derive_from_path(seed, &path).map_err(|e| CoreError::Parsing(e.to_string()))?;
+
+        Self::from_bytes(&xprv.private_key().to_bytes())
+    }
+
+    pub fn generate_mnemonic() -> String {
+        Mnemonic::generate(MNEMONIC_WORD_COUNT)
+            .expect("valid mnemonic word count")
+            .to_string()
+    }
+
     pub fn address(&self) -> Address {
         self.pub_key().address().unwrap()
     }
@@ -43,10 +86,7 @@ impl PrivateKey {
     }
 
     pub fn from_pem(path: &Path) -> Result<Self, CoreError> {
-        let mut file = File::open(path).map_err(|e| CoreError::Parsing(e.to_string()))?;
-        let mut pem_data = Vec::new();
-        file.read_to_end(&mut pem_data)
-            .map_err(|e| CoreError::Parsing(e.to_string()))?;
+        let pem_data = fs::read(path)?;
 
         let pem = parse(&pem_data).map_err(|e| CoreError::Parsing(e.to_string()))?;
 
@@ -137,6 +177,38 @@ mod tests {
         assert_eq!(pvt_key.to_hex().unwrap(), new_pvt_key.to_hex().unwrap());
     }
 
+    #[test]
+    fn test_from_mnemonic() {
+        let phrase = PrivateKey::generate_mnemonic();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+
+        let key = PrivateKey::from_mnemonic(&phrase, 0).unwrap();
+        let same = PrivateKey::from_mnemonic(&phrase, 0).unwrap();
+        assert_eq!(key.to_hex().unwrap(), same.to_hex().unwrap());
+
+        let other = PrivateKey::from_mnemonic(&phrase, 1).unwrap();
+        assert_ne!(key.to_hex().unwrap(), other.to_hex().unwrap());
+
+        // different phrase gives a different key at the same index
+        let phrase_2 = PrivateKey::generate_mnemonic();
+        let key_2 = PrivateKey::from_mnemonic(&phrase_2, 0).unwrap();
+        assert_ne!(key.to_hex().unwrap(), key_2.to_hex().unwrap());
+
+        assert!(PrivateKey::from_mnemonic("not a valid phrase", 0).is_err());
+    }
+
+    #[test]
+    fn test_from_seed() {
+        let key = PrivateKey::from_seed([1; 32]);
+        let same = PrivateKey::from_seed([1; 32]);
+        assert_eq!(key.to_hex().unwrap(), same.to_hex().unwrap());
+        assert_eq!(key.address(), same.address());
+
+        let other = PrivateKey::from_seed([2; 32]);
+        assert_ne!(key.to_hex().unwrap(), other.to_hex().unwrap());
+        assert_ne!(key.address(), other.address());
+    }
+
     #[test]
     fn test_sign() {
         let pvt_key = PrivateKey::new();
@@ -12,7 +12,12 @@ use crate::core::{
     error::CoreError,
 };
 
-use super::{address::Address, error::CryptoError, signature::Signature};
+use super::{
+    address::Address,
+    error::CryptoError,
+    scheme::{SignatureScheme, ED25519_KEY_LEN, SECP256K1_KEY_LEN},
+    signature::Signature,
+};
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct PublicKey {
@@ -48,7 +53,15 @@ impl PublicKey {
 
 impl ByteEncoding<PublicKey> for PublicKey {
     fn from_bytes(data: &[u8]) -> Result<PublicKey, CoreError> {
-        let res = VerifyingKey::<Secp256k1>::from_sec1_bytes(data);
+        let key_bytes = PublicKeyBytes::new(data)?;
+        if key_bytes.scheme() != SignatureScheme::Secp256k1 {
+            return Err(CoreError::CryptoError(format!(
+                "unsupported signature scheme {:?} for PublicKey",
+                key_bytes.scheme()
+            )));
+        }
+
+        let res = VerifyingKey::<Secp256k1>::from_sec1_bytes(key_bytes.key());
         if res.is_err() {
             return Err(CoreError::Parsing(
                 "unable to correctly parse bytes".to_string(),
@@ -92,22 +105,98 @@ impl From<PublicKeyBytes> for PublicKey {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
-pub struct PublicKeyBytes([u8; 33]);
+// serialized public key tagged with its signature scheme,
+// secp256k1 keys encode to the legacy untagged 33 bytes, all other
+// schemes are prefixed with the scheme byte
+#[derive(Debug, Clone, PartialEq)]
+pub enum PublicKeyBytes {
+    Secp256k1([u8; SECP256K1_KEY_LEN]),
+    Ed25519([u8; ED25519_KEY_LEN]),
+}
 
 impl PublicKeyBytes {
     pub fn new(data: &[u8]) -> Result<Self, CoreError> {
-        let mut buf = [0_u8; 33];
+        // legacy untagged secp256k1 compressed key, always
+        // starts with 0x02 or 0x03 so never collides with a scheme tag
+        if data.len() == SECP256K1_KEY_LEN && matches!(data[0], 0x02 | 0x03) {
+            return Self::new_with_scheme(SignatureScheme::Secp256k1, data);
+        }
+
+        match data.split_first() {
+            Some((&tag, key)) => Self::new_with_scheme(SignatureScheme::try_from(tag)?, key),
+            None => Err(CoreError::Parsing(
+                "incorrect data length for new PublicKeyBytes".to_string(),
+            )),
+        }
+    }
 
-        if data.len() != 33 {
+    pub fn new_with_scheme(scheme: SignatureScheme, data: &[u8]) -> Result<Self, CoreError> {
+        if data.len() != scheme.key_len() {
             return Err(CoreError::Parsing(
                 "incorrect data length for new PublicKeyBytes".to_string(),
             ));
         }
-        for (i, b) in data.iter().enumerate() {
-            buf[i] = b.clone();
+
+        match scheme {
+            SignatureScheme::Secp256k1 => {
+                let mut buf = [0_u8; SECP256K1_KEY_LEN];
+                buf.copy_from_slice(data);
+                Ok(Self::Secp256k1(buf))
+            }
+            SignatureScheme::Ed25519 => {
+                let mut buf = [0_u8; ED25519_KEY_LEN];
+                buf.copy_from_slice(data);
+                Ok(Self::Ed25519(buf))
+            }
+        }
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            Self::Secp256k1(_) => SignatureScheme::Secp256k1,
+            Self::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+
+    // raw key bytes without scheme tag
+    pub fn key(&self) -> &[u8] {
+        match self {
+            Self::Secp256k1(key) => key,
+            Self::Ed25519(key) => key,
+        }
+    }
+}
+
+// borsh uses the same layout as to_bytes so keys stored or sent
+// before schemes were added still decode
+impl BorshSerialize for PublicKeyBytes {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        if let Self::Ed25519(key) = self {
+            writer.write_all(&[u8::from(self.scheme())])?;
+            return writer.write_all(key);
         }
-        Ok(Self(buf))
+        writer.write_all(self.key())
+    }
+}
+
+impl BorshDeserialize for PublicKeyBytes {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let first = u8::deserialize_reader(reader)?;
+
+        let (scheme, mut buf) = if matches!(first, 0x02 | 0x03) {
+            (SignatureScheme::Secp256k1, vec![first])
+        } else {
+            let scheme = SignatureScheme::try_from(first)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+            (scheme, vec![])
+        };
+
+        let start = buf.len();
+        buf.resize(scheme.key_len(), 0);
+        reader.read_exact(&mut buf[start..])?;
+
+        Self::new_with_scheme(scheme, &buf)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
     }
 }
 
@@ -153,7 +242,14 @@ impl ByteEncoding<PublicKeyBytes> for PublicKeyBytes {
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
-        Ok(self.0.to_vec())
+        match self {
+            Self::Secp256k1(key) => Ok(key.to_vec()),
+            _ => {
+                let mut buf = vec![u8::from(self.scheme())];
+                buf.extend_from_slice(self.key());
+                Ok(buf)
+            }
+        }
     }
 }
 
@@ -169,9 +265,9 @@ impl HexEncoding<PublicKeyBytes> for PublicKeyBytes {
 }
 
 impl Deref for PublicKeyBytes {
-    type Target = [u8; 33];
+    type Target = [u8];
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.key()
     }
 }
 
@@ -199,4 +295,48 @@ mod tests {
         assert_eq!(pub_key.to_bytes().unwrap().len(), 33);
         assert_eq!(66, pub_key.to_hex().unwrap().len());
     }
+
+    #[test]
+    fn test_public_key_bytes_schemes() {
+        use super::*;
+        use crate::crypto::private_key::PrivateKey;
+
+        // secp256k1 keys keep the legacy untagged 33 byte encoding
+        let pub_key = PrivateKey::new().pub_key();
+        let secp = PublicKeyBytes::new(&pub_key.to_bytes().unwrap()).unwrap();
+
+        assert_eq!(secp.scheme(), SignatureScheme::Secp256k1);
+        assert_eq!(secp.to_bytes().unwrap(), pub_key.to_bytes().unwrap());
+        assert_eq!(
+            PublicKeyBytes::from_bytes(&secp.to_bytes().unwrap()).unwrap(),
+            secp
+        );
+        assert_eq!(
+            PublicKeyBytes::from_hex(&secp.to_hex().unwrap()).unwrap(),
+            secp
+        );
+
+        // tagged secp256k1 bytes are also accepted
+        let mut tagged = vec![u8::from(SignatureScheme::Secp256k1)];
+        tagged.extend_from_slice(secp.key());
+        assert_eq!(PublicKeyBytes::from_bytes(&tagged).unwrap(), secp);
+
+        // ed25519 keys are prefixed with the scheme byte
+        let ed = PublicKeyBytes::new_with_scheme(SignatureScheme::Ed25519, &[7_u8; 32]).unwrap();
+        let ed_bytes = ed.to_bytes().unwrap();
+
+        assert_eq!(ed_bytes.len(), 33);
+        assert_eq!(ed_bytes[0], u8::from(SignatureScheme::Ed25519));
+        assert_eq!(PublicKeyBytes::from_bytes(&ed_bytes).unwrap(), ed);
+        assert_eq!(PublicKeyBytes::from_hex(&ed.to_hex().unwrap()).unwrap(), ed);
+
+        let borsh_bytes = borsh::to_vec(&ed).unwrap();
+        assert_eq!(
+            borsh::from_slice::<PublicKeyBytes>(&borsh_bytes).unwrap(),
+            ed
+        );
+
+        // ed25519 keys cannot be used as a secp256k1 PublicKey
+        assert!(PublicKey::from_bytes(&ed_bytes).is_err());
+    }
 }
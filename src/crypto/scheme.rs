@@ -0,0 +1,63 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use serde::{Deserialize, Serialize};
+
+use crate::core::error::CoreError;
+
+pub const SECP256K1_KEY_LEN: usize = 33;
+pub const ED25519_KEY_LEN: usize = 32;
+pub const SIGNATURE_LEN: usize = 64;
+
+// signature scheme tag written as the first byte of tagged
+// key and signature bytes, secp256k1 values are also accepted
+// untagged for backwards compatibility
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    BorshSerialize,
+    BorshDeserialize,
+)]
+#[borsh(use_discriminant = true)]
+#[repr(u8)]
+pub enum SignatureScheme {
+    Secp256k1 = 0,
+    Ed25519 = 1,
+}
+
+impl SignatureScheme {
+    pub fn key_len(&self) -> usize {
+        match self {
+            SignatureScheme::Secp256k1 => SECP256K1_KEY_LEN,
+            SignatureScheme::Ed25519 => ED25519_KEY_LEN,
+        }
+    }
+
+    pub fn signature_len(&self) -> usize {
+        SIGNATURE_LEN
+    }
+}
+
+impl TryFrom<u8> for SignatureScheme {
+    type Error = CoreError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(SignatureScheme::Secp256k1),
+            1 => Ok(SignatureScheme::Ed25519),
+            _ => Err(CoreError::Parsing(format!(
+                "unknown signature scheme tag: {value}"
+            ))),
+        }
+    }
+}
+
+impl From<SignatureScheme> for u8 {
+    fn from(value: SignatureScheme) -> u8 {
+        value as u8
+    }
+}
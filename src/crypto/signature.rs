@@ -14,6 +14,8 @@ use crate::core::{
     error::CoreError,
 };
 
+use super::scheme::{SignatureScheme, SIGNATURE_LEN};
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Signature {
     pub inner: ECDASignature<Secp256k1>,
@@ -25,23 +27,96 @@ impl Signature {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, BorshDeserialize, BorshSerialize)]
-pub struct SignatureBytes([u8; 64]);
+// serialized signature tagged with its signature scheme, untagged
+// 64 byte signatures are treated as secp256k1 for backwards compatibility
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureBytes {
+    Secp256k1([u8; SIGNATURE_LEN]),
+    Ed25519([u8; SIGNATURE_LEN]),
+}
 
 impl SignatureBytes {
     pub fn new(data: &[u8]) -> Result<Self, CoreError> {
-        if data.len() != 64 {
+        if data.len() == SIGNATURE_LEN {
+            return Self::new_with_scheme(SignatureScheme::Secp256k1, data);
+        }
+
+        match data.split_first() {
+            Some((&tag, sig)) => Self::new_with_scheme(SignatureScheme::try_from(tag)?, sig),
+            None => Err(CoreError::Parsing(
+                "incorrect data length for new SignatureBytes".to_string(),
+            )),
+        }
+    }
+
+    pub fn new_with_scheme(scheme: SignatureScheme, data: &[u8]) -> Result<Self, CoreError> {
+        if data.len() != scheme.signature_len() {
             return Err(CoreError::Parsing(
                 "incorrect data length for new SignatureBytes".to_string(),
             ));
         }
 
-        let mut buf = [0_u8; 64];
-        for (i, b) in data.iter().enumerate() {
-            buf[i] = b.clone()
+        let mut buf = [0_u8; SIGNATURE_LEN];
+        buf.copy_from_slice(data);
+
+        match scheme {
+            SignatureScheme::Secp256k1 => Ok(Self::Secp256k1(buf)),
+            SignatureScheme::Ed25519 => Ok(Self::Ed25519(buf)),
+        }
+    }
+
+    pub fn scheme(&self) -> SignatureScheme {
+        match self {
+            Self::Secp256k1(_) => SignatureScheme::Secp256k1,
+            Self::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+
+    // raw signature bytes without scheme tag
+    pub fn signature(&self) -> &[u8] {
+        match self {
+            Self::Secp256k1(sig) => sig,
+            Self::Ed25519(sig) => sig,
+        }
+    }
+}
+
+// borsh prefix of signatures other than secp256k1, secp256k1 signatures
+// are written as the legacy untagged 64 bytes and their r value is below
+// the curve order, so can never start with 16 0xff bytes
+const TAGGED_SIGNATURE_MARKER: [u8; 16] = [0xff; 16];
+
+impl BorshSerialize for SignatureBytes {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        if let Self::Ed25519(sig) = self {
+            writer.write_all(&TAGGED_SIGNATURE_MARKER)?;
+            writer.write_all(&[u8::from(self.scheme())])?;
+            return writer.write_all(sig);
+        }
+        writer.write_all(self.signature())
+    }
+}
+
+impl BorshDeserialize for SignatureBytes {
+    fn deserialize_reader<R: std::io::Read>(reader: &mut R) -> std::io::Result<Self> {
+        let mut buf = [0_u8; SIGNATURE_LEN];
+        reader.read_exact(&mut buf)?;
+
+        if buf[..TAGGED_SIGNATURE_MARKER.len()] != TAGGED_SIGNATURE_MARKER {
+            return Ok(Self::Secp256k1(buf));
         }
 
-        Ok(Self(buf))
+        // marker and scheme tag take the first 17 bytes read
+        let tagged_len = TAGGED_SIGNATURE_MARKER.len() + 1;
+        let scheme = SignatureScheme::try_from(buf[TAGGED_SIGNATURE_MARKER.len()])
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+
+        let mut sig = buf[tagged_len..].to_vec();
+        sig.resize(SIGNATURE_LEN, 0);
+        reader.read_exact(&mut sig[SIGNATURE_LEN - tagged_len..])?;
+
+        Self::new_with_scheme(scheme, &sig)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))
     }
 }
 
@@ -87,7 +162,14 @@ impl ByteEncoding<SignatureBytes> for SignatureBytes {
     }
 
     fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
-        Ok(self.0.to_vec())
+        match self {
+            Self::Secp256k1(sig) => Ok(sig.to_vec()),
+            _ => {
+                let mut buf = vec![u8::from(self.scheme())];
+                buf.extend_from_slice(self.signature());
+                Ok(buf)
+            }
+        }
     }
 }
 
@@ -103,9 +185,9 @@ impl HexEncoding<SignatureBytes> for SignatureBytes {
 }
 
 impl Deref for SignatureBytes {
-    type Target = [u8; 64];
+    type Target = [u8];
     fn deref(&self) -> &Self::Target {
-        &self.0
+        self.signature()
     }
 }
 
@@ -132,7 +214,15 @@ impl ByteEncoding<Signature> for Signature {
     }
 
     fn from_bytes(bytes: &[u8]) -> Result<Signature, CoreError> {
-        match ECDASignature::from_slice(bytes) {
+        let sig_bytes = SignatureBytes::new(bytes)?;
+        if sig_bytes.scheme() != SignatureScheme::Secp256k1 {
+            return Err(CoreError::CryptoError(format!(
+                "unsupported signature scheme {:?} for Signature",
+                sig_bytes.scheme()
+            )));
+        }
+
+        match ECDASignature::from_slice(sig_bytes.signature()) {
             Ok(sig) => Ok(Self { inner: sig }),
             Err(e) => Err(CoreError::Parsing(format!(
                 "unable to generate signature from bytes: {e}"
@@ -181,4 +271,27 @@ mod tests {
 
         assert_eq!(sig.to_hex().unwrap(), sig_3.to_hex().unwrap());
     }
+
+    #[test]
+    fn test_signature_bytes_schemes() {
+        let sig = PrivateKey::new().sign(b"Hello world");
+
+        // secp256k1 signatures keep the legacy untagged 64 byte encoding
+        let secp = SignatureBytes::new(&sig.to_bytes().unwrap()).unwrap();
+        assert_eq!(secp.scheme(), SignatureScheme::Secp256k1);
+        assert_eq!(secp.to_bytes().unwrap(), sig.to_bytes().unwrap());
+        assert_eq!(
+            SignatureBytes::from_hex(&secp.to_hex().unwrap()).unwrap(),
+            secp
+        );
+
+        // ed25519 signatures are prefixed with the scheme byte
+        let ed = SignatureBytes::new_with_scheme(SignatureScheme::Ed25519, &[9_u8; 64]).unwrap();
+        let ed_bytes = ed.to_bytes().unwrap();
+
+        assert_eq!(ed_bytes.len(), 65);
+        assert_eq!(SignatureBytes::from_bytes(&ed_bytes).unwrap(), ed);
+        assert_eq!(SignatureBytes::from_hex(&ed.to_hex().unwrap()).unwrap(), ed);
+        assert!(Signature::from_bytes(&ed_bytes).is_err());
+    }
 }
@@ -12,6 +12,8 @@ pub mod crypto;
 pub mod network;
 pub mod rpc;
 pub mod state;
+#[cfg(test)]
+pub mod testing;
 pub mod util;
 pub mod vm;
 
@@ -41,7 +43,7 @@ pub type Result<T> = std::result::Result<T, GenericError>;
 pub fn build_full_node() -> Result<ChainNode> {
     let config = NodeConfig::default();
 
-    Ok(ChainNode::new(config))
+    Ok(ChainNode::try_new(config)?)
 }
 
 static INIT: Once = Once::new();
@@ -66,6 +68,7 @@ pub fn transaction_tester_thread(handler: Arc<Mutex<RpcController>>) {
             header: RpcHeader::NewTx,
             // TODO: Error handling on byte encoding
             payload: tx.to_bytes().unwrap(),
+            id: None,
         };
 
         if let Ok(handler) = handler.lock() {
@@ -8,13 +8,14 @@ use orion_chain::core::blockchain::Blockchain;
 use orion_chain::core::encoding::ByteEncoding;
 use orion_chain::core::header::random_header;
 use orion_chain::core::transaction::random_signed_tx;
+use orion_chain::crypto::address::AddressFormat;
 use orion_chain::crypto::hash::Hash;
 use orion_chain::crypto::utils::random_hash;
 
 use orion_chain::logger_init;
 use orion_chain::{
     crypto::private_key::PrivateKey,
-    network::node::{ChainNode, NodeConfig},
+    network::node::{BlockProduction, ChainNode, NodeConfig},
     Result,
 };
 
@@ -24,23 +25,36 @@ async fn main() -> Result<()> {
 
     // TODO: Get config from file
     let node_config = NodeConfig::default();
+    let manual_mining = node_config.block_production == BlockProduction::Manual;
 
     // Create a ChainNode with newly created blockchain. ChainNode
     // serves the purpose of composing all blockchain functionality together
     // inter peer communication as well as block syncing, transaction processing
     // loops
-    let mut chain_node = ChainNode::new(node_config);
+    let mut chain_node = ChainNode::try_new(node_config)?;
     chain_node.start()?;
 
     // Create main entry point for HTTP API server for the node,
     // pass in Arc of ChainNode to access blockchain functionality
     // within the Api
-    let api_config = ApiServerConfig::default();
+    let mut api_config = ApiServerConfig::default();
+    api_config.admin_token = std::env::var("ORION_ADMIN_TOKEN").ok();
+    api_config.manual_mining = manual_mining;
+    api_config.debug_routes = std::env::var("ORION_DEBUG_ROUTES").is_ok();
+    if let Ok(format) = std::env::var("ORION_ADDRESS_FORMAT") {
+        match AddressFormat::parse(&format) {
+            Some(format) => api_config.address_format = format,
+            None => return Err(format!("unknown ORION_ADDRESS_FORMAT: {format}").into()),
+        }
+    }
     let server = ApiServer::new(api_config, chain_node.rpc_controller());
-    server
-        .start()
-        .await
-        .expect("Unable to start server")
-        .await?;
+    let running = server.start().await.expect("Unable to start server");
+    if let Some(handle) = server.handle() {
+        chain_node.attach_api(handle);
+    }
+
+    // server future resolves once the API is stopped, eg. on ctrl-c
+    running.await?;
+    chain_node.shutdown(true).await;
     Ok(())
 }
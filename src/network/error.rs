@@ -13,6 +13,10 @@ pub enum NetworkError {
     Message(String),
     Decoding(String),
     RPC(String),
+    // address string which could not be parsed as a SocketAddr
+    InvalidAddress(String),
+    // failure on this node rather than in the request, eg. a poisoned lock
+    Internal(String),
 }
 
 impl Error for NetworkError {}
@@ -25,6 +29,8 @@ impl Display for NetworkError {
             NetworkError::Message(msg) => write!(f, "{msg}"),
             NetworkError::Decoding(msg) => write!(f, "{msg}"),
             NetworkError::RPC(msg) => write!(f, "{msg}"),
+            NetworkError::InvalidAddress(msg) => write!(f, "{msg}"),
+            NetworkError::Internal(msg) => write!(f, "{msg}"),
         }
     }
 }
@@ -35,19 +41,24 @@ impl From<CoreError> for NetworkError {
     }
 }
 
+impl NetworkError {
+    // HTTP status for the error when returned from an API route
+    pub fn status(&self) -> StatusCode {
+        match self {
+            NetworkError::NotFound(_) => StatusCode::NOT_FOUND,
+            NetworkError::Decoding(_)
+            | NetworkError::InvalidAddress(_)
+            | NetworkError::Message(_)
+            | NetworkError::RPC(_) => StatusCode::BAD_REQUEST,
+            NetworkError::Connect(_) => StatusCode::BAD_GATEWAY,
+            NetworkError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl Responder for NetworkError {
     type Body = BoxBody;
     fn respond_to(self, _req: &actix_web::HttpRequest) -> HttpResponse<Self::Body> {
-        let message = match self {
-            NetworkError::Connect(msg) => msg,
-            NetworkError::NotFound(msg) => msg,
-            NetworkError::Message(msg) => msg,
-            NetworkError::Decoding(msg) => msg,
-            NetworkError::RPC(msg) => msg,
-        };
-
-        let status = StatusCode::from_u16(403).unwrap_or(StatusCode::BAD_REQUEST);
-
-        HttpResponse::build(status).json(json!({"error": message}))
+        HttpResponse::build(self.status()).json(json!({"error": self.to_string()}))
     }
 }
@@ -0,0 +1,74 @@
+use rand::seq::SliceRandom;
+use rand::{rngs::StdRng, SeedableRng};
+
+// peers a transaction is forwarded to, instead of every peer
+pub const DEFAULT_TX_FANOUT: usize = 8;
+
+// picks a random subset of peers to forward a payload to, a tx which
+// comes back from another peer is dropped by the SeenTxCache before it
+// is gossiped again
+pub struct Gossip {
+    fanout: usize,
+    rng: StdRng,
+}
+
+impl Gossip {
+    pub fn new(fanout: usize) -> Self {
+        Self::with_rng(fanout, StdRng::from_entropy())
+    }
+
+    // same peers are picked for the same seed, used by tests
+    pub fn with_seed(fanout: usize, seed: u64) -> Self {
+        Self::with_rng(fanout, StdRng::seed_from_u64(seed))
+    }
+
+    fn with_rng(fanout: usize, rng: StdRng) -> Self {
+        Self { fanout, rng }
+    }
+
+    pub fn fanout(&self) -> usize {
+        self.fanout
+    }
+
+    pub fn set_fanout(&mut self, fanout: usize) {
+        self.fanout = fanout;
+    }
+
+    // up to fanout random peers other than ignore
+    pub fn targets<T: Copy + PartialEq>(&mut self, peers: &[T], ignore: Option<T>) -> Vec<T> {
+        let peers: Vec<T> = peers
+            .iter()
+            .copied()
+            .filter(|peer| Some(*peer) != ignore)
+            .collect();
+
+        peers
+            .choose_multiple(&mut self.rng, self.fanout)
+            .copied()
+            .collect()
+    }
+}
+
+impl Default for Gossip {
+    fn default() -> Self {
+        Self::new(DEFAULT_TX_FANOUT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gossip_targets() {
+        let mut gossip = Gossip::new(3);
+        let peers: Vec<usize> = (0..10).collect();
+
+        let targets = gossip.targets(&peers, Some(4));
+        assert_eq!(targets.len(), 3);
+        assert!(!targets.contains(&4));
+
+        // fewer peers than the fanout
+        assert_eq!(gossip.targets(&peers[..2], Some(0)), vec![1]);
+    }
+}
@@ -1,9 +1,33 @@
 use crate::network::error::NetworkError;
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::io::{Read, Write};
 use std::net::SocketAddr;
 
+// version of the peer wire protocol, peers with a different
+// version are disconnected during handshake
+pub const PROTOCOL_VERSION: u16 = 3;
+
+// compressed RPC payloads larger than this once decompressed are rejected
+pub const MAX_DECOMPRESSED_RPC_SIZE: usize = 64 * 1024 * 1024;
+
 #[derive(Debug)]
 pub enum PeerMessage {
     RPC(SocketAddr, Vec<u8>),
+    // sent by both sides when a connection is established
+    Handshake {
+        addr: SocketAddr,
+        chain_id: u32,
+        protocol_version: u16,
+        node_addr: SocketAddr,
+        // key of the remote node, its node id is the address of the key
+        public_key: Vec<u8>,
+        // random bytes the receiver must sign to prove it holds the key
+        challenge: [u8; 32],
+        // peer accepts compressed RPC payloads
+        compression: bool,
+    },
+    // signature over the challenge of the handshake received
+    HandshakeAuth(SocketAddr, Vec<u8>),
     Error(SocketAddr, String),
     Disconnect(SocketAddr, String),
     Ping(SocketAddr, Vec<u8>),
@@ -14,6 +38,10 @@ pub enum PeerMessage {
 #[repr(u8)]
 pub enum MessageCodeMap {
     RPC = 1,
+    Handshake = 2,
+    // RPC with a deflate compressed payload
+    CompressedRPC = 3,
+    HandshakeAuth = 4,
     Ping = 100,
     Pong = 101,
     Error = 200,
@@ -55,6 +83,29 @@ impl PeerMessage {
         // get message type from code
         let val = match code {
             MessageCodeMap::RPC => PeerMessage::RPC(addr, drop_first_byte),
+            MessageCodeMap::Handshake => {
+                let (chain_id, protocol_version, node_addr, public_key, challenge, compression): (
+                    u32,
+                    u16,
+                    SocketAddr,
+                    Vec<u8>,
+                    [u8; 32],
+                    bool,
+                ) = bincode::deserialize(&drop_first_byte).map_err(|e| {
+                    NetworkError::Decoding(format!("unable to decode handshake: {e}"))
+                })?;
+                PeerMessage::Handshake {
+                    addr,
+                    chain_id,
+                    protocol_version,
+                    node_addr,
+                    public_key,
+                    challenge,
+                    compression,
+                }
+            }
+            MessageCodeMap::HandshakeAuth => PeerMessage::HandshakeAuth(addr, drop_first_byte),
+            MessageCodeMap::CompressedRPC => PeerMessage::RPC(addr, decompress(&data[1..])?),
             MessageCodeMap::Error => PeerMessage::Error(addr, data_str),
             MessageCodeMap::Disconnect => PeerMessage::Disconnect(addr, data_str),
             MessageCodeMap::Ping => PeerMessage::Ping(addr, drop_first_byte),
@@ -93,13 +144,100 @@ impl PeerMessage {
                 buf.extend_from_slice(msg);
                 buf
             }
+            Self::HandshakeAuth(_, signature) => {
+                buf.extend_from_slice(&[MessageCodeMap::HandshakeAuth.into()]);
+                buf.extend_from_slice(signature);
+                buf
+            }
+            Self::Handshake {
+                chain_id,
+                protocol_version,
+                node_addr,
+                public_key,
+                challenge,
+                compression,
+                ..
+            } => {
+                buf.extend_from_slice(&[MessageCodeMap::Handshake.into()]);
+                // SAFETY: tuple of plain values and address always serializes
+                buf.extend_from_slice(
+                    &bincode::serialize(&(
+                        chain_id,
+                        protocol_version,
+                        node_addr,
+                        public_key,
+                        challenge,
+                        compression,
+                    ))
+                    .unwrap(),
+                );
+                buf
+            }
         }
     }
+
+    // same as payload but RPC payloads larger than threshold are
+    // compressed and sent as CompressedRPC
+    pub fn compressed_payload(&self, threshold: usize) -> Vec<u8> {
+        match self {
+            Self::RPC(_, msg) if msg.len() > threshold => match compress(msg) {
+                Ok(compressed) => {
+                    let mut buf = vec![MessageCodeMap::CompressedRPC.into()];
+                    buf.extend_from_slice(&compressed);
+                    buf
+                }
+                Err(_) => self.payload(),
+            },
+            _ => self.payload(),
+        }
+    }
+}
+
+// bytes signed in HandshakeAuth, the chain id is included so a
+// signature can not be used to join another chain
+pub fn handshake_auth_data(chain_id: u32, challenge: &[u8; 32]) -> Vec<u8> {
+    let mut buf = b"orion-handshake".to_vec();
+    buf.extend_from_slice(&chain_id.to_be_bytes());
+    buf.extend_from_slice(challenge);
+    buf
+}
+
+fn compress(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+// decompressed size is capped so a small frame can not expand without bound
+fn decompress(data: &[u8]) -> Result<Vec<u8>, NetworkError> {
+    let mut buf = vec![];
+    DeflateDecoder::new(data)
+        .take(MAX_DECOMPRESSED_RPC_SIZE as u64 + 1)
+        .read_to_end(&mut buf)
+        .map_err(|e| NetworkError::Decoding(format!("unable to decompress RPC: {e}")))?;
+
+    if buf.len() > MAX_DECOMPRESSED_RPC_SIZE {
+        return Err(NetworkError::Decoding(format!(
+            "decompressed RPC larger than: {MAX_DECOMPRESSED_RPC_SIZE}"
+        )));
+    }
+
+    Ok(buf)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        core::{
+            block::Block,
+            encoding::ByteEncoding,
+            header::random_header,
+            transaction::{random_signed_tx, Transaction},
+        },
+        crypto::utils::random_hash,
+        rpc::types::{RpcHeader, RPC},
+    };
     use std::net::{IpAddr, Ipv4Addr};
     #[test]
     fn test_message_code() {
@@ -135,4 +273,58 @@ mod tests {
 
         assert_eq!(format!("{:?}", message), format!("{:?}", decoded));
     }
+
+    #[test]
+    fn test_handshake_message() {
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
+        let addr = SocketAddr::new(ip, 5000);
+        let node_addr = SocketAddr::new(ip, 6000);
+        let message = PeerMessage::Handshake {
+            addr,
+            chain_id: 7,
+            protocol_version: PROTOCOL_VERSION,
+            node_addr,
+            public_key: vec![2; 33],
+            challenge: [9; 32],
+            compression: true,
+        };
+
+        // peer reads into a fixed size buffer so payload may have trailing zeros
+        let mut payload = message.payload();
+        payload.resize(1024, 0);
+
+        let decoded = PeerMessage::from_payload(addr, &payload).unwrap();
+        assert_eq!(format!("{:?}", message), format!("{:?}", decoded));
+    }
+
+    #[test]
+    fn test_compressed_block_rpc() {
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+
+        let txs: Vec<Transaction> = (0..50).map(|_| random_signed_tx()).collect();
+        let header = random_header(1, random_hash());
+        let block = Block::new(header, txs).unwrap();
+        let rpc = RPC {
+            header: RpcHeader::CommitBlock,
+            payload: block.to_bytes().unwrap(),
+            id: None,
+        };
+        let message = PeerMessage::RPC(addr, rpc.to_bytes().unwrap());
+
+        let payload = message.compressed_payload(1024);
+        assert_eq!(payload[0], u8::from(MessageCodeMap::CompressedRPC));
+        assert!(payload.len() < message.payload().len());
+
+        let decoded = match PeerMessage::from_payload(addr, &payload).unwrap() {
+            PeerMessage::RPC(_, bytes) => RPC::from_bytes(&bytes).unwrap(),
+            msg => panic!("expected RPC message, got: {msg:?}"),
+        };
+        let decoded_block = Block::from_bytes(&decoded.payload).unwrap();
+        assert_eq!(decoded_block.hash(), block.hash());
+        assert_eq!(decoded_block.num_txs(), 50);
+
+        // payloads under the threshold are sent as is
+        let small = PeerMessage::RPC(addr, b"Hello world".to_vec());
+        assert_eq!(small.compressed_payload(1024), small.payload());
+    }
 }
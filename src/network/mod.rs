@@ -1,8 +1,13 @@
 pub mod encoder;
 pub mod error;
+pub mod gossip;
 pub mod message;
 pub mod node;
+pub mod node_id;
 pub mod peer;
+pub mod peer_set;
+pub mod seen;
+pub mod sync;
 pub mod tcp;
 pub mod tx_pool;
 pub mod types;
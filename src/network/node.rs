@@ -1,28 +1,29 @@
-use core::time;
 use std::{
     error::Error,
     fs,
     net::SocketAddr,
     path::{Path, PathBuf},
     sync::{
+        atomic::{AtomicBool, Ordering},
         mpsc::{channel, Receiver, Sender},
         Arc, Mutex,
     },
     thread,
-    time::Instant,
+    time::{Duration, Instant},
     vec,
 };
 
+use actix_web::dev::ServerHandle;
 use log::{debug, error, info, warn};
 
 use crate::{
-    core::{block::random_block, error::CoreError},
-    crypto::hash::Hash,
+    core::{encoding::ByteEncoding, error::CoreError, genesis::GenesisConfig},
     lock,
 };
 
 use crate::rpc::{
     controller::RpcController,
+    request::PendingRequests,
     types::{RpcHeader, RpcResponse, RPC},
 };
 
@@ -31,40 +32,138 @@ use crate::{
         block::Block,
         blockchain::Blockchain,
         header::{random_header, Header},
+        height::BlockHeight,
         transaction::Transaction,
     },
     crypto::{private_key::PrivateKey, utils::random_hash},
-    vm::validator::BlockValidator,
+    state::cache::DEFAULT_ACCOUNT_CACHE_SIZE,
+    vm::validator::{BlockValidator, DEFAULT_FORK_GRACE, DEFAULT_VERIFY_THREADS},
     GenericError,
 };
 
 use super::{
     error::NetworkError,
-    tx_pool::TxPool,
+    gossip::DEFAULT_TX_FANOUT,
+    seen::DEFAULT_SEEN_TX_TTL,
+    sync::{sync_blocks, DEFAULT_SYNC_WINDOW},
+    tx_pool::{default_tx_classes, TxClass, TxPool},
     types::{Payload, RpcChanMsg},
 };
-use super::{tcp::TcpController, types::ArcMut};
+use super::{
+    tcp::{TcpConfig, TcpController, DEFAULT_MAX_FRAME_SIZE},
+    types::ArcMut,
+};
+
+// how often the mem_pool is checked for txs with BlockProduction::OnDemand
+pub const ON_DEMAND_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// attempts after the first when producing a block fails with a transient error
+pub const DEFAULT_PROPOSE_RETRIES: usize = 3;
+
+// wait between block production attempts
+pub const PROPOSE_RETRY_BACKOFF: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockProduction {
+    // propose a block every block time, even when it has no txs
+    Interval(Duration),
+    // propose a block shortly after txs arrive in the mem_pool
+    OnDemand,
+    // only propose blocks when requested through /admin/mine
+    Manual,
+}
 
 pub struct NodeConfig {
-    pub block_time: time::Duration,
+    pub block_production: BlockProduction,
     pub private_key: PrivateKey,
     pub state_storage_path: PathBuf,
     pub chain_storage_path: PathBuf,
-    pub dev: bool,
+    // remove chain and state storage before the node opens them
+    pub reset_on_start: bool,
     pub mem_pool_size: usize,
+    // amount pending transfers from a sender may exceed its balance by
+    pub mem_pool_pending_grace: u64,
+    // gas price classes sharing each block by weight
+    pub mem_pool_classes: Vec<TxClass>,
     pub peer_addr: String,
+    pub batch_state_commits: bool,
+    // accounts kept in the state account cache, 0 disables the cache
+    pub account_cache_size: usize,
+    // threads used to verify transaction signatures in blocks
+    pub verify_threads: usize,
+    // compact chain storage on this interval, None disables compaction
+    pub compaction_interval: Option<Duration>,
+    // clear orphaned state backups on this interval, None disables pruning
+    pub backup_prune_interval: Option<Duration>,
+    // how long to wait for a peer to respond to an outbound RPC
+    pub rpc_timeout: Duration,
+    pub genesis: GenesisConfig,
+    // peers must use the same chain id to connect
+    pub chain_id: u32,
+    // maximum size of a single message frame from a peer
+    pub max_frame_size: usize,
+    // compress RPC payloads above this size for peers supporting
+    // compression, None disables compression
+    pub compression_threshold: Option<usize>,
+    // blocks synced from this source skip transaction signature checks
+    pub trusted_sync_source: Option<SocketAddr>,
+    // fraction of gas fees burned instead of paid to the block producer
+    pub fee_burn_ratio: f64,
+    // block heights requested concurrently while syncing, 1 is sequential
+    pub sync_window: usize,
+    // peers each new transaction is forwarded to
+    pub tx_fanout: usize,
+    // how long a tx is remembered so peers sending it again are ignored
+    pub seen_tx_ttl: Duration,
+    // retries for a block which failed on a transient state error,
+    // invalid blocks are never retried
+    pub propose_retries: usize,
+    // a block for the tip height replaces the tip if preferred by fork
+    // choice while the tip is younger than this
+    pub fork_grace: Duration,
+}
+
+impl NodeConfig {
+    pub fn peer_socket_addr(&self) -> Result<SocketAddr, NetworkError> {
+        parse_addr(&self.peer_addr)
+    }
+}
+
+// parse an address from config, the error names the offending string
+pub fn parse_addr(addr: &str) -> Result<SocketAddr, NetworkError> {
+    addr.parse()
+        .map_err(|e| NetworkError::InvalidAddress(format!("invalid socket address: '{addr}', {e}")))
 }
 
 impl Default for NodeConfig {
     fn default() -> Self {
         NodeConfig {
-            block_time: time::Duration::from_secs(5),
+            block_production: BlockProduction::Interval(Duration::from_secs(5)),
             private_key: PrivateKey::from_pem(Path::new("data/private_key.pem")).unwrap(),
             state_storage_path: Path::new("data/state.db").to_owned(),
             chain_storage_path: Path::new("data/chain.db").to_owned(),
-            dev: true,
+            reset_on_start: true,
             mem_pool_size: 50,
+            mem_pool_pending_grace: 0,
+            mem_pool_classes: default_tx_classes(),
             peer_addr: "0.0.0.0:5000".to_string(),
+            batch_state_commits: true,
+            account_cache_size: DEFAULT_ACCOUNT_CACHE_SIZE,
+            verify_threads: DEFAULT_VERIFY_THREADS,
+            compaction_interval: None,
+            backup_prune_interval: Some(Duration::from_secs(60)),
+            rpc_timeout: Duration::from_secs(5),
+            genesis: GenesisConfig::default(),
+            chain_id: 1,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            compression_threshold: None,
+            trusted_sync_source: None,
+            fee_burn_ratio: 0.0,
+            sync_window: DEFAULT_SYNC_WINDOW,
+            tx_fanout: DEFAULT_TX_FANOUT,
+            seen_tx_ttl: DEFAULT_SEEN_TX_TTL,
+            propose_retries: DEFAULT_PROPOSE_RETRIES,
+            fork_grace: DEFAULT_FORK_GRACE,
         }
     }
 }
@@ -78,44 +177,86 @@ pub struct ChainNode {
     validator: ArcMut<BlockValidator>,
     pub chain: ArcMut<Blockchain>,
     rpc_controller: Arc<RpcController>,
+    pending_requests: Arc<PendingRequests>,
+    // set by shutdown, background loops exit on their next iteration
+    stopped: Arc<AtomicBool>,
+    // API server stopped together with the node
+    api_handle: Mutex<Option<ServerHandle>>,
 }
 
 impl ChainNode {
+    // panics if the node can not be created, see try_new
     pub fn new(config: NodeConfig) -> Self {
-        // TODO: start chain from config
-        if config.dev {
-            clear_all_data().unwrap()
+        match Self::try_new(config) {
+            Ok(node) => node,
+            Err(e) => panic!("unable to create ChainNode, {e}"),
         }
+    }
 
-        // TODO: do not start chain with genesis, start from storage
-        let chain = Blockchain::new_with_genesis().unwrap();
+    pub fn try_new(config: NodeConfig) -> Result<Self, NetworkError> {
+        // config is checked before any data is cleared or opened
+        let addr = config.peer_socket_addr()?;
+
+        if config.reset_on_start {
+            clear_storage(&config)
+                .map_err(|e| NetworkError::Message(format!("unable to clear node data, {e}")))?;
+        }
+
+        // an existing store is reopened, it must hold the same genesis
+        let mut chain = Blockchain::new(
+            storage_path(&config.state_storage_path)?,
+            storage_path(&config.chain_storage_path)?,
+            Block::genesis(&config.genesis)?,
+        )?;
+        chain.set_batch_state_commits(config.batch_state_commits);
+        chain.set_spend_cap(config.genesis.max_block_spend);
+        chain
+            .state()
+            .set_account_cache_size(config.account_cache_size);
 
         let (tx, rx) = channel::<RpcChanMsg>();
         let (rpc_tx, rpc_rx) = (ArcMut::new(tx), ArcMut::new(rx));
 
-        // TODO: CONFIG, get listener address from config
-        let addr: SocketAddr = config.peer_addr.parse().unwrap();
-        let tcp_controller = TcpController::new(addr, rpc_tx.clone()).unwrap();
+        let tcp_controller = TcpController::new(
+            addr,
+            TcpConfig {
+                chain_id: config.chain_id,
+                max_frame_size: config.max_frame_size,
+                compression_threshold: config.compression_threshold,
+                tx_fanout: config.tx_fanout,
+                private_key: config.private_key.clone(),
+                ..TcpConfig::default()
+            },
+            rpc_tx.clone(),
+        )?;
 
         let tcp_controller = ArcMut::new(tcp_controller);
 
-        let mem_pool = ArcMut::new(TxPool::new());
+        let mut tx_pool = TxPool::new();
+        tx_pool.set_pending_grace(config.mem_pool_pending_grace);
+        tx_pool.set_classes(config.mem_pool_classes.clone())?;
+        let mem_pool = ArcMut::new(tx_pool);
         let chain = ArcMut::new(chain);
-        let validator = ArcMut::new(BlockValidator::new(
-            config.private_key.clone(),
-            config.mem_pool_size,
-        ));
-
-        let rpc_controller = RpcController::new(
+        let mut validator = BlockValidator::new(config.private_key.clone(), config.mem_pool_size);
+        validator.set_trust_mode(config.trusted_sync_source);
+        validator.set_fork_grace(config.fork_grace);
+        validator.set_verify_threads(config.verify_threads)?;
+        validator.set_burn_ratio(config.fee_burn_ratio)?;
+        validator.set_allowed_schemes(config.genesis.allowed_schemes.clone());
+        validator.set_spend_cap(config.genesis.max_block_spend);
+        let validator = ArcMut::new(validator);
+
+        let mut rpc_controller = RpcController::new(
             mem_pool.clone(),
             validator.clone(),
             chain.clone(),
             tcp_controller.clone(),
         );
+        rpc_controller.set_seen_tx_ttl(config.seen_tx_ttl);
 
         let rpc_controller = Arc::new(rpc_controller);
 
-        Self {
+        Ok(Self {
             config,
             rpc_rx,
             rpc_tx,
@@ -124,7 +265,10 @@ impl ChainNode {
             chain,
             tcp_controller,
             rpc_controller,
-        }
+            pending_requests: Arc::new(PendingRequests::new()),
+            stopped: Arc::new(AtomicBool::new(false)),
+            api_handle: Mutex::new(None),
+        })
     }
 
     // Proxy method for TCP Controller
@@ -135,33 +279,147 @@ impl ChainNode {
             // TODO: get header from args
             header: RpcHeader::GetBlock,
             payload,
+            id: None,
         };
         tcp.send_rpc(peer_addr, &rpc);
 
         Ok(())
     }
 
+    // send RPC to peer and wait for the response, returns
+    // NetworkError if peer does not respond within config.rpc_timeout
+    pub fn request_rpc(
+        &self,
+        peer_addr: SocketAddr,
+        header: RpcHeader,
+        payload: Payload,
+    ) -> Result<RpcResponse, NetworkError> {
+        let (id, rx) = self.pending_requests.register();
+        let rpc = RPC {
+            header,
+            payload,
+            id: Some(id),
+        };
+        lock!(self.tcp_controller).send_rpc(peer_addr, &rpc);
+
+        let res = self.pending_requests.wait(id, rx, self.config.rpc_timeout);
+
+        // responses and timeouts feed the peer score
+        let tcp = lock!(self.tcp_controller);
+        match res {
+            Ok(res) => {
+                tcp.record_peer_success(peer_addr);
+                Ok(res)
+            }
+            Err(e) => {
+                tcp.record_peer_failure(peer_addr);
+                Err(NetworkError::RPC(format!("peer {peer_addr}: {e}")))
+            }
+        }
+    }
+
+    // sync blocks up to target from connected peers, up to
+    // config.sync_window heights are requested at once
+    pub fn sync_to(&self, target: BlockHeight) -> Result<usize, NetworkError> {
+        let peer_addrs = lock!(self.tcp_controller).get_peer_addrs_by_score();
+        sync_blocks(
+            self,
+            &peer_addrs,
+            target,
+            self.config.sync_window,
+            self.validator.clone(),
+            self.chain.clone(),
+        )
+    }
+
+    // height of the chain tip of a connected peer
+    pub fn peer_chain_height(&self, peer_addr: SocketAddr) -> Result<BlockHeight, NetworkError> {
+        match self.request_rpc(peer_addr, RpcHeader::GetChainHeight, vec![])? {
+            RpcResponse::Height(height) => Ok(height),
+            RpcResponse::Error(e) => Err(NetworkError::RPC(format!("peer {peer_addr}: {e}"))),
+            res => Err(NetworkError::RPC(format!(
+                "unexpected response to GetChainHeight from peer {peer_addr}: {res:?}"
+            ))),
+        }
+    }
+
+    // sync up to the highest chain reported by connected peers, peers
+    // which do not respond are skipped, returns the number of blocks added
+    pub fn catch_up(&self) -> Result<usize, NetworkError> {
+        let peer_addrs = lock!(self.tcp_controller).get_peer_addrs_by_score();
+        let target = peer_addrs
+            .iter()
+            .filter_map(|peer_addr| match self.peer_chain_height(*peer_addr) {
+                Ok(height) => Some(height),
+                Err(e) => {
+                    warn!("unable to get chain height, {e}");
+                    None
+                }
+            })
+            .max();
+
+        match target {
+            Some(target) if target > lock!(self.chain).height() => self.sync_to(target),
+            _ => Ok(0),
+        }
+    }
+
     pub fn start(&mut self) -> Result<(), GenericError> {
         // Start TcpController
         // launches all threads need to communicate with peers
         // all messages received from peers are send back on self.rpc_tx
         // chanel which is handled by RpcController struct withing api module
-        let tcp_controller = self.tcp_controller.clone();
-        let mut tcp = lock!(tcp_controller);
         // TODO: get peer addresses from config
-        tcp.start(vec![]);
+        lock!(self.tcp_controller).start(vec![]);
 
         // Start thread to listen for all incoming RPC
         // messages from peers
         self.spawn_peer_rpc_thread();
 
+        // sync before proposing so blocks are not built on a stale tip,
+        // responses to sync requests arrive on the peer RPC thread
+        match self.catch_up() {
+            Ok(0) => {}
+            Ok(count) => info!("synced {count} blocks from peers"),
+            Err(e) => warn!("unable to sync from peers on start, {e}"),
+        }
+
         // Spawn validator thread if ChainNode is validator
         // TODO: Check if is full node in config, if not full node then validator is not needed
         self.spawn_propose_block_thread();
 
+        if let Some(interval) = self.config.compaction_interval {
+            self.spawn_compaction_thread(interval);
+        }
+
+        if let Some(interval) = self.config.backup_prune_interval {
+            self.spawn_backup_prune_thread(interval);
+        }
+
         Ok(())
     }
 
+    // stop the API server with the handle when the node shuts down
+    pub fn attach_api(&self, handle: ServerHandle) {
+        *lock!(self.api_handle) = Some(handle);
+    }
+
+    // stop block proposal and compaction loops and the attached
+    // API server, a graceful stop lets in flight requests finish
+    pub async fn shutdown(&self, graceful: bool) {
+        info!("shutting down chain node");
+        self.stopped.store(true, Ordering::SeqCst);
+
+        let handle = lock!(self.api_handle).take();
+        if let Some(handle) = handle {
+            handle.stop(graceful).await;
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
     // Get the a ArcMut of RPC handler
     pub fn rpc_controller(&self) -> Arc<RpcController> {
         self.rpc_controller.clone()
@@ -179,13 +437,51 @@ impl ChainNode {
     fn spawn_peer_rpc_thread(&self) {
         let rpc_rx = self.rpc_rx.clone();
         let handler = self.rpc_controller();
+        let pending_requests = self.pending_requests.clone();
+        let tcp_controller = self.tcp_controller.clone();
 
         // Spawn thread to handle message, main RPC handler thread
         thread::spawn(move || {
             let rpc_rx = lock!(rpc_rx);
             for (peer_addr, rpc) in rpc_rx.iter() {
-                if let Err(e) = handler.handle_rpc(&rpc, Some(peer_addr)) {
-                    error!("{e}");
+                // response to one of our outbound requests
+                if let RpcHeader::Response = rpc.header {
+                    let Some(id) = rpc.id else {
+                        warn!("response from peer: {peer_addr} has no correlation id");
+                        continue;
+                    };
+                    match RpcResponse::from_bytes(&rpc.payload) {
+                        Ok(res) => {
+                            if !pending_requests.resolve(id, res) {
+                                debug!("no pending request with id: {id}, it may have timed out");
+                            }
+                        }
+                        Err(e) => error!("unable to decode response from peer: {peer_addr}, {e}"),
+                    }
+                    continue;
+                }
+
+                let res = match handler.handle_rpc(&rpc, Some(peer_addr)) {
+                    Ok(res) => res,
+                    Err(e) => {
+                        debug!("rpc from peer: {peer_addr} failed, {e}");
+                        RpcResponse::Error(e.to_string())
+                    }
+                };
+
+                // peer is waiting on a response
+                if let Some(id) = rpc.id {
+                    match res.to_bytes() {
+                        Ok(payload) => {
+                            let response = RPC {
+                                header: RpcHeader::Response,
+                                payload,
+                                id: Some(id),
+                            };
+                            lock!(tcp_controller).send_rpc(peer_addr, &response);
+                        }
+                        Err(e) => error!("{e}"),
+                    }
                 }
             }
         });
@@ -193,65 +489,505 @@ impl ChainNode {
 
     // TODO: change validator to VM
     fn spawn_propose_block_thread(&self) {
-        let block_time = self.config.block_time;
+        let (interval, on_demand) = match self.config.block_production {
+            BlockProduction::Interval(block_time) => (block_time, false),
+            BlockProduction::OnDemand => (ON_DEMAND_POLL_INTERVAL, true),
+            // blocks are only produced through /admin/mine
+            BlockProduction::Manual => return,
+        };
+
         let validator = self.validator.clone();
         let mem_pool = self.mem_pool.clone();
         let chain = self.chain.clone();
+        let stopped = self.stopped.clone();
+        let retries = self.config.propose_retries;
 
         thread::spawn(move || {
-            loop {
-                thread::sleep(block_time);
-                // TODO: check is validator is current leader
-                let validator = lock!(validator);
-                if let Ok(mut pool) = mem_pool.lock() {
-                    // validator takes transactions from mem pool on each block duration
-                    let txs = pool.take(validator.pool_size);
-
-                    if let Ok(mut chain) = chain.lock() {
-                        match validator.propose_block(&chain, txs) {
-                            Ok(block) => {
-                                // TODO: propose block to network
-                                // broadcast added block
-                                // once block is confirmed by majority voting
-                                // adding block to chain is handled by RPC Controller
-                                if let Err(e) = chain.add_block(block) {
-                                    error!(
-                                        "unable to add block in ChainNode::spawn_validator_thread: {e}"
-                                    );
-                                }
-                            }
-                            Err(e) => {
-                                error!("unable to propose block: {e}");
-                            }
-                        }
-                    } else {
-                        error!("unable to lock chain in ChainNode::spawn_validator_thread");
-                    }
-                } else {
-                    error!("unable to lock mem_pool in ChainNode::spawn_validator_thread");
+            propose_block_loop(
+                validator, mem_pool, chain, stopped, interval, on_demand, retries,
+            )
+        });
+    }
+
+    // periodically compact storage to clear tombstones left by
+    // state backups which are written and deleted every block
+    fn spawn_compaction_thread(&self, interval: Duration) {
+        let chain = self.chain.clone();
+        let stopped = self.stopped.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            if let Err(e) = lock!(chain).compact() {
+                error!("unable to compact storage in ChainNode::spawn_compaction_thread: {e}");
+            }
+        });
+    }
+
+    // backups are cleared after every committed block, any found here
+    // were left by a block which failed part way through committing
+    fn spawn_backup_prune_thread(&self, interval: Duration) {
+        let chain = self.chain.clone();
+        let stopped = self.stopped.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            if stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            match lock!(chain).prune_backups() {
+                Ok(0) => {}
+                Ok(count) => warn!("pruned {count} orphaned state backups"),
+                Err(e) => {
+                    error!("unable to prune backups in ChainNode::spawn_backup_prune_thread: {e}")
                 }
             }
         });
     }
 }
 
-fn clear_all_data() -> Result<(), Box<dyn Error>> {
-    let block_data_dir = PathBuf::from("data/chain.db");
-    let state_data_dir = PathBuf::from("data/state.db");
+// propose a block every interval, when on_demand is set a block is
+// only proposed once the mem_pool has transactions ready
+fn propose_block_loop(
+    validator: Arc<Mutex<BlockValidator>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+    chain: Arc<Mutex<Blockchain>>,
+    stopped: Arc<AtomicBool>,
+    interval: Duration,
+    on_demand: bool,
+    retries: usize,
+) {
+    loop {
+        thread::sleep(interval);
+        if stopped.load(Ordering::SeqCst) {
+            break;
+        }
+        let Some(height) = leader_height(&validator, &mem_pool, &chain, on_demand) else {
+            continue;
+        };
+
+        // TODO: propose block to network once blocks are confirmed
+        // by majority voting
+        let res = produce_with_retry(
+            &validator,
+            &mem_pool,
+            retries,
+            PROPOSE_RETRY_BACKOFF,
+            |txs| produce_at(&validator, &chain, height, txs),
+        );
+        if let Err(e) = res {
+            error!("unable to produce block in ChainNode::spawn_propose_block_thread: {e}");
+        }
+    }
+}
+
+// height of the next block when this node is its leader and, with
+// on_demand, the mem_pool has transactions ready
+fn leader_height(
+    validator: &Mutex<BlockValidator>,
+    mem_pool: &Mutex<TxPool>,
+    chain: &Mutex<Blockchain>,
+    on_demand: bool,
+) -> Option<BlockHeight> {
+    let validator = lock!(validator);
+    let Ok(pool) = mem_pool.lock() else {
+        error!("unable to lock mem_pool in ChainNode::spawn_propose_block_thread");
+        return None;
+    };
+    if on_demand && pool.ready_count() == 0 {
+        return None;
+    }
+    let Ok(chain) = chain.lock() else {
+        error!("unable to lock chain in ChainNode::spawn_propose_block_thread");
+        return None;
+    };
+
+    match validator.is_leader(&chain) {
+        Ok(true) => Some(chain.height().next()),
+        Ok(false) => {
+            debug!("not the leader for height: {}", chain.height() + 1);
+            None
+        }
+        Err(e) => {
+            error!("unable to select leader in ChainNode::spawn_propose_block_thread: {e}");
+            None
+        }
+    }
+}
+
+// produce the block at height, locks are taken for this attempt only,
+// fails when another block was added at height since the leader check
+fn produce_at(
+    validator: &Mutex<BlockValidator>,
+    chain: &Mutex<Blockchain>,
+    height: BlockHeight,
+    txs: Vec<Transaction>,
+) -> Result<Block, CoreError> {
+    let validator = lock!(validator);
+    let mut chain = lock!(chain);
+    if chain.height().next() != height {
+        return Err(CoreError::Block(format!(
+            "chain moved past height: {height} before the block was produced"
+        )));
+    }
+    validator.produce_block_with(&mut chain, txs)
+}
 
-    if block_data_dir.exists() && block_data_dir.is_dir() {
-        fs::remove_dir_all(block_data_dir)?;
-        debug!("Block Data and its contents removed successfully.");
-    } else {
-        debug!("Block Data directory does not exist.");
+// txs are taken from the mem_pool once so every attempt proposes the
+// same txs, they are returned to the mem_pool if no block is added,
+// no lock is held between attempts
+fn produce_with_retry(
+    validator: &Mutex<BlockValidator>,
+    mem_pool: &Mutex<TxPool>,
+    retries: usize,
+    backoff: Duration,
+    mut produce: impl FnMut(Vec<Transaction>) -> Result<Block, CoreError>,
+) -> Result<Block, CoreError> {
+    let pool_size = lock!(validator).pool_size;
+    let txs = lock!(mem_pool).take(pool_size);
+    let res = retry_transient(retries, backoff, || produce(txs.clone()));
+
+    let mut pool = lock!(mem_pool);
+    match res {
+        Ok(block) => {
+            pool.confirm(block.txs());
+            Ok(block)
+        }
+        Err(e) => {
+            pool.restore(txs);
+            Err(e)
+        }
     }
+}
 
-    if state_data_dir.exists() && state_data_dir.is_dir() {
-        fs::remove_dir_all(state_data_dir)?;
-        debug!("State Data and its contents removed successfully.");
-    } else {
-        debug!("State Data directory does not exist.");
+// run attempt until it succeeds or fails with an error which is not
+// transient, at most retries more times, the last error is returned
+fn retry_transient<T>(
+    retries: usize,
+    backoff: Duration,
+    mut attempt: impl FnMut() -> Result<T, CoreError>,
+) -> Result<T, CoreError> {
+    let mut retried = 0;
+    loop {
+        match attempt() {
+            Err(e) if e.is_transient() && retried < retries => {
+                retried += 1;
+                warn!("transient error producing block, retry {retried} of {retries}: {e}");
+                thread::sleep(backoff);
+            }
+            res => return res,
+        }
+    }
+}
+
+// remove the configured chain and state storage, nothing outside
+// those paths is touched
+fn clear_storage(config: &NodeConfig) -> Result<(), Box<dyn Error>> {
+    for path in [&config.chain_storage_path, &config.state_storage_path] {
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+            debug!("removed node data at: {}", path.display());
+        } else {
+            debug!("no node data to remove at: {}", path.display());
+        }
     }
 
     Ok(())
 }
+
+fn storage_path(path: &Path) -> Result<&str, NetworkError> {
+    path.to_str().ok_or_else(|| {
+        NetworkError::Message(format!(
+            "storage path: {} is not valid UTF-8",
+            path.display()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::transaction::{Transaction, TransferData, TxType},
+        state::account::Account,
+        testing::TestNetwork,
+    };
+
+    // built field by field, the default config reads a key from disk
+    fn test_config(peer_addr: &str) -> NodeConfig {
+        NodeConfig {
+            block_production: BlockProduction::Interval(Duration::from_secs(5)),
+            private_key: PrivateKey::new(),
+            state_storage_path: PathBuf::from("data/state.db"),
+            chain_storage_path: PathBuf::from("data/chain.db"),
+            reset_on_start: false,
+            mem_pool_size: 50,
+            mem_pool_pending_grace: 0,
+            mem_pool_classes: default_tx_classes(),
+            peer_addr: peer_addr.to_string(),
+            batch_state_commits: true,
+            account_cache_size: DEFAULT_ACCOUNT_CACHE_SIZE,
+            verify_threads: DEFAULT_VERIFY_THREADS,
+            compaction_interval: None,
+            backup_prune_interval: None,
+            rpc_timeout: Duration::from_secs(5),
+            genesis: GenesisConfig::default(),
+            chain_id: 1,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            compression_threshold: None,
+            trusted_sync_source: None,
+            fee_burn_ratio: 0.0,
+            sync_window: DEFAULT_SYNC_WINDOW,
+            tx_fanout: DEFAULT_TX_FANOUT,
+            seen_tx_ttl: DEFAULT_SEEN_TX_TTL,
+            propose_retries: DEFAULT_PROPOSE_RETRIES,
+            fork_grace: DEFAULT_FORK_GRACE,
+        }
+    }
+
+    #[test]
+    fn test_peer_addr_parsing() {
+        let addr = test_config("127.0.0.1:5000").peer_socket_addr().unwrap();
+        assert_eq!(addr, "127.0.0.1:5000".parse::<SocketAddr>().unwrap());
+
+        let err = test_config("127.0.0.1:not-a-port")
+            .peer_socket_addr()
+            .unwrap_err();
+        assert!(matches!(err, NetworkError::InvalidAddress(_)));
+        assert!(err.to_string().contains("'127.0.0.1:not-a-port'"), "{err}");
+    }
+
+    #[test]
+    fn test_try_new_invalid_peer_addr() {
+        let res = ChainNode::try_new(test_config("localhost"));
+        match res {
+            Err(NetworkError::InvalidAddress(msg)) => {
+                assert!(msg.contains("invalid socket address: 'localhost'"), "{msg}")
+            }
+            Err(e) => panic!("expected InvalidAddress error, got: {e}"),
+            Ok(_) => panic!("expected InvalidAddress error"),
+        }
+    }
+
+    #[test]
+    fn test_reset_on_start_clears_configured_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_config = |reset_on_start: bool| NodeConfig {
+            state_storage_path: dir.path().join("state.db"),
+            chain_storage_path: dir.path().join("chain.db"),
+            reset_on_start,
+            ..test_config("127.0.0.1:0")
+        };
+        let other = dir.path().join("other.db");
+        fs::create_dir(&other).unwrap();
+        let address = PrivateKey::new().address();
+
+        let node = ChainNode::try_new(storage_config(false)).unwrap();
+        lock!(node.chain)
+            .state()
+            .set_account(&address, &Account { balance: 10 })
+            .unwrap();
+        drop(node);
+
+        // data is kept without reset_on_start
+        let node = ChainNode::try_new(storage_config(false)).unwrap();
+        assert_eq!(
+            lock!(node.chain).state().get_account(&address),
+            Some(Account { balance: 10 })
+        );
+        drop(node);
+
+        let node = ChainNode::try_new(storage_config(true)).unwrap();
+        assert_eq!(lock!(node.chain).state().get_account(&address), None);
+        drop(node);
+
+        // only the configured paths are cleared
+        assert!(other.is_dir());
+        clear_storage(&storage_config(true)).unwrap();
+        assert!(!dir.path().join("state.db").exists());
+        assert!(!dir.path().join("chain.db").exists());
+        assert!(other.is_dir());
+    }
+
+    #[test]
+    fn test_catch_up_without_peers() {
+        let dir = tempfile::tempdir().unwrap();
+        let node = ChainNode::try_new(NodeConfig {
+            state_storage_path: dir.path().join("state.db"),
+            chain_storage_path: dir.path().join("chain.db"),
+            ..test_config("127.0.0.1:0")
+        })
+        .unwrap();
+
+        // nothing to sync from, the node keeps its own chain
+        assert_eq!(node.catch_up().unwrap(), 0);
+        assert_eq!(lock!(node.chain).height(), 0);
+    }
+
+    #[test]
+    fn test_on_demand_block_production() {
+        let network = TestNetwork::new(1);
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+        network.fund(&receiver, 0);
+
+        let (validator, mem_pool, chain) = {
+            let node = network.node(0);
+            (
+                node.validator.clone(),
+                node.mem_pool.clone(),
+                node.chain.clone(),
+            )
+        };
+        let stopped = Arc::new(AtomicBool::new(false));
+        {
+            let (chain, stopped) = (chain.clone(), stopped.clone());
+            thread::spawn(move || {
+                propose_block_loop(
+                    validator,
+                    mem_pool,
+                    chain,
+                    stopped,
+                    Duration::from_millis(10),
+                    true,
+                    DEFAULT_PROPOSE_RETRIES,
+                )
+            });
+        }
+
+        // no block while the mem_pool is empty
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(lock!(chain).height(), 0);
+
+        let anchor = lock!(chain).tip().unwrap().hash;
+        let data = TransferData {
+            from: sender.address(),
+            to: receiver.clone(),
+            amount: 25,
+        };
+        let mut tx = Transaction::new_transfer(
+            sender.address(),
+            receiver.clone(),
+            anchor,
+            &data.to_bytes().unwrap(),
+            1,
+        )
+        .unwrap();
+        tx.sign(&sender).unwrap();
+        network.inject_tx(0, tx);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while lock!(chain).height() == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        stopped.store(true, Ordering::SeqCst);
+
+        let block = lock!(chain).last_block().unwrap();
+        assert_eq!(block.height(), 1);
+        assert!(block.txs().iter().any(|tx| tx.tx_type == TxType::Transfer));
+        assert_eq!(lock!(network.node(0).mem_pool).len(), 0);
+    }
+
+    #[test]
+    fn test_propose_retry_transient_error() {
+        let network = TestNetwork::new(1);
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+        network.fund(&receiver, 0);
+
+        let anchor = lock!(network.node(0).chain).tip().unwrap().hash;
+        let transfer = |nonce: u64| {
+            let data = TransferData {
+                from: sender.address(),
+                to: receiver.clone(),
+                amount: 10,
+            };
+            let mut tx = Transaction::new_transfer(
+                sender.address(),
+                receiver.clone(),
+                anchor,
+                &data.to_bytes().unwrap(),
+                1,
+            )
+            .unwrap();
+            tx.nonce = nonce;
+            tx.sign(&sender).unwrap();
+            tx
+        };
+        network.inject_tx(0, transfer(1));
+
+        let node = network.node(0);
+        let (validator, pool, chain) = (&node.validator, &node.mem_pool, &node.chain);
+
+        // the first failures attempts fail with an io error
+        let failing = |mut failures: usize, height: u64| {
+            move |txs| {
+                if failures > 0 {
+                    failures -= 1;
+                    return Err(CoreError::Io("unable to read state".to_string()));
+                }
+                produce_at(validator, chain, BlockHeight(height), txs)
+            }
+        };
+
+        // proposal fails once on a state read, the retried block for the
+        // slot still holds the tx taken from the mem_pool
+        let block = produce_with_retry(validator, pool, 2, Duration::ZERO, failing(1, 1)).unwrap();
+        assert_eq!(block.height(), 1);
+        assert!(block.txs().iter().any(|tx| tx.tx_type == TxType::Transfer));
+        assert_eq!(lock!(pool).len(), 0);
+
+        // txs go back to the mem_pool once retries run out
+        lock!(pool).add(transfer(2));
+        let res = produce_with_retry(validator, pool, 2, Duration::ZERO, failing(3, 2));
+        assert!(matches!(res, Err(CoreError::Io(_))));
+        assert_eq!(lock!(chain).height(), 1);
+        assert_eq!(lock!(pool).ready_count(), 1);
+
+        // a block added at the height since the leader check is not replaced
+        let res = produce_with_retry(validator, pool, 2, Duration::ZERO, |txs| {
+            produce_at(validator, chain, BlockHeight(1), txs)
+        });
+        assert!(matches!(res, Err(CoreError::Block(_))));
+        assert_eq!(lock!(pool).ready_count(), 1);
+
+        let block = produce_with_retry(validator, pool, 2, Duration::ZERO, |txs| {
+            produce_at(validator, chain, BlockHeight(2), txs)
+        })
+        .unwrap();
+        assert!(block.txs().iter().any(|tx| tx.nonce == 2));
+
+        // invalid blocks are not retried
+        let mut attempts = 0;
+        let res: Result<(), CoreError> = retry_transient(2, Duration::ZERO, || {
+            attempts += 1;
+            Err(CoreError::Block("invalid block".to_string()))
+        });
+        assert!(matches!(res, Err(CoreError::Block(_))));
+        assert_eq!(attempts, 1);
+
+        // state errors are not retried
+        let mut attempts = 0;
+        let res: Result<(), CoreError> = retry_transient(2, Duration::ZERO, || {
+            attempts += 1;
+            Err(CoreError::State(
+                "no account in state for address".to_string(),
+            ))
+        });
+        assert!(matches!(res, Err(CoreError::State(_))));
+        assert_eq!(attempts, 1);
+
+        // retries are bounded
+        let mut attempts = 0;
+        let res: Result<(), CoreError> = retry_transient(2, Duration::ZERO, || {
+            attempts += 1;
+            Err(CoreError::Io("unable to read state".to_string()))
+        });
+        assert!(matches!(res, Err(CoreError::Io(_))));
+        assert_eq!(attempts, 3);
+    }
+}
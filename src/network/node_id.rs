@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::crypto::{address::Address, private_key::PrivateKey, public_key::PublicKey};
+
+// stable identity of a node, the address of its public key, so a node
+// reconnecting from a new port is recognized, peers prove their id by
+// signing the challenge sent in the handshake
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct NodeId([u8; 20]);
+
+impl NodeId {
+    pub fn from_address(address: &Address) -> Self {
+        Self(**address)
+    }
+
+    pub fn from_private_key(key: &PrivateKey) -> Self {
+        Self::from_address(&key.address())
+    }
+
+    pub fn from_public_key(key: &PublicKey) -> Option<Self> {
+        key.address()
+            .ok()
+            .map(|address| Self::from_address(&address))
+    }
+}
+
+impl Display for NodeId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
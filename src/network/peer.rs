@@ -1,17 +1,33 @@
-use log::{error, info};
+use log::{error, info, warn};
 
-use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
 
 use crate::core::util::timestamp;
-use std::net::{SocketAddr, TcpStream};
+use std::net::{Shutdown, SocketAddr, TcpStream};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time;
 
-use super::{message::PeerMessage, types::ArcMut};
+use crate::crypto::public_key::PublicKey;
 
-#[derive(Debug)]
+use super::{message::PeerMessage, node_id::NodeId, types::ArcMut};
+
+// peer score bounds and adjustments, failures cost more than successes
+// earn so an unreliable peer drops below reliable ones quickly
+pub const MAX_PEER_SCORE: i32 = 100;
+pub const MIN_PEER_SCORE: i32 = -100;
+const PEER_SUCCESS_SCORE: i32 = 1;
+const PEER_FAILURE_SCORE: i32 = 5;
+
+// handshake received from a peer which has not yet signed the challenge
+pub struct HandshakeClaim {
+    pub node_addr: SocketAddr,
+    pub public_key: PublicKey,
+    pub compression: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PeerStreamDirection {
     Incoming,
     Outgoing,
@@ -20,10 +36,25 @@ pub enum PeerStreamDirection {
 pub struct TcpPeer {
     reader: ArcMut<BufReader<TcpStream>>,
     writer: ArcMut<BufWriter<TcpStream>>,
-    _direction: PeerStreamDirection,
+    direction: PeerStreamDirection,
     remote_addr: SocketAddr,
     tcp_controller_tx: Arc<Mutex<Sender<PeerMessage>>>,
     pub last_hb: u64,
+    // frames with a length prefix above this are rejected
+    max_frame_size: usize,
+    // listening address of the remote node, set once handshake succeeds
+    node_addr: Option<SocketAddr>,
+    // identity of the remote node, set once handshake succeeds
+    node_id: Option<NodeId>,
+    // sent in the handshake, the remote node signs it to prove its node id
+    challenge: [u8; 32],
+    // handshake waiting for the signed challenge
+    claim: Option<HandshakeClaim>,
+    // reliability of the peer, used to prefer peers for RPC requests
+    score: i32,
+    // RPC payloads above this size are compressed, set once both
+    // sides agree on compression during handshake
+    compression_threshold: Option<usize>,
 }
 
 impl TcpPeer {
@@ -33,15 +64,23 @@ impl TcpPeer {
         reader: ArcMut<BufReader<TcpStream>>,
         writer: ArcMut<BufWriter<TcpStream>>,
         tcp_controller_tx: Arc<Mutex<Sender<PeerMessage>>>,
+        max_frame_size: usize,
     ) -> Self {
         let last_hb = timestamp(time::SystemTime::now());
         Self {
             remote_addr,
             reader,
             writer,
-            _direction: direction,
+            direction,
             tcp_controller_tx,
             last_hb,
+            max_frame_size,
+            node_addr: None,
+            node_id: None,
+            challenge: rand::random(),
+            claim: None,
+            score: 0,
+            compression_threshold: None,
         }
     }
 
@@ -54,70 +93,64 @@ impl TcpPeer {
 
         // get information of node to be used in messages
         let remote_addr = self.remote_addr;
+        let max_frame_size = self.max_frame_size;
 
         // start thread to listen to reads on stream
         thread::spawn(move || {
-            // create buffer to handle incoming bytes
-            let mut buf = [0u8; 1024];
-
             if let Ok(reader) = stream.lock().as_mut() {
                 loop {
-                    match reader.read(&mut buf) {
-                        // successful read
-                        Ok(bytes_read) => {
-                            // if zero bytes read then connection is terminated
-                            if bytes_read == 0 {
-                                if let Ok(message_tx) = tcp_controller_tx.lock() {
-                                    // send error back to TCP controller
-                                    message_tx
-                                        .send(PeerMessage::Disconnect(
-                                            remote_addr,
-                                            "disconnected".to_string(),
-                                        ))
-                                        .ok();
-                                    break;
-                                }
+                    let msg = match read_frame(&mut **reader, max_frame_size) {
+                        // decode message from frame received
+                        // MAIN return of PeerMessage
+                        Ok(frame) => match PeerMessage::from_payload(remote_addr, &frame) {
+                            Ok(msg) => msg,
+                            Err(e) => {
+                                error!("unable to decode message from: {remote_addr}, {e}");
+                                continue;
                             }
+                        },
 
-                            // get TCP controller tx channel
-                            if let Ok(message_tx) = tcp_controller_tx.lock() {
-                                // decode message from payload received
-                                // MAIN return of PeerMessage
-                                if let Ok(msg) = PeerMessage::from_payload(remote_addr, &buf) {
-                                    // try send message back to TCP controller
-                                    if let Err(e) = message_tx.send(msg) {
-                                        let err = PeerMessage::Error(remote_addr, e.to_string());
-
-                                        // try send back to TCP controller again
-                                        message_tx.send(err).ok();
-                                    }
-                                }
-
-                                // clear buffer
-                                buf = [0_u8; 1024];
-                            }
+                        // connection closed or reset by remote
+                        Err(e)
+                            if e.kind() == ErrorKind::UnexpectedEof
+                                || e.kind() == ErrorKind::ConnectionReset =>
+                        {
+                            send_to_controller(
+                                &tcp_controller_tx,
+                                PeerMessage::Disconnect(remote_addr, "disconnected".to_string()),
+                            );
+                            break;
                         }
 
-                        // connection reset by remote
-                        Err(e) if e.kind() == ErrorKind::ConnectionReset => {
-                            if let Ok(tcp_controller_tx) = tcp_controller_tx.lock() {
-                                tcp_controller_tx
-                                    .send(PeerMessage::Disconnect(
-                                        remote_addr,
-                                        "disconnected".to_string(),
-                                    ))
-                                    .ok();
-                                break;
-                            }
+                        // frame rejected, close connection so the peer
+                        // can not keep sending
+                        Err(e) if e.kind() == ErrorKind::InvalidData => {
+                            warn!("disconnecting peer: {remote_addr}, {e}");
+                            reader.get_ref().shutdown(Shutdown::Both).ok();
+                            send_to_controller(
+                                &tcp_controller_tx,
+                                PeerMessage::Disconnect(remote_addr, e.to_string()),
+                            );
+                            break;
                         }
 
                         // unknown error
                         Err(e) => {
-                            if let Ok(message_tx) = tcp_controller_tx.lock() {
-                                let err = PeerMessage::Error(remote_addr, e.to_string());
-                                message_tx.send(err).ok();
-                                break;
-                            }
+                            send_to_controller(
+                                &tcp_controller_tx,
+                                PeerMessage::Error(remote_addr, e.to_string()),
+                            );
+                            break;
+                        }
+                    };
+
+                    // try send message back to TCP controller
+                    if let Ok(message_tx) = tcp_controller_tx.lock() {
+                        if let Err(e) = message_tx.send(msg) {
+                            let err = PeerMessage::Error(remote_addr, e.to_string());
+
+                            // try send back to TCP controller again
+                            message_tx.send(err).ok();
                         }
                     }
                 }
@@ -130,10 +163,22 @@ impl TcpPeer {
         if let Ok(writer) = self.writer.lock().as_mut() {
             // main method to send messages to remote peers
             // always send payload type as defined in PeerMessage payload
-            // the receiver will always decode the message with
-            // PeerMessage.from_payload()
-            if let Ok(n) = writer.write(&msg.payload()) {
-                info!("message sent to: {remote_addr:?}, num bytes: {n}",)
+            // prefixed with its length, the receiver reads the frame
+            // and decodes the message with PeerMessage.from_payload()
+            let payload = match self.compression_threshold {
+                Some(threshold) => msg.compressed_payload(threshold),
+                None => msg.payload(),
+            };
+            let len = (payload.len() as u32).to_be_bytes();
+            match writer
+                .write_all(&len)
+                .and_then(|_| writer.write_all(&payload))
+            {
+                Ok(_) => info!(
+                    "message sent to: {remote_addr:?}, num bytes: {}",
+                    payload.len()
+                ),
+                Err(e) => error!("unable to write message to: {remote_addr:?}, error: {e}"),
             }
 
             // flush writer to ensure message is sent
@@ -146,4 +191,123 @@ impl TcpPeer {
     pub fn set_last_hb(&mut self, ts: u64) {
         self.last_hb = ts;
     }
+
+    pub fn set_handshake(&mut self, node_addr: SocketAddr, node_id: NodeId) {
+        self.node_addr = Some(node_addr);
+        self.node_id = Some(node_id);
+    }
+
+    pub fn has_handshake(&self) -> bool {
+        self.node_addr.is_some()
+    }
+
+    pub fn challenge(&self) -> [u8; 32] {
+        self.challenge
+    }
+
+    pub fn set_claim(&mut self, claim: HandshakeClaim) {
+        self.claim = Some(claim);
+    }
+
+    pub fn has_claim(&self) -> bool {
+        self.claim.is_some()
+    }
+
+    pub fn take_claim(&mut self) -> Option<HandshakeClaim> {
+        self.claim.take()
+    }
+
+    pub fn set_compression(&mut self, threshold: Option<usize>) {
+        self.compression_threshold = threshold;
+    }
+
+    pub fn node_addr(&self) -> Option<SocketAddr> {
+        self.node_addr
+    }
+
+    pub fn node_id(&self) -> Option<NodeId> {
+        self.node_id
+    }
+
+    pub fn remote_addr(&self) -> SocketAddr {
+        self.remote_addr
+    }
+
+    pub fn direction(&self) -> PeerStreamDirection {
+        self.direction
+    }
+
+    pub fn score(&self) -> i32 {
+        self.score
+    }
+
+    // keep the score of a previous connection to the same node
+    pub fn set_score(&mut self, score: i32) {
+        self.score = score.clamp(MIN_PEER_SCORE, MAX_PEER_SCORE);
+    }
+
+    // valid message or response received from peer
+    pub fn record_success(&mut self) {
+        self.score = (self.score + PEER_SUCCESS_SCORE).min(MAX_PEER_SCORE);
+    }
+
+    // invalid message, error or timeout from peer
+    pub fn record_failure(&mut self) {
+        self.score = (self.score - PEER_FAILURE_SCORE).max(MIN_PEER_SCORE);
+    }
+
+    // close both halves of the connection, the incoming handler
+    // thread then sends Disconnect back to the TCP controller
+    pub fn shutdown(&mut self) {
+        if let Ok(writer) = self.writer.lock() {
+            if let Err(e) = writer.get_ref().shutdown(Shutdown::Both) {
+                error!(
+                    "unable to shutdown connection to: {}, error: {e}",
+                    self.remote_addr
+                );
+            }
+        }
+    }
+}
+
+// read a single length prefixed frame, the length is checked against
+// max_frame_size before the frame buffer is allocated
+fn read_frame<R: Read>(reader: &mut R, max_frame_size: usize) -> io::Result<Vec<u8>> {
+    let mut len_buf = [0_u8; 4];
+    reader.read_exact(&mut len_buf)?;
+
+    let len = u32::from_be_bytes(len_buf) as usize;
+    if len == 0 || len > max_frame_size {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("frame size: {len} is outside allowed range 1..={max_frame_size}"),
+        ));
+    }
+
+    let mut frame = vec![0_u8; len];
+    reader.read_exact(&mut frame)?;
+    Ok(frame)
+}
+
+fn send_to_controller(tcp_controller_tx: &Arc<Mutex<Sender<PeerMessage>>>, msg: PeerMessage) {
+    if let Ok(message_tx) = tcp_controller_tx.lock() {
+        message_tx.send(msg).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_frame_max_size() {
+        let mut data = 5_u32.to_be_bytes().to_vec();
+        data.extend_from_slice(b"hello");
+        assert_eq!(read_frame(&mut data.as_slice(), 5).unwrap(), b"hello");
+
+        // length is rejected before reading the frame
+        let data = u32::MAX.to_be_bytes();
+        let err = read_frame(&mut data.as_slice(), 1024).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
 }
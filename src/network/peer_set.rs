@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use super::{
+    node_id::NodeId,
+    peer::{PeerStreamDirection, TcpPeer},
+};
+
+// connected peers keyed by node id once the handshake completes, the
+// remote address of each connection is tracked so messages from the
+// connection can still be matched to its peer
+#[derive(Default)]
+pub struct PeerSet {
+    // connections which have not completed the handshake
+    pending: HashMap<SocketAddr, TcpPeer>,
+    peers: HashMap<NodeId, TcpPeer>,
+    addrs: HashMap<SocketAddr, NodeId>,
+}
+
+impl PeerSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_pending(&mut self, addr: SocketAddr, peer: TcpPeer) {
+        self.pending.insert(addr, peer);
+    }
+
+    pub fn get(&self, addr: &SocketAddr) -> Option<&TcpPeer> {
+        match self.addrs.get(addr) {
+            Some(node_id) => self.peers.get(node_id),
+            None => self.pending.get(addr),
+        }
+    }
+
+    pub fn get_mut(&mut self, addr: &SocketAddr) -> Option<&mut TcpPeer> {
+        match self.addrs.get(addr) {
+            Some(node_id) => self.peers.get_mut(node_id),
+            None => self.pending.get_mut(addr),
+        }
+    }
+
+    pub fn remove(&mut self, addr: &SocketAddr) -> Option<TcpPeer> {
+        match self.addrs.remove(addr) {
+            Some(node_id) => self.peers.remove(&node_id),
+            None => self.pending.remove(addr),
+        }
+    }
+
+    pub fn node_id(&self, addr: &SocketAddr) -> Option<NodeId> {
+        self.addrs.get(addr).copied()
+    }
+
+    // current remote address of the connection to node_id
+    pub fn addr(&self, node_id: &NodeId) -> Option<SocketAddr> {
+        self.peers.get(node_id).map(|peer| peer.remote_addr())
+    }
+
+    pub fn node_ids(&self) -> Vec<NodeId> {
+        self.peers.keys().copied().collect()
+    }
+
+    // remote address of every connection, including pending ones
+    pub fn addrs(&self) -> Vec<SocketAddr> {
+        self.pending
+            .keys()
+            .chain(self.addrs.keys())
+            .copied()
+            .collect()
+    }
+
+    // peers which have completed the handshake
+    pub fn handshake_peers(&self) -> impl Iterator<Item = &TcpPeer> {
+        self.peers.values()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut TcpPeer> {
+        self.pending.values_mut().chain(self.peers.values_mut())
+    }
+
+    // move a pending connection under the node id set by its handshake,
+    // when the node is already connected only one connection is kept and
+    // the other is returned to be closed
+    pub fn complete_handshake(&mut self, addr: &SocketAddr, local_id: NodeId) -> Option<TcpPeer> {
+        let mut peer = self.pending.remove(addr)?;
+        let node_id = peer.node_id()?;
+
+        let Some(existing) = self.peers.remove(&node_id) else {
+            self.addrs.insert(*addr, node_id);
+            self.peers.insert(node_id, peer);
+            return None;
+        };
+
+        let (keep, close) = if keep_new_connection(&existing, &peer, local_id) {
+            peer.set_score(existing.score());
+            (peer, existing)
+        } else {
+            (existing, peer)
+        };
+
+        self.addrs.remove(&close.remote_addr());
+        self.addrs.insert(keep.remote_addr(), node_id);
+        self.peers.insert(node_id, keep);
+        Some(close)
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len() + self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// both nodes connecting to each other at the same time must keep the same
+// connection, the one opened by the lower node id, a node reconnecting on
+// a new connection it opened replaces its old one
+fn keep_new_connection(existing: &TcpPeer, new: &TcpPeer, local_id: NodeId) -> bool {
+    let opened_by = |peer: &TcpPeer| match peer.direction() {
+        PeerStreamDirection::Outgoing => Some(local_id),
+        PeerStreamDirection::Incoming => peer.node_id(),
+    };
+
+    let (existing, new) = (opened_by(existing), opened_by(new));
+    if existing == new {
+        return true;
+    }
+    new < existing
+}
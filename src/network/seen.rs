@@ -0,0 +1,127 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::crypto::hash::Hash;
+
+// how long a transaction received from a peer is remembered
+pub const DEFAULT_SEEN_TX_TTL: Duration = Duration::from_secs(600);
+
+// hashes remembered at most, the oldest is forgotten first even
+// before it expires
+pub const DEFAULT_SEEN_TX_CAPACITY: usize = 10_000;
+
+// time bounded set of transaction content hashes already handled, a
+// tx received again before it expires is dropped without reaching the
+// mem_pool or being gossiped, expired hashes are pruned on insert
+pub struct SeenTxCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<SeenEntries>,
+}
+
+#[derive(Default)]
+struct SeenEntries {
+    seen: HashMap<Hash, Instant>,
+    // insertion order, oldest first, used to prune expired hashes
+    order: VecDeque<(Hash, Instant)>,
+}
+
+impl SeenTxCache {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: Mutex::new(SeenEntries::default()),
+        }
+    }
+
+    pub fn contains(&self, hash: &Hash) -> bool {
+        let entries = self.entries.lock().unwrap();
+        match entries.seen.get(hash) {
+            Some(seen_at) => seen_at.elapsed() < self.ttl,
+            None => false,
+        }
+    }
+
+    // remember hash, false when it was already seen within the ttl
+    pub fn insert(&self, hash: Hash) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+
+        while let Some((oldest, seen_at)) = entries.order.front().copied() {
+            if seen_at.elapsed() < self.ttl {
+                break;
+            }
+            entries.order.pop_front();
+            // a hash seen again after expiring has a newer entry
+            if entries.seen.get(&oldest) == Some(&seen_at) {
+                entries.seen.remove(&oldest);
+            }
+        }
+
+        if entries.seen.contains_key(&hash) {
+            return false;
+        }
+
+        let now = Instant::now();
+        entries.seen.insert(hash, now);
+        entries.order.push_back((hash, now));
+
+        if entries.seen.len() > self.capacity {
+            if let Some((oldest, _)) = entries.order.pop_front() {
+                entries.seen.remove(&oldest);
+            }
+        }
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().seen.len()
+    }
+}
+
+impl Default for SeenTxCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_SEEN_TX_TTL, DEFAULT_SEEN_TX_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::utils::random_hash;
+    use std::thread;
+
+    #[test]
+    fn test_seen_tx_cache_ttl() {
+        let cache = SeenTxCache::new(Duration::from_millis(50), 10);
+        let (hash_1, hash_2) = (random_hash(), random_hash());
+
+        assert!(cache.insert(hash_1));
+        assert!(!cache.insert(hash_1));
+        assert!(cache.contains(&hash_1));
+        assert!(!cache.contains(&hash_2));
+
+        thread::sleep(Duration::from_millis(60));
+        assert!(!cache.contains(&hash_1));
+
+        // expired hashes are pruned on next insert
+        assert!(cache.insert(hash_2));
+        assert_eq!(cache.len(), 1);
+        assert!(cache.insert(hash_1));
+    }
+
+    #[test]
+    fn test_seen_tx_cache_capacity() {
+        let cache = SeenTxCache::new(DEFAULT_SEEN_TX_TTL, 2);
+        let hashes = [random_hash(), random_hash(), random_hash()];
+        for hash in hashes {
+            assert!(cache.insert(hash));
+        }
+
+        // the oldest hash was forgotten once the third was seen
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&hashes[0]));
+        assert!(cache.insert(hashes[0]));
+    }
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{mpsc::channel, Arc, Mutex},
+    thread,
+};
+
+use log::{debug, warn};
+
+use crate::{
+    api::routes::block::GetBlockReq,
+    core::{block::Block, blockchain::Blockchain, height::BlockHeight},
+    lock,
+    rpc::types::{RpcHeader, RpcResponse},
+    vm::validator::{BlockSource, BlockValidator},
+};
+
+use super::{error::NetworkError, node::ChainNode};
+
+// block heights requested at once while syncing, 1 fetches sequentially
+pub const DEFAULT_SYNC_WINDOW: usize = 8;
+
+// times a height is requested again after a failed fetch or a block
+// which does not apply, each retry goes to the next peer
+pub const MAX_SYNC_RETRIES: usize = 3;
+
+// fetch a single block by height from a peer
+pub trait BlockFetcher: Sync {
+    fn fetch_block(
+        &self,
+        peer_addr: SocketAddr,
+        height: BlockHeight,
+    ) -> Result<Block, NetworkError>;
+}
+
+impl BlockFetcher for ChainNode {
+    fn fetch_block(
+        &self,
+        peer_addr: SocketAddr,
+        height: BlockHeight,
+    ) -> Result<Block, NetworkError> {
+        let req = GetBlockReq {
+            height: Some(height.to_string()),
+            hash: None,
+        };
+        let payload = bincode::serialize(&req)
+            .map_err(|e| NetworkError::Decoding(format!("unable to encode GetBlockReq, {e}")))?;
+
+        match self.request_rpc(peer_addr, RpcHeader::GetBlock, payload)? {
+            RpcResponse::Block(block) => Ok(block),
+            RpcResponse::Error(e) => Err(NetworkError::RPC(format!("peer {peer_addr}: {e}"))),
+            res => Err(NetworkError::RPC(format!(
+                "unexpected response to GetBlock from peer {peer_addr}: {res:?}"
+            ))),
+        }
+    }
+}
+
+// sync blocks after the chain tip up to target from peers, up to window
+// heights are fetched concurrently and spread across peers, blocks are
+// buffered and added in height order, returns the number of blocks added
+pub fn sync_blocks<F: BlockFetcher>(
+    fetcher: &F,
+    peer_addrs: &[SocketAddr],
+    target: BlockHeight,
+    window: usize,
+    validator: Arc<Mutex<BlockValidator>>,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<usize, NetworkError> {
+    let start = lock!(chain).height().next();
+    if target < start {
+        return Ok(0);
+    }
+    if peer_addrs.is_empty() {
+        return Err(NetworkError::RPC("no peers to sync from".to_string()));
+    }
+    let window = window.max(1) as u64;
+
+    thread::scope(|s| {
+        let (tx, rx) = channel();
+        let fetch = |height: BlockHeight, attempt: usize| {
+            let peer_addr = peer_addrs[(height.value() as usize + attempt) % peer_addrs.len()];
+            let tx = tx.clone();
+            s.spawn(move || {
+                let res = fetcher.fetch_block(peer_addr, height);
+                // receiver is gone once sync has returned
+                let _ = tx.send((height, attempt, peer_addr, res));
+            });
+        };
+
+        // next height to add to the chain and to request
+        let (mut next, mut requested) = (start, start);
+        let mut ready: BTreeMap<BlockHeight, (SocketAddr, Block)> = BTreeMap::new();
+
+        let retry = |height: BlockHeight, attempt: usize, e: NetworkError| {
+            if attempt >= MAX_SYNC_RETRIES {
+                return Err(NetworkError::RPC(format!(
+                    "unable to sync block at height: {height} after {} attempts, {e}",
+                    attempt + 1
+                )));
+            }
+            warn!("retrying block at height: {height} from another peer, {e}");
+            fetch(height, attempt + 1);
+            Ok(())
+        };
+
+        // attempts made so far for heights waiting in ready
+        let mut attempts: BTreeMap<BlockHeight, usize> = BTreeMap::new();
+
+        while next <= target {
+            // the window bounds heights in flight and buffered
+            while requested <= target && requested < next + window {
+                fetch(requested, 0);
+                requested += 1;
+            }
+
+            let (height, attempt, peer_addr, res) = rx
+                .recv()
+                .map_err(|e| NetworkError::Internal(format!("sync channel closed, {e}")))?;
+
+            match res {
+                Ok(block) if block.height() == height => {
+                    ready.insert(height, (peer_addr, block));
+                    attempts.insert(height, attempt);
+                }
+                Ok(block) => retry(
+                    height,
+                    attempt,
+                    NetworkError::RPC(format!(
+                        "peer {peer_addr} returned block at height: {}",
+                        block.height()
+                    )),
+                )?,
+                Err(e) => retry(height, attempt, e)?,
+            }
+
+            // add the contiguous run of buffered blocks, a gap waits
+            // for its height to arrive
+            while let Some((peer_addr, block)) = ready.remove(&next) {
+                let attempt = attempts.remove(&next).unwrap_or(0);
+                match add_synced_block(&validator, &chain, block, peer_addr) {
+                    Ok(()) => {
+                        debug!("synced block at height: {next} from peer: {peer_addr}");
+                        next += 1;
+                    }
+                    Err(e) => {
+                        retry(next, attempt, e)?;
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok((next - start) as usize)
+    })
+}
+
+fn add_synced_block(
+    validator: &Arc<Mutex<BlockValidator>>,
+    chain: &Arc<Mutex<Blockchain>>,
+    block: Block,
+    peer_addr: SocketAddr,
+) -> Result<(), NetworkError> {
+    let validator = lock!(validator);
+    validator.validate_block_signer(&block)?;
+
+    // sync only requests heights above the tip, a block competing with
+    // the tip is left to CommitBlock which returns the replaced tip's
+    // txs to the mem_pool, sync has no mem_pool to return them to
+    let mut chain = lock!(chain);
+    validator.validate_block_from(&chain, &block, BlockSource::Sync(peer_addr))?;
+    chain.add_block(block)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    use crate::{crypto::private_key::PrivateKey, state::account::Account};
+
+    // serves blocks from a chain after a fixed delay, like a peer
+    // on a high latency link
+    struct DelayedPeer {
+        blocks: Vec<Block>,
+        delay: Duration,
+        // heights which fail on the first request
+        fail_once: Mutex<Vec<u64>>,
+    }
+
+    impl BlockFetcher for DelayedPeer {
+        fn fetch_block(
+            &self,
+            peer_addr: SocketAddr,
+            height: BlockHeight,
+        ) -> Result<Block, NetworkError> {
+            thread::sleep(self.delay);
+            let mut fail_once = lock!(self.fail_once);
+            if let Some(i) = fail_once.iter().position(|h| height == *h) {
+                fail_once.remove(i);
+                return Err(NetworkError::RPC(format!("peer {peer_addr}: timed out")));
+            }
+            self.blocks
+                .get(height.value() as usize)
+                .cloned()
+                .ok_or_else(|| NetworkError::NotFound(format!("no block at height: {height}")))
+        }
+    }
+
+    // every node starts from the same state so synced state roots match
+    fn new_node(
+        key: &PrivateKey,
+        producer: &PrivateKey,
+    ) -> (Arc<Mutex<BlockValidator>>, Arc<Mutex<Blockchain>>) {
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        chain
+            .state()
+            .set_account(&producer.address(), &Account { balance: 0 })
+            .unwrap();
+        let mut validator = BlockValidator::new(key.clone(), 10);
+        validator.add_validator(producer.address());
+        (Arc::new(Mutex::new(validator)), Arc::new(Mutex::new(chain)))
+    }
+
+    #[test]
+    fn test_pipelined_sync() {
+        let producer = PrivateKey::new();
+        let (validator, chain) = new_node(&producer, &producer);
+        let mut blocks = vec![lock!(chain).last_block().unwrap()];
+        for _ in 0..12 {
+            let validator = lock!(validator);
+            let mut chain = lock!(chain);
+            let block = validator.propose_block(&chain, vec![]).unwrap();
+            validator
+                .add_proposed_block(&mut chain, block.clone())
+                .unwrap();
+            blocks.push(block);
+        }
+
+        let peer = DelayedPeer {
+            blocks,
+            delay: Duration::from_millis(20),
+            fail_once: Mutex::new(vec![3, 7]),
+        };
+        let peer_addrs: Vec<SocketAddr> = vec![
+            "127.0.0.1:5001".parse().unwrap(),
+            "127.0.0.1:5002".parse().unwrap(),
+        ];
+
+        let sync = |window: usize| {
+            *lock!(peer.fail_once) = vec![3, 7];
+            let (validator, chain) = new_node(&PrivateKey::new(), &producer);
+            let started = Instant::now();
+            let added = sync_blocks(
+                &peer,
+                &peer_addrs,
+                BlockHeight(12),
+                window,
+                validator,
+                chain.clone(),
+            )
+            .unwrap();
+            (added, started.elapsed(), chain)
+        };
+
+        let (added, sequential, chain) = sync(1);
+        assert_eq!(added, 12);
+        assert_eq!(lock!(chain).height(), 12);
+
+        // failed heights are retried and blocks still added in order
+        let (added, pipelined, chain) = sync(4);
+        assert_eq!(added, 12);
+        let chain = lock!(chain);
+        assert_eq!(chain.height(), 12);
+        assert_eq!(chain.last_block().unwrap().hash(), peer.blocks[12].hash());
+        assert!(pipelined < sequential, "{pipelined:?} >= {sequential:?}");
+
+        // nothing to do once the chain reaches the target
+        drop(chain);
+        let (validator, chain) = new_node(&PrivateKey::new(), &producer);
+        sync_blocks(
+            &peer,
+            &peer_addrs,
+            BlockHeight(12),
+            4,
+            validator.clone(),
+            chain.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            sync_blocks(&peer, &peer_addrs, BlockHeight(12), 4, validator, chain).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sync_gives_up_on_missing_block() {
+        let producer = PrivateKey::new();
+        let (validator, chain) = new_node(&PrivateKey::new(), &producer);
+        let peer = DelayedPeer {
+            blocks: vec![lock!(chain).last_block().unwrap()],
+            delay: Duration::ZERO,
+            fail_once: Mutex::new(vec![]),
+        };
+        let peer_addrs = vec!["127.0.0.1:5001".parse().unwrap()];
+
+        let err = sync_blocks(
+            &peer,
+            &peer_addrs,
+            BlockHeight(2),
+            4,
+            validator,
+            chain.clone(),
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unable to sync block at height: 1"),
+            "{err}"
+        );
+        assert_eq!(lock!(chain).height(), 0);
+    }
+}
@@ -2,32 +2,105 @@ use log::{debug, error, info, warn};
 
 use std::io::{BufReader, BufWriter};
 
-use std::collections::HashMap;
 use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time;
+use std::time::{self, Duration};
+
+use socket2::{Domain, Socket, Type};
 
 use crate::core::encoding::ByteEncoding;
 use crate::core::util::timestamp;
+use crate::crypto::{private_key::PrivateKey, public_key::PublicKey, signature::Signature};
 use crate::lock;
 use crate::network::error::NetworkError;
 use crate::rpc::types::RPC;
 
+use super::gossip::{Gossip, DEFAULT_TX_FANOUT};
 use super::types::RpcChanMsg;
 use super::{
-    message::PeerMessage,
-    peer::{PeerStreamDirection, TcpPeer},
+    message::{handshake_auth_data, PeerMessage, PROTOCOL_VERSION},
+    node_id::NodeId,
+    peer::{HandshakeClaim, PeerStreamDirection, TcpPeer},
+    peer_set::PeerSet,
     types::ArcMut,
 };
 
+// default maximum size of a single peer message frame, 4MiB
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 4 * 1024 * 1024;
+
+// default bind retries while the OS releases the port after a restart
+pub const DEFAULT_BIND_RETRIES: u32 = 5;
+pub const DEFAULT_BIND_RETRY_INTERVAL: Duration = Duration::from_millis(200);
+
+pub struct TcpConfig {
+    // peers on a different chain are disconnected during handshake
+    pub chain_id: u32,
+    // peers sending a frame larger than this are disconnected
+    pub max_frame_size: usize,
+    // extra attempts to bind the listener before giving up
+    pub bind_retries: u32,
+    pub bind_retry_interval: Duration,
+    // compress RPC payloads above this size for peers which also
+    // enable compression, None disables compression
+    pub compression_threshold: Option<usize>,
+    // peers each new transaction is forwarded to
+    pub tx_fanout: usize,
+    // key the node id is derived from, peers must sign the handshake
+    // challenge with it before they are keyed by node id
+    pub private_key: PrivateKey,
+}
+
+impl Default for TcpConfig {
+    fn default() -> Self {
+        Self {
+            chain_id: 1,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            bind_retries: DEFAULT_BIND_RETRIES,
+            bind_retry_interval: DEFAULT_BIND_RETRY_INTERVAL,
+            compression_threshold: None,
+            tx_fanout: DEFAULT_TX_FANOUT,
+            private_key: PrivateKey::new(),
+        }
+    }
+}
+
+// handshake sent to every peer, each connection gets its own challenge
+struct LocalHandshake {
+    chain_id: u32,
+    node_addr: SocketAddr,
+    public_key: Vec<u8>,
+    compression: bool,
+}
+
+impl LocalHandshake {
+    fn message(&self, challenge: [u8; 32]) -> PeerMessage {
+        PeerMessage::Handshake {
+            addr: self.node_addr,
+            chain_id: self.chain_id,
+            protocol_version: PROTOCOL_VERSION,
+            node_addr: self.node_addr,
+            public_key: self.public_key.clone(),
+            challenge,
+            compression: self.compression,
+        }
+    }
+}
+
 pub struct TcpController {
     pub node_addr: SocketAddr,
+    node_id: NodeId,
+    private_key: PrivateKey,
+    chain_id: u32,
+    max_frame_size: usize,
+    compression_threshold: Option<usize>,
     hb_interval: u64,
     _hb_threshhold: u64,
     listener: ArcMut<TcpListener>,
-    peers: ArcMut<HashMap<SocketAddr, TcpPeer>>,
+    peers: ArcMut<PeerSet>,
+    // picks the peers each transaction is forwarded to
+    tx_gossip: Mutex<Gossip>,
 
     // channel used to send messages to ChainNode
     rpc_tx: Arc<Mutex<Sender<RpcChanMsg>>>,
@@ -40,12 +113,13 @@ pub struct TcpController {
 impl TcpController {
     pub fn new(
         node_addr: SocketAddr,
+        config: TcpConfig,
         rpc_tx: Arc<Mutex<Sender<RpcChanMsg>>>,
     ) -> Result<Self, NetworkError> {
-        let listener = match TcpListener::bind(node_addr) {
-            Ok(listener) => listener,
-            Err(e) => return Err(NetworkError::Connect(e.to_string())),
-        };
+        let listener = bind_listener(node_addr, config.bind_retries, config.bind_retry_interval)?;
+
+        // resolve actual address if bound to port 0
+        let node_addr = listener.local_addr().unwrap_or(node_addr);
 
         // create channels to be used to communicate with remote peers
         let (tx, rx) = channel::<PeerMessage>();
@@ -53,8 +127,14 @@ impl TcpController {
 
         Ok(Self {
             node_addr,
+            node_id: NodeId::from_private_key(&config.private_key),
+            private_key: config.private_key,
+            chain_id: config.chain_id,
+            max_frame_size: config.max_frame_size,
+            compression_threshold: config.compression_threshold,
             listener: ArcMut::new(listener),
-            peers: ArcMut::new(HashMap::new()),
+            peers: ArcMut::new(PeerSet::new()),
+            tx_gossip: Mutex::new(Gossip::new(config.tx_fanout)),
             rpc_tx,
             peer_msg_rx,
             peer_msg_tx,
@@ -75,8 +155,65 @@ impl TcpController {
         self.init_listener();
     }
 
+    pub fn node_id(&self) -> NodeId {
+        self.node_id
+    }
+
     pub fn get_peer_addrs(&self) -> Vec<SocketAddr> {
-        self.peers.lock().unwrap().keys().cloned().collect()
+        self.peers.lock().unwrap().addrs()
+    }
+
+    // peers which have completed the handshake
+    pub fn get_handshake_peer_addrs(&self) -> Vec<SocketAddr> {
+        self.peers
+            .lock()
+            .unwrap()
+            .handshake_peers()
+            .map(|peer| peer.remote_addr())
+            .collect()
+    }
+
+    pub fn get_peer_node_ids(&self) -> Vec<NodeId> {
+        self.peers.lock().unwrap().node_ids()
+    }
+
+    // identity of the node connected from addr
+    pub fn get_node_id(&self, addr: &SocketAddr) -> Option<NodeId> {
+        self.peers.lock().unwrap().node_id(addr)
+    }
+
+    // current address of the connection to node_id
+    pub fn get_node_addr(&self, node_id: &NodeId) -> Option<SocketAddr> {
+        self.peers.lock().unwrap().addr(node_id)
+    }
+
+    // handshake peers ordered by score, highest first
+    pub fn get_peer_addrs_by_score(&self) -> Vec<SocketAddr> {
+        let peers = self.peers.lock().unwrap();
+        let mut scored: Vec<(SocketAddr, i32)> = peers
+            .handshake_peers()
+            .map(|peer| (peer.remote_addr(), peer.score()))
+            .collect();
+
+        // sort by address on equal score so selection is stable
+        scored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        scored.into_iter().map(|(addr, _)| addr).collect()
+    }
+
+    pub fn best_peer_addr(&self) -> Option<SocketAddr> {
+        self.get_peer_addrs_by_score().first().copied()
+    }
+
+    pub fn record_peer_success(&self, addr: SocketAddr) {
+        if let Some(peer) = self.peers.lock().unwrap().get_mut(&addr) {
+            peer.record_success();
+        }
+    }
+
+    pub fn record_peer_failure(&self, addr: SocketAddr) {
+        if let Some(peer) = self.peers.lock().unwrap().get_mut(&addr) {
+            peer.record_failure();
+        }
     }
 
     // pub fn send_rpc(&self, addr: SocketAddr, rpc: RPC) {
@@ -90,14 +227,30 @@ impl TcpController {
     }
 
     pub fn broadcast(&self, rpc: &RPC, ignore_addr: Option<SocketAddr>) {
-        for addr in self.peers.lock().unwrap().keys() {
+        // collect addresses first, send_rpc locks peers
+        for addr in self.get_peer_addrs() {
             // Skip current peer address if ignore address is set
             if let Some(ignore_addr) = ignore_addr {
-                if ignore_addr == *addr {
+                if ignore_addr == addr {
                     continue;
                 }
             }
-            self.send_rpc(*addr, rpc);
+            self.send_rpc(addr, rpc);
+        }
+    }
+
+    // forward rpc to a random subset of peers other than ignore_addr, a
+    // payload which was already forwarded is dropped
+    pub fn gossip(&self, rpc: &RPC, ignore_addr: Option<SocketAddr>) {
+        let peer_addrs = self.get_peer_addrs();
+        let targets = self
+            .tx_gossip
+            .lock()
+            .unwrap()
+            .targets(&peer_addrs, ignore_addr);
+
+        for addr in targets {
+            self.send_rpc(addr, rpc);
         }
     }
 
@@ -110,6 +263,10 @@ impl TcpController {
     fn init_message_receiver(&self) {
         // get data to be used in thread below
         let _node_addr = self.node_addr;
+        let node_id = self.node_id;
+        let private_key = self.private_key.clone();
+        let chain_id = self.chain_id;
+        let compression_threshold = self.compression_threshold;
         let peers = self.peers.clone();
         let rpc_tx = self.rpc_tx.clone();
         let peer_msg_rx = self.peer_msg_rx.clone();
@@ -129,16 +286,142 @@ impl TcpController {
                             warn!("error received from peer: {addr} with message: {msg}");
                             peers.lock().unwrap().remove(&addr);
                         }
+                        PeerMessage::Handshake {
+                            addr,
+                            chain_id: peer_chain_id,
+                            protocol_version,
+                            node_addr,
+                            public_key,
+                            challenge,
+                            compression,
+                        } => {
+                            let mut peers = peers.lock().unwrap();
+                            if let Some(peer) = peers.get_mut(&addr) {
+                                let public_key = PublicKey::from_bytes(&public_key).ok();
+                                let peer_node_id =
+                                    public_key.as_ref().and_then(NodeId::from_public_key);
+                                if peer.has_handshake() || peer.has_claim() {
+                                    warn!("ignoring repeated handshake from peer: {addr}");
+                                } else if peer_chain_id != chain_id
+                                    || protocol_version != PROTOCOL_VERSION
+                                {
+                                    warn!(
+                                        "handshake rejected from peer: {addr}, chain id: {peer_chain_id}, protocol version: {protocol_version}"
+                                    );
+                                    let msg = PeerMessage::Disconnect(
+                                        addr,
+                                        "chain id or protocol version mismatch".to_string(),
+                                    );
+                                    peer.send_msg(&msg);
+                                    peer.shutdown();
+                                    peers.remove(&addr);
+                                } else if peer_node_id == Some(node_id) {
+                                    warn!("disconnecting peer: {addr}, connected to self");
+                                    peer.shutdown();
+                                    peers.remove(&addr);
+                                } else if let (Some(public_key), Some(_)) =
+                                    (public_key, peer_node_id)
+                                {
+                                    // node id is only trusted once the peer signs
+                                    // the challenge sent in our handshake
+                                    peer.set_claim(HandshakeClaim {
+                                        node_addr,
+                                        public_key,
+                                        compression,
+                                    });
+                                    match private_key
+                                        .sign(&handshake_auth_data(chain_id, &challenge))
+                                        .to_bytes()
+                                    {
+                                        Ok(signature) => peer
+                                            .send_msg(&PeerMessage::HandshakeAuth(addr, signature)),
+                                        Err(e) => error!("unable to sign handshake challenge: {e}"),
+                                    }
+                                } else {
+                                    warn!(
+                                        "handshake rejected from peer: {addr}, invalid public key"
+                                    );
+                                    peer.shutdown();
+                                    peers.remove(&addr);
+                                }
+                            }
+                        }
+                        PeerMessage::HandshakeAuth(addr, signature) => {
+                            let mut peers = peers.lock().unwrap();
+                            let Some(peer) = peers.get_mut(&addr) else {
+                                continue;
+                            };
+                            let Some(claim) = peer.take_claim() else {
+                                warn!(
+                                    "ignoring handshake auth from peer: {addr} without handshake"
+                                );
+                                continue;
+                            };
+
+                            let signed = handshake_auth_data(chain_id, &peer.challenge());
+                            let verified =
+                                Signature::from_bytes(&signature).is_ok_and(|signature| {
+                                    claim.public_key.verify(&signed, &signature)
+                                });
+                            let peer_node_id = NodeId::from_public_key(&claim.public_key);
+
+                            match peer_node_id {
+                                Some(peer_node_id) if verified => {
+                                    let node_addr = claim.node_addr;
+                                    info!("handshake completed with peer: {addr}, node address: {node_addr}, node id: {peer_node_id}");
+                                    peer.set_handshake(node_addr, peer_node_id);
+                                    if claim.compression {
+                                        peer.set_compression(compression_threshold);
+                                    }
+                                    if let Some(mut closed) =
+                                        peers.complete_handshake(&addr, node_id)
+                                    {
+                                        info!(
+                                            "closing duplicate connection: {} to node id: {peer_node_id}",
+                                            closed.remote_addr()
+                                        );
+                                        closed.shutdown();
+                                    }
+                                }
+                                _ => {
+                                    warn!("handshake rejected from peer: {addr}, invalid challenge signature");
+                                    let msg = PeerMessage::Disconnect(
+                                        addr,
+                                        "invalid handshake signature".to_string(),
+                                    );
+                                    peer.send_msg(&msg);
+                                    peer.shutdown();
+                                    peers.remove(&addr);
+                                }
+                            }
+                        }
                         PeerMessage::RPC(addr, rpc_bytes) => {
+                            let has_handshake = peers
+                                .lock()
+                                .unwrap()
+                                .get(&addr)
+                                .map(|peer| peer.has_handshake())
+                                .unwrap_or(false);
+                            if !has_handshake {
+                                warn!("dropping RPC from peer: {addr} before handshake");
+                                continue;
+                            }
+
                             match RPC::from_bytes(&rpc_bytes) {
                                 Ok(rpc) => {
+                                    if let Some(peer) = peers.lock().unwrap().get_mut(&addr) {
+                                        peer.record_success();
+                                    }
                                     // Send message back to ChainNode
                                     if let Err(e) = lock!(rpc_tx).send((addr, rpc)) {
                                         error!("error sending message on RPC chanel from TCPController: {e}, to ChainNode");
                                     };
                                 }
                                 Err(e) => {
-                                    error!("unable to decode RPC from peer message: {e}")
+                                    error!("unable to decode RPC from peer message: {e}");
+                                    if let Some(peer) = peers.lock().unwrap().get_mut(&addr) {
+                                        peer.record_failure();
+                                    }
                                 }
                             }
                         }
@@ -157,6 +440,7 @@ impl TcpController {
                             if let Some(peer) = peers.lock().unwrap().get_mut(&addr) {
                                 let ts = timestamp(time::SystemTime::now());
                                 peer.set_last_hb(ts);
+                                peer.record_success();
                                 debug!("PONG message received from: {addr}");
                             }
                         }
@@ -173,6 +457,8 @@ impl TcpController {
         let listener = self.listener.clone();
         let peer_msg_tx = self.peer_msg_tx.clone();
         let node_addr = self.node_addr;
+        let handshake = self.local_handshake();
+        let max_frame_size = self.max_frame_size;
 
         // spawn main thread to listen to incoming connections
         // create new peer and add to peer set on each
@@ -194,13 +480,19 @@ impl TcpController {
                         reader,
                         writer,
                         peer_msg_tx.clone(),
+                        max_frame_size,
                     );
 
+                    // hold peers lock until peer is inserted so the handshake
+                    // reply is not handled before the peer is known
+                    let mut peers = peers.lock().unwrap();
+
                     // start handler for incoming messages on peer
+                    peer.send_msg(&handshake.message(peer.challenge()));
                     peer.spawn_incoming_handler();
 
                     // insert peer into peer set
-                    peers.lock().unwrap().insert(remote_addr, peer);
+                    peers.insert_pending(remote_addr, peer);
                 }
             } else {
                 error!("unable to get lock on listener in TCP controller");
@@ -224,13 +516,17 @@ impl TcpController {
                         reader,
                         writer,
                         self.peer_msg_tx.clone(),
+                        self.max_frame_size,
                     );
 
+                    let mut peers = self.peers.lock().unwrap();
+
                     // start incoming message handler
+                    peer.send_msg(&self.local_handshake().message(peer.challenge()));
                     peer.spawn_incoming_handler();
 
                     // add new peer to self peer set
-                    self.peers.lock().unwrap().insert(addr, peer);
+                    peers.insert_pending(addr, peer);
                 }
                 Err(e) => {
                     error!("{e}")
@@ -239,6 +535,16 @@ impl TcpController {
         }
     }
 
+    fn local_handshake(&self) -> LocalHandshake {
+        LocalHandshake {
+            chain_id: self.chain_id,
+            node_addr: self.node_addr,
+            // SAFETY: secp256k1 public key always encodes
+            public_key: self.private_key.pub_key().to_bytes().unwrap(),
+            compression: self.compression_threshold.is_some(),
+        }
+    }
+
     // Initialize heartbeat thread to check status
     // of all peers determined by heartbeat interval set
     // on main struct
@@ -250,10 +556,10 @@ impl TcpController {
         thread::spawn(move || loop {
             debug!(
                 "trying to send to all peers {:?}",
-                peers.lock().unwrap().keys()
+                peers.lock().unwrap().addrs()
             );
-            for (addr, peer) in peers.lock().as_mut().unwrap().iter_mut() {
-                let msg = PeerMessage::Ping(*addr, b"PING".to_vec());
+            for peer in peers.lock().as_mut().unwrap().iter_mut() {
+                let msg = PeerMessage::Ping(peer.remote_addr(), b"PING".to_vec());
                 peer.send_msg(&msg);
             }
             thread::sleep(time::Duration::from_secs(hb_interval));
@@ -272,3 +578,331 @@ pub fn split_stream(stream: TcpStream) -> (ThreadBufReader, ThreadBufWriter) {
     let output = BufWriter::new(stream);
     (ArcMut::new(input), ArcMut::new(output))
 }
+
+// bind with SO_REUSEADDR so a restarted node can take the port back while
+// the old socket is in TIME_WAIT, retrying a few times if it is still held
+fn bind_listener(
+    addr: SocketAddr,
+    retries: u32,
+    retry_interval: Duration,
+) -> Result<TcpListener, NetworkError> {
+    let mut attempt = 0;
+    loop {
+        match try_bind(addr) {
+            Ok(listener) => return Ok(listener),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                warn!("unable to bind listener to: {addr}, attempt: {attempt}, {e}");
+                thread::sleep(retry_interval);
+            }
+            Err(e) => return Err(NetworkError::Connect(e.to_string())),
+        }
+    }
+}
+
+fn try_bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, None)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(128)?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::message::MessageCodeMap;
+    use std::io::{Read, Write};
+
+    fn frame(msg: &PeerMessage) -> Vec<u8> {
+        let payload = msg.payload();
+        let mut frame = (payload.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    fn handshake_frame(chain_id: u32, key: &PrivateKey) -> Vec<u8> {
+        frame(&PeerMessage::Handshake {
+            addr: "127.0.0.1:0".parse().unwrap(),
+            chain_id,
+            protocol_version: PROTOCOL_VERSION,
+            node_addr: "127.0.0.1:7000".parse().unwrap(),
+            public_key: key.pub_key().to_bytes().unwrap(),
+            challenge: [0; 32],
+            compression: false,
+        })
+    }
+
+    fn read_msg(stream: &mut TcpStream) -> PeerMessage {
+        let mut len = [0_u8; 4];
+        stream.read_exact(&mut len).unwrap();
+        let mut payload = vec![0_u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut payload).unwrap();
+        PeerMessage::from_payload(stream.peer_addr().unwrap(), &payload).unwrap()
+    }
+
+    // send a handshake for key and sign the challenge from the controller
+    // with signer, the handshake is only completed when both keys match
+    fn handshake_signed_by(
+        stream: &mut TcpStream,
+        chain_id: u32,
+        key: &PrivateKey,
+        signer: &PrivateKey,
+    ) {
+        stream
+            .set_read_timeout(Some(time::Duration::from_secs(5)))
+            .unwrap();
+        stream.write_all(&handshake_frame(chain_id, key)).unwrap();
+
+        let PeerMessage::Handshake { challenge, .. } = read_msg(stream) else {
+            panic!("expected handshake from controller");
+        };
+        let signature = signer
+            .sign(&handshake_auth_data(chain_id, &challenge))
+            .to_bytes()
+            .unwrap();
+        let auth = PeerMessage::HandshakeAuth(stream.local_addr().unwrap(), signature);
+        stream.write_all(&frame(&auth)).unwrap();
+    }
+
+    fn handshake(stream: &mut TcpStream, chain_id: u32, key: &PrivateKey) {
+        handshake_signed_by(stream, chain_id, key, key);
+    }
+
+    fn start_controller(chain_id: u32) -> TcpController {
+        let (tx, _rx) = channel();
+        let config = TcpConfig {
+            chain_id,
+            max_frame_size: 1024,
+            ..TcpConfig::default()
+        };
+        let mut controller = TcpController::new(
+            "127.0.0.1:0".parse().unwrap(),
+            config,
+            ArcMut::new(tx).inner,
+        )
+        .unwrap();
+        controller.start(vec![]);
+        controller
+    }
+
+    fn wait_for(f: impl Fn() -> bool) -> bool {
+        for _ in 0..50 {
+            if f() {
+                return true;
+            }
+            thread::sleep(time::Duration::from_millis(20));
+        }
+        false
+    }
+
+    #[test]
+    fn test_rebind_after_drop() {
+        let (tx, _rx) = channel();
+        let rpc_tx = ArcMut::new(tx).inner;
+
+        let controller = TcpController::new(
+            "127.0.0.1:0".parse().unwrap(),
+            TcpConfig::default(),
+            rpc_tx.clone(),
+        )
+        .unwrap();
+        let addr = controller.node_addr;
+        drop(controller);
+
+        let controller = TcpController::new(addr, TcpConfig::default(), rpc_tx).unwrap();
+        assert_eq!(controller.node_addr, addr);
+    }
+
+    #[test]
+    fn test_handshake_chain_id_mismatch_rejected() {
+        let controller = start_controller(1);
+
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        stream
+            .set_read_timeout(Some(time::Duration::from_secs(5)))
+            .unwrap();
+        assert!(wait_for(|| controller.get_peer_addrs().len() == 1));
+
+        stream
+            .write_all(&handshake_frame(2, &PrivateKey::new()))
+            .unwrap();
+
+        // controller closes the connection and forgets the peer
+        let mut buf = vec![];
+        stream.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.get(4), Some(&u8::from(MessageCodeMap::Handshake)));
+        assert!(wait_for(|| controller.get_peer_addrs().is_empty()));
+    }
+
+    #[test]
+    fn test_oversized_frame_disconnects_peer() {
+        let controller = start_controller(1);
+
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut stream, 1, &PrivateKey::new());
+        assert!(wait_for(|| controller.get_handshake_peer_addrs().len() == 1));
+
+        // claim a 4GiB frame, rejected from the length prefix alone
+        stream.write_all(&u32::MAX.to_be_bytes()).unwrap();
+
+        let mut buf = vec![];
+        assert!(stream.read_to_end(&mut buf).is_ok());
+        assert!(wait_for(|| controller.get_peer_addrs().is_empty()));
+    }
+
+    #[test]
+    fn test_best_peer_by_score() {
+        let controller = start_controller(1);
+
+        let mut streams = vec![];
+        for _ in 0..2 {
+            let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+            handshake(&mut stream, 1, &PrivateKey::new());
+            streams.push(stream);
+        }
+        assert!(wait_for(|| controller.get_handshake_peer_addrs().len() == 2));
+
+        // peers are keyed by the remote address of the connection
+        let unreliable = streams[0].local_addr().unwrap();
+        let reliable = streams[1].local_addr().unwrap();
+
+        for _ in 0..3 {
+            controller.record_peer_failure(unreliable);
+            controller.record_peer_success(reliable);
+        }
+
+        assert_eq!(controller.best_peer_addr(), Some(reliable));
+        assert_eq!(
+            controller.get_peer_addrs_by_score(),
+            vec![reliable, unreliable]
+        );
+    }
+
+    #[test]
+    fn test_handshake_accepted() {
+        let controller = start_controller(1);
+
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut stream, 1, &PrivateKey::new());
+
+        assert!(wait_for(|| controller.get_handshake_peer_addrs().len() == 1));
+    }
+
+    #[test]
+    fn test_handshake_requires_signed_challenge() {
+        let controller = start_controller(1);
+
+        // handshake without a signed challenge is never completed
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        stream
+            .write_all(&handshake_frame(1, &PrivateKey::new()))
+            .unwrap();
+        assert!(wait_for(|| controller.get_peer_addrs().len() == 1));
+        assert!(!wait_for(
+            || controller.get_handshake_peer_addrs().len() == 1
+        ));
+
+        // challenge signed by a different key disconnects the peer
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        handshake_signed_by(&mut stream, 1, &PrivateKey::new(), &PrivateKey::new());
+
+        let mut buf = vec![];
+        assert!(stream.read_to_end(&mut buf).is_ok());
+        assert!(wait_for(|| controller.get_peer_addrs().len() == 1));
+        assert!(controller.get_handshake_peer_addrs().is_empty());
+    }
+
+    #[test]
+    fn test_unverified_node_id_keeps_connection() {
+        let controller = start_controller(1);
+        let key = PrivateKey::new();
+        let node_id = NodeId::from_private_key(&key);
+
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut stream, 1, &key);
+        let addr = stream.local_addr().unwrap();
+        assert!(wait_for(|| controller.get_node_id(&addr) == Some(node_id)));
+        for _ in 0..5 {
+            controller.record_peer_success(addr);
+        }
+
+        // another connection claims the node id without its key
+        let mut imposter = TcpStream::connect(controller.node_addr).unwrap();
+        handshake_signed_by(&mut imposter, 1, &key, &PrivateKey::new());
+
+        let mut buf = vec![];
+        assert!(imposter.read_to_end(&mut buf).is_ok());
+        assert!(wait_for(|| controller.get_peer_addrs() == vec![addr]));
+
+        // live connection and its score are kept
+        assert_eq!(controller.get_node_addr(&node_id), Some(addr));
+        let mut other = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut other, 1, &PrivateKey::new());
+        assert!(wait_for(|| controller.get_handshake_peer_addrs().len() == 2));
+        let other_addr = other.local_addr().unwrap();
+        for _ in 0..3 {
+            controller.record_peer_success(other_addr);
+        }
+        assert_eq!(controller.best_peer_addr(), Some(addr));
+    }
+
+    #[test]
+    fn test_reconnect_keeps_node_id() {
+        let controller = start_controller(1);
+        let key = PrivateKey::new();
+        let node_id = NodeId::from_private_key(&key);
+
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut stream, 1, &key);
+        assert!(wait_for(|| controller.get_handshake_peer_addrs().len() == 1));
+
+        let first_addr = stream.local_addr().unwrap();
+        assert_eq!(controller.get_node_id(&first_addr), Some(node_id));
+        for _ in 0..5 {
+            controller.record_peer_success(first_addr);
+        }
+
+        // same node connects again from a new port before the old
+        // connection is dropped
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut stream, 1, &key);
+        let second_addr = stream.local_addr().unwrap();
+        assert_ne!(first_addr, second_addr);
+        assert!(wait_for(
+            || controller.get_node_id(&second_addr) == Some(node_id)
+        ));
+
+        assert_eq!(controller.get_peer_node_ids(), vec![node_id]);
+        assert_eq!(controller.get_node_addr(&node_id), Some(second_addr));
+        assert_eq!(controller.get_node_id(&first_addr), None);
+        assert_eq!(controller.get_handshake_peer_addrs(), vec![second_addr]);
+
+        // score of the node is kept across connections
+        let mut other = TcpStream::connect(controller.node_addr).unwrap();
+        handshake(&mut other, 1, &PrivateKey::new());
+        assert!(wait_for(|| controller.get_handshake_peer_addrs().len() == 2));
+        let other_addr = other.local_addr().unwrap();
+        for _ in 0..3 {
+            controller.record_peer_success(other_addr);
+        }
+        assert_eq!(controller.best_peer_addr(), Some(second_addr));
+    }
+
+    #[test]
+    fn test_self_connection_rejected() {
+        let controller = start_controller(1);
+
+        let mut stream = TcpStream::connect(controller.node_addr).unwrap();
+        stream
+            .set_read_timeout(Some(time::Duration::from_secs(5)))
+            .unwrap();
+        stream
+            .write_all(&handshake_frame(1, &controller.private_key))
+            .unwrap();
+
+        let mut buf = vec![];
+        assert!(stream.read_to_end(&mut buf).is_ok());
+        assert!(wait_for(|| controller.get_peer_addrs().is_empty()));
+    }
+}
@@ -1,54 +1,685 @@
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::Arc;
+use std::thread;
 
-use crate::core::transaction::Transaction;
+use borsh::{BorshDeserialize, BorshSerialize};
+use log::{debug, warn};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    core::{
+        encoding::ByteEncoding,
+        transaction::{Transaction, TransferData, TxType},
+    },
+    crypto::{address::Address, hash::Hash},
+};
+
+use super::error::NetworkError;
+
+// number of events buffered for each subscriber, slow
+// subscribers miss the oldest events once this is full
+pub const MEMPOOL_EVENT_CAPACITY: usize = 1024;
+
+// replaced transactions remembered so their status can
+// point at the replacement, oldest are forgotten first
+pub const MAX_REPLACED_TXS: usize = 1024;
+
+// future nonce transactions kept for each sender and in total, past
+// either limit the transaction furthest from ready is dropped
+pub const MAX_QUEUED_PER_SENDER: usize = 64;
+pub const MAX_QUEUED_TXS: usize = 4096;
+
+// transactions with a gas price of at least min_gas_price, each class
+// has its own ready queue and weight is its share of every take
+// relative to the other classes
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxClass {
+    pub min_gas_price: u64,
+    pub weight: usize,
+}
+
+// single class holding every transaction
+pub fn default_tx_classes() -> Vec<TxClass> {
+    vec![TxClass {
+        min_gas_price: 0,
+        weight: 1,
+    }]
+}
+
+#[derive(Debug, Clone)]
+pub enum MempoolEvent {
+    Added(Transaction),
+    // new transaction took the place of one with the same sender and nonce
+    Replaced { old_hash: Hash, new_hash: Hash },
+    // dropped from the pool without being included in a block
+    Evicted(Transaction),
+    // included in a block added to the chain
+    Confirmed(Transaction),
+}
+
+// mempool events of each kind received by a MempoolCounters
+#[derive(Debug, Clone, Copy, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct MempoolMetrics {
+    pub added: u64,
+    pub replaced: u64,
+    pub evicted: u64,
+    pub confirmed: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct MempoolCounters {
+    added: AtomicU64,
+    replaced: AtomicU64,
+    evicted: AtomicU64,
+    confirmed: AtomicU64,
+}
+
+impl MempoolCounters {
+    // count events from the subscription on a background thread, the
+    // thread exits once the pool is dropped
+    pub fn track(self: Arc<Self>, mut events: broadcast::Receiver<MempoolEvent>) {
+        thread::spawn(move || loop {
+            match events.blocking_recv() {
+                Ok(event) => self.record(&event),
+                Err(RecvError::Lagged(missed)) => warn!("mempool metrics missed {missed} events"),
+                Err(RecvError::Closed) => break,
+            }
+        });
+    }
+
+    pub fn record(&self, event: &MempoolEvent) {
+        let counter = match event {
+            MempoolEvent::Added(_) => &self.added,
+            MempoolEvent::Replaced { .. } => &self.replaced,
+            MempoolEvent::Evicted(_) => &self.evicted,
+            MempoolEvent::Confirmed(_) => &self.confirmed,
+        };
+        counter.fetch_add(1, atomic::Ordering::Relaxed);
+    }
+
+    pub fn metrics(&self) -> MempoolMetrics {
+        MempoolMetrics {
+            added: self.added.load(atomic::Ordering::Relaxed),
+            replaced: self.replaced.load(atomic::Ordering::Relaxed),
+            evicted: self.evicted.load(atomic::Ordering::Relaxed),
+            confirmed: self.confirmed.load(atomic::Ordering::Relaxed),
+        }
+    }
+}
 
 pub struct TxPool {
-    transactions: VecDeque<Transaction>,
+    // transactions ready to be taken for each class, nonces from a
+    // sender follow on from its last taken nonce without gaps
+    ready: Vec<VecDeque<Transaction>>,
+    // highest min_gas_price first, ready holds a queue for each
+    classes: Vec<TxClass>,
+    // transactions with a future nonce for each sender, promoted to
+    // ready once the gap before them is filled
+    queued: BTreeMap<Address, BTreeMap<u64, Transaction>>,
+    max_queued_per_sender: usize,
+    max_queued: usize,
+    // last nonce taken from the pool for each sender
+    last_nonces: BTreeMap<Address, u64>,
+    // amount pending transfers from a sender may exceed its balance by
+    pending_grace: u64,
+    // replaced transactions by hash with the hash of their replacement,
+    // replaced_order holds the same hashes oldest first
+    replaced: HashMap<Hash, (Transaction, Hash)>,
+    replaced_order: VecDeque<Hash>,
+    events: broadcast::Sender<MempoolEvent>,
 }
 
 impl TxPool {
     pub fn new() -> Self {
+        let (events, _) = broadcast::channel(MEMPOOL_EVENT_CAPACITY);
         Self {
-            transactions: VecDeque::new(),
+            ready: vec![VecDeque::new()],
+            classes: default_tx_classes(),
+            queued: BTreeMap::new(),
+            max_queued_per_sender: MAX_QUEUED_PER_SENDER,
+            max_queued: MAX_QUEUED_TXS,
+            last_nonces: BTreeMap::new(),
+            pending_grace: 0,
+            replaced: HashMap::new(),
+            replaced_order: VecDeque::new(),
+            events,
         }
     }
 
+    // receive events for every change to the pool from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<MempoolEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: MempoolEvent) {
+        // send only fails when there are no subscribers
+        self.events.send(event).ok();
+    }
+
+    // split transactions into classes by gas price, transactions
+    // already in the pool are moved to the queue of their new class
+    pub fn set_classes(&mut self, mut classes: Vec<TxClass>) -> Result<(), NetworkError> {
+        if classes.is_empty() {
+            return Err(NetworkError::Internal(
+                "mem_pool needs at least one transaction class".to_string(),
+            ));
+        }
+        if let Some(class) = classes.iter().find(|class| class.weight == 0) {
+            return Err(NetworkError::Internal(format!(
+                "transaction class with min_gas_price: {} has weight 0",
+                class.min_gas_price
+            )));
+        }
+
+        classes.sort_by(|a, b| b.min_gas_price.cmp(&a.min_gas_price));
+        if classes
+            .windows(2)
+            .any(|pair| pair[0].min_gas_price == pair[1].min_gas_price)
+        {
+            return Err(NetworkError::Internal(
+                "transaction classes must have different min_gas_price".to_string(),
+            ));
+        }
+
+        let ready = std::mem::take(&mut self.ready);
+        self.ready = vec![VecDeque::new(); classes.len()];
+        self.classes = classes;
+        for tx in ready.into_iter().flatten() {
+            self.push_ready(tx);
+        }
+
+        Ok(())
+    }
+
+    pub fn classes(&self) -> &[TxClass] {
+        &self.classes
+    }
+
+    // index of the class tx belongs to, prices below every
+    // min_gas_price go to the lowest class
+    fn class_of(&self, tx: &Transaction) -> usize {
+        self.classes
+            .iter()
+            .position(|class| tx.gas_price >= class.min_gas_price)
+            .unwrap_or(self.classes.len() - 1)
+    }
+
+    fn push_ready(&mut self, tx: Transaction) {
+        let class = self.class_of(&tx);
+        self.ready[class].push_back(tx);
+    }
+
+    // share of len for each class by weight, the remainder goes to
+    // the highest classes
+    fn class_quotas(&self, len: usize) -> Vec<usize> {
+        let total: usize = self.classes.iter().map(|class| class.weight).sum();
+        let mut quotas: Vec<usize> = self
+            .classes
+            .iter()
+            .map(|class| len * class.weight / total)
+            .collect();
+
+        let mut remainder = len - quotas.iter().sum::<usize>();
+        for quota in quotas.iter_mut() {
+            if remainder == 0 {
+                break;
+            }
+            *quota += 1;
+            remainder -= 1;
+        }
+
+        quotas
+    }
+
+    // take up to len transactions, each class is given its weighted share
+    // of len and any share a class can't fill goes to the other classes,
+    // within a class the highest gas price goes first then insertion
+    // order, a transaction is only released once its nonce immediately
+    // follows the last nonce taken for the sender, out of order transactions
+    // stay in the pool until the gap is filled and transactions with an
+    // already taken nonce are dropped
     pub fn take(&mut self, len: usize) -> Vec<Transaction> {
         let mut txs = vec![];
-        let self_len = self.transactions.len();
-        for i in 0..len {
-            if i < self_len {
-                // SAFETY: checked length of transactions above
-                // guaranteed to have at least one element
-                txs.push(self.transactions.pop_front().unwrap());
+
+        // stable sort keeps insertion order between equal prices
+        for queue in self.ready.iter_mut() {
+            queue
+                .make_contiguous()
+                .sort_by(|a, b| b.gas_price.cmp(&a.gas_price));
+        }
+
+        // releasing a transaction may unblock a deferred transaction
+        // from the same sender, in any class, so scan again
+        let quotas = self.class_quotas(len);
+        let mut taken = vec![0; quotas.len()];
+        loop {
+            let mut released = 0;
+            for class in 0..quotas.len() {
+                let count = self.take_class(class, quotas[class] - taken[class], &mut txs);
+                taken[class] += count;
+                released += count;
+            }
+            if released == 0 {
+                break;
             }
         }
+
+        // share left unused, highest class first
+        loop {
+            let mut released = 0;
+            for class in 0..self.ready.len() {
+                released += self.take_class(class, len - txs.len(), &mut txs);
+            }
+            if released == 0 || txs.len() >= len {
+                break;
+            }
+        }
+
         txs
     }
 
+    // one pass over the ready queue of class releasing up to limit
+    // transactions into txs, returns the number released
+    fn take_class(&mut self, class: usize, limit: usize, txs: &mut Vec<Transaction>) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+
+        let mut released = 0;
+        let queue = std::mem::take(&mut self.ready[class]);
+        let mut deferred = VecDeque::with_capacity(queue.len());
+
+        for tx in queue {
+            if released >= limit {
+                deferred.push_back(tx);
+                continue;
+            }
+
+            match self.nonce_order(&tx) {
+                Ordering::Equal => {
+                    if tx.nonce > 0 {
+                        self.last_nonces.insert(tx.sender.clone(), tx.nonce);
+                    }
+                    txs.push(tx);
+                    released += 1;
+                }
+                Ordering::Greater => deferred.push_back(tx),
+                Ordering::Less => {
+                    debug!(
+                        "dropping transaction with stale nonce: {} from mem_pool",
+                        tx.nonce
+                    );
+                    self.emit(MempoolEvent::Evicted(tx));
+                }
+            }
+        }
+
+        self.ready[class] = deferred;
+        released
+    }
+
+    // put back txs taken for a block which was not added, each sender's
+    // last taken nonce goes back to before its first returned tx
+    pub fn restore(&mut self, txs: Vec<Transaction>) {
+        for tx in txs.iter().filter(|tx| tx.nonce > 0) {
+            let last = self.last_nonces.entry(tx.sender.clone()).or_insert(0);
+            if *last >= tx.nonce {
+                *last = tx.nonce - 1;
+            }
+        }
+
+        // returned txs were taken first so they go back in front
+        for tx in txs.into_iter().rev() {
+            let class = self.class_of(&tx);
+            self.ready[class].push_front(tx);
+        }
+    }
+
+    // put back txs of a tip replaced by fork choice, txs the winning
+    // block includes or whose nonce it used are dropped
+    pub fn restore_replaced(&mut self, replaced: &[Transaction], winner: &[Transaction]) {
+        let included: HashSet<Hash> = winner
+            .iter()
+            .filter_map(|tx| tx.content_hash().ok())
+            .collect();
+        let used_nonces: BTreeSet<(&Address, u64)> = winner
+            .iter()
+            .filter(|tx| tx.nonce > 0)
+            .map(|tx| (&tx.sender, tx.nonce))
+            .collect();
+
+        let txs = replaced
+            .iter()
+            .filter(|tx| !matches!(tx.tx_type, TxType::BlockReward | TxType::GasReward))
+            .filter(|tx| {
+                tx.content_hash()
+                    .is_ok_and(|hash| !included.contains(&hash))
+            })
+            .filter(|tx| !used_nonces.contains(&(&tx.sender, tx.nonce)))
+            .filter(|tx| !self.has(tx))
+            .cloned()
+            .collect();
+        self.restore(txs);
+    }
+
+    // record nonce as already included for sender, eg. from confirmed chain state
+    pub fn set_last_nonce(&mut self, sender: &Address, nonce: u64) {
+        self.last_nonces.insert(sender.clone(), nonce);
+        self.promote(sender);
+    }
+
+    // nonce the next ready transaction from sender must have
+    fn next_ready_nonce(&self, sender: &Address) -> u64 {
+        let last = self.last_nonces.get(sender).copied().unwrap_or(0);
+
+        self.ready
+            .iter()
+            .flatten()
+            .filter(|tx| &tx.sender == sender && tx.nonce > 0)
+            .map(|tx| tx.nonce + 1)
+            .fold(last + 1, u64::max)
+    }
+
+    // move queued transactions from sender to ready while their
+    // nonces follow on without a gap
+    fn promote(&mut self, sender: &Address) {
+        let mut next = self.next_ready_nonce(sender);
+        let Some(mut queue) = self.queued.remove(sender) else {
+            return;
+        };
+
+        // queued nonces already taken can never become ready
+        while let Some(entry) = queue.first_entry() {
+            match entry.key().cmp(&next) {
+                Ordering::Less => {
+                    let tx = entry.remove();
+                    self.emit(MempoolEvent::Evicted(tx));
+                }
+                Ordering::Equal => {
+                    self.push_ready(entry.remove());
+                    next += 1;
+                }
+                Ordering::Greater => break,
+            }
+        }
+
+        if !queue.is_empty() {
+            self.queued.insert(sender.clone(), queue);
+        }
+    }
+
+    // limits on queued transactions for each sender and in total
+    pub fn set_queue_limits(&mut self, per_sender: usize, total: usize) {
+        self.max_queued_per_sender = per_sender;
+        self.max_queued = total;
+    }
+
+    // evict queued transactions so tx fits within the queue limits, the
+    // highest nonce of a full sender goes first and past the total limit
+    // the sender with the longest queue gives one up, false when tx
+    // itself is furthest from ready and should be dropped instead
+    fn make_queue_room(&mut self, tx: &Transaction) -> bool {
+        let highest = |queue: &BTreeMap<u64, Transaction>| {
+            queue.last_key_value().map_or(0, |(nonce, _)| *nonce)
+        };
+
+        if let Some(queue) = self.queued.get(&tx.sender) {
+            if queue.len() >= self.max_queued_per_sender {
+                let nonce = highest(queue);
+                if tx.nonce > nonce {
+                    return false;
+                }
+                self.evict_queued(&tx.sender.clone(), nonce);
+            }
+        }
+
+        if self.queued_count() >= self.max_queued {
+            let longest = self
+                .queued
+                .iter()
+                .max_by_key(|(_, queue)| queue.len())
+                .map(|(sender, queue)| (sender.clone(), highest(queue)));
+            if let Some((sender, nonce)) = longest {
+                if sender == tx.sender && tx.nonce > nonce {
+                    return false;
+                }
+                self.evict_queued(&sender, nonce);
+            }
+        }
+
+        self.max_queued_per_sender > 0 && self.max_queued > 0
+    }
+
+    fn evict_queued(&mut self, sender: &Address, nonce: u64) {
+        let Some(queue) = self.queued.get_mut(sender) else {
+            return;
+        };
+        let evicted = queue.remove(&nonce);
+        if queue.is_empty() {
+            self.queued.remove(sender);
+        }
+        if let Some(tx) = evicted {
+            self.emit(MempoolEvent::Evicted(tx));
+        }
+    }
+
+    // compare transaction nonce with next expected nonce for its sender,
+    // transactions without a nonce are always ready
+    fn nonce_order(&self, tx: &Transaction) -> Ordering {
+        if tx.nonce == 0 {
+            return Ordering::Equal;
+        }
+
+        let last = self.last_nonces.get(&tx.sender).copied().unwrap_or(0);
+        tx.nonce.cmp(&(last + 1))
+    }
+
+    pub fn set_pending_grace(&mut self, grace: u64) {
+        self.pending_grace = grace;
+    }
+
+    pub fn pending_grace(&self) -> u64 {
+        self.pending_grace
+    }
+
+    // total amount plus max fee of transfers from sender waiting in the pool
+    pub fn pending_spend(&self, sender: &Address) -> u64 {
+        self.iter()
+            .filter(|tx| &tx.sender == sender)
+            .filter_map(transfer_cost)
+            .fold(0, u64::saturating_add)
+    }
+
+    // highest nonce of ready or queued transactions from sender, None when
+    // the sender has no nonce ordered transactions in the pool
+    pub fn highest_pending_nonce(&self, sender: &Address) -> Option<u64> {
+        self.iter()
+            .filter(|tx| &tx.sender == sender && tx.nonce > 0)
+            .map(|tx| tx.nonce)
+            .max()
+    }
+
+    // a transaction with the same sender and nonce as one already in the
+    // pool replaces it, transactions without a nonce are always added,
+    // a nonce after a gap is queued until the gap is filled
     pub fn add(&mut self, tx: Transaction) {
-        self.transactions.push_back(tx);
+        if let Some(old) = self.replace_pooled(&tx) {
+            if let (Some(old_hash), Some(new_hash)) = (old.hash, tx.hash) {
+                self.emit(MempoolEvent::Replaced { old_hash, new_hash });
+                self.record_replaced(old_hash, old, new_hash);
+            }
+            return;
+        }
+
+        if tx.nonce > self.next_ready_nonce(&tx.sender) {
+            if !self.make_queue_room(&tx) {
+                debug!(
+                    "sender queue is full, dropping transaction with nonce: {}",
+                    tx.nonce
+                );
+                return;
+            }
+            self.queued
+                .entry(tx.sender.clone())
+                .or_default()
+                .insert(tx.nonce, tx.clone());
+            self.emit(MempoolEvent::Added(tx));
+            return;
+        }
+
+        let sender = tx.sender.clone();
+        self.push_ready(tx.clone());
+        self.emit(MempoolEvent::Added(tx));
+        self.promote(&sender);
+    }
+
+    // swap tx for a pooled transaction with the same sender and nonce,
+    // returns the transaction it replaced
+    fn replace_pooled(&mut self, tx: &Transaction) -> Option<Transaction> {
+        if tx.nonce == 0 {
+            return None;
+        }
+
+        let queued = self
+            .queued
+            .get_mut(&tx.sender)
+            .and_then(|queue| queue.get_mut(&tx.nonce));
+        if let Some(pooled) = queued {
+            return Some(std::mem::replace(pooled, tx.clone()));
+        }
+
+        // a new gas price may put the replacement in another class
+        let old = self.ready.iter_mut().find_map(|queue| {
+            let i = queue
+                .iter()
+                .position(|pooled| pooled.nonce == tx.nonce && pooled.sender == tx.sender)?;
+            queue.remove(i)
+        })?;
+        self.push_ready(tx.clone());
+        Some(old)
+    }
+
+    // transactions included in a block added to the chain, copies still
+    // in the pool are removed, reward transactions never come from the pool
+    pub fn confirm(&mut self, txs: &[Transaction]) {
+        let confirmed: Vec<&Transaction> = txs
+            .iter()
+            .filter(|tx| !matches!(tx.tx_type, TxType::BlockReward | TxType::GasReward))
+            .collect();
+
+        let hashes: HashSet<Hash> = confirmed
+            .iter()
+            .filter_map(|tx| tx.content_hash().ok())
+            .collect();
+        let keep = |tx: &Transaction| match tx.content_hash() {
+            Ok(hash) => !hashes.contains(&hash),
+            Err(_) => true,
+        };
+        for queue in self.ready.iter_mut() {
+            queue.retain(keep);
+        }
+        for queue in self.queued.values_mut() {
+            queue.retain(|_, tx| keep(tx));
+        }
+        self.queued.retain(|_, queue| !queue.is_empty());
+
+        for tx in confirmed {
+            self.emit(MempoolEvent::Confirmed(tx.clone()));
+        }
+    }
+
+    pub fn get(&self, tx_hash: &str) -> Option<Transaction> {
+        self.iter()
+            .find(|tx| tx.hash.map(|hash| hash.to_string()).as_deref() == Some(tx_hash))
+            .cloned()
+    }
+
+    // transaction with tx_hash which was replaced in the pool, with the
+    // hash of the transaction which replaced it
+    pub fn replaced_by(&self, tx_hash: &Hash) -> Option<(Transaction, Hash)> {
+        self.replaced.get(tx_hash).cloned()
+    }
+
+    // keep at most MAX_REPLACED_TXS, the oldest is dropped first
+    fn record_replaced(&mut self, old_hash: Hash, old: Transaction, new_hash: Hash) {
+        if self.replaced.insert(old_hash, (old, new_hash)).is_some() {
+            return;
+        }
+        self.replaced_order.push_back(old_hash);
+        if self.replaced_order.len() > MAX_REPLACED_TXS {
+            if let Some(oldest) = self.replaced_order.pop_front() {
+                self.replaced.remove(&oldest);
+            }
+        }
     }
 
     pub fn has(&self, tx: &Transaction) -> bool {
-        self.transactions.contains(tx)
+        self.iter().any(|pooled| pooled == tx)
+    }
+
+    // true if a transaction with the same signed content is in the pool,
+    // the hash of the transaction itself changes when it is signed again
+    pub fn has_content(&self, content_hash: &Hash) -> bool {
+        self.iter()
+            .any(|tx| tx.content_hash().ok().as_ref() == Some(content_hash))
     }
 
+    // ready and queued transactions
     pub fn len(&self) -> usize {
-        self.transactions.len()
+        self.ready_count() + self.queued_count()
+    }
+
+    pub fn ready_count(&self) -> usize {
+        self.ready.iter().map(|queue| queue.len()).sum()
+    }
+
+    pub fn queued_count(&self) -> usize {
+        self.queued.values().map(|queue| queue.len()).sum()
+    }
+
+    // ready transactions followed by queued transactions
+    fn iter(&self) -> impl Iterator<Item = &Transaction> {
+        self.ready
+            .iter()
+            .flatten()
+            .chain(self.queued.values().flat_map(|queue| queue.values()))
     }
 
     pub fn flush(&mut self) {
-        self.transactions.clear()
+        let queued = std::mem::take(&mut self.queued);
+        let ready = vec![VecDeque::new(); self.classes.len()];
+        let txs = std::mem::replace(&mut self.ready, ready)
+            .into_iter()
+            .flatten()
+            .chain(queued.into_values().flat_map(|queue| queue.into_values()));
+
+        for tx in txs {
+            self.emit(MempoolEvent::Evicted(tx));
+        }
     }
 }
 
+// amount plus max fee a transfer can spend, None if tx is
+// not a transfer or its data is not a transfer payload
+pub fn transfer_cost(tx: &Transaction) -> Option<u64> {
+    if !matches!(tx.tx_type, TxType::Transfer) {
+        return None;
+    }
+
+    TransferData::from_bytes(&tx.data)
+        .ok()
+        .map(|data| data.amount.saturating_add(tx.max_fee()))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{
         core::transaction::random_tx,
-        crypto::{address::random_sender_receiver, utils::random_hash},
+        crypto::{address::random_sender_receiver, private_key::PrivateKey, utils::random_hash},
     };
+    use std::time::{Duration, Instant};
 
     use super::*;
     #[test]
@@ -118,4 +749,290 @@ mod tests {
         assert_eq!(tx_pool.len(), 17);
         assert_eq!(tx_pool.has(&tx), false);
     }
+
+    #[test]
+    fn test_take_nonce_order() {
+        let mut tx_pool = TxPool::new();
+        let r_hash = random_hash();
+        let (sender, receiver) = random_sender_receiver();
+
+        for nonce in [3, 1, 2, 5] {
+            let mut tx =
+                Transaction::new_transfer(receiver.clone(), sender.clone(), r_hash, &[], 7)
+                    .unwrap();
+            tx.nonce = nonce;
+            tx_pool.add(tx);
+        }
+
+        let nonces: Vec<u64> = tx_pool.take(10).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![1, 2, 3]);
+
+        // nonce 5 is deferred until 4 is added
+        assert_eq!(tx_pool.len(), 1);
+        assert!(tx_pool.take(10).is_empty());
+
+        let mut tx =
+            Transaction::new_transfer(receiver.clone(), sender.clone(), r_hash, &[], 7).unwrap();
+        tx.nonce = 4;
+        tx_pool.add(tx);
+
+        let nonces: Vec<u64> = tx_pool.take(1).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![4]);
+
+        let nonces: Vec<u64> = tx_pool.take(10).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![5]);
+        assert_eq!(tx_pool.len(), 0);
+    }
+
+    #[test]
+    fn test_take_priority_order() {
+        let mut tx_pool = TxPool::new();
+        let r_hash = random_hash();
+
+        // equal gas limits, only the gas price differs
+        for (data, gas_price) in [(1_u8, 1), (2, 5), (3, 1), (4, 3)] {
+            let (sender, receiver) = random_sender_receiver();
+            let mut tx = Transaction::new_transfer(receiver, sender, r_hash, &[data], 7).unwrap();
+            tx.gas_price = gas_price;
+            tx_pool.add(tx);
+        }
+
+        let taken: Vec<(u8, u64)> = tx_pool
+            .take(10)
+            .iter()
+            .map(|tx| (tx.data[0], tx.gas_price))
+            .collect();
+        assert_eq!(taken, vec![(2, 5), (4, 3), (1, 1), (3, 1)]);
+
+        // a higher price can't skip ahead of an earlier nonce from its sender
+        let (sender, receiver) = random_sender_receiver();
+        for (nonce, gas_price) in [(1, 1), (2, 9)] {
+            let mut tx =
+                Transaction::new_transfer(receiver.clone(), sender.clone(), r_hash, &[], 7)
+                    .unwrap();
+            tx.nonce = nonce;
+            tx.gas_price = gas_price;
+            tx_pool.add(tx);
+        }
+        let nonces: Vec<u64> = tx_pool.take(10).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_take_class_ratio() {
+        let mut tx_pool = TxPool::new();
+        tx_pool
+            .set_classes(vec![
+                TxClass {
+                    min_gas_price: 0,
+                    weight: 1,
+                },
+                TxClass {
+                    min_gas_price: 5,
+                    weight: 3,
+                },
+            ])
+            .unwrap();
+        assert_eq!(tx_pool.classes()[0].min_gas_price, 5);
+
+        let r_hash = random_hash();
+        let add = |tx_pool: &mut TxPool, count: u8, gas_price: u64| {
+            for i in 0..count {
+                let (sender, receiver) = random_sender_receiver();
+                let mut tx = Transaction::new_transfer(receiver, sender, r_hash, &[i], 7).unwrap();
+                tx.gas_price = gas_price;
+                tx_pool.add(tx);
+            }
+        };
+        add(&mut tx_pool, 20, 1);
+        add(&mut tx_pool, 20, 9);
+
+        // 3 expensive for every cheap tx, cheap txs are not starved
+        let prices: Vec<u64> = tx_pool.take(8).iter().map(|tx| tx.gas_price).collect();
+        assert_eq!(prices.iter().filter(|price| **price == 9).count(), 6);
+        assert_eq!(prices.iter().filter(|price| **price == 1).count(), 2);
+
+        // remainder of an odd share goes to the higher class
+        let prices: Vec<u64> = tx_pool.take(5).iter().map(|tx| tx.gas_price).collect();
+        assert_eq!(prices.iter().filter(|price| **price == 9).count(), 4);
+
+        // share a class can't fill goes to the other class
+        let mut tx_pool_2 = TxPool::new();
+        tx_pool_2.set_classes(tx_pool.classes().to_vec()).unwrap();
+        add(&mut tx_pool_2, 1, 9);
+        add(&mut tx_pool_2, 20, 1);
+        let prices: Vec<u64> = tx_pool_2.take(8).iter().map(|tx| tx.gas_price).collect();
+        assert_eq!(prices, vec![9, 1, 1, 1, 1, 1, 1, 1]);
+
+        // pooled txs move to their class when classes change
+        tx_pool.set_classes(default_tx_classes()).unwrap();
+        assert_eq!(tx_pool.ready_count(), 27);
+        assert!(tx_pool
+            .set_classes(vec![TxClass {
+                min_gas_price: 0,
+                weight: 0,
+            }])
+            .is_err());
+    }
+
+    #[test]
+    fn test_mempool_events() {
+        let mut tx_pool = TxPool::new();
+        let mut events = tx_pool.subscribe();
+        let counters = Arc::new(MempoolCounters::default());
+        counters.clone().track(tx_pool.subscribe());
+        let (sender, receiver) = random_sender_receiver();
+
+        let mut tx =
+            Transaction::new_transfer(receiver.clone(), sender.clone(), random_hash(), &[], 7)
+                .unwrap();
+        tx.nonce = 1;
+        tx_pool.add(tx.clone());
+
+        // included in a block
+        let txs = tx_pool.take(10);
+        tx_pool.confirm(&txs);
+
+        assert!(matches!(events.try_recv(), Ok(MempoolEvent::Added(added)) if added == tx));
+        assert!(
+            matches!(events.try_recv(), Ok(MempoolEvent::Confirmed(confirmed)) if confirmed == tx)
+        );
+
+        // same sender and nonce replaces the pooled tx
+        let mut first =
+            Transaction::new_transfer(receiver.clone(), sender.clone(), random_hash(), &[], 7)
+                .unwrap();
+        first.nonce = 2;
+        let mut second = first.clone();
+        second.gas_limit = 9;
+        let key = PrivateKey::new();
+        first.sign(&key).unwrap();
+        second.sign(&key).unwrap();
+        tx_pool.add(first.clone());
+        tx_pool.add(second.clone());
+        assert_eq!(tx_pool.len(), 1);
+        assert!(matches!(events.try_recv(), Ok(MempoolEvent::Added(_))));
+        assert!(matches!(
+            events.try_recv(),
+            Ok(MempoolEvent::Replaced { old_hash, new_hash })
+                if Some(old_hash) == first.hash && Some(new_hash) == second.hash
+        ));
+        let (replaced, by) = tx_pool.replaced_by(&first.hash.unwrap()).unwrap();
+        assert_eq!((replaced, Some(by)), (first, second.hash));
+
+        tx_pool.flush();
+        assert!(
+            matches!(events.try_recv(), Ok(MempoolEvent::Evicted(evicted)) if evicted == second)
+        );
+        assert!(events.try_recv().is_err());
+
+        // counted on the tracking thread
+        let expected = MempoolMetrics {
+            added: 2,
+            replaced: 1,
+            evicted: 1,
+            confirmed: 1,
+        };
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while counters.metrics() != expected && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(counters.metrics(), expected);
+    }
+
+    #[test]
+    fn test_queue_nonce_gap() {
+        let mut tx_pool = TxPool::new();
+        let (sender, receiver) = random_sender_receiver();
+        let new_tx = |nonce| {
+            let mut tx =
+                Transaction::new_transfer(receiver.clone(), sender.clone(), random_hash(), &[], 7)
+                    .unwrap();
+            tx.nonce = nonce;
+            tx
+        };
+
+        tx_pool.add(new_tx(3));
+        assert_eq!((tx_pool.ready_count(), tx_pool.queued_count()), (0, 1));
+
+        tx_pool.add(new_tx(1));
+        assert_eq!((tx_pool.ready_count(), tx_pool.queued_count()), (1, 1));
+
+        // filling the gap promotes nonce 3
+        tx_pool.add(new_tx(2));
+        assert_eq!((tx_pool.ready_count(), tx_pool.queued_count()), (3, 0));
+
+        let nonces: Vec<u64> = tx_pool.take(10).iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![1, 2, 3]);
+        assert_eq!(tx_pool.len(), 0);
+
+        // queued txs are found and replaced like ready ones
+        let queued = new_tx(6);
+        tx_pool.add(queued.clone());
+        assert!(tx_pool.has(&queued));
+        tx_pool.add(new_tx(6));
+        assert_eq!(tx_pool.queued_count(), 1);
+
+        // chain nonce moving past the gap promotes the queue
+        tx_pool.set_last_nonce(&sender, 5);
+        assert_eq!((tx_pool.ready_count(), tx_pool.queued_count()), (1, 0));
+    }
+
+    #[test]
+    fn test_queue_limits() {
+        let mut tx_pool = TxPool::new();
+        tx_pool.set_queue_limits(2, 3);
+        let mut events = tx_pool.subscribe();
+        let receiver = random_sender_receiver().1;
+        let (a, b, c) = (
+            random_sender_receiver().0,
+            random_sender_receiver().0,
+            random_sender_receiver().0,
+        );
+        let new_tx = |sender: &Address, nonce| {
+            let mut tx =
+                Transaction::new_transfer(receiver.clone(), sender.clone(), random_hash(), &[], 7)
+                    .unwrap();
+            tx.nonce = nonce;
+            tx
+        };
+        let queued_nonces = |tx_pool: &TxPool, sender: &Address| -> Vec<u64> {
+            tx_pool
+                .iter()
+                .filter(|tx| &tx.sender == sender)
+                .map(|tx| tx.nonce)
+                .collect()
+        };
+
+        tx_pool.add(new_tx(&a, 3));
+        tx_pool.add(new_tx(&a, 4));
+
+        // a full sender can't queue a nonce further ahead
+        let dropped = new_tx(&a, 5);
+        tx_pool.add(dropped.clone());
+        assert!(!tx_pool.has(&dropped));
+
+        // a nearer nonce takes the place of the highest
+        tx_pool.add(new_tx(&a, 2));
+        assert_eq!(queued_nonces(&tx_pool, &a), vec![2, 3]);
+
+        // past the total limit the longest queue gives up its highest nonce
+        tx_pool.add(new_tx(&b, 3));
+        tx_pool.add(new_tx(&c, 3));
+        assert_eq!(tx_pool.queued_count(), 3);
+        assert_eq!(queued_nonces(&tx_pool, &a), vec![2]);
+        assert_eq!(queued_nonces(&tx_pool, &c), vec![3]);
+
+        let evicted: Vec<u64> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                MempoolEvent::Evicted(tx) => Some(tx.nonce),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(evicted, vec![4, 3]);
+
+        // ready transactions are not limited
+        tx_pool.add(new_tx(&a, 1));
+        assert_eq!((tx_pool.ready_count(), tx_pool.queued_count()), (2, 2));
+    }
 }
@@ -2,30 +2,56 @@ use std::{
     fmt::Debug,
     net::SocketAddr,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
 
 use crate::{
-    core::{block::Block, blockchain::Blockchain, encoding::ByteEncoding, error::CoreError},
+    api::request_id::current_request_id,
+    core::{
+        block::Block, blockchain::Blockchain, encoding::ByteEncoding, error::CoreError,
+        view::SharedChainView,
+    },
     lock,
     rpc::handlers::{
-        block::{get_block, get_block_header, get_last_block},
-        transaction::new_tx,
+        block::{
+            execute_block, get_block, get_block_header, get_headers, get_last_block,
+            get_state_root, mine_block, submit_block,
+        },
+        chain::{get_block_metrics, get_chain_height, get_total_supply},
+        state::{diff_state, get_account_proof, get_rich_list, get_state_dump, get_state_snapshot},
+        transaction::{
+            get_account_txs, get_next_nonce, get_tx, get_tx_block, get_tx_by_hash, get_txs, new_tx,
+        },
     },
     vm::validator::BlockValidator,
 };
 
-use crate::network::{error::NetworkError, tcp::TcpController, tx_pool::TxPool, types::Payload};
+use crate::network::{
+    error::NetworkError,
+    seen::{SeenTxCache, DEFAULT_SEEN_TX_CAPACITY},
+    tcp::TcpController,
+    tx_pool::{MempoolCounters, MempoolEvent, TxPool},
+    types::Payload,
+};
 
-use crate::rpc::types::{RpcHeader, RpcResponse, RPC};
+use crate::rpc::types::{RpcPayload, RpcResponse, RPC};
 
 pub struct RpcController {
     mem_pool: Arc<Mutex<TxPool>>,
     validator: Arc<Mutex<BlockValidator>>,
     chain: Arc<Mutex<Blockchain>>,
+    // read snapshot of the chain, read RPCs use this before
+    // falling back to locking chain
+    view: SharedChainView,
     tcp_controller: Arc<Mutex<TcpController>>,
+    // content hashes of admitted txs, a peer sending one again is ignored
+    seen_txs: SeenTxCache,
+    // mempool events counted for GetMetrics
+    mempool_counters: Arc<MempoolCounters>,
 }
 
 impl RpcController {
@@ -35,74 +61,582 @@ impl RpcController {
         chain: Arc<Mutex<Blockchain>>,
         tcp_controller: Arc<Mutex<TcpController>>,
     ) -> Self {
+        let view = lock!(chain).view();
+        let mempool_counters = Arc::new(MempoolCounters::default());
+        mempool_counters.clone().track(lock!(mem_pool).subscribe());
+
         Self {
             mem_pool,
             validator,
             chain: chain,
+            view,
             tcp_controller,
+            seen_txs: SeenTxCache::default(),
+            mempool_counters,
         }
     }
 
+    // receive mempool events from now on, used by the /ws/mempool route
+    pub fn subscribe_mempool(&self) -> broadcast::Receiver<MempoolEvent> {
+        lock!(self.mem_pool).subscribe()
+    }
+
+    // how long admitted txs are remembered, set before the controller
+    // is shared
+    pub fn set_seen_tx_ttl(&mut self, ttl: Duration) {
+        self.seen_txs = SeenTxCache::new(ttl, DEFAULT_SEEN_TX_CAPACITY);
+    }
+
     // simple wrapper method to be used in api routes/handlers
     // calls main handle_rpc method which is used for both peer RPC messages and client http requests
     pub fn handle_client_rpc(&self, rpc: &RPC) -> Result<RpcResponse, NetworkError> {
+        if let Some(id) = current_request_id() {
+            debug!("handling client rpc: {:?}, request_id={id}", rpc.header);
+        }
         self.handle_rpc(rpc, None)
     }
 
+    // handler errors are returned as Err so callers can tell error
+    // classes apart, Generic and Ack are only informational or success
     pub fn handle_rpc(
         &self,
         rpc: &RPC,
-        _peer_addr: Option<SocketAddr>,
+        peer_addr: Option<SocketAddr>,
     ) -> Result<RpcResponse, NetworkError> {
-        match rpc.header {
-            RpcHeader::GetBlock => {
+        // decoded up front, a payload which does not match the header
+        // never reaches a handler
+        let payload = RpcPayload::try_decode(rpc.header, &rpc.payload)?;
+
+        match payload {
+            RpcPayload::GetBlock(req) => {
                 debug!("rpc message received in handler at RpcHeader::GetBlock");
-                match get_block(&rpc, self.chain.clone()) {
-                    Ok(block) => Ok(RpcResponse::Block(block)),
-                    Err(msg) => Ok(RpcResponse::Generic(msg.to_string())),
-                }
+                let block = get_block(&req, &self.view, self.chain.clone())?;
+                Ok(RpcResponse::Block(block))
             }
-            RpcHeader::GetLastBlock => {
+            RpcPayload::GetLastBlock => {
                 debug!("rpc message received in handler at RpcHeader::GetLastBlock");
 
-                match get_last_block(&rpc, self.chain.clone()) {
-                    Ok(block) => Ok(RpcResponse::Block(block.clone())),
-                    Err(msg) => Ok(RpcResponse::Generic(msg.to_string())),
-                }
+                let block = get_last_block(&self.view)?;
+                Ok(RpcResponse::Block(block))
             }
-            RpcHeader::CommitBlock => {
-                debug!("rpc message received in handler at RpcHeader::NewBlock");
+            RpcPayload::CommitBlock(block) => {
+                debug!("rpc message received in handler at RpcHeader::CommitBlock");
 
-                Ok(RpcResponse::Generic(format!("Generic response")))
+                let (block, replaced) =
+                    submit_block(block, self.validator.clone(), self.chain.clone())?;
+                let mut mem_pool = lock!(self.mem_pool);
+                if let Some(replaced) = replaced {
+                    mem_pool.restore_replaced(replaced.txs(), block.txs());
+                }
+                mem_pool.confirm(block.txs());
+                drop(mem_pool);
+
+                // forward accepted block to all other peers, without the
+                // correlation id so peers do not reply to us
+                let forward = RPC {
+                    id: None,
+                    ..rpc.clone()
+                };
+                lock!(self.tcp_controller).broadcast(&forward, peer_addr);
+                Ok(RpcResponse::Ack(Some(block.hash().clone())))
             }
-            RpcHeader::GetChainHeight => {
+            RpcPayload::GetChainHeight => {
                 debug!("rpc message received in handler at RpcHeader::GetChainHeight");
 
-                Ok(RpcResponse::Generic(format!("Generic response")))
+                let height = get_chain_height(self.chain.clone())?;
+                Ok(RpcResponse::Height(height))
             }
-            RpcHeader::GetTx => {
+            RpcPayload::GetTx(req) => {
                 debug!("rpc message received in RpcHeader::GetTx");
 
-                Ok(RpcResponse::Generic(format!("Generic response")))
+                let status = get_tx(&req, self.chain.clone(), self.mem_pool.clone())?;
+                Ok(RpcResponse::TxStatus(status))
             }
-            RpcHeader::NewTx => {
+            RpcPayload::NewTx(tx) => {
                 debug!("rpc message received in handler at RpcHeader::NewTx");
 
-                match new_tx(&rpc, self.mem_pool.clone()) {
-                    Ok(tx) => Ok(RpcResponse::Transaction(tx)),
-                    Err(msg) => Ok(RpcResponse::Generic(msg.to_string())),
+                // dropped before the mem_pool so a tx gossiped back by
+                // another peer is neither checked nor forwarded again
+                let content_hash = tx.content_hash()?;
+                if peer_addr.is_some() && self.seen_txs.contains(&content_hash) {
+                    return Ok(RpcResponse::Generic(format!(
+                        "transaction: {content_hash} already seen"
+                    )));
+                }
+
+                // only admitted txs are remembered, the content hash does
+                // not cover the signature so a rejected copy must not
+                // block the tx it was copied from
+                let tx = new_tx(tx, self.chain.clone(), self.mem_pool.clone())?;
+                if self.seen_txs.insert(content_hash) {
+                    // forward admitted tx to a random subset of peers, each
+                    // of them forwards it on so it reaches every validator
+                    let forward = RPC {
+                        id: None,
+                        ..rpc.clone()
+                    };
+                    lock!(self.tcp_controller).gossip(&forward, peer_addr);
                 }
+                Ok(RpcResponse::Transaction(tx))
             }
-            RpcHeader::GetBlockHeader => {
+            RpcPayload::GetBlockHeader(req) => {
                 debug!("rpc message received in handler at RpcHeader::GetBlockHeader");
-                match get_block_header(&rpc, self.chain.clone()) {
-                    Ok(header) => Ok(RpcResponse::Header(header.clone())),
-                    Err(msg) => Ok(RpcResponse::Generic(msg.to_string())),
+                let header = get_block_header(&req, &self.view, self.chain.clone())?;
+                Ok(RpcResponse::Header(header))
+            }
+            RpcPayload::GetHeaders(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetHeaders");
+                let headers = get_headers(&req, self.chain.clone())?;
+                Ok(RpcResponse::Headers(headers))
+            }
+            RpcPayload::GetAccountTxs(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetAccountTxs");
+                let txs = get_account_txs(&req, self.chain.clone())?;
+                Ok(RpcResponse::Transactions(txs))
+            }
+            RpcPayload::GetStateRoot(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetStateRoot");
+                let (height, state_root) = get_state_root(&req, self.chain.clone())?;
+                Ok(RpcResponse::StateRoot(height, state_root))
+            }
+            RpcPayload::GetRichList(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetRichList");
+                // admin only over http, peers must not list balances
+                if peer_addr.is_some() {
+                    return Err(NetworkError::RPC(
+                        "GetRichList is only accepted from clients".to_string(),
+                    ));
+                }
+                let accounts = get_rich_list(&req, self.chain.clone())?;
+                Ok(RpcResponse::Accounts(accounts))
+            }
+            RpcPayload::GetSupply => {
+                debug!("rpc message received in handler at RpcHeader::GetSupply");
+                let supply = get_total_supply(self.chain.clone())?;
+                Ok(RpcResponse::Supply(supply))
+            }
+            RpcPayload::GetStateDump(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetStateDump");
+                if peer_addr.is_some() {
+                    return Err(NetworkError::RPC(
+                        "GetStateDump is only accepted from clients".to_string(),
+                    ));
                 }
+                let accounts = get_state_dump(&req, self.chain.clone())?;
+                Ok(RpcResponse::Accounts(accounts))
             }
-            _ => Ok(RpcResponse::Generic(
+            RpcPayload::MineBlock => {
+                debug!("rpc message received in handler at RpcHeader::MineBlock");
+                // peers must never make this node produce blocks
+                if peer_addr.is_some() {
+                    return Err(NetworkError::RPC(
+                        "MineBlock is only accepted from clients".to_string(),
+                    ));
+                }
+                let block = mine_block(
+                    self.validator.clone(),
+                    self.mem_pool.clone(),
+                    self.chain.clone(),
+                )?;
+                Ok(RpcResponse::Block(block))
+            }
+            RpcPayload::GetMetrics => {
+                debug!("rpc message received in handler at RpcHeader::GetMetrics");
+                let metrics = get_block_metrics(self.validator.clone())?;
+                Ok(RpcResponse::Metrics(
+                    metrics,
+                    self.mempool_counters.metrics(),
+                ))
+            }
+            RpcPayload::GetTxByHash(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetTxByHash");
+                let tx = get_tx_by_hash(&req, self.chain.clone(), self.mem_pool.clone())?;
+                Ok(RpcResponse::Transaction(tx))
+            }
+            RpcPayload::ExecuteBlock(block) => {
+                debug!("rpc message received in handler at RpcHeader::ExecuteBlock");
+                if peer_addr.is_some() {
+                    return Err(NetworkError::RPC(
+                        "ExecuteBlock is only accepted from clients".to_string(),
+                    ));
+                }
+                let execution = execute_block(&block, self.chain.clone())?;
+                Ok(RpcResponse::Execution(execution))
+            }
+            RpcPayload::GetNextNonce(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetNextNonce");
+                let nonce = get_next_nonce(&req, self.chain.clone(), self.mem_pool.clone())?;
+                Ok(RpcResponse::Nonce(nonce))
+            }
+            RpcPayload::GetAccountProof(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetAccountProof");
+                let proof = get_account_proof(&req, self.chain.clone())?;
+                Ok(RpcResponse::AccountProof(proof))
+            }
+            RpcPayload::GetTxs(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetTxs");
+                let batch = get_txs(&req, self.chain.clone(), self.mem_pool.clone())?;
+                Ok(RpcResponse::TxBatch(batch))
+            }
+            RpcPayload::GetTxBlock(req) => {
+                debug!("rpc message received in handler at RpcHeader::GetTxBlock");
+                let block = get_tx_block(&req, self.chain.clone())?;
+                Ok(RpcResponse::Block(block))
+            }
+            RpcPayload::GetStateSnapshot => {
+                debug!("rpc message received in handler at RpcHeader::GetStateSnapshot");
+                if peer_addr.is_some() {
+                    return Err(NetworkError::RPC(
+                        "GetStateSnapshot is only accepted from clients".to_string(),
+                    ));
+                }
+                let snapshot = get_state_snapshot(self.chain.clone())?;
+                Ok(RpcResponse::Snapshot(snapshot))
+            }
+            RpcPayload::DiffState(snapshot) => {
+                debug!("rpc message received in handler at RpcHeader::DiffState");
+                if peer_addr.is_some() {
+                    return Err(NetworkError::RPC(
+                        "DiffState is only accepted from clients".to_string(),
+                    ));
+                }
+                let diffs = diff_state(&snapshot, self.chain.clone())?;
+                Ok(RpcResponse::StateDiff(diffs))
+            }
+            RpcPayload::Unhandled(_) => Ok(RpcResponse::Generic(
                 "unknown RPC header requested".to_string(),
             )),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::routes::{
+            block::GetBlockReq,
+            state::{GetRichListReq, GetStateDumpReq},
+        },
+        core::{
+            height::BlockHeight,
+            transaction::{random_signed_tx, Transaction, TransferData},
+        },
+        crypto::private_key::PrivateKey,
+        crypto::utils::random_hash,
+        rpc::handlers::state::MAX_STATE_DUMP,
+        rpc::types::RpcHeader,
+        testing::TestNetwork,
+    };
+    use std::thread;
+
+    #[test]
+    fn test_commit_block_returns_ack() {
+        let network = TestNetwork::new(2);
+        let proposer = network.node(0);
+        let block = {
+            let chain = lock!(proposer.chain);
+            lock!(proposer.validator)
+                .propose_block(&chain, vec![])
+                .unwrap()
+        };
+
+        let rpc = RPC {
+            header: RpcHeader::CommitBlock,
+            payload: block.to_bytes().unwrap(),
+            id: None,
+        };
+
+        let node = network.node(1);
+        let res = node.rpc_controller.handle_client_rpc(&rpc).unwrap();
+        assert!(matches!(res, RpcResponse::Ack(Some(hash)) if &hash == block.hash()));
+        assert_eq!(node.height(), 1);
+
+        // block no longer links to the tip
+        let res = node.rpc_controller.handle_client_rpc(&rpc);
+        assert!(matches!(res, Err(NetworkError::RPC(_))));
+    }
+
+    #[test]
+    fn test_competing_block_fork_choice() {
+        let network = TestNetwork::new(3);
+        let blocks = competing_blocks(&network, vec![vec![], vec![]]);
+        let (preferred, other) = (&blocks[0], &blocks[1]);
+
+        let node = network.node(2);
+
+        node.rpc_controller
+            .handle_client_rpc(&commit(other))
+            .unwrap();
+        assert_eq!(lock!(node.chain).last_block().unwrap().hash(), other.hash());
+
+        // preferred block at the same height replaces the tip
+        let res = node
+            .rpc_controller
+            .handle_client_rpc(&commit(preferred))
+            .unwrap();
+        assert!(matches!(res, RpcResponse::Ack(Some(hash)) if &hash == preferred.hash()));
+        assert_eq!(node.height(), 1);
+        assert_eq!(
+            lock!(node.chain).last_block().unwrap().hash(),
+            preferred.hash()
+        );
+
+        // the other block arriving again does not replace it
+        let res = node.rpc_controller.handle_client_rpc(&commit(other));
+        assert!(matches!(res, Err(NetworkError::RPC(_))), "{res:?}");
+        assert_eq!(
+            lock!(node.chain)
+                .get_block_by_height(BlockHeight(1))
+                .unwrap()
+                .hash(),
+            preferred.hash()
+        );
+    }
+
+    // blocks proposed at height 1 by nodes 0 and 1, preferred first
+    fn competing_blocks(network: &TestNetwork, txs: Vec<Vec<Transaction>>) -> Vec<Block> {
+        let mut blocks: Vec<Block> = txs
+            .into_iter()
+            .enumerate()
+            .map(|(index, txs)| {
+                let proposer = network.node(index);
+                let chain = lock!(proposer.chain);
+                lock!(proposer.validator)
+                    .propose_block(&chain, txs)
+                    .unwrap()
+            })
+            .collect();
+        blocks.sort_by_key(|block| *block.hash());
+        blocks
+    }
+
+    fn commit(block: &Block) -> RPC {
+        RPC {
+            header: RpcHeader::CommitBlock,
+            payload: block.to_bytes().unwrap(),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_replaced_tip_txs_return_to_pool() {
+        let network = TestNetwork::new(3);
+        let genesis = *lock!(network.node(0).chain).last_block().unwrap().hash();
+        let txs: Vec<Transaction> = (0..2)
+            .map(|_| {
+                let key = PrivateKey::new();
+                network.fund(&key.address(), 100);
+                let to = PrivateKey::new().address();
+                let data = TransferData {
+                    from: key.address(),
+                    to: to.clone(),
+                    amount: 10,
+                };
+                let mut tx = Transaction::new_transfer(
+                    to,
+                    key.address(),
+                    genesis,
+                    &data.to_bytes().unwrap(),
+                    1,
+                )
+                .unwrap();
+                tx.nonce = 1;
+                tx.sign(&key).unwrap();
+                tx
+            })
+            .collect();
+
+        let blocks = competing_blocks(&network, txs.iter().map(|tx| vec![tx.clone()]).collect());
+        let (preferred, other) = (&blocks[0], &blocks[1]);
+        let tx_in = |block: &Block| {
+            txs.iter()
+                .find(|tx| block.txs().iter().any(|block_tx| block_tx.hash == tx.hash))
+                .unwrap()
+                .clone()
+        };
+
+        let node = network.node(2);
+        node.rpc_controller
+            .handle_client_rpc(&commit(other))
+            .unwrap();
+        node.rpc_controller
+            .handle_client_rpc(&commit(preferred))
+            .unwrap();
+
+        // tx only in the replaced tip is pending again
+        let mem_pool = lock!(node.mem_pool);
+        assert!(mem_pool.has(&tx_in(other)));
+        assert!(!mem_pool.has(&tx_in(preferred)));
+        assert_eq!(mem_pool.len(), 1);
+    }
+
+    #[test]
+    fn test_fork_grace_from_local_receipt() {
+        let network = TestNetwork::new(3);
+        let blocks = competing_blocks(&network, vec![vec![], vec![]]);
+        let (preferred, other) = (&blocks[0], &blocks[1]);
+
+        let node = network.node(2);
+        lock!(node.validator).set_fork_grace(Duration::from_secs(1));
+
+        // blocks proposed longer ago than the grace period still compete
+        // when the tip was only just received
+        thread::sleep(Duration::from_secs(2));
+        node.rpc_controller
+            .handle_client_rpc(&commit(other))
+            .unwrap();
+        node.rpc_controller
+            .handle_client_rpc(&commit(preferred))
+            .unwrap();
+        assert_eq!(
+            lock!(node.chain).last_block().unwrap().hash(),
+            preferred.hash()
+        );
+
+        // the window closes a grace period after the tip was received
+        let node = network.node(1);
+        lock!(node.validator).set_fork_grace(Duration::from_secs(1));
+        node.rpc_controller
+            .handle_client_rpc(&commit(other))
+            .unwrap();
+        thread::sleep(Duration::from_millis(1_100));
+        let res = node.rpc_controller.handle_client_rpc(&commit(preferred));
+        assert!(matches!(res, Err(NetworkError::RPC(_))), "{res:?}");
+        assert_eq!(lock!(node.chain).last_block().unwrap().hash(), other.hash());
+    }
+
+    #[test]
+    fn test_admin_rpcs_rejected_from_peers() {
+        let network = TestNetwork::new(2);
+        let rich_list = RPC {
+            header: RpcHeader::GetRichList,
+            payload: bincode::serialize(&GetRichListReq {
+                min: None,
+                limit: None,
+            })
+            .unwrap(),
+            id: None,
+        };
+        let state_dump = RPC {
+            header: RpcHeader::GetStateDump,
+            payload: bincode::serialize(&GetStateDumpReq {
+                offset: None,
+                limit: None,
+            })
+            .unwrap(),
+            id: None,
+        };
+
+        for rpc in [rich_list, state_dump] {
+            let res = network.request(0, 1, &rpc);
+            assert!(matches!(res, Err(NetworkError::RPC(_))), "{res:?}");
+
+            let res = network.node(1).rpc_controller.handle_client_rpc(&rpc);
+            assert!(matches!(res, Ok(RpcResponse::Accounts(_))), "{res:?}");
+        }
+    }
+
+    #[test]
+    fn test_state_dump_limit_above_default() {
+        let network = TestNetwork::new(1);
+        for _ in 0..MAX_STATE_DUMP + 10 {
+            network.fund(&PrivateKey::new().address(), 1);
+        }
+
+        // the limit set by the api is not capped again by the handler
+        let rpc = RPC {
+            header: RpcHeader::GetStateDump,
+            payload: bincode::serialize(&GetStateDumpReq {
+                offset: None,
+                limit: Some(MAX_STATE_DUMP + 5),
+            })
+            .unwrap(),
+            id: None,
+        };
+        let res = network.node(0).rpc_controller.handle_client_rpc(&rpc);
+        assert!(
+            matches!(&res, Ok(RpcResponse::Accounts(accounts)) if accounts.len() == MAX_STATE_DUMP + 5),
+            "{res:?}"
+        );
+    }
+
+    #[test]
+    fn test_block_not_found_is_err() {
+        let network = TestNetwork::new(1);
+        let node = network.node(0);
+
+        for req in [
+            GetBlockReq {
+                height: Some("5".to_string()),
+                hash: None,
+            },
+            GetBlockReq {
+                height: None,
+                hash: Some(random_hash().to_string()),
+            },
+        ] {
+            let rpc = RPC {
+                header: RpcHeader::GetBlock,
+                payload: bincode::serialize(&req).unwrap(),
+                id: None,
+            };
+            let res = node.rpc_controller.handle_client_rpc(&rpc);
+            assert!(matches!(res, Err(NetworkError::NotFound(_))), "{res:?}");
+        }
+
+        // bad payload is a decoding error, not a missing block
+        let rpc = RPC {
+            header: RpcHeader::GetBlock,
+            payload: vec![1, 2, 3],
+            id: None,
+        };
+        let res = node.rpc_controller.handle_client_rpc(&rpc);
+        assert!(matches!(res, Err(NetworkError::Decoding(_))), "{res:?}");
+    }
+
+    #[test]
+    fn test_mismatched_payload_is_decoding_err() {
+        let network = TestNetwork::new(1);
+        let node = network.node(0);
+
+        let block_req = bincode::serialize(&GetBlockReq {
+            height: Some("0".to_string()),
+            hash: None,
+        })
+        .unwrap();
+        let tx_bytes = random_signed_tx().to_bytes().unwrap();
+
+        for (header, payload) in [
+            (RpcHeader::GetBlock, tx_bytes.clone()),
+            (RpcHeader::NewTx, block_req.clone()),
+            (RpcHeader::CommitBlock, block_req.clone()),
+            (RpcHeader::GetTx, tx_bytes.clone()),
+            (RpcHeader::GetLastBlock, block_req.clone()),
+            (RpcHeader::MineBlock, tx_bytes),
+        ] {
+            let rpc = RPC {
+                header,
+                payload,
+                id: None,
+            };
+            let res = node.rpc_controller.handle_client_rpc(&rpc);
+            assert!(
+                matches!(res, Err(NetworkError::Decoding(_))),
+                "{header:?}: {res:?}"
+            );
+        }
+
+        // nothing was admitted or produced
+        assert_eq!(lock!(node.mem_pool).len(), 0);
+        assert_eq!(node.height(), 0);
+
+        let rpc = RPC {
+            header: RpcHeader::GetBlock,
+            payload: block_req,
+            id: None,
+        };
+        let res = node.rpc_controller.handle_client_rpc(&rpc).unwrap();
+        assert!(matches!(res, RpcResponse::Block(block) if block.height() == 0));
+    }
+}
@@ -1,27 +1,29 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, RwLockReadGuard};
 
 use log::debug;
 
 use crate::{
-    api::routes::block::GetBlockReq,
+    api::routes::{block::GetBlockReq, header::GetHeadersReq, state::GetStateRootReq},
     core::{
-        block::Block, blockchain::Blockchain, encoding::ByteEncoding, header::Header,
+        block::Block,
+        blockchain::{Blockchain, MAX_HEADER_RANGE},
+        error::CoreError,
+        header::Header,
+        height::BlockHeight,
         transaction::Transaction,
+        view::{ChainView, SharedChainView},
     },
-    crypto::private_key::PrivateKey,
+    crypto::hash::Hash,
     lock,
     network::{error::NetworkError, node::ChainNode, tx_pool::TxPool, types::ArcMut},
-    rpc::types::RPC,
+    vm::{types::BlockExecution, validator::BlockValidator},
 };
 
-pub fn get_block(rpc: &RPC, chain: Arc<Mutex<Blockchain>>) -> Result<Block, NetworkError> {
-    let req: GetBlockReq = match bincode::deserialize(&rpc.payload) {
-        Ok(req) => req,
-        Err(e) => return Err(NetworkError::Decoding(e.to_string())),
-    };
-
-    let chain = lock!(chain);
-
+pub fn get_block(
+    req: &GetBlockReq,
+    view: &SharedChainView,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Block, NetworkError> {
     if req.hash.is_none() && req.height.is_none() {
         return Err(NetworkError::RPC(format!(
             "Incorrect request, must request with height or hash"
@@ -29,14 +31,28 @@ pub fn get_block(rpc: &RPC, chain: Arc<Mutex<Blockchain>>) -> Result<Block, Netw
     }
 
     let block = if let Some(height) = &req.height {
-        let block_height = match height.parse::<usize>() {
-            Ok(height) => height,
-            Err(e) => return Err(NetworkError::Decoding(e.to_string())),
-        };
+        let block_height = parse_block_height(height, view)?;
 
-        chain.get_block_by_height(block_height)
+        // recent blocks are served from the view, only
+        // older blocks need the chain lock
+        match read_view(view)?.get_block_by_height(block_height) {
+            Some(block) => Some(block),
+            None => {
+                // a height above the tip is reported separately from a
+                // block missing from storage
+                let chain = lock!(chain);
+                let chain_height = chain.height();
+                if block_height > chain_height {
+                    return Err(NetworkError::NotFound(format!(
+                        "requested height {block_height} exceeds chain height {chain_height}"
+                    )));
+                }
+                chain.get_block_by_height(block_height)
+            }
+        }
     } else if let Some(hash) = &req.hash {
-        chain.get_block_by_hash(&hash)
+        let cached = read_view(view)?.get_block_by_hash(&hash);
+        cached.or_else(|| lock!(chain).get_block_by_hash(&hash))
     } else {
         return Err(NetworkError::Decoding(
             "height or hash not supplied".to_string(),
@@ -46,34 +62,274 @@ pub fn get_block(rpc: &RPC, chain: Arc<Mutex<Blockchain>>) -> Result<Block, Netw
     if let Some(block) = block {
         Ok(block.clone())
     } else {
-        if let Some(height) = req.height {
-            return Err(NetworkError::RPC(format!(
+        if let Some(height) = &req.height {
+            return Err(NetworkError::NotFound(format!(
                 "Block with height: {height} not found"
             )));
         } else {
-            let hash = req.hash.unwrap();
-            return Err(NetworkError::RPC(format!(
+            let hash = req.hash.as_ref().unwrap();
+            return Err(NetworkError::NotFound(format!(
                 "Block with hash: {hash} not found"
             )));
         }
     }
 }
 
-pub fn get_block_header(rpc: &RPC, chain: Arc<Mutex<Blockchain>>) -> Result<Header, NetworkError> {
-    match get_block(rpc, chain) {
-        Ok(block) => return Ok(block.header().clone()),
-        Err(msg) => Err(NetworkError::RPC(msg.to_string())),
-    }
+pub fn get_block_header(
+    req: &GetBlockReq,
+    view: &SharedChainView,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Header, NetworkError> {
+    Ok(get_block(req, view, chain)?.header().clone())
 }
 
-pub fn get_last_block(_rpc: &RPC, chain: Arc<Mutex<Blockchain>>) -> Result<Block, NetworkError> {
-    let chain = lock!(chain);
-
-    let block = chain.last_block();
+pub fn get_last_block(view: &SharedChainView) -> Result<Block, NetworkError> {
+    let block = read_view(view)?.last_block();
 
     if let Some(block) = block {
         Ok(block.clone())
     } else {
-        Err(NetworkError::RPC(format!("Last block not found")))
+        Err(NetworkError::NotFound("Last block not found".to_string()))
+    }
+}
+
+// height is a number or one of the keywords "latest" or "genesis"
+fn parse_block_height(height: &str, view: &SharedChainView) -> Result<BlockHeight, NetworkError> {
+    match height {
+        "latest" => Ok(read_view(view)?.height()),
+        "genesis" => Ok(BlockHeight::GENESIS),
+        _ => height
+            .parse::<u64>()
+            .map(BlockHeight)
+            .map_err(|e| NetworkError::Decoding(format!("invalid block height: {height}, {e}"))),
+    }
+}
+
+fn read_view(view: &SharedChainView) -> Result<RwLockReadGuard<'_, ChainView>, NetworkError> {
+    view.read()
+        .map_err(|e| NetworkError::Internal(format!("unable to read ChainView, {e}")))
+}
+
+// validate and add a block built outside of this node, the block
+// must be signed by a member of the validator set, returns the block
+// and the tip it replaced if any
+pub fn submit_block(
+    block: Block,
+    validator: Arc<Mutex<BlockValidator>>,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<(Block, Option<Block>), NetworkError> {
+    let validator = lock!(validator);
+    validator.validate_block_signer(&block)?;
+
+    let mut chain = lock!(chain);
+
+    // a block competing with the tip, likely proposed while this node
+    // produced its own, replaces the tip if fork choice prefers it
+    if block.height() != BlockHeight::GENESIS && block.height() == chain.height() {
+        validator.validate_competing_block(&chain, &block)?;
+        let replaced = chain.replace_tip(block.clone())?;
+        return Ok((block, Some(replaced)));
+    }
+
+    validator.validate_block(&chain, &block)?;
+    chain.add_block(block.clone())?;
+
+    Ok((block, None))
+}
+
+// produce a block on request, used when block production is manual
+pub fn mine_block(
+    validator: Arc<Mutex<BlockValidator>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Block, NetworkError> {
+    let validator = lock!(validator);
+    let mut mem_pool = lock!(mem_pool);
+    let mut chain = lock!(chain);
+
+    Ok(validator.produce_block(&mut chain, &mut mem_pool)?)
+}
+
+// dry run block txs against the current state and compute the roots
+// its header should hold, nothing is written to the chain or state
+pub fn execute_block(
+    block: &Block,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<BlockExecution, NetworkError> {
+    let chain = lock!(chain);
+
+    let tip = chain
+        .tip()
+        .ok_or_else(|| NetworkError::RPC("chain has no tip".to_string()))?;
+    if block.prev_hash() != &tip.hash {
+        return Err(NetworkError::RPC(format!(
+            "block must build on the chain tip: {}, has previous hash: {}",
+            tip.hash,
+            block.prev_hash()
+        )));
+    }
+
+    let (txs, state_root) = chain
+        .runtime()
+        .trace_block(block.txs(), chain.state())
+        .map_err(CoreError::from)?;
+
+    Ok(BlockExecution {
+        txs,
+        state_root,
+        tx_root: Header::gen_tx_root(block.txs())?,
+        poh: Header::gen_poh(block.txs())?,
+    })
+}
+
+// maximum number of headers returned for a single GetHeaders request
+pub const MAX_HEADERS_PER_REQUEST: usize = MAX_HEADER_RANGE;
+
+pub fn get_headers(
+    req: &GetHeadersReq,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Vec<Header>, NetworkError> {
+    if req.from > req.to {
+        return Err(NetworkError::RPC(format!(
+            "Incorrect request, from: {} is greater than to: {}",
+            req.from, req.to
+        )));
+    }
+
+    let count = (req.to - req.from) as usize + 1;
+    if count > MAX_HEADERS_PER_REQUEST {
+        return Err(NetworkError::RPC(format!(
+            "Incorrect request, at most {MAX_HEADERS_PER_REQUEST} headers can be requested"
+        )));
+    }
+
+    let headers = lock!(chain).get_header_range(req.from, count);
+
+    if headers.len() < count {
+        return Err(NetworkError::NotFound(format!(
+            "Header with height: {} not found",
+            req.from + headers.len() as u64
+        )));
+    }
+
+    Ok(headers)
+}
+
+// state root from the header of the block at height, the header is
+// checked against the stored block before the root is returned
+pub fn get_state_root(
+    req: &GetStateRootReq,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<(BlockHeight, Hash), NetworkError> {
+    let chain = lock!(chain);
+
+    let block = chain.get_block_by_height(req.height).ok_or_else(|| {
+        NetworkError::NotFound(format!("Block with height: {} not found", req.height))
+    })?;
+    let header = block.header();
+
+    if header.height() != req.height {
+        return Err(NetworkError::RPC(format!(
+            "Stored block at height: {} has height: {}",
+            req.height,
+            header.height()
+        )));
+    }
+
+    // genesis hash is built from the genesis config, all other
+    // block hashes commit to the state root
+    if req.height > BlockHeight::GENESIS {
+        let blockhash = Header::gen_blockhash(
+            header.height(),
+            header.prev_hash(),
+            header.poh,
+            header.tx_root,
+            header.state_root,
+        )?;
+        if &blockhash != block.hash() {
+            return Err(NetworkError::RPC(format!(
+                "Stored block at height: {} does not match its header",
+                req.height
+            )));
+        }
+    }
+
+    Ok((req.height, header.state_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{block::random_signed_block, header::random_header};
+
+    #[test]
+    fn test_get_block_height_keywords() {
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+
+        for height in 1..=3 {
+            let prev_hash = bc.last_block().unwrap().hash().clone();
+            bc.add_block(random_signed_block(random_header(height, prev_hash)))
+                .unwrap();
+        }
+
+        let view = bc.view();
+        let chain = Arc::new(Mutex::new(bc));
+
+        let get = |height: &str| {
+            let req = GetBlockReq {
+                height: Some(height.to_string()),
+                hash: None,
+            };
+            get_block(&req, &view, chain.clone())
+        };
+
+        assert_eq!(get("latest").unwrap().height(), 3);
+        assert_eq!(get("genesis").unwrap().height(), 0);
+        assert_eq!(get("2").unwrap().height(), 2);
+        assert!(matches!(get("earliest"), Err(NetworkError::Decoding(_))));
+
+        let err = get("7").unwrap_err();
+        assert!(matches!(err, NetworkError::NotFound(_)));
+        assert_eq!(err.to_string(), "requested height 7 exceeds chain height 3");
+    }
+
+    #[test]
+    fn test_get_headers_link() {
+        let mut bc = Blockchain::new_with_genesis_in_memory().unwrap();
+
+        for height in 1..=6 {
+            let prev_hash = bc.last_block().unwrap().hash().clone();
+            bc.add_block(random_signed_block(random_header(height, prev_hash)))
+                .unwrap();
+        }
+
+        let chain = Arc::new(Mutex::new(bc));
+
+        let headers = get_headers(
+            &GetHeadersReq {
+                from: BlockHeight(1),
+                to: BlockHeight(5),
+            },
+            chain.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(headers.len(), 5);
+        assert_eq!(headers[0].height(), 1);
+        assert_eq!(headers[4].height(), 5);
+
+        for pair in headers.windows(2) {
+            assert_eq!(pair[1].prev_hash(), pair[0].hash());
+        }
+
+        // range past the tip is rejected
+        assert!(get_headers(
+            &GetHeadersReq {
+                from: BlockHeight(5),
+                to: BlockHeight(10),
+            },
+            chain
+        )
+        .is_err());
     }
 }
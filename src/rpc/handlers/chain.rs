@@ -0,0 +1,47 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    core::{blockchain::Blockchain, height::BlockHeight},
+    network::error::NetworkError,
+    vm::validator::{BlockMetrics, BlockValidator},
+};
+
+// height of the chain tip, a poisoned lock or a tip block missing from
+// storage is an error so it is never reported as height 0
+pub fn get_chain_height(chain: Arc<Mutex<Blockchain>>) -> Result<BlockHeight, NetworkError> {
+    let chain = chain
+        .lock()
+        .map_err(|e| NetworkError::Internal(format!("unable to lock chain, {e}")))?;
+
+    let height = chain.height();
+    if chain.get_header_by_height(height).is_none() {
+        return Err(NetworkError::Internal(format!(
+            "unable to read block at chain height: {height}"
+        )));
+    }
+
+    Ok(height)
+}
+
+// total coin supply, sum of every account balance in state
+pub fn get_total_supply(chain: Arc<Mutex<Blockchain>>) -> Result<u64, NetworkError> {
+    let chain = chain
+        .lock()
+        .map_err(|e| NetworkError::Internal(format!("unable to lock chain, {e}")))?;
+
+    chain
+        .state()
+        .total_supply()
+        .map_err(|e| NetworkError::Internal(e.to_string()))
+}
+
+// blocks proposed by this node and how many were accepted or rejected
+pub fn get_block_metrics(
+    validator: Arc<Mutex<BlockValidator>>,
+) -> Result<BlockMetrics, NetworkError> {
+    let validator = validator
+        .lock()
+        .map_err(|e| NetworkError::Internal(format!("unable to lock validator, {e}")))?;
+
+    Ok(validator.metrics())
+}
@@ -1,3 +1,4 @@
 pub mod block;
 pub mod chain;
+pub mod state;
 pub mod transaction;
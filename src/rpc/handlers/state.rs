@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    api::routes::{
+        account::GetAccountProofReq,
+        state::{GetRichListReq, GetStateDumpReq},
+    },
+    core::blockchain::Blockchain,
+    crypto::address::Address,
+    lock,
+    network::error::NetworkError,
+    rpc::types::AccountProof,
+    state::snapshot::{AccountDiff, StateSnapshot},
+};
+
+// default and maximum number of accounts returned by GetRichList
+pub const MAX_RICH_LIST: usize = 100;
+
+// default number of accounts returned by GetStateDump and default of
+// max_dump_accounts in the api config
+pub const MAX_STATE_DUMP: usize = 1000;
+
+// accounts with a balance above the requested minimum, highest
+// balance first
+pub fn get_rich_list(
+    req: &GetRichListReq,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Vec<(Address, u64)>, NetworkError> {
+    let limit = req.limit.unwrap_or(MAX_RICH_LIST).min(MAX_RICH_LIST);
+    let accounts = lock!(chain).state().accounts_above(req.min.unwrap_or(0))?;
+
+    Ok(accounts
+        .into_iter()
+        .take(limit)
+        .map(|(address, account)| (address, account.balance))
+        .collect())
+}
+
+// page of every account with its balance in address order, only
+// accepted from clients, the api caps limit with max_dump_accounts
+pub fn get_state_dump(
+    req: &GetStateDumpReq,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Vec<(Address, u64)>, NetworkError> {
+    let limit = req.limit.unwrap_or(MAX_STATE_DUMP);
+    let accounts = lock!(chain)
+        .state()
+        .accounts_page(req.offset.unwrap_or(0), limit)?;
+
+    Ok(accounts
+        .into_iter()
+        .map(|(address, account)| (address, account.balance))
+        .collect())
+}
+
+pub fn get_state_snapshot(chain: Arc<Mutex<Blockchain>>) -> Result<StateSnapshot, NetworkError> {
+    Ok(StateSnapshot::from_chain(&lock!(chain))?)
+}
+
+// accounts which differ between the current state and the snapshot,
+// the current state is the before side of each diff
+pub fn diff_state(
+    snapshot: &StateSnapshot,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Vec<AccountDiff>, NetworkError> {
+    let current = get_state_snapshot(chain)?;
+    Ok(current.diff(snapshot))
+}
+
+// account balance with its proof and the tip block it can be checked
+// against, the proof is for the current state which matches the tip
+// header once the tip block is committed
+pub fn get_account_proof(
+    req: &GetAccountProofReq,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<AccountProof, NetworkError> {
+    let address = Address::decode(&req.address)?;
+
+    let chain = lock!(chain);
+    let tip = chain
+        .tip_header()
+        .ok_or_else(|| NetworkError::RPC("chain has no tip".to_string()))?;
+    let (account, proof) = chain
+        .state()
+        .account_proof(&address)
+        .map_err(|e| NetworkError::NotFound(e.to_string()))?;
+
+    Ok(AccountProof {
+        height: tip.height(),
+        state_root: tip.state_root,
+        balance: account.balance,
+        proof,
+    })
+}
@@ -3,35 +3,361 @@ use std::sync::{Arc, Mutex};
 use log::debug;
 
 use crate::{
-    core::{encoding::ByteEncoding, transaction::Transaction},
-    crypto::private_key::PrivateKey,
-    network::{error::NetworkError, tx_pool::TxPool, types::ArcMut},
-    rpc::types::RPC,
+    api::routes::{
+        account::{GetAccountTxsReq, GetNextNonceReq},
+        transaction::{GetTxReq, GetTxsReq},
+    },
+    core::{block::Block, blockchain::Blockchain, encoding::HexEncoding, transaction::Transaction},
+    crypto::{address::Address, hash::Hash, private_key::PrivateKey},
+    lock,
+    network::{
+        error::NetworkError,
+        tx_pool::{transfer_cost, TxPool},
+    },
+    rpc::types::{TxBatch, TxStatus},
+    vm::validator::MAX_TX_BLOCKHASH_AGE,
 };
 
-pub fn new_tx(rpc: &RPC, mem_pool: Arc<Mutex<TxPool>>) -> Result<Transaction, NetworkError> {
-    let tx = Transaction::from_bytes(&rpc.payload);
-
-    match tx {
-        Ok(mut tx) => {
-            // TODO: TX should be signed by client
-            let key = PrivateKey::new();
-            let ver_data = tx.sign(&key)?;
-            if let Ok(mut mem_pool) = mem_pool.lock() {
-                mem_pool.add(tx.clone());
-                debug!(
-                    "adding transaction to the mem_pool in RpcController, hash: {}",
-                    ver_data.hash
-                );
-                Ok(tx)
-            } else {
-                Err(NetworkError::RPC(
-                    "unable to lock mem_pool in RpcController".to_string(),
-                ))
-            }
+pub fn new_tx(
+    mut tx: Transaction,
+    chain: Arc<Mutex<Blockchain>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+) -> Result<Transaction, NetworkError> {
+    // already seen txs are rejected so they are not broadcast again
+    if lock!(mem_pool).has_content(&tx.content_hash()?) {
+        return Err(NetworkError::RPC(
+            "transaction already in mem_pool".to_string(),
+        ));
+    }
+
+    check_anchor(&tx, chain.clone())?;
+    check_balance(&tx, chain, mem_pool.clone())?;
+
+    // TODO: TX should be signed by client
+    let key = PrivateKey::new();
+    let ver_data = tx.sign(&key)?;
+    if let Ok(mut mem_pool) = mem_pool.lock() {
+        mem_pool.add(tx.clone());
+        // a future nonce is dropped once the sender's queue is full
+        if !mem_pool.has(&tx) {
+            return Err(NetworkError::RPC(format!(
+                "transaction nonce: {} is too far ahead, the sender queue is full",
+                tx.nonce
+            )));
         }
-        Err(e) => Err(NetworkError::RPC(format!(
-            "unable to handle RpcHeader::NewTx in RpcController, {e}"
+        debug!(
+            "adding transaction to the mem_pool in RpcController, hash: {}",
+            ver_data.hash
+        );
+        Ok(tx)
+    } else {
+        Err(NetworkError::Internal(
+            "unable to lock mem_pool in RpcController".to_string(),
+        ))
+    }
+}
+
+// the tx blockhash must be a block at or below the tip, an unknown
+// hash could only be a fabricated or future block so it is rejected
+// separately from anchors which are merely too old
+fn check_anchor(tx: &Transaction, chain: Arc<Mutex<Blockchain>>) -> Result<(), NetworkError> {
+    let chain = lock!(chain);
+    let tip_height = chain.height();
+
+    let anchor_height = match chain.get_header_by_hash(&tx.blockhash.to_string()) {
+        Some(header) if header.height() <= tip_height => header.height(),
+        _ => {
+            return Err(NetworkError::RPC(format!(
+                "transaction blockhash: {} does not match any block at or below the tip",
+                tx.blockhash
+            )))
+        }
+    };
+
+    if anchor_height + MAX_TX_BLOCKHASH_AGE as u64 <= tip_height {
+        return Err(NetworkError::RPC(format!(
+            "transaction blockhash: {} is too old, anchored at height: {anchor_height}, tip height: {tip_height}",
+            tx.blockhash
+        )));
+    }
+
+    Ok(())
+}
+
+// reject transfers the sender can not afford, counting transfers from the
+// same sender already in the mem_pool, txs without a transfer payload are
+// left for the runtime to reject
+fn check_balance(
+    tx: &Transaction,
+    chain: Arc<Mutex<Blockchain>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+) -> Result<(), NetworkError> {
+    let cost = match transfer_cost(tx) {
+        Some(cost) => cost,
+        None => return Ok(()),
+    };
+
+    let balance = match lock!(chain).state().get_account(&tx.sender) {
+        Some(account) => account.balance,
+        None => {
+            return Err(NetworkError::RPC(format!(
+                "sender account: {} not found",
+                tx.sender.to_hex()?
+            )))
+        }
+    };
+
+    let (pending, grace) = {
+        let mem_pool = lock!(mem_pool);
+        (mem_pool.pending_spend(&tx.sender), mem_pool.pending_grace())
+    };
+
+    let required = cost.saturating_add(pending);
+    if required > balance.saturating_add(grace) {
+        return Err(NetworkError::RPC(format!(
+            "insufficient balance for transaction, required: {required}, available: {balance}"
+        )));
+    }
+
+    Ok(())
+}
+
+// default and maximum number of transactions returned by GetAccountTxs
+pub const MAX_ACCOUNT_TXS: usize = 100;
+
+pub fn get_account_txs(
+    req: &GetAccountTxsReq,
+    chain: Arc<Mutex<Blockchain>>,
+) -> Result<Vec<Transaction>, NetworkError> {
+    let address = Address::decode(&req.address)?;
+    let limit = req.limit.unwrap_or(MAX_ACCOUNT_TXS).min(MAX_ACCOUNT_TXS);
+
+    let chain = lock!(chain);
+
+    Ok(chain.get_account_txs(&address, limit))
+}
+
+// nonce the next transaction from an address should use, follows on
+// from the highest nonce in the chain or the mem_pool
+pub fn get_next_nonce(
+    req: &GetNextNonceReq,
+    chain: Arc<Mutex<Blockchain>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+) -> Result<u64, NetworkError> {
+    let address = Address::decode(&req.address)?;
+
+    let committed = lock!(chain).last_nonce(&address);
+    let pending = lock!(mem_pool).highest_pending_nonce(&address).unwrap_or(0);
+
+    Ok(committed.max(pending) + 1)
+}
+
+// look up transaction in the chain then the mem_pool, confirmations
+// count the including block, so a tx in the tip block has 1
+pub fn get_tx(
+    req: &GetTxReq,
+    chain: Arc<Mutex<Blockchain>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+) -> Result<TxStatus, NetworkError> {
+    {
+        let chain = lock!(chain);
+        if let Some((tx, block_height)) = chain.get_tx(&req.hash) {
+            let confirmations =
+                chain.height().value().saturating_sub(block_height.value()) as usize + 1;
+            return Ok(TxStatus {
+                tx,
+                block_height: Some(block_height),
+                confirmations,
+                replaced_by: None,
+            });
+        }
+    }
+
+    let mem_pool = lock!(mem_pool);
+    if let Some(tx) = mem_pool.get(&req.hash) {
+        return Ok(TxStatus {
+            tx,
+            block_height: None,
+            confirmations: 0,
+            replaced_by: None,
+        });
+    }
+
+    let replaced = Hash::from_hex(&req.hash)
+        .ok()
+        .and_then(|hash| mem_pool.replaced_by(&hash));
+    match replaced {
+        Some((tx, replaced_by)) => Ok(TxStatus {
+            tx,
+            block_height: None,
+            confirmations: 0,
+            replaced_by: Some(replaced_by),
+        }),
+        None => Err(NetworkError::NotFound(format!(
+            "transaction with hash: {} not found",
+            req.hash
         ))),
     }
 }
+
+// most hashes accepted by one GetTxs request
+pub const MAX_BATCH_TXS: usize = 100;
+
+// look up each hash like get_tx, hashes which are not found are
+// reported as missing instead of failing the request
+pub fn get_txs(
+    req: &GetTxsReq,
+    chain: Arc<Mutex<Blockchain>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+) -> Result<TxBatch, NetworkError> {
+    if req.hashes.len() > MAX_BATCH_TXS {
+        return Err(NetworkError::RPC(format!(
+            "too many hashes requested: {}, maximum is {MAX_BATCH_TXS}",
+            req.hashes.len()
+        )));
+    }
+
+    let mut batch = TxBatch {
+        found: vec![],
+        missing: vec![],
+    };
+    for hash in &req.hashes {
+        let req = GetTxReq { hash: hash.clone() };
+        match get_tx(&req, chain.clone(), mem_pool.clone()) {
+            Ok(status) => batch.found.push(status),
+            Err(NetworkError::NotFound(_)) => batch.missing.push(hash.clone()),
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(batch)
+}
+
+// block which included the tx, pending txs are not found
+pub fn get_tx_block(req: &GetTxReq, chain: Arc<Mutex<Blockchain>>) -> Result<Block, NetworkError> {
+    lock!(chain).get_block_for_tx(&req.hash).ok_or_else(|| {
+        NetworkError::NotFound(format!(
+            "no block includes transaction with hash: {}",
+            req.hash
+        ))
+    })
+}
+
+// tx by hash for a peer, included txs are found through the chain tx
+// index before the mem_pool is checked
+pub fn get_tx_by_hash(
+    req: &GetTxReq,
+    chain: Arc<Mutex<Blockchain>>,
+    mem_pool: Arc<Mutex<TxPool>>,
+) -> Result<Transaction, NetworkError> {
+    Ok(get_tx(req, chain, mem_pool)?.tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::{
+            block::Block,
+            encoding::ByteEncoding,
+            genesis::GenesisConfig,
+            header::{random_header, Header},
+            height::BlockHeight,
+            transaction::TransferData,
+        },
+        crypto::{address::random_sender_receiver, hash::Hash, utils::random_hash},
+        state::account::Account,
+    };
+
+    fn transfer(from: &Address, to: &Address, amount: u64) -> Transaction {
+        let genesis = Block::genesis(&GenesisConfig::default()).unwrap();
+        anchored_transfer(from, to, amount, genesis.hash().clone())
+    }
+
+    fn anchored_transfer(from: &Address, to: &Address, amount: u64, anchor: Hash) -> Transaction {
+        let data = TransferData {
+            from: from.clone(),
+            to: to.clone(),
+            amount,
+        };
+        Transaction::new_transfer(
+            to.clone(),
+            from.clone(),
+            anchor,
+            &data.to_bytes().unwrap(),
+            5,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_new_tx_balance_check() {
+        let (from, to) = random_sender_receiver();
+        let chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        chain
+            .state()
+            .set_account(&from, &Account { balance: 100 })
+            .unwrap();
+        let chain = Arc::new(Mutex::new(chain));
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+
+        // amount plus gas limit above balance
+        let res = new_tx(transfer(&from, &to, 96), chain.clone(), mem_pool.clone());
+        assert!(res.is_err());
+        assert_eq!(lock!(mem_pool).len(), 0);
+
+        // unknown sender
+        let res = new_tx(transfer(&to, &from, 1), chain.clone(), mem_pool.clone());
+        assert!(res.is_err());
+
+        let res = new_tx(transfer(&from, &to, 60), chain.clone(), mem_pool.clone());
+        assert!(res.is_ok());
+        assert_eq!(lock!(mem_pool).len(), 1);
+
+        // pending transfer from the same sender is counted
+        let res = new_tx(transfer(&from, &to, 31), chain.clone(), mem_pool.clone());
+        assert!(res.is_err());
+
+        lock!(mem_pool).set_pending_grace(10);
+        let res = new_tx(transfer(&from, &to, 31), chain.clone(), mem_pool.clone());
+        assert!(res.is_ok());
+        assert_eq!(lock!(mem_pool).len(), 2);
+    }
+
+    #[test]
+    fn test_new_tx_rejects_future_anchor() {
+        let (from, to) = random_sender_receiver();
+        let mut chain = Blockchain::new_with_genesis_in_memory().unwrap();
+        chain
+            .state()
+            .set_account(&from, &Account { balance: 100 })
+            .unwrap();
+        let tip = chain.last_block().unwrap();
+        chain
+            .add_block(Block::new(random_header(1, tip.hash().clone()), vec![]).unwrap())
+            .unwrap();
+        let tip = chain.tip().unwrap();
+        let chain = Arc::new(Mutex::new(chain));
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+
+        // hash of a block which would follow the tip but was never added
+        let (poh, tx_root, state_root) = (random_hash(), random_hash(), random_hash());
+        let future =
+            Header::gen_blockhash(BlockHeight(2), tip.hash.clone(), poh, tx_root, state_root)
+                .unwrap();
+
+        for anchor in [future, random_hash()] {
+            let tx = anchored_transfer(&from, &to, 1, anchor);
+            let res = new_tx(tx, chain.clone(), mem_pool.clone());
+            assert!(
+                matches!(&res, Err(NetworkError::RPC(msg)) if msg.contains("does not match any block")),
+                "{res:?}"
+            );
+        }
+        assert_eq!(lock!(mem_pool).len(), 0);
+
+        let tx = anchored_transfer(&from, &to, 1, tip.hash);
+        assert!(new_tx(tx, chain.clone(), mem_pool.clone()).is_ok());
+        assert_eq!(lock!(mem_pool).len(), 1);
+    }
+}
@@ -1,4 +1,5 @@
 pub mod controller;
 pub mod error;
 pub mod handlers;
+pub mod request;
 pub mod types;
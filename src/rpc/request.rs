@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::network::error::NetworkError;
+
+use super::types::RpcResponse;
+
+// outbound RPC requests waiting on a response from a peer, keyed
+// by the correlation id sent with the request
+pub struct PendingRequests {
+    next_id: AtomicU64,
+    pending: Mutex<HashMap<u64, Sender<RpcResponse>>>,
+}
+
+impl PendingRequests {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // allocate a new correlation id and the receiver the response
+    // for that id will be delivered on
+    pub fn register(&self) -> (u64, Receiver<RpcResponse>) {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        (id, rx)
+    }
+
+    // deliver response to the waiting request, returns false if no
+    // request is waiting on id, eg. it has already timed out
+    pub fn resolve(&self, id: u64, response: RpcResponse) -> bool {
+        match self.pending.lock().unwrap().remove(&id) {
+            Some(tx) => tx.send(response).is_ok(),
+            None => false,
+        }
+    }
+
+    pub fn wait(
+        &self,
+        id: u64,
+        rx: Receiver<RpcResponse>,
+        timeout: Duration,
+    ) -> Result<RpcResponse, NetworkError> {
+        let res = rx.recv_timeout(timeout);
+
+        // request is no longer pending whether it succeeded or not
+        self.pending.lock().unwrap().remove(&id);
+
+        match res {
+            Ok(response) => Ok(response),
+            Err(RecvTimeoutError::Timeout) => Err(NetworkError::RPC(format!(
+                "request with id: {id} timed out after {}ms",
+                timeout.as_millis()
+            ))),
+            Err(RecvTimeoutError::Disconnected) => Err(NetworkError::RPC(format!(
+                "request with id: {id} was dropped before a response was received"
+            ))),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+}
+
+impl Default for PendingRequests {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Instant;
+
+    #[test]
+    fn test_request_times_out() {
+        let requests = PendingRequests::new();
+        let timeout = Duration::from_millis(100);
+
+        // peer never responds
+        let (id, rx) = requests.register();
+
+        let start = Instant::now();
+        let res = requests.wait(id, rx, timeout);
+        let elapsed = start.elapsed();
+
+        assert!(res.is_err());
+        assert!(elapsed >= timeout);
+        assert!(elapsed < timeout * 10);
+        assert_eq!(requests.len(), 0);
+
+        // late response is ignored
+        assert!(!requests.resolve(id, RpcResponse::Generic("late".to_string())));
+    }
+
+    #[test]
+    fn test_request_resolved() {
+        let requests = std::sync::Arc::new(PendingRequests::new());
+        let (id, rx) = requests.register();
+
+        let responder = requests.clone();
+        thread::spawn(move || {
+            responder.resolve(id, RpcResponse::Generic("pong".to_string()));
+        });
+
+        match requests.wait(id, rx, Duration::from_secs(5)).unwrap() {
+            RpcResponse::Generic(msg) => assert_eq!(msg, "pong"),
+            res => panic!("unexpected response: {res:?}"),
+        }
+    }
+}
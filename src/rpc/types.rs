@@ -4,19 +4,33 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+use bincode::Options;
+use borsh::{BorshDeserialize, BorshSerialize};
 use log::{debug, info};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
+    api::routes::{
+        account::{GetAccountProofReq, GetAccountTxsReq, GetNextNonceReq},
+        block::GetBlockReq,
+        header::GetHeadersReq,
+        state::{GetRichListReq, GetStateDumpReq, GetStateRootReq},
+        transaction::{GetTxReq, GetTxsReq},
+    },
     core::{
         block::Block, blockchain::Blockchain, encoding::ByteEncoding, error::CoreError,
-        header::Header, transaction::Transaction,
+        header::Header, height::BlockHeight, transaction::Transaction,
     },
-    crypto::private_key::PrivateKey,
+    crypto::{address::Address, hash::Hash},
     lock,
+    state::{
+        root::ProofNode,
+        snapshot::{AccountDiff, StateSnapshot},
+    },
+    vm::{types::BlockExecution, validator::BlockMetrics},
 };
 
-use crate::network::types::Payload;
+use crate::network::{error::NetworkError, tx_pool::MempoolMetrics, types::Payload};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 #[repr(u16)]
@@ -31,6 +45,36 @@ pub enum RpcHeader {
     CommitBlock,
     BlockProposal,
     BlockVote,
+    GetHeaders,
+    GetAccountTxs,
+    // reply to an RPC sent with a correlation id, payload
+    // is the encoded RpcResponse
+    Response,
+    GetStateRoot,
+    GetRichList,
+    GetStateDump,
+    GetSupply,
+    // produce a block from the mem_pool now, clients only
+    MineBlock,
+    GetMetrics,
+    // single tx from the chain or mem_pool, used by peers to fetch
+    // a tx they are missing
+    GetTxByHash,
+    // dry run a block against the current state, clients only
+    ExecuteBlock,
+    // next nonce for a sender including its mem_pool transactions
+    GetNextNonce,
+    // account with a proof against the state root of the tip block
+    GetAccountProof,
+    // several txs by hash, hashes which are not found are listed
+    GetTxs,
+    // block which included a tx
+    GetTxBlock,
+    // every account with its balance and nonce, clients only
+    GetStateSnapshot,
+    // accounts which differ between the current state and the snapshot
+    // in the payload, clients only
+    DiffState,
 }
 
 impl From<u16> for RpcHeader {
@@ -45,21 +89,177 @@ impl From<RpcHeader> for u16 {
     }
 }
 
-#[derive(Debug, Clone)]
+// transaction with its inclusion status, block_height is
+// None and confirmations 0 while the transaction is pending
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct TxStatus {
+    pub tx: Transaction,
+    pub block_height: Option<BlockHeight>,
+    pub confirmations: usize,
+    // set when the pending tx was replaced in the mem_pool
+    pub replaced_by: Option<Hash>,
+}
+
+// account balance with the proof linking it to state_root, taken from
+// the header of the block at height
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct AccountProof {
+    pub height: BlockHeight,
+    pub state_root: Hash,
+    pub balance: u64,
+    pub proof: Vec<ProofNode>,
+}
+
+// txs found for a GetTxs request in request order, with the
+// requested hashes which are in neither the chain nor the mem_pool
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct TxBatch {
+    pub found: Vec<TxStatus>,
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Clone, BorshSerialize, BorshDeserialize)]
 pub enum RpcResponse {
     Block(Block),
     Transaction(Transaction),
     Error(String),
     Generic(String),
     Header(Header),
+    Headers(Vec<Header>),
+    Transactions(Vec<Transaction>),
+    TxStatus(TxStatus),
+    // block height and state root from its header
+    StateRoot(BlockHeight, Hash),
+    // write operation succeeded, with the hash of what was written
+    Ack(Option<Hash>),
+    // account addresses with their balance
+    Accounts(Vec<(Address, u64)>),
+    // height of the chain tip, 0 for a genesis only chain
+    Height(BlockHeight),
+    // sum of all account balances
+    Supply(u64),
+    // block production and mempool event counters
+    Metrics(BlockMetrics, MempoolMetrics),
+    Execution(BlockExecution),
+    Nonce(u64),
+    AccountProof(AccountProof),
+    TxBatch(TxBatch),
+    Snapshot(StateSnapshot),
+    StateDiff(Vec<AccountDiff>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RPC {
     pub header: RpcHeader,
     pub payload: Payload,
+    // correlation id, set when the sender expects a Response
+    pub id: Option<u64>,
 }
 
+// payload of an RPC decoded by its header, handle_rpc decodes the
+// payload before any handler runs so a body sent under the wrong
+// header is rejected as a decoding error
+#[derive(Debug)]
+pub enum RpcPayload {
+    GetBlock(GetBlockReq),
+    GetBlockHeader(GetBlockReq),
+    GetLastBlock,
+    GetChainHeight,
+    GetTx(GetTxReq),
+    NewTx(Transaction),
+    CommitBlock(Block),
+    GetHeaders(GetHeadersReq),
+    GetAccountTxs(GetAccountTxsReq),
+    GetStateRoot(GetStateRootReq),
+    GetRichList(GetRichListReq),
+    GetStateDump(GetStateDumpReq),
+    GetSupply,
+    MineBlock,
+    GetMetrics,
+    GetTxByHash(GetTxReq),
+    ExecuteBlock(Block),
+    GetNextNonce(GetNextNonceReq),
+    GetAccountProof(GetAccountProofReq),
+    GetTxs(GetTxsReq),
+    GetTxBlock(GetTxReq),
+    GetStateSnapshot,
+    DiffState(StateSnapshot),
+    // headers the controller has no handler for, payload is not decoded
+    Unhandled(RpcHeader),
+}
+
+impl RpcPayload {
+    pub fn try_decode(header: RpcHeader, bytes: &[u8]) -> Result<Self, NetworkError> {
+        let payload = match header {
+            RpcHeader::GetBlock => Self::GetBlock(decode_req(bytes)?),
+            RpcHeader::GetBlockHeader => Self::GetBlockHeader(decode_req(bytes)?),
+            RpcHeader::GetLastBlock => decode_empty(header, bytes, Self::GetLastBlock)?,
+            RpcHeader::GetChainHeight => decode_empty(header, bytes, Self::GetChainHeight)?,
+            RpcHeader::GetTx => Self::GetTx(decode_req(bytes)?),
+            RpcHeader::NewTx => Self::NewTx(
+                Transaction::from_bytes(bytes)
+                    .map_err(|e| NetworkError::Decoding(e.to_string()))?,
+            ),
+            RpcHeader::CommitBlock => Self::CommitBlock(
+                Block::from_bytes(bytes).map_err(|e| NetworkError::Decoding(e.to_string()))?,
+            ),
+            RpcHeader::GetHeaders => Self::GetHeaders(decode_req(bytes)?),
+            RpcHeader::GetAccountTxs => Self::GetAccountTxs(decode_req(bytes)?),
+            RpcHeader::GetStateRoot => Self::GetStateRoot(decode_req(bytes)?),
+            RpcHeader::GetRichList => Self::GetRichList(decode_req(bytes)?),
+            RpcHeader::GetStateDump => Self::GetStateDump(decode_req(bytes)?),
+            RpcHeader::GetSupply => decode_empty(header, bytes, Self::GetSupply)?,
+            RpcHeader::MineBlock => decode_empty(header, bytes, Self::MineBlock)?,
+            RpcHeader::GetMetrics => decode_empty(header, bytes, Self::GetMetrics)?,
+            RpcHeader::GetTxByHash => Self::GetTxByHash(decode_req(bytes)?),
+            RpcHeader::ExecuteBlock => Self::ExecuteBlock(
+                Block::from_bytes(bytes).map_err(|e| NetworkError::Decoding(e.to_string()))?,
+            ),
+            RpcHeader::GetNextNonce => Self::GetNextNonce(decode_req(bytes)?),
+            RpcHeader::GetAccountProof => Self::GetAccountProof(decode_req(bytes)?),
+            RpcHeader::GetTxs => Self::GetTxs(decode_req(bytes)?),
+            RpcHeader::GetTxBlock => Self::GetTxBlock(decode_req(bytes)?),
+            RpcHeader::GetStateSnapshot => decode_empty(header, bytes, Self::GetStateSnapshot)?,
+            RpcHeader::DiffState => Self::DiffState(
+                StateSnapshot::from_bytes(bytes)
+                    .map_err(|e| NetworkError::Decoding(e.to_string()))?,
+            ),
+            _ => Self::Unhandled(header),
+        };
+
+        Ok(payload)
+    }
+}
+
+// request structs are bincode encoded by the api routes, trailing
+// bytes are rejected so a longer body meant for another header fails
+fn decode_req<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, NetworkError> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+        .map_err(|e| NetworkError::Decoding(e.to_string()))
+}
+
+fn decode_empty(
+    header: RpcHeader,
+    bytes: &[u8],
+    payload: RpcPayload,
+) -> Result<RpcPayload, NetworkError> {
+    match bytes.is_empty() {
+        true => Ok(payload),
+        false => Err(NetworkError::Decoding(format!(
+            "RpcHeader::{header:?} takes no payload, received {} bytes",
+            bytes.len()
+        ))),
+    }
+}
+
+// single wire format for RPC, used for peer messages, client requests
+// and replies, bincode with its default little endian layout:
+//   header   u32 variant index of RpcHeader, not its u16 value
+//   payload  u64 length followed by the payload bytes
+//   id       u8 0 for None, or 1 followed by u64 id
 impl ByteEncoding<RPC> for RPC {
     fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
         Ok(bincode::serialize(&self)?)
@@ -69,3 +269,47 @@ impl ByteEncoding<RPC> for RPC {
         Ok(bincode::deserialize(data)?)
     }
 }
+
+impl ByteEncoding<RpcResponse> for RpcResponse {
+    fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
+        borsh::to_vec(self).map_err(|e| CoreError::Serialize(e.to_string()))
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<RpcResponse, CoreError> {
+        borsh::from_slice(data).map_err(|e| CoreError::Parsing(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::message::PeerMessage;
+
+    #[test]
+    fn test_rpc_wire_format() {
+        let rpc = RPC {
+            header: RpcHeader::GetTx,
+            payload: vec![7, 8, 9],
+            id: Some(42),
+        };
+
+        let mut expected = 4_u32.to_le_bytes().to_vec();
+        expected.extend_from_slice(&3_u64.to_le_bytes());
+        expected.extend_from_slice(&[7, 8, 9]);
+        expected.push(1);
+        expected.extend_from_slice(&42_u64.to_le_bytes());
+        assert_eq!(rpc.to_bytes().unwrap(), expected);
+
+        // RPC sent to a peer decodes to the same RPC the controller
+        // handles for client requests
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let payload = PeerMessage::RPC(addr, rpc.to_bytes().unwrap()).payload();
+        let decoded = match PeerMessage::from_payload(addr, &payload).unwrap() {
+            PeerMessage::RPC(_, bytes) => RPC::from_bytes(&bytes).unwrap(),
+            msg => panic!("expected RPC message, got: {msg:?}"),
+        };
+        assert_eq!(decoded.to_bytes().unwrap(), expected);
+        assert!(matches!(decoded.header, RpcHeader::GetTx));
+        assert_eq!(decoded.id, Some(42));
+    }
+}
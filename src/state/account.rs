@@ -5,7 +5,7 @@ use std::sync::Arc;
 use crate::core::encoding::ByteEncoding;
 use crate::core::error::CoreError;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Account {
     pub balance: u64,
     // TODO: implement nonce on account
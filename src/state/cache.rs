@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+
+use crate::crypto::address::Address;
+
+use super::account::Account;
+
+// default number of accounts kept in StateManager's account cache
+pub const DEFAULT_ACCOUNT_CACHE_SIZE: usize = 10_000;
+
+// least recently used cache of accounts as they are in the store,
+// entries are ordered by a tick bumped on every get and insert so the
+// oldest entry is evicted first, capacity 0 disables the cache
+#[derive(Debug, Default)]
+pub struct AccountCache {
+    capacity: usize,
+    tick: u64,
+    entries: BTreeMap<Address, (u64, Account)>,
+    order: BTreeMap<u64, Address>,
+    hits: u64,
+    misses: u64,
+}
+
+impl AccountCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Self::default()
+        }
+    }
+
+    pub fn get(&mut self, address: &Address) -> Option<Account> {
+        let tick = self.next_tick();
+        match self.entries.get_mut(address) {
+            Some((last_used, account)) => {
+                self.order.remove(last_used);
+                self.order.insert(tick, address.clone());
+                *last_used = tick;
+                self.hits += 1;
+                Some(account.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, address: &Address, account: &Account) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        self.remove(address);
+        while self.entries.len() >= self.capacity {
+            self.evict();
+        }
+
+        let tick = self.next_tick();
+        self.order.insert(tick, address.clone());
+        self.entries
+            .insert(address.clone(), (tick, account.clone()));
+    }
+
+    pub fn remove(&mut self, address: &Address) {
+        if let Some((last_used, _)) = self.entries.remove(address) {
+            self.order.remove(&last_used);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    // shrinking the capacity evicts the least recently used entries
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.evict();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    fn evict(&mut self) {
+        if let Some((_, address)) = self.order.pop_first() {
+            self.entries.remove(&address);
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_cache_evicts_least_recently_used() {
+        let mut cache = AccountCache::new(2);
+        let (a, b, c) = (
+            Address::new(&[1_u8; 20]),
+            Address::new(&[2_u8; 20]),
+            Address::new(&[3_u8; 20]),
+        );
+
+        cache.insert(&a, &Account { balance: 1 });
+        cache.insert(&b, &Account { balance: 2 });
+        // a is now more recently used than b
+        assert_eq!(cache.get(&a), Some(Account { balance: 1 }));
+
+        cache.insert(&c, &Account { balance: 3 });
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&b), None);
+        assert_eq!(cache.get(&a), Some(Account { balance: 1 }));
+        assert_eq!(cache.get(&c), Some(Account { balance: 3 }));
+        assert_eq!((cache.hits(), cache.misses()), (3, 1));
+
+        cache.set_capacity(0);
+        assert_eq!(cache.len(), 0);
+        cache.insert(&a, &Account { balance: 1 });
+        assert_eq!(cache.len(), 0);
+    }
+}
@@ -1,29 +1,165 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
 use tempfile::tempdir;
 
 use crate::{
-    core::{encoding::HexEncoding, error::CoreError},
-    crypto::{address::Address, hash::Hash, utils::random_hash},
+    core::error::CoreError,
+    crypto::{address::Address, hash::Hash},
 };
 
-use super::{account::Account, storage::StateStorage};
+use super::{
+    account::Account,
+    cache::{AccountCache, DEFAULT_ACCOUNT_CACHE_SIZE},
+    root::{ProofNode, StateRootCache},
+    storage::{PendingBlock, StateStorage},
+};
 
 pub struct StateManager {
     store: StateStorage,
+    // account writes buffered while a batch is open, flushed
+    // to the store in a single WriteBatch on commit_batch
+    pending: Mutex<Option<BTreeMap<Address, Account>>>,
+    // accounts as they are in the store, never holds buffered writes,
+    // lock order is always pending then account_cache
+    account_cache: Mutex<AccountCache>,
+    // state root kept up to date on each account write, None when
+    // the root must be fully recomputed eg. after rollback
+    // lock order is always pending then root_cache
+    root_cache: Mutex<Option<StateRootCache>>,
 }
 
 impl StateManager {
     pub fn new(storage_path: &str) -> Self {
         Self {
             store: StateStorage::new(storage_path),
+            pending: Mutex::new(None),
+            account_cache: Mutex::new(AccountCache::new(DEFAULT_ACCOUNT_CACHE_SIZE)),
+            root_cache: Mutex::new(None),
         }
     }
 
     pub fn get_account(&self, address: &Address) -> Option<Account> {
-        self.store.get_account(address)
+        if let Some(pending) = self.pending.lock().unwrap().as_ref() {
+            if let Some(account) = pending.get(address) {
+                return Some(account.clone());
+            }
+        }
+
+        // cache is held across the store read so a concurrent write
+        // can not be overwritten with the value read here
+        let mut cache = self.account_cache.lock().unwrap();
+        if let Some(account) = cache.get(address) {
+            return Some(account);
+        }
+        let account = self.store.get_account(address)?;
+        cache.insert(address, &account);
+        Some(account)
     }
 
     pub fn set_account(&self, address: &Address, account: &Account) -> Result<(), CoreError> {
-        self.store.set_account(address, account)
+        let mut pending = self.pending.lock().unwrap();
+        let res = match pending.as_mut() {
+            Some(pending) => {
+                pending.insert(address.clone(), account.clone());
+                Ok(())
+            }
+            None => {
+                let mut cache = self.account_cache.lock().unwrap();
+                let res = self.store.set_account(address, account);
+                match res {
+                    Ok(()) => cache.insert(address, account),
+                    Err(_) => cache.remove(address),
+                }
+                res
+            }
+        };
+
+        let mut root_cache = self.root_cache.lock().unwrap();
+        if let Some(cache) = root_cache.as_mut() {
+            if res.is_err() || cache.update(address, account).is_err() {
+                *root_cache = None;
+            }
+        }
+
+        res
+    }
+
+    // start buffering account writes, reads will see buffered
+    // writes until the batch is committed or discarded
+    pub fn begin_batch(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.is_none() {
+            *pending = Some(BTreeMap::new());
+        }
+    }
+
+    pub fn commit_batch(&self) -> Result<(), CoreError> {
+        let pending = self.pending.lock().unwrap().take();
+        match pending {
+            Some(accounts) => {
+                let accounts: Vec<(Address, Account)> = accounts.into_iter().collect();
+                let mut cache = self.account_cache.lock().unwrap();
+                self.store.set_accounts(&accounts)?;
+                for (address, account) in &accounts {
+                    cache.insert(address, account);
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    // commit the open batch as the state of block, the previous accounts
+    // are backed up and the block marked pending in the same write so
+    // rollback_block can undo it until finish_block is called
+    pub fn commit_block_batch(&self, block: &PendingBlock) -> Result<(), CoreError> {
+        let pending = self.pending.lock().unwrap().take();
+        let accounts: Vec<(Address, Account)> = pending.unwrap_or_default().into_iter().collect();
+
+        let mut cache = self.account_cache.lock().unwrap();
+        let res = self.store.set_block_accounts(&accounts, block);
+        for (address, account) in &accounts {
+            match res {
+                Ok(()) => cache.insert(address, account),
+                Err(_) => cache.remove(address),
+            }
+        }
+        drop(cache);
+        if res.is_err() {
+            // root cache includes the writes which were not stored
+            self.invalidate_state_root();
+        }
+        res
+    }
+
+    // block marked by commit_block_batch which was not finished
+    pub fn pending_block(&self) -> Result<Option<PendingBlock>, CoreError> {
+        self.store.pending_block()
+    }
+
+    // the pending block is in the chain, its backups are not needed
+    pub fn finish_block(&self) -> Result<(), CoreError> {
+        self.store.clear_account_backups()?;
+        self.store.clear_pending_block()
+    }
+
+    // restore accounts from before the pending block
+    pub fn rollback_block(&self) -> Result<(), CoreError> {
+        self.rollback()?;
+        self.store.clear_pending_block()
+    }
+
+    pub fn discard_batch(&self) {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.take().is_some() {
+            // cache includes the discarded writes
+            self.invalidate_state_root();
+        }
+    }
+
+    pub fn is_batching(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
     }
 
     pub fn backup_account(&self, address: &Address) -> Result<(), CoreError> {
@@ -31,22 +167,169 @@ impl StateManager {
             Some(acc) => self.store.backup_account(address, &acc),
             None => {
                 // no account exists for address, create new blank account
-                self.store.set_account(address, &Account::new())
+                // which is removed again on rollback
+                self.store.backup_missing_account(address)?;
+                self.set_account(address, &Account::new())
             }
         }
     }
 
     pub fn rollback(&self) -> Result<(), CoreError> {
-        self.store.rollback_accounts()
+        // buffered writes were never persisted, drop them before
+        // restoring backed up accounts
+        self.discard_batch();
+        // restored accounts are not known here, drop every cached
+        // account rather than risk serving a reverted value
+        let mut cache = self.account_cache.lock().unwrap();
+        let res = self.store.rollback_accounts();
+        cache.clear();
+        drop(cache);
+        self.invalidate_state_root();
+        res
+    }
+
+    // number of accounts kept in the account cache, 0 disables it
+    pub fn set_account_cache_size(&self, size: usize) {
+        self.account_cache.lock().unwrap().set_capacity(size);
+    }
+
+    // account cache (hits, misses) since the manager was created
+    pub fn account_cache_stats(&self) -> (u64, u64) {
+        let cache = self.account_cache.lock().unwrap();
+        (cache.hits(), cache.misses())
     }
 
     pub fn clear_backups(&self) -> Result<(), CoreError> {
         self.store.clear_account_backups()
     }
 
+    pub fn backup_count(&self) -> Result<usize, CoreError> {
+        self.store.backup_count()
+    }
+
+    pub fn compact(&self) -> Result<(), CoreError> {
+        self.store.compact()
+    }
+
+    // state root over all accounts ordered by address bytes, see StateRootCache,
+    // storage iteration order must never leak into the root otherwise
+    // nodes with the same state could compute different roots
     pub fn gen_state_root(&self) -> Result<Hash, CoreError> {
-        let hash = Hash::new(&[1_u8; 32])?;
-        Ok(hash)
+        let pending = self.pending.lock().unwrap();
+        let mut root_cache = self.root_cache.lock().unwrap();
+
+        if root_cache.is_none() {
+            *root_cache = Some(self.build_root_cache(pending.as_ref())?);
+        }
+
+        // SAFETY: cache set above
+        root_cache.as_mut().unwrap().root()
+    }
+
+    // account with the proof linking it to the current state root,
+    // checked with root::verify_account_proof
+    pub fn account_proof(&self, address: &Address) -> Result<(Account, Vec<ProofNode>), CoreError> {
+        let pending = self.pending.lock().unwrap();
+        let buffered = pending.as_ref().and_then(|pending| pending.get(address));
+        let account = match buffered {
+            Some(account) => account.clone(),
+            None => self
+                .store
+                .get_account(address)
+                .ok_or_else(|| CoreError::State("no account in state for address".to_string()))?,
+        };
+
+        let mut root_cache = self.root_cache.lock().unwrap();
+        if root_cache.is_none() {
+            *root_cache = Some(self.build_root_cache(pending.as_ref())?);
+        }
+
+        // SAFETY: cache set above
+        let proof = root_cache.as_mut().unwrap().proof(address)?;
+        Ok((account, proof))
+    }
+
+    // recompute state root from every account without using the cache
+    pub fn gen_state_root_full(&self) -> Result<Hash, CoreError> {
+        let pending = self.pending.lock().unwrap();
+        self.build_root_cache(pending.as_ref())?.root()
+    }
+
+    // accounts with a balance above threshold, highest balance first
+    pub fn accounts_above(&self, threshold: u64) -> Result<Vec<(Address, Account)>, CoreError> {
+        let pending = self.pending.lock().unwrap();
+        let mut accounts: Vec<(Address, Account)> = self
+            .all_accounts(pending.as_ref())?
+            .into_iter()
+            .filter(|(_, account)| account.balance > threshold)
+            .collect();
+
+        // address breaks ties so the order is stable
+        accounts.sort_by(|(a_addr, a), (b_addr, b)| {
+            b.balance.cmp(&a.balance).then_with(|| a_addr.cmp(b_addr))
+        });
+
+        Ok(accounts)
+    }
+
+    // sum of every account balance, including writes buffered in an
+    // open batch
+    pub fn total_supply(&self) -> Result<u64, CoreError> {
+        let pending = self.pending.lock().unwrap();
+
+        self.all_accounts(pending.as_ref())?
+            .values()
+            .try_fold(0_u64, |total, account| total.checked_add(account.balance))
+            .ok_or_else(|| CoreError::State("total supply overflows u64".to_string()))
+    }
+
+    // page of accounts in address order, writes buffered in an open
+    // batch are included
+    pub fn accounts_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(Address, Account)>, CoreError> {
+        let pending = self.pending.lock().unwrap();
+
+        match pending.as_ref() {
+            None => self.store.accounts_page(offset, limit),
+            Some(pending) => Ok(self
+                .all_accounts(Some(pending))?
+                .into_iter()
+                .skip(offset)
+                .take(limit)
+                .collect()),
+        }
+    }
+
+    fn build_root_cache(
+        &self,
+        pending: Option<&BTreeMap<Address, Account>>,
+    ) -> Result<StateRootCache, CoreError> {
+        let accounts = self.all_accounts(pending)?;
+        StateRootCache::from_accounts(accounts.iter())
+    }
+
+    // every stored account including writes buffered in an open batch
+    fn all_accounts(
+        &self,
+        pending: Option<&BTreeMap<Address, Account>>,
+    ) -> Result<BTreeMap<Address, Account>, CoreError> {
+        let mut accounts: BTreeMap<Address, Account> =
+            self.store.all_accounts()?.into_iter().collect();
+
+        if let Some(pending) = pending {
+            for (address, account) in pending {
+                accounts.insert(address.clone(), account.clone());
+            }
+        }
+
+        Ok(accounts)
+    }
+
+    fn invalidate_state_root(&self) {
+        self.root_cache.lock().unwrap().take();
     }
 
     pub fn new_in_memory() -> Self {
@@ -54,6 +337,9 @@ impl StateManager {
         let db_path = temp_dir.path().to_str().unwrap();
         Self {
             store: StateStorage::new(db_path),
+            pending: Mutex::new(None),
+            account_cache: Mutex::new(AccountCache::new(DEFAULT_ACCOUNT_CACHE_SIZE)),
+            root_cache: Mutex::new(None),
         }
     }
 }
@@ -63,3 +349,221 @@ impl Default for StateManager {
         Self::new("data/state.db")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounts_above() {
+        let state = StateManager::new_in_memory();
+        let balances = [(1_u8, 50), (2, 5), (3, 200), (4, 10), (5, 200)];
+        for (i, balance) in balances {
+            state
+                .set_account(&Address::new(&[i; 20]), &Account { balance })
+                .unwrap();
+        }
+
+        let accounts = state.accounts_above(10).unwrap();
+        let found: Vec<(Address, u64)> = accounts
+            .into_iter()
+            .map(|(address, account)| (address, account.balance))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (Address::new(&[3; 20]), 200),
+                (Address::new(&[5; 20]), 200),
+                (Address::new(&[1; 20]), 50),
+            ]
+        );
+
+        // buffered writes are included
+        state.begin_batch();
+        state
+            .set_account(&Address::new(&[2; 20]), &Account { balance: 100 })
+            .unwrap();
+        assert_eq!(state.accounts_above(60).unwrap().len(), 3);
+        state.discard_batch();
+        assert_eq!(state.accounts_above(60).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_total_supply() {
+        let state = StateManager::new_in_memory();
+        assert_eq!(state.total_supply().unwrap(), 0);
+
+        for (i, balance) in [(1_u8, 50), (2, 5), (3, 200)] {
+            state
+                .set_account(&Address::new(&[i; 20]), &Account { balance })
+                .unwrap();
+        }
+        assert_eq!(state.total_supply().unwrap(), 255);
+
+        state
+            .set_account(&Address::new(&[4; 20]), &Account { balance: u64::MAX })
+            .unwrap();
+        assert!(state.total_supply().is_err());
+    }
+
+    #[test]
+    fn test_state_root_order_independent() {
+        let accounts: Vec<(Address, Account)> = (1..=5_u8)
+            .map(|i| {
+                (
+                    Address::new(&[i; 20]),
+                    Account {
+                        balance: i as u64 * 10,
+                    },
+                )
+            })
+            .collect();
+
+        let state_1 = StateManager::new_in_memory();
+        let state_2 = StateManager::new_in_memory();
+
+        for (address, account) in accounts.iter() {
+            state_1.set_account(address, account).unwrap();
+        }
+        for (address, account) in accounts.iter().rev() {
+            state_2.set_account(address, account).unwrap();
+        }
+
+        let root_1 = state_1.gen_state_root().unwrap();
+        assert_eq!(root_1, state_2.gen_state_root().unwrap());
+
+        // buffered writes are included in the root
+        state_2.begin_batch();
+        state_2
+            .set_account(&accounts[0].0, &Account { balance: 1 })
+            .unwrap();
+        assert_ne!(root_1, state_2.gen_state_root().unwrap());
+        state_2.discard_batch();
+        assert_eq!(root_1, state_2.gen_state_root().unwrap());
+    }
+
+    #[test]
+    fn test_incremental_state_root_matches_full() {
+        let state = StateManager::new_in_memory();
+        let address = |i: u8| {
+            let mut bytes = [i; 20];
+            bytes[1] = i.wrapping_mul(7);
+            Address::new(&bytes)
+        };
+
+        // empty state
+        assert_eq!(
+            state.gen_state_root().unwrap(),
+            state.gen_state_root_full().unwrap()
+        );
+
+        for i in 0..20_u8 {
+            state
+                .set_account(&address(i % 4), &Account { balance: i as u64 })
+                .unwrap();
+            state
+                .set_account(&address(i), &Account { balance: 100 })
+                .unwrap();
+            assert_eq!(
+                state.gen_state_root().unwrap(),
+                state.gen_state_root_full().unwrap()
+            );
+        }
+
+        // writes in a batch and after rollback
+        state.begin_batch();
+        state.backup_account(&address(1)).unwrap();
+        state.backup_account(&address(200)).unwrap();
+        state
+            .set_account(&address(1), &Account { balance: 5 })
+            .unwrap();
+        assert_eq!(
+            state.gen_state_root().unwrap(),
+            state.gen_state_root_full().unwrap()
+        );
+        state.commit_batch().unwrap();
+        state.clear_backups().unwrap();
+        let committed = state.gen_state_root().unwrap();
+        assert_eq!(committed, state.gen_state_root_full().unwrap());
+
+        state.backup_account(&address(2)).unwrap();
+        state
+            .set_account(&address(2), &Account { balance: 999 })
+            .unwrap();
+        assert_ne!(committed, state.gen_state_root().unwrap());
+
+        state.rollback().unwrap();
+        assert_eq!(committed, state.gen_state_root().unwrap());
+        assert_eq!(
+            state.gen_state_root().unwrap(),
+            state.gen_state_root_full().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_account_cache_hits() {
+        let state = StateManager::new_in_memory();
+        let address = Address::new(&[1_u8; 20]);
+        state
+            .set_account(&address, &Account { balance: 10 })
+            .unwrap();
+
+        for _ in 0..3 {
+            assert_eq!(state.get_account(&address), Some(Account { balance: 10 }));
+        }
+        assert_eq!(state.account_cache_stats(), (3, 0));
+
+        // written accounts are updated in place
+        state
+            .set_account(&address, &Account { balance: 20 })
+            .unwrap();
+        assert_eq!(state.get_account(&address), Some(Account { balance: 20 }));
+        assert_eq!(state.account_cache_stats(), (4, 0));
+
+        // missing accounts are read from the store every time
+        let missing = Address::new(&[2_u8; 20]);
+        assert_eq!(state.get_account(&missing), None);
+        assert_eq!(state.get_account(&missing), None);
+        assert_eq!(state.account_cache_stats(), (4, 2));
+
+        // disabled cache always reads the store
+        state.set_account_cache_size(0);
+        assert_eq!(state.get_account(&address), Some(Account { balance: 20 }));
+        assert_eq!(state.account_cache_stats(), (4, 3));
+    }
+
+    #[test]
+    fn test_account_cache_rollback() {
+        let state = StateManager::new_in_memory();
+        let address = Address::new(&[1_u8; 20]);
+        let created = Address::new(&[2_u8; 20]);
+        state
+            .set_account(&address, &Account { balance: 10 })
+            .unwrap();
+
+        state.backup_account(&address).unwrap();
+        state.backup_account(&created).unwrap();
+        state
+            .set_account(&address, &Account { balance: 5 })
+            .unwrap();
+        state
+            .set_account(&created, &Account { balance: 5 })
+            .unwrap();
+        assert_eq!(state.get_account(&address), Some(Account { balance: 5 }));
+        assert_eq!(state.get_account(&created), Some(Account { balance: 5 }));
+
+        state.rollback().unwrap();
+        assert_eq!(state.get_account(&address), Some(Account { balance: 10 }));
+        assert_eq!(state.get_account(&created), None);
+
+        // writes committed from a batch are cached
+        state.begin_batch();
+        state
+            .set_account(&address, &Account { balance: 7 })
+            .unwrap();
+        state.commit_batch().unwrap();
+        let (hits, _) = state.account_cache_stats();
+        assert_eq!(state.get_account(&address), Some(Account { balance: 7 }));
+        assert_eq!(state.account_cache_stats().0, hits + 1);
+    }
+}
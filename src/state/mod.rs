@@ -1,3 +1,6 @@
 pub mod account;
+pub mod cache;
 pub mod manager;
+pub mod root;
+pub mod snapshot;
 pub mod storage;
@@ -0,0 +1,216 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    core::{
+        encoding::{ByteEncoding, HexEncoding},
+        error::CoreError,
+    },
+    crypto::{address::Address, hash::Hash},
+};
+
+use super::account::Account;
+
+// state root is a two level tree, accounts are grouped into buckets by
+// the first byte of their address:
+//
+//   leaf   = sha256(address || account)
+//   bucket = sha256(leaf hashes sorted by address)
+//   root   = sha256((bucket byte || bucket hash) for each non empty bucket)
+//
+// changing an account only invalidates its leaf, its bucket and the root,
+// so the root can be kept up to date as accounts are written
+#[derive(Debug, Default)]
+pub struct StateRootCache {
+    leaves: BTreeMap<Vec<u8>, Hash>,
+    buckets: BTreeMap<u8, Hash>,
+    dirty: BTreeSet<u8>,
+    root: Option<Hash>,
+}
+
+impl StateRootCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // root of a state without accounts, sha256 of no bytes
+    pub fn empty_root() -> Result<Hash, CoreError> {
+        Self::new().root()
+    }
+
+    pub fn from_accounts<'a>(
+        accounts: impl IntoIterator<Item = (&'a Address, &'a Account)>,
+    ) -> Result<Self, CoreError> {
+        let mut cache = Self::new();
+        for (address, account) in accounts {
+            cache.update(address, account)?;
+        }
+        Ok(cache)
+    }
+
+    pub fn update(&mut self, address: &Address, account: &Account) -> Result<(), CoreError> {
+        let key = address.to_bytes()?;
+
+        let mut buf = key.clone();
+        buf.extend_from_slice(&account.to_bytes()?);
+        let leaf = Hash::sha256(&buf)?;
+
+        if self.leaves.get(&key) == Some(&leaf) {
+            return Ok(());
+        }
+
+        self.dirty.insert(Self::bucket(&key));
+        self.leaves.insert(key, leaf);
+        self.root = None;
+
+        Ok(())
+    }
+
+    pub fn root(&mut self) -> Result<Hash, CoreError> {
+        if let Some(root) = &self.root {
+            return Ok(root.clone());
+        }
+
+        // only rehash buckets touched since the last root
+        let dirty = std::mem::take(&mut self.dirty);
+        for bucket in dirty {
+            let mut buf = vec![];
+            for (_, leaf) in self
+                .leaves
+                .range(vec![bucket]..)
+                .take_while(|(key, _)| Self::bucket(key) == bucket)
+            {
+                buf.extend_from_slice(&leaf.to_bytes()?);
+            }
+            self.buckets.insert(bucket, Hash::sha256(&buf)?);
+        }
+
+        let mut buf = vec![];
+        for (bucket, hash) in self.buckets.iter() {
+            buf.push(*bucket);
+            buf.extend_from_slice(&hash.to_bytes()?);
+        }
+
+        let root = Hash::sha256(&buf)?;
+        self.root = Some(root.clone());
+        Ok(root)
+    }
+
+    // sibling hashes linking the leaf of address to the root, errors if
+    // the address has no leaf
+    pub fn proof(&mut self, address: &Address) -> Result<Vec<ProofNode>, CoreError> {
+        // brings every bucket hash up to date
+        self.root()?;
+
+        let key = address.to_bytes()?;
+        if !self.leaves.contains_key(&key) {
+            return Err(CoreError::State(format!(
+                "no account in state for address: {}",
+                address.to_hex()?
+            )));
+        }
+        let bucket = Self::bucket(&key);
+
+        let in_bucket = self
+            .leaves
+            .range(vec![bucket]..)
+            .take_while(|(leaf_key, _)| Self::bucket(leaf_key) == bucket);
+        let (mut before, mut after) = (vec![], vec![]);
+        for (leaf_key, leaf) in in_bucket {
+            match leaf_key.cmp(&key) {
+                Ordering::Less => before.push(*leaf),
+                Ordering::Greater => after.push(*leaf),
+                Ordering::Equal => {}
+            }
+        }
+        let leaves = ProofNode::Leaves { before, after };
+
+        let (mut before, mut after) = (vec![], vec![]);
+        for (other, hash) in self.buckets.iter() {
+            match other.cmp(&bucket) {
+                Ordering::Less => before.push((*other, *hash)),
+                Ordering::Greater => after.push((*other, *hash)),
+                Ordering::Equal => {}
+            }
+        }
+        let buckets = ProofNode::Buckets { before, after };
+
+        Ok(vec![leaves, buckets])
+    }
+
+    fn bucket(key: &[u8]) -> u8 {
+        key.first().copied().unwrap_or(0)
+    }
+}
+
+// one level of an account proof, hashes are split around the position
+// of the account being proven, see StateRootCache::proof
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum ProofNode {
+    // other leaf hashes in the account's bucket, in address order
+    Leaves {
+        before: Vec<Hash>,
+        after: Vec<Hash>,
+    },
+    // every other non empty bucket with its hash, in bucket order
+    Buckets {
+        before: Vec<(u8, Hash)>,
+        after: Vec<(u8, Hash)>,
+    },
+}
+
+// true if proof links address with account to root, only needs the root
+// from a block header so light clients don't have to hold any state
+pub fn verify_account_proof(
+    root: &Hash,
+    address: &Address,
+    account: &Account,
+    proof: &[ProofNode],
+) -> bool {
+    let (leaves, buckets) = match proof {
+        [ProofNode::Leaves { before, after }, ProofNode::Buckets {
+            before: buckets_before,
+            after: buckets_after,
+        }] => ((before, after), (buckets_before, buckets_after)),
+        _ => return false,
+    };
+
+    let computed = || -> Result<Hash, CoreError> {
+        let key = address.to_bytes()?;
+        let bucket = StateRootCache::bucket(&key);
+
+        let mut buf = key;
+        buf.extend_from_slice(&account.to_bytes()?);
+        let leaf = Hash::sha256(&buf)?;
+
+        let mut buf = vec![];
+        for hash in leaves.0.iter().chain([&leaf]).chain(leaves.1) {
+            buf.extend_from_slice(&hash.to_bytes()?);
+        }
+        let bucket_hash = Hash::sha256(&buf)?;
+
+        let mut buf = vec![];
+        let mut last: Option<u8> = None;
+        for (other, hash) in buckets
+            .0
+            .iter()
+            .chain([&(bucket, bucket_hash)])
+            .chain(buckets.1)
+        {
+            // buckets must be strictly ordered, so the account's bucket is
+            // the one named by its address
+            if last.is_some_and(|last| last >= *other) {
+                return Err(CoreError::State("proof buckets out of order".to_string()));
+            }
+            last = Some(*other);
+            buf.push(*other);
+            buf.extend_from_slice(&hash.to_bytes()?);
+        }
+
+        Ok(Hash::sha256(&buf)?)
+    };
+
+    matches!(computed(), Ok(computed) if &computed == root)
+}
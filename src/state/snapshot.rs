@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    core::{blockchain::Blockchain, encoding::ByteEncoding, error::CoreError, height::BlockHeight},
+    crypto::address::Address,
+};
+
+// balance and nonce of an account when the snapshot was taken, the
+// nonce is the highest one sent from the account in the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, BorshSerialize, BorshDeserialize)]
+pub struct SnapshotAccount {
+    pub balance: u64,
+    pub nonce: u64,
+}
+
+// every account in the state at a chain height, used to compare the
+// state of two nodes
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct StateSnapshot {
+    pub height: BlockHeight,
+    pub accounts: BTreeMap<Address, SnapshotAccount>,
+}
+
+// change to a single account between two snapshots
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub enum AccountDiff {
+    Added {
+        address: Address,
+        after: SnapshotAccount,
+    },
+    Removed {
+        address: Address,
+        before: SnapshotAccount,
+    },
+    Changed {
+        address: Address,
+        before: SnapshotAccount,
+        after: SnapshotAccount,
+    },
+}
+
+impl AccountDiff {
+    pub fn address(&self) -> &Address {
+        match self {
+            AccountDiff::Added { address, .. }
+            | AccountDiff::Removed { address, .. }
+            | AccountDiff::Changed { address, .. } => address,
+        }
+    }
+}
+
+impl StateSnapshot {
+    // snapshot of the current state, writes buffered in an open batch
+    // are included
+    pub fn from_chain(chain: &Blockchain) -> Result<Self, CoreError> {
+        let nonces = chain.last_nonces()?;
+        let accounts = chain
+            .state()
+            .accounts_page(0, usize::MAX)?
+            .into_iter()
+            .map(|(address, account)| {
+                let nonce = nonces.get(&address).copied().unwrap_or(0);
+                let account = SnapshotAccount {
+                    balance: account.balance,
+                    nonce,
+                };
+                (address, account)
+            })
+            .collect();
+
+        Ok(Self {
+            height: chain.height(),
+            accounts,
+        })
+    }
+
+    // accounts which differ from self in other, in address order, self
+    // is the before side of each diff
+    pub fn diff(&self, other: &StateSnapshot) -> Vec<AccountDiff> {
+        let mut diffs = vec![];
+
+        for (address, before) in &self.accounts {
+            match other.accounts.get(address) {
+                Some(after) if after != before => diffs.push(AccountDiff::Changed {
+                    address: address.clone(),
+                    before: *before,
+                    after: *after,
+                }),
+                Some(_) => {}
+                None => diffs.push(AccountDiff::Removed {
+                    address: address.clone(),
+                    before: *before,
+                }),
+            }
+        }
+
+        for (address, after) in &other.accounts {
+            if !self.accounts.contains_key(address) {
+                diffs.push(AccountDiff::Added {
+                    address: address.clone(),
+                    after: *after,
+                });
+            }
+        }
+
+        diffs.sort_by(|a, b| a.address().cmp(b.address()));
+        diffs
+    }
+}
+
+impl ByteEncoding<StateSnapshot> for StateSnapshot {
+    fn to_bytes(&self) -> Result<Vec<u8>, CoreError> {
+        borsh::to_vec(self).map_err(|e| CoreError::Parsing(e.to_string()))
+    }
+
+    fn from_bytes(data: &[u8]) -> Result<StateSnapshot, CoreError> {
+        borsh::from_slice(data).map_err(|e| CoreError::Parsing(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::address::random_sender_receiver;
+
+    fn account(balance: u64, nonce: u64) -> SnapshotAccount {
+        SnapshotAccount { balance, nonce }
+    }
+
+    #[test]
+    fn test_diff_changed_balance() {
+        let (a, b) = random_sender_receiver();
+        let before = StateSnapshot {
+            height: BlockHeight(3),
+            accounts: BTreeMap::from([(a.clone(), account(100, 2)), (b.clone(), account(50, 0))]),
+        };
+        let mut after = before.clone();
+        after.accounts.insert(b.clone(), account(75, 0));
+
+        assert_eq!(
+            before.diff(&after),
+            vec![AccountDiff::Changed {
+                address: b,
+                before: account(50, 0),
+                after: account(75, 0),
+            }]
+        );
+        assert!(before.diff(&before).is_empty());
+
+        let bytes = after.to_bytes().unwrap();
+        assert_eq!(StateSnapshot::from_bytes(&bytes).unwrap(), after);
+    }
+
+    #[test]
+    fn test_diff_added_removed() {
+        let (a, b) = random_sender_receiver();
+        let before = StateSnapshot {
+            height: BlockHeight(1),
+            accounts: BTreeMap::from([(a.clone(), account(10, 1))]),
+        };
+        let after = StateSnapshot {
+            height: BlockHeight(2),
+            accounts: BTreeMap::from([(b.clone(), account(20, 0))]),
+        };
+
+        let diffs = after.diff(&before);
+        assert_eq!(diffs.len(), 2);
+        assert!(diffs.contains(&AccountDiff::Added {
+            address: a,
+            after: account(10, 1),
+        }));
+        assert!(diffs.contains(&AccountDiff::Removed {
+            address: b,
+            before: account(20, 0),
+        }));
+    }
+}
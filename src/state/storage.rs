@@ -1,22 +1,38 @@
+use borsh::{BorshDeserialize, BorshSerialize};
 use log::{error, warn};
 use rocksdb::{ColumnFamilyDescriptor, Options, WriteBatch, DB};
 
 use crate::core::encoding::HexEncoding;
 use crate::core::error::CoreError;
+use crate::core::height::BlockHeight;
+use crate::crypto::hash::Hash;
 use crate::{core::encoding::ByteEncoding, crypto::address::Address};
 
 use crate::state::account::Account;
 
+// key in meta_cf of the block whose state is being applied
+const PENDING_BLOCK_KEY: &str = "pending_block";
+
+// block whose state was written but which may not be in the chain yet,
+// written together with the state and cleared once the block is stored
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct PendingBlock {
+    pub height: BlockHeight,
+    pub hash: Hash,
+}
+
 pub struct StateStorage {
     db: DB,
     account_cf: String,
     backup_account_cf: String,
+    meta_cf: String,
 }
 
 impl StateStorage {
     pub fn new(path: &str) -> Self {
         let account_cf = "account_cf".to_string();
         let backup_account_cf = "backup_account_cf".to_string();
+        let meta_cf = "meta_cf".to_string();
 
         let mut options = Options::default();
         options.create_if_missing(true);
@@ -25,11 +41,16 @@ impl StateStorage {
         let account_cf_descriptor = ColumnFamilyDescriptor::new(&account_cf, Options::default());
         let backup_account_cf_descriptor =
             ColumnFamilyDescriptor::new(&backup_account_cf, Options::default());
+        let meta_cf_descriptor = ColumnFamilyDescriptor::new(&meta_cf, Options::default());
 
         let db = DB::open_cf_descriptors(
             &options,
             path,
-            vec![account_cf_descriptor, backup_account_cf_descriptor],
+            vec![
+                account_cf_descriptor,
+                backup_account_cf_descriptor,
+                meta_cf_descriptor,
+            ],
         )
         .expect("Unable to open DB with column families");
 
@@ -37,6 +58,7 @@ impl StateStorage {
             db,
             account_cf,
             backup_account_cf,
+            meta_cf,
         }
     }
 
@@ -97,18 +119,141 @@ impl StateStorage {
         }
     }
 
+    // write all accounts in a single WriteBatch, either all
+    // accounts are written or none are
+    pub fn set_accounts(&self, accounts: &[(Address, Account)]) -> Result<(), CoreError> {
+        let handle = self.db.cf_handle(&self.account_cf).ok_or_else(|| {
+            CoreError::State(
+                "unable to get ColumnFamily handle in StateStorage.set_accounts".to_string(),
+            )
+        })?;
+
+        let mut batch = WriteBatch::default();
+
+        for (address, account) in accounts {
+            batch.put_cf(handle, address.to_hex()?, account.to_bytes()?);
+        }
+
+        self.db.write(batch).map_err(|e| {
+            CoreError::State(format!(
+                "unable to write account batch in StateStorage.set_accounts, {e}"
+            ))
+        })?;
+
+        Ok(())
+    }
+
+    // write accounts for a block in a single WriteBatch together with a
+    // backup of each account before the block and the pending block
+    // marker, rollback_accounts undoes the block until the marker is cleared
+    pub fn set_block_accounts(
+        &self,
+        accounts: &[(Address, Account)],
+        block: &PendingBlock,
+    ) -> Result<(), CoreError> {
+        if let Some(pending) = self.pending_block()? {
+            return Err(CoreError::State(format!(
+                "block at height: {} is still pending in StateStorage",
+                pending.height
+            )));
+        }
+
+        let (account_handle, backup_handle, meta_handle) = (
+            self.cf(&self.account_cf)?,
+            self.cf(&self.backup_account_cf)?,
+            self.cf(&self.meta_cf)?,
+        );
+
+        let mut batch = WriteBatch::default();
+
+        // backups left without a pending block belong to no block, rollback
+        // must only restore accounts from before this one
+        for iter in self
+            .db
+            .iterator_cf(backup_handle, rocksdb::IteratorMode::Start)
+        {
+            let (key, _) = iter.map_err(|e| {
+                CoreError::State(format!("unable to iterate through backup_account_cf, {e}"))
+            })?;
+            batch.delete_cf(backup_handle, key);
+        }
+
+        for (address, account) in accounts {
+            let addr_str = address.to_hex()?;
+            let before = match self.get_account(address) {
+                Some(account) => account.to_bytes()?,
+                None => vec![],
+            };
+            batch.put_cf(backup_handle, &addr_str, before);
+            batch.put_cf(account_handle, &addr_str, account.to_bytes()?);
+        }
+
+        batch.put_cf(meta_handle, PENDING_BLOCK_KEY, borsh::to_vec(block)?);
+
+        self.db.write(batch).map_err(|e| {
+            CoreError::State(format!(
+                "unable to write block accounts in StateStorage.set_block_accounts, {e}"
+            ))
+        })
+    }
+
+    pub fn pending_block(&self) -> Result<Option<PendingBlock>, CoreError> {
+        let value = self
+            .db
+            .get_cf(self.cf(&self.meta_cf)?, PENDING_BLOCK_KEY)
+            .map_err(|e| CoreError::State(format!("unable to get pending block marker, {e}")))?;
+
+        match value {
+            Some(bytes) => Ok(Some(borsh::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn clear_pending_block(&self) -> Result<(), CoreError> {
+        self.db
+            .delete_cf(self.cf(&self.meta_cf)?, PENDING_BLOCK_KEY)
+            .map_err(|e| CoreError::State(format!("unable to clear pending block marker, {e}")))
+    }
+
+    fn cf(&self, name: &str) -> Result<&rocksdb::ColumnFamily, CoreError> {
+        self.db
+            .cf_handle(name)
+            .ok_or_else(|| CoreError::State(format!("unable to get ColumnFamily handle: {name}")))
+    }
+
+    // only the first backup of an address is kept until backups are
+    // rolled back or cleared, so rollback restores the original account
     pub fn backup_account(&self, address: &Address, account: &Account) -> Result<(), CoreError> {
+        self.put_backup(address, account.to_bytes()?)
+    }
+
+    // record that no account existed for address, rollback
+    // will delete any account created for it
+    pub fn backup_missing_account(&self, address: &Address) -> Result<(), CoreError> {
+        self.put_backup(address, vec![])
+    }
+
+    fn put_backup(&self, address: &Address, value: Vec<u8>) -> Result<(), CoreError> {
         let addr_str = address.to_hex()?;
         match self.db.cf_handle(&self.backup_account_cf) {
             Some(handle) => {
-                self.db
-                    .put_cf(handle, &addr_str, account.to_bytes()?)
-                    .map_err(|e| {
-                        CoreError::State(format!(
-                            "unable to put address: {} in StateStorage, {e}",
-                            addr_str
-                        ))
-                    })?;
+                let exists = self.db.get_cf(handle, &addr_str).map_err(|e| {
+                    CoreError::State(format!(
+                        "unable to get backup for address: {} in StateStorage, {e}",
+                        addr_str
+                    ))
+                })?;
+
+                if exists.is_some() {
+                    return Ok(());
+                }
+
+                self.db.put_cf(handle, &addr_str, value).map_err(|e| {
+                    CoreError::State(format!(
+                        "unable to put address: {} in StateStorage, {e}",
+                        addr_str
+                    ))
+                })?;
                 Ok(())
             }
             None => Err(CoreError::State(
@@ -117,6 +262,45 @@ impl StateStorage {
         }
     }
 
+    // all accounts in storage, in storage key order
+    pub fn all_accounts(&self) -> Result<Vec<(Address, Account)>, CoreError> {
+        self.accounts_page(0, usize::MAX)
+    }
+
+    // at most limit accounts after skipping offset, in storage key order
+    pub fn accounts_page(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(Address, Account)>, CoreError> {
+        let handle = self.db.cf_handle(&self.account_cf).ok_or_else(|| {
+            CoreError::State(
+                "unable to get ColumnFamily handle in StateStorage.all_accounts".to_string(),
+            )
+        })?;
+
+        let mut accounts = vec![];
+
+        for item in self
+            .db
+            .iterator_cf(handle, rocksdb::IteratorMode::Start)
+            .skip(offset)
+            .take(limit)
+        {
+            let (key, value) = item.map_err(|e| {
+                CoreError::State(format!(
+                    "unable to iterate through account_cf in StateStorage.all_accounts, {e}"
+                ))
+            })?;
+            let addr_str = String::from_utf8(key.to_vec())
+                .map_err(|e| CoreError::State(format!("failed to convert key to string: {}", e)))?;
+
+            accounts.push((Address::from_hex(&addr_str)?, Account::from_bytes(&value)?));
+        }
+
+        Ok(accounts)
+    }
+
     pub fn rollback_accounts(&self) -> Result<(), CoreError> {
         // Get the handle for the backup column family
         let backup_handle = match self.db.cf_handle(&self.backup_account_cf) {
@@ -143,11 +327,16 @@ impl StateStorage {
                     })?;
                     let address = Address::from_hex(&addr_str)?;
 
-                    // Convert the value bytes back to Account
-                    let account = Account::from_bytes(&value)?;
+                    if value.is_empty() {
+                        // account did not exist before backup
+                        self.delete_account(&address)?;
+                    } else {
+                        // Convert the value bytes back to Account
+                        let account = Account::from_bytes(&value)?;
 
-                    // Restore the account to the state storage
-                    self.set_account(&address, &account)?;
+                        // Restore the account to the state storage
+                        self.set_account(&address, &account)?;
+                    }
 
                     // add key to batch delete which will clear all account backups at end
                     batch.delete_cf(backup_handle, &addr_str);
@@ -169,6 +358,56 @@ impl StateStorage {
         Ok(())
     }
 
+    // sync the WAL and flush memtables of every column family to disk
+    pub fn flush(&self) -> Result<(), CoreError> {
+        self.db
+            .flush_wal(true)
+            .map_err(|e| CoreError::State(format!("unable to flush WAL, {e}")))?;
+
+        for name in [&self.account_cf, &self.backup_account_cf, &self.meta_cf] {
+            let cf = self.db.cf_handle(name).ok_or_else(|| {
+                CoreError::State(format!("unable to get ColumnFamily handle: {name}"))
+            })?;
+            self.db
+                .flush_cf(cf)
+                .map_err(|e| CoreError::State(format!("unable to flush {name}, {e}")))?;
+        }
+
+        Ok(())
+    }
+
+    // compact the full key range of every column family, removes
+    // tombstones left by clearing and rolling back backups
+    pub fn compact(&self) -> Result<(), CoreError> {
+        for name in [&self.account_cf, &self.backup_account_cf] {
+            let cf = self.db.cf_handle(name).ok_or_else(|| {
+                CoreError::State(format!("unable to get ColumnFamily handle: {name}"))
+            })?;
+            self.db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+
+        Ok(())
+    }
+
+    // number of accounts currently backed up
+    pub fn backup_count(&self) -> Result<usize, CoreError> {
+        let backup_handle = self.db.cf_handle(&self.backup_account_cf).ok_or_else(|| {
+            CoreError::State("unable to get ColumnFamily handle in backup_count".to_string())
+        })?;
+
+        let mut count = 0;
+        for iter in self
+            .db
+            .iterator_cf(backup_handle, rocksdb::IteratorMode::Start)
+        {
+            iter.map_err(|e| {
+                CoreError::State(format!("unable to iterate through backup_account_cf, {e}"))
+            })?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
     pub fn clear_account_backups(&self) -> Result<(), CoreError> {
         // Iterate over all key-value pairs in the backup column family        // Get the handle for the backup column family
         let backup_handle = match self.db.cf_handle(&self.backup_account_cf) {
@@ -226,6 +465,14 @@ impl StateStorage {
     }
 }
 
+impl Drop for StateStorage {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            error!("unable to flush StateStorage on drop, {e}");
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,6 +501,53 @@ mod tests {
         assert_eq!(retrieved_account.balance, account.balance);
     }
 
+    #[test]
+    fn test_state_storage_durable_after_drop() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let address = Address::new(&[3u8; 20]);
+
+        let storage = StateStorage::new(db_path);
+        storage
+            .set_account(&address, &Account { balance: 42 })
+            .unwrap();
+        drop(storage);
+
+        let storage = StateStorage::new(db_path);
+        assert_eq!(storage.get_account(&address).unwrap().balance, 42);
+    }
+
+    #[test]
+    fn test_compact_after_rollbacks() {
+        let temp_dir = tempdir().unwrap();
+        let storage = StateStorage::new(temp_dir.path().to_str().unwrap());
+
+        let addresses: Vec<Address> = (0..10).map(|i| Address::new(&[i as u8; 20])).collect();
+        for (i, address) in addresses.iter().enumerate() {
+            storage
+                .set_account(address, &Account { balance: i as u64 })
+                .unwrap();
+        }
+
+        // each cycle writes then deletes a backup for every account
+        for cycle in 0..100 {
+            for address in &addresses {
+                let account = storage.get_account(address).unwrap();
+                storage.backup_account(address, &account).unwrap();
+                storage
+                    .set_account(address, &Account { balance: cycle })
+                    .unwrap();
+            }
+            storage.rollback_accounts().unwrap();
+        }
+
+        assert!(storage.compact().is_ok());
+
+        for (i, address) in addresses.iter().enumerate() {
+            assert_eq!(storage.get_account(address).unwrap().balance, i as u64);
+        }
+    }
+
     #[test]
     fn test_state_storage_get_nonexistent_account() {
         // Create a temporary directory for RocksDB
@@ -376,4 +670,33 @@ mod tests {
             .iterator_cf(backup_handle, rocksdb::IteratorMode::Start);
         assert!(backup_iter.next().is_none()); // Backup column family should be empty
     }
+
+    #[test]
+    fn test_backup_keeps_first_snapshot() {
+        let dir = tempdir().unwrap();
+        let state_storage = StateStorage::new(dir.path().to_str().unwrap());
+
+        let address = Address::new(&[1u8; 20]);
+        let missing = Address::new(&[2u8; 20]);
+
+        state_storage
+            .backup_account(&address, &Account { balance: 100 })
+            .unwrap();
+        state_storage
+            .backup_account(&address, &Account { balance: 50 })
+            .unwrap();
+        state_storage.backup_missing_account(&missing).unwrap();
+
+        state_storage
+            .set_account(&address, &Account { balance: 10 })
+            .unwrap();
+        state_storage
+            .set_account(&missing, &Account { balance: 10 })
+            .unwrap();
+
+        state_storage.rollback_accounts().unwrap();
+
+        assert_eq!(state_storage.get_account(&address).unwrap().balance, 100);
+        assert!(state_storage.get_account(&missing).is_none());
+    }
 }
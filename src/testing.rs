@@ -0,0 +1,522 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{mpsc::channel, Arc, Mutex},
+};
+
+use crate::{
+    core::{
+        block::Block, blockchain::Blockchain, encoding::ByteEncoding, error::CoreError,
+        height::BlockHeight, transaction::Transaction,
+    },
+    crypto::{address::Address, private_key::PrivateKey},
+    lock,
+    network::error::NetworkError,
+    network::{
+        gossip::{Gossip, DEFAULT_TX_FANOUT},
+        tcp::{TcpConfig, TcpController},
+        tx_pool::TxPool,
+        types::ArcMut,
+    },
+    rpc::{
+        controller::RpcController,
+        types::{RpcHeader, RpcResponse, RPC},
+    },
+    state::account::Account,
+    vm::validator::BlockValidator,
+};
+
+pub struct TestNode {
+    pub addr: SocketAddr,
+    pub private_key: PrivateKey,
+    pub chain: Arc<Mutex<Blockchain>>,
+    pub mem_pool: Arc<Mutex<TxPool>>,
+    pub validator: Arc<Mutex<BlockValidator>>,
+    pub rpc_controller: Arc<RpcController>,
+}
+
+impl TestNode {
+    fn new(private_key: PrivateKey) -> Self {
+        let chain = Arc::new(Mutex::new(
+            Blockchain::new_with_genesis_in_memory().unwrap(),
+        ));
+        let mem_pool = Arc::new(Mutex::new(TxPool::new()));
+        let validator = Arc::new(Mutex::new(BlockValidator::new(private_key.clone(), 10)));
+
+        // tcp controller is never started, it only gives the node an
+        // address, all messages go through the TestNetwork queue
+        let (tx, _rx) = channel();
+        let tcp = TcpController::new(
+            "127.0.0.1:0".parse().unwrap(),
+            TcpConfig::default(),
+            ArcMut::new(tx).inner,
+        )
+        .unwrap();
+        let addr = tcp.node_addr;
+
+        let rpc_controller = Arc::new(RpcController::new(
+            mem_pool.clone(),
+            validator.clone(),
+            chain.clone(),
+            Arc::new(Mutex::new(tcp)),
+        ));
+
+        Self {
+            addr,
+            private_key,
+            chain,
+            mem_pool,
+            validator,
+            rpc_controller,
+        }
+    }
+
+    pub fn height(&self) -> BlockHeight {
+        lock!(self.chain).height()
+    }
+
+    pub fn get_account(&self, address: &Address) -> Option<Account> {
+        lock!(self.chain).state().get_account(address)
+    }
+}
+
+// fully connected network of in memory nodes, messages are queued and only
+// delivered when deliver_all is called so tests run deterministically
+pub struct TestNetwork {
+    nodes: Vec<TestNode>,
+    // (from, to, rpc) waiting to be delivered
+    queue: VecDeque<(usize, usize, RPC)>,
+    // picks the peers each node forwards a tx to, like its TcpController
+    gossip: Vec<Gossip>,
+    // NewTx messages sent by each node
+    tx_sent: Vec<usize>,
+}
+
+impl TestNetwork {
+    pub fn new(n: usize) -> Self {
+        let nodes: Vec<TestNode> = (0..n).map(|_| TestNode::new(PrivateKey::new())).collect();
+
+        // seeded so the peers picked for a tx are the same every run
+        let gossip = (0..n)
+            .map(|i| Gossip::with_seed(DEFAULT_TX_FANOUT, i as u64))
+            .collect();
+        let network = Self {
+            nodes,
+            queue: VecDeque::new(),
+            gossip,
+            tx_sent: vec![0; n],
+        };
+
+        // every node accepts blocks from every other node and starts
+        // with an account for each validator to receive block rewards
+        for node in &network.nodes {
+            let address = node.private_key.address();
+            for other in &network.nodes {
+                lock!(other.validator).add_validator(address.clone());
+            }
+            network.fund(&address, 0);
+        }
+
+        network
+    }
+
+    pub fn node(&self, index: usize) -> &TestNode {
+        &self.nodes[index]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    // peers each node forwards a new tx to
+    pub fn set_tx_fanout(&mut self, fanout: usize) {
+        for gossip in &mut self.gossip {
+            gossip.set_fanout(fanout);
+        }
+    }
+
+    pub fn tx_sent(&self, index: usize) -> usize {
+        self.tx_sent[index]
+    }
+
+    pub fn heights(&self) -> Vec<BlockHeight> {
+        self.nodes.iter().map(|node| node.height()).collect()
+    }
+
+    // set the same account balance on every node, only call
+    // before blocks are produced so state roots stay in sync
+    pub fn fund(&self, address: &Address, balance: u64) {
+        for node in &self.nodes {
+            lock!(node.chain)
+                .state()
+                .set_account(address, &Account { balance })
+                .unwrap();
+        }
+    }
+
+    pub fn inject_tx(&self, index: usize, tx: Transaction) {
+        lock!(self.nodes[index].mem_pool).add(tx);
+    }
+
+    // submit tx to node as a client would, an admitted tx is
+    // gossiped to its peers
+    pub fn submit_tx(
+        &mut self,
+        index: usize,
+        tx: &Transaction,
+    ) -> Result<RpcResponse, NetworkError> {
+        let rpc = RPC {
+            header: RpcHeader::NewTx,
+            payload: tx.to_bytes()?,
+            id: None,
+        };
+
+        let res = self.nodes[index].rpc_controller.handle_client_rpc(&rpc)?;
+        if let RpcResponse::Transaction(_) = res {
+            self.gossip(index, &rpc, None);
+        }
+
+        Ok(res)
+    }
+
+    // propose a block on node from its mem_pool, add it to the node's
+    // chain and queue it for every peer
+    pub fn produce_block(&mut self, index: usize) -> Result<Block, CoreError> {
+        let block = {
+            let node = &self.nodes[index];
+            let validator = lock!(node.validator);
+            let mut mem_pool = lock!(node.mem_pool);
+            let mut chain = lock!(node.chain);
+            validator.produce_block(&mut chain, &mut mem_pool)?
+        };
+
+        let rpc = RPC {
+            header: RpcHeader::CommitBlock,
+            payload: block.to_bytes()?,
+            id: None,
+        };
+        self.broadcast(index, &rpc, None);
+
+        Ok(block)
+    }
+
+    // deliver queued messages until the network is quiet, accepted blocks are
+    // forwarded to all peers except the sender and txs are gossiped like
+    // RpcController does over tcp, returns the number of messages delivered
+    pub fn deliver_all(&mut self) -> usize {
+        let mut delivered = 0;
+
+        while let Some((from, to, rpc)) = self.queue.pop_front() {
+            delivered += 1;
+
+            let from_addr = self.nodes[from].addr;
+            let res = self.nodes[to]
+                .rpc_controller
+                .handle_rpc(&rpc, Some(from_addr));
+
+            match (&rpc.header, res) {
+                (RpcHeader::CommitBlock, Ok(RpcResponse::Ack(_))) => {
+                    self.broadcast(to, &rpc, Some(from));
+                }
+                (RpcHeader::NewTx, Ok(RpcResponse::Transaction(_))) => {
+                    self.gossip(to, &rpc, Some(from));
+                }
+                _ => {}
+            }
+        }
+
+        delivered
+    }
+
+    // queue rpc from one node to another, delivered by deliver_all
+    pub fn send(&mut self, from: usize, to: usize, rpc: &RPC) {
+        self.queue.push_back((from, to, rpc.clone()));
+    }
+
+    // send rpc from one node to another and return the reply directly,
+    // like request_rpc on a ChainNode without going through the queue
+    pub fn request(&self, from: usize, to: usize, rpc: &RPC) -> Result<RpcResponse, NetworkError> {
+        let from_addr = self.nodes[from].addr;
+        self.nodes[to]
+            .rpc_controller
+            .handle_rpc(rpc, Some(from_addr))
+    }
+
+    fn gossip(&mut self, from: usize, rpc: &RPC, ignore: Option<usize>) {
+        let peers: Vec<usize> = (0..self.nodes.len()).filter(|to| *to != from).collect();
+        let targets = self.gossip[from].targets(&peers, ignore);
+
+        self.tx_sent[from] += targets.len();
+        for to in targets {
+            self.queue.push_back((from, to, rpc.clone()));
+        }
+    }
+
+    fn broadcast(&mut self, from: usize, rpc: &RPC, ignore: Option<usize>) {
+        for to in 0..self.nodes.len() {
+            if to == from || Some(to) == ignore {
+                continue;
+            }
+            self.queue.push_back((from, to, rpc.clone()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::routes::transaction::GetTxReq;
+    use crate::core::transaction::TransferData;
+    use crate::crypto::hash::Hash;
+
+    fn transfer_tx(key: &PrivateKey, to: &Address, amount: u64, blockhash: Hash) -> Transaction {
+        let from = key.address();
+        let data = TransferData {
+            from: from.clone(),
+            to: to.clone(),
+            amount,
+        };
+        let mut tx =
+            Transaction::new_transfer(to.clone(), from, blockhash, &data.to_bytes().unwrap(), 1)
+                .unwrap();
+        tx.sign(key).unwrap();
+        tx
+    }
+
+    #[test]
+    fn test_block_propagation() {
+        let mut network = TestNetwork::new(3);
+
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+        network.fund(&receiver, 0);
+
+        let anchor = lock!(network.node(0).chain).tip().unwrap().hash;
+        let tx = transfer_tx(&sender, &receiver, 25, anchor);
+        let tx_hash = tx.hash().unwrap().to_string();
+        network.inject_tx(0, tx);
+        let block = network.produce_block(0).unwrap();
+
+        // node 0 sends to both peers, each peer forwards to the
+        // other which already has the block
+        assert_eq!(network.deliver_all(), 4);
+        assert_eq!(network.heights(), vec![1, 1, 1]);
+
+        for i in 0..network.len() {
+            let node = network.node(i);
+            let chain = lock!(node.chain);
+            assert_eq!(chain.last_block().unwrap().hash(), block.hash());
+            assert_eq!(chain.get_tx(&tx_hash).unwrap().1, 1);
+        }
+
+        // blocks from any node reach the rest of the network
+        network.produce_block(2).unwrap();
+        network.deliver_all();
+        assert_eq!(network.heights(), vec![2, 2, 2]);
+    }
+
+    #[test]
+    fn test_tx_propagation() {
+        let mut network = TestNetwork::new(2);
+
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+
+        let anchor = lock!(network.node(0).chain).tip().unwrap().hash;
+        // clients submit unsigned txs, the receiving node signs them
+        let data = TransferData {
+            from: sender.address(),
+            to: receiver.clone(),
+            amount: 25,
+        };
+        let tx = Transaction::new_transfer(
+            receiver,
+            sender.address(),
+            anchor,
+            &data.to_bytes().unwrap(),
+            1,
+        )
+        .unwrap();
+        let content_hash = tx.content_hash().unwrap();
+
+        let res = network.submit_tx(0, &tx).unwrap();
+        assert!(matches!(res, RpcResponse::Transaction(_)));
+
+        // node 1 has no other peer to forward to
+        assert_eq!(network.deliver_all(), 1);
+        for i in 0..network.len() {
+            let mem_pool = lock!(network.node(i).mem_pool);
+            assert_eq!(mem_pool.len(), 1);
+            assert!(mem_pool.has_content(&content_hash));
+        }
+
+        // already seen tx is not admitted or broadcast again
+        let res = network.submit_tx(1, &tx);
+        assert!(matches!(res, Err(NetworkError::RPC(_))));
+        assert_eq!(network.deliver_all(), 0);
+        assert_eq!(lock!(network.node(1).mem_pool).len(), 1);
+    }
+
+    #[test]
+    fn test_tx_gossip_fanout() {
+        let mut network = TestNetwork::new(8);
+        network.set_tx_fanout(4);
+
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+
+        let anchor = lock!(network.node(0).chain).tip().unwrap().hash;
+        let data = TransferData {
+            from: sender.address(),
+            to: receiver.clone(),
+            amount: 25,
+        };
+        let tx = Transaction::new_transfer(
+            receiver,
+            sender.address(),
+            anchor,
+            &data.to_bytes().unwrap(),
+            1,
+        )
+        .unwrap();
+        let content_hash = tx.content_hash().unwrap();
+
+        network.submit_tx(0, &tx).unwrap();
+        let delivered = network.deliver_all();
+
+        for i in 0..network.len() {
+            assert!(lock!(network.node(i).mem_pool).has_content(&content_hash));
+            assert!(network.tx_sent(i) <= 4, "node {i}: {}", network.tx_sent(i));
+        }
+
+        // broadcasting sends 7 messages from node 0 and 6 from each peer
+        assert!(delivered < 7 + 7 * 6, "{delivered}");
+    }
+
+    #[test]
+    fn test_seen_tx_dropped() {
+        let mut network = TestNetwork::new(3);
+
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+
+        let anchor = lock!(network.node(0).chain).tip().unwrap().hash;
+        let data = TransferData {
+            from: sender.address(),
+            to: receiver.clone(),
+            amount: 25,
+        };
+        let tx = Transaction::new_transfer(
+            receiver,
+            sender.address(),
+            anchor,
+            &data.to_bytes().unwrap(),
+            1,
+        )
+        .unwrap();
+        let rpc = RPC {
+            header: RpcHeader::NewTx,
+            payload: tx.to_bytes().unwrap(),
+            id: None,
+        };
+
+        // node 0 admits the tx from node 2 and gossips it back to node 1
+        network.send(0, 1, &rpc);
+        assert_eq!(network.deliver_all(), 4);
+        assert_eq!(lock!(network.node(1).mem_pool).len(), 1);
+        let sent = network.tx_sent(1);
+
+        // included in a block so the mem_pool no longer has it
+        network.produce_block(1).unwrap();
+        assert_eq!(lock!(network.node(1).mem_pool).len(), 0);
+
+        // the same tx from a peer is dropped before the mem_pool
+        network.send(0, 1, &rpc);
+        network.deliver_all();
+        assert_eq!(network.heights(), vec![1, 1, 1]);
+        assert_eq!(lock!(network.node(1).mem_pool).len(), 0);
+        assert_eq!(network.tx_sent(1), sent);
+    }
+
+    #[test]
+    fn test_rejected_copy_does_not_block_tx() {
+        let mut network = TestNetwork::new(2);
+        let sender = PrivateKey::new();
+        let receiver = PrivateKey::new().address();
+        network.fund(&sender.address(), 100);
+
+        let anchor = lock!(network.node(0).chain).tip().unwrap().hash;
+        let data = TransferData {
+            from: sender.address(),
+            to: receiver.clone(),
+            amount: 25,
+        };
+        let tx = Transaction::new_transfer(
+            receiver.clone(),
+            sender.address(),
+            anchor,
+            &data.to_bytes().unwrap(),
+            1,
+        )
+        .unwrap();
+        let content_hash = tx.content_hash().unwrap();
+
+        // same content with a signature over another tx
+        let attacker = PrivateKey::new();
+        let mut copy = tx.clone();
+        copy.sign(&attacker).unwrap();
+        copy.signature = transfer_tx(&attacker, &receiver, 1, anchor).signature;
+        assert_eq!(copy.content_hash().unwrap(), content_hash);
+        assert!(copy.verify().is_err());
+
+        let new_tx_rpc = |tx: &Transaction| RPC {
+            header: RpcHeader::NewTx,
+            payload: tx.to_bytes().unwrap(),
+            id: None,
+        };
+
+        network.send(0, 1, &new_tx_rpc(&copy));
+        network.deliver_all();
+        assert_eq!(lock!(network.node(1).mem_pool).len(), 0);
+
+        // the valid tx is still admitted after the copy was rejected
+        network.send(0, 1, &new_tx_rpc(&tx));
+        network.deliver_all();
+        assert!(lock!(network.node(1).mem_pool).has_content(&content_hash));
+    }
+
+    #[test]
+    fn test_get_tx_by_hash_from_peer() {
+        let network = TestNetwork::new(2);
+        let key = PrivateKey::new();
+        let genesis = lock!(network.node(1).chain).last_block().unwrap();
+        let tx = transfer_tx(&key, &Address::new(&[9_u8; 20]), 5, genesis.hash().clone());
+        let hash = tx.hash.unwrap();
+        network.inject_tx(1, tx.clone());
+
+        let rpc = RPC {
+            header: RpcHeader::GetTxByHash,
+            payload: bincode::serialize(&GetTxReq {
+                hash: hash.to_string(),
+            })
+            .unwrap(),
+            id: None,
+        };
+
+        match network.request(0, 1, &rpc) {
+            Ok(RpcResponse::Transaction(found)) => {
+                assert_eq!(found.hash, Some(hash));
+                assert_eq!(found.to_bytes().unwrap(), tx.to_bytes().unwrap());
+            }
+            res => panic!("expected transaction, got: {res:?}"),
+        }
+
+        // node 0 never saw the tx
+        let res = network.request(1, 0, &rpc);
+        assert!(matches!(res, Err(NetworkError::NotFound(_))), "{res:?}");
+    }
+}
@@ -0,0 +1,57 @@
+use std::{error::Error, fmt::Display};
+
+use crate::core::error::CoreError;
+
+// errors returned while executing transactions in the runtime
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    AccountNotFound(String),
+    InsufficientBalance { required: u64, available: u64 },
+    OutOfGas { required: u64, limit: u64 },
+    // transfers from one account in a block went over the spend cap
+    SpendCapExceeded { cap: u64, spent: u64 },
+    InvalidTxData(String),
+    UnsupportedTx(String),
+    State(String),
+}
+
+impl Error for VmError {}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::AccountNotFound(address) => write!(f, "account not found: {address}"),
+            Self::InsufficientBalance {
+                required,
+                available,
+            } => write!(
+                f,
+                "insufficient balance, required: {required}, available: {available}"
+            ),
+            Self::OutOfGas { required, limit } => {
+                write!(f, "out of gas, required: {required}, limit: {limit}")
+            }
+            Self::SpendCapExceeded { cap, spent } => {
+                write!(
+                    f,
+                    "spend cap exceeded, cap per block: {cap}, spent: {spent}"
+                )
+            }
+            Self::InvalidTxData(msg) => write!(f, "invalid transaction data: {msg}"),
+            Self::UnsupportedTx(msg) => write!(f, "unsupported transaction: {msg}"),
+            Self::State(msg) => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl From<CoreError> for VmError {
+    fn from(value: CoreError) -> Self {
+        VmError::State(value.to_string())
+    }
+}
+
+impl From<VmError> for CoreError {
+    fn from(value: VmError) -> Self {
+        CoreError::Vm(value.to_string())
+    }
+}
@@ -1,50 +1,232 @@
+use std::collections::BTreeMap;
+
 use crate::{
     core::{
-        encoding::ByteEncoding,
-        error::CoreError,
+        encoding::{ByteEncoding, HexEncoding},
         transaction::{BlockRewardData, Transaction, TransferData, TxType},
     },
-    state::manager::StateManager,
+    crypto::{address::Address, hash::Hash},
+    state::{account::Account, manager::StateManager},
+};
+
+use super::{
+    error::VmError,
+    types::{ExecutionMode, RuntimeExecData, TxExecution},
 };
 
-use super::types::RuntimeExecData;
+// gas charged for executing a transfer
+pub const TRANSFER_GAS_COST: u64 = 1;
+
+// amount each sender has transferred in the block being executed
+type BlockSpend = BTreeMap<Address, u64>;
 
-pub struct ValidatorRuntime;
+pub struct ValidatorRuntime {
+    // most an account may transfer out in a single block, None is no cap
+    spend_cap: Option<u64>,
+}
 
 impl ValidatorRuntime {
     pub fn new() -> Self {
-        Self
+        Self { spend_cap: None }
+    }
+
+    // every node must use the same cap, blocks are otherwise
+    // executed differently across the network
+    pub fn set_spend_cap(&mut self, cap: Option<u64>) {
+        self.spend_cap = cap;
+    }
+
+    pub fn spend_cap(&self) -> Option<u64> {
+        self.spend_cap
+    }
+
+    // returns true if state was changed, a dry run never changes state
+    pub fn execute(&self, exec_data: RuntimeExecData) -> Result<bool, VmError> {
+        let RuntimeExecData { tx, state, mode } = exec_data;
+
+        match mode {
+            ExecutionMode::Commit => {
+                self.apply(tx, state, &mut BlockSpend::new())?;
+                Ok(true)
+            }
+            ExecutionMode::DryRun => {
+                Self::begin_dry_run(state)?;
+                let res = self.apply(tx, state, &mut BlockSpend::new());
+                state.discard_batch();
+                res.map(|_| false)
+            }
+        }
+    }
+
+    // dry run all transactions in order and return the resulting
+    // state root, state is left unchanged
+    pub fn dry_run_block(
+        &self,
+        txs: &[Transaction],
+        state: &StateManager,
+    ) -> Result<Hash, VmError> {
+        Self::begin_dry_run(state)?;
+
+        let mut spent = BlockSpend::new();
+        let res = txs
+            .iter()
+            .try_for_each(|tx| self.apply(tx, state, &mut spent))
+            .and_then(|_| Ok(state.gen_state_root()?));
+
+        state.discard_batch();
+        res
+    }
+
+    // dry run txs in order and split them into txs which execute and txs
+    // which fail with their error, txs are applied exactly as a commit
+    // applies them so every executable tx also commits, a failed tx does
+    // not change the state seen by the txs after it, state is left unchanged
+    pub fn split_executable(
+        &self,
+        txs: Vec<Transaction>,
+        state: &StateManager,
+    ) -> Result<(Vec<Transaction>, Vec<(Transaction, VmError)>), VmError> {
+        Self::begin_dry_run(state)?;
+
+        let mut executable = vec![];
+        let mut failed = vec![];
+        let mut spent = BlockSpend::new();
+        for tx in txs {
+            match self.apply(&tx, state, &mut spent) {
+                Ok(_) => executable.push(tx),
+                Err(e) => failed.push((tx, e)),
+            }
+        }
+
+        state.discard_batch();
+        Ok((executable, failed))
     }
 
-    pub fn execute(&self, exec_data: RuntimeExecData) -> Result<(), CoreError> {
-        let RuntimeExecData { tx, state, backup } = exec_data;
+    // dry run txs in order recording the outcome of each, a failed tx
+    // does not change the state seen by the txs after it, returns the
+    // outcomes with the resulting state root, state is left unchanged
+    pub fn trace_block(
+        &self,
+        txs: &[Transaction],
+        state: &StateManager,
+    ) -> Result<(Vec<TxExecution>, Hash), VmError> {
+        Self::begin_dry_run(state)?;
+
+        let mut spent = BlockSpend::new();
+        let results = txs
+            .iter()
+            .map(|tx| match self.apply(tx, state, &mut spent) {
+                Ok(_) => TxExecution {
+                    hash: tx.hash,
+                    gas_used: self.gas_used(tx),
+                    error: None,
+                },
+                Err(e) => TxExecution {
+                    hash: tx.hash,
+                    gas_used: 0,
+                    error: Some(e.to_string()),
+                },
+            })
+            .collect();
+        let state_root = state.gen_state_root();
+
+        state.discard_batch();
+        Ok((results, state_root?))
+    }
 
+    // gas consumed by executing tx, the fee for the rest of the gas
+    // limit is refunded
+    pub fn gas_used(&self, tx: &Transaction) -> u64 {
+        match tx.tx_type {
+            TxType::Transfer => TRANSFER_GAS_COST,
+            TxType::BlockReward | TxType::GasReward | TxType::SmartContract => 0,
+        }
+    }
+
+    // execute all block transactions, when batch is set account writes
+    // are buffered and flushed to storage once at the end of the block
+    pub fn execute_block(
+        &self,
+        txs: &[Transaction],
+        state: &StateManager,
+        batch: bool,
+    ) -> Result<(), VmError> {
+        if !batch {
+            let mut spent = BlockSpend::new();
+            for tx in txs {
+                self.apply(tx, state, &mut spent)?;
+            }
+            return Ok(());
+        }
+
+        self.stage_block(txs, state)?;
+        Ok(state.commit_batch()?)
+    }
+
+    // execute all block transactions into an open state batch which the
+    // caller commits, the batch is discarded if a transaction fails
+    pub fn stage_block(&self, txs: &[Transaction], state: &StateManager) -> Result<(), VmError> {
+        state.begin_batch();
+
+        let mut spent = BlockSpend::new();
+        for tx in txs {
+            if let Err(e) = self.apply(tx, state, &mut spent) {
+                state.discard_batch();
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        tx: &Transaction,
+        state: &StateManager,
+        spent: &mut BlockSpend,
+    ) -> Result<(), VmError> {
         match tx.tx_type {
             TxType::BlockReward | TxType::GasReward => {
-                let data = BlockRewardData::from_bytes(&tx.data)?;
-                self.execute_block_reward(data, state, backup)
+                let data = BlockRewardData::from_bytes(&tx.data)
+                    .map_err(|e| VmError::InvalidTxData(e.to_string()))?;
+                self.execute_block_reward(data, state)
             }
             TxType::Transfer => {
-                let data = TransferData::from_bytes(&tx.data)?;
-                self.execute_transfer(data, state, backup)
+                if tx.gas_limit < TRANSFER_GAS_COST {
+                    return Err(VmError::OutOfGas {
+                        required: TRANSFER_GAS_COST,
+                        limit: tx.gas_limit,
+                    });
+                }
+                let data = TransferData::from_bytes(&tx.data)
+                    .map_err(|e| VmError::InvalidTxData(e.to_string()))?;
+                let (max_fee, fee) = (tx.max_fee(), tx.fee(self.gas_used(tx)));
+                self.execute_transfer(data, max_fee, fee, state, spent)
             }
-            _ => todo!(),
+            TxType::SmartContract => Err(VmError::UnsupportedTx(
+                "smart contract execution is not implemented".to_string(),
+            )),
         }
     }
 
+    // a dry run owns the state batch, it can not share a batch
+    // opened by a commit otherwise its writes would be committed
+    fn begin_dry_run(state: &StateManager) -> Result<(), VmError> {
+        if state.is_batching() {
+            return Err(VmError::State(
+                "unable to dry run while a state batch is open".to_string(),
+            ));
+        }
+        state.begin_batch();
+        Ok(())
+    }
+
     fn execute_block_reward(
         &self,
         data: BlockRewardData,
         state: &StateManager,
-        backup: bool,
-    ) -> Result<(), CoreError> {
-        if backup {
-            state.backup_account(&data.to)?;
-        }
-
-        let mut to_account = state
-            .get_account(&data.to)
-            .ok_or_else(|| CoreError::State("account not found".to_string()))?;
+    ) -> Result<(), VmError> {
+        let mut to_account = Self::get_or_new_account(state, &data.to);
 
         to_account.balance += data.amount;
 
@@ -56,51 +238,315 @@ impl ValidatorRuntime {
     fn execute_transfer(
         &self,
         data: TransferData,
+        max_fee: u64,
+        fee: u64,
         state: &StateManager,
-        backup: bool,
-    ) -> Result<(), CoreError> {
-        if backup {
-            state.backup_account(&data.from)?;
-            state.backup_account(&data.to)?;
+        spent: &mut BlockSpend,
+    ) -> Result<(), VmError> {
+        let block_spend = spent
+            .get(&data.from)
+            .copied()
+            .unwrap_or(0)
+            .saturating_add(data.amount);
+        if let Some(cap) = self.spend_cap {
+            if block_spend > cap {
+                return Err(VmError::SpendCapExceeded {
+                    cap,
+                    spent: block_spend,
+                });
+            }
         }
 
-        let mut from_account = state
-            .get_account(&data.from)
-            .ok_or_else(|| CoreError::State("account not found".to_string()))?;
-        let mut to_account = state
-            .get_account(&data.to)
-            .ok_or_else(|| CoreError::State("account not found".to_string()))?;
+        let mut from_account = Self::get_account(state, &data.from)?;
+        let mut to_account = Self::get_or_new_account(state, &data.to);
 
-        if from_account.balance < data.amount {
-            return Err(CoreError::State("Insufficient balance".to_string()));
+        // sender must cover the fee for the full gas limit up front
+        let required = data.amount.saturating_add(max_fee);
+        if from_account.balance < required {
+            return Err(VmError::InsufficientBalance {
+                required,
+                available: from_account.balance,
+            });
         }
 
-        from_account.balance -= data.amount;
+        // debit amount and max fee then refund the fee for unused gas
+        from_account.balance -= required;
+        from_account.balance += max_fee - fee;
         to_account.balance += data.amount;
 
         state.set_account(&data.from, &from_account)?;
         state.set_account(&data.to, &to_account)?;
+        spent.insert(data.from, block_spend);
 
         Ok(())
     }
+
+    // same in every execution mode so a dry run and a commit of the
+    // same txs always agree, a sender must already exist
+    fn get_account(state: &StateManager, address: &Address) -> Result<Account, VmError> {
+        state.get_account(address).ok_or_else(|| {
+            VmError::AccountNotFound(address.to_hex().unwrap_or_else(|_| "unknown".to_string()))
+        })
+    }
+
+    // receivers which do not exist yet start as a new blank account
+    fn get_or_new_account(state: &StateManager, address: &Address) -> Account {
+        state.get_account(address).unwrap_or_else(Account::new)
+    }
 }
 
-// TODO: runtime tests
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        core::transaction::SmartContractData,
+        crypto::address::{random_sender_receiver, Address},
+        crypto::utils::random_hash,
+        state::account::Account,
+    };
     use std::str;
     use tempfile::tempdir;
 
-    // #[test]
-    // fn test_execute_transfer_success() {
-    // }
+    fn transfer_tx(from: &Address, to: &Address, amount: u64) -> Transaction {
+        let data = TransferData {
+            from: from.clone(),
+            to: to.clone(),
+            amount,
+        };
+        Transaction::new_transfer(
+            to.clone(),
+            from.clone(),
+            random_hash(),
+            &data.to_bytes().unwrap(),
+            1,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_execute_block_batch_matches_unbatched() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+
+        let txs = vec![
+            transfer_tx(&a, &b, 30),
+            transfer_tx(&b, &a, 10),
+            transfer_tx(&a, &b, 5),
+        ];
+
+        let batched = StateManager::new_in_memory();
+        let unbatched = StateManager::new_in_memory();
+
+        for state in [&batched, &unbatched] {
+            state.set_account(&a, &Account { balance: 100 }).unwrap();
+            state.set_account(&b, &Account { balance: 0 }).unwrap();
+        }
+
+        runtime.execute_block(&txs, &batched, true).unwrap();
+        runtime.execute_block(&txs, &unbatched, false).unwrap();
+
+        assert!(!batched.is_batching());
+        assert_eq!(batched.get_account(&a), unbatched.get_account(&a));
+        assert_eq!(batched.get_account(&b), unbatched.get_account(&b));
+        // each transfer is charged TRANSFER_GAS_COST
+        assert_eq!(batched.get_account(&a).unwrap().balance, 73);
+        assert_eq!(batched.get_account(&b).unwrap().balance, 24);
+    }
+
+    #[test]
+    fn test_execute_block_batch_failure_writes_nothing() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&a, &Account { balance: 10 }).unwrap();
+        state.set_account(&b, &Account { balance: 0 }).unwrap();
+
+        let txs = vec![transfer_tx(&a, &b, 5), transfer_tx(&a, &b, 50)];
+
+        assert!(runtime.execute_block(&txs, &state, true).is_err());
+        assert!(!state.is_batching());
+        assert_eq!(state.get_account(&a).unwrap().balance, 10);
+        assert_eq!(state.get_account(&b).unwrap().balance, 0);
+    }
+
+    #[test]
+    fn test_execute_transfer_insufficient_balance() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&a, &Account { balance: 10 }).unwrap();
+        state.set_account(&b, &Account { balance: 0 }).unwrap();
+
+        let tx = transfer_tx(&a, &b, 50);
+        let res = runtime.execute(RuntimeExecData::new(&tx, &state));
+
+        assert_eq!(
+            res,
+            Err(VmError::InsufficientBalance {
+                required: 51,
+                available: 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_spend_cap_per_block() {
+        let mut runtime = ValidatorRuntime::new();
+        runtime.set_spend_cap(Some(50));
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&a, &Account { balance: 100 }).unwrap();
+        state.set_account(&b, &Account { balance: 0 }).unwrap();
+
+        // within the cap across the block
+        let txs = vec![transfer_tx(&a, &b, 20), transfer_tx(&a, &b, 30)];
+        runtime.execute_block(&txs, &state, true).unwrap();
+        assert_eq!(state.get_account(&b).unwrap().balance, 50);
 
-    // #[test]
-    // fn test_execute_transfer_insufficient_balance() {
-    // }
+        // the cap applies to each block on its own
+        let txs = vec![transfer_tx(&a, &b, 20), transfer_tx(&a, &b, 31)];
+        assert_eq!(
+            runtime.execute_block(&txs, &state, true),
+            Err(VmError::SpendCapExceeded { cap: 50, spent: 51 })
+        );
+        assert_eq!(state.get_account(&a).unwrap().balance, 48);
+        assert_eq!(state.get_account(&b).unwrap().balance, 50);
 
-    // #[test]
-    // fn test_execute_transfer_account_not_found() {
-    // }
+        // the transfer over the cap is left out of a proposal, others
+        // from the sender and transfers from other accounts are kept
+        let txs = vec![
+            transfer_tx(&a, &b, 40),
+            transfer_tx(&a, &b, 11),
+            transfer_tx(&b, &a, 50),
+        ];
+        let (executable, failed) = runtime.split_executable(txs, &state).unwrap();
+        assert_eq!(executable.len(), 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(
+            failed[0].1,
+            VmError::SpendCapExceeded { cap: 50, spent: 51 }
+        );
+    }
+
+    #[test]
+    fn test_execute_transfer_refunds_unused_gas() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&a, &Account { balance: 100 }).unwrap();
+        state.set_account(&b, &Account { balance: 0 }).unwrap();
+
+        let mut tx = transfer_tx(&a, &b, 20);
+        tx.gas_limit = 10;
+        runtime.execute(RuntimeExecData::new(&tx, &state)).unwrap();
+
+        assert_eq!(runtime.gas_used(&tx), TRANSFER_GAS_COST);
+        assert_eq!(
+            state.get_account(&a).unwrap().balance,
+            100 - 20 - TRANSFER_GAS_COST
+        );
+        assert_eq!(state.get_account(&b).unwrap().balance, 20);
+    }
+
+    #[test]
+    fn test_execute_dry_run_leaves_state_unchanged() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&a, &Account { balance: 100 }).unwrap();
+        let root = state.gen_state_root().unwrap();
+
+        let tx = transfer_tx(&a, &b, 20);
+        let changed = runtime
+            .execute(RuntimeExecData::new_dry_run(&tx, &state))
+            .unwrap();
+
+        assert!(!changed);
+        assert!(!state.is_batching());
+        assert_eq!(state.get_account(&a).unwrap().balance, 100);
+        assert!(state.get_account(&b).is_none());
+        assert_eq!(state.gen_state_root().unwrap(), root);
+
+        // dry run block root matches the root after committing
+        let dry_root = runtime.dry_run_block(&[tx.clone()], &state).unwrap();
+        assert_eq!(state.gen_state_root().unwrap(), root);
+
+        // commit creates the missing receiver same as the dry run
+        let changed = runtime.execute(RuntimeExecData::new(&tx, &state)).unwrap();
+
+        assert!(changed);
+        assert_eq!(
+            state.get_account(&a).unwrap().balance,
+            100 - 20 - TRANSFER_GAS_COST
+        );
+        assert_eq!(state.get_account(&b).unwrap().balance, 20);
+        assert_eq!(state.gen_state_root().unwrap(), dry_root);
+    }
+
+    #[test]
+    fn test_execute_transfer_account_not_found() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&b, &Account { balance: 10 }).unwrap();
+
+        // a missing sender fails the same way in both modes
+        let tx = transfer_tx(&a, &b, 5);
+        let not_found = Err(VmError::AccountNotFound(a.to_hex().unwrap()));
+        let res = runtime.execute(RuntimeExecData::new_dry_run(&tx, &state));
+        assert_eq!(res, not_found.clone());
+        let res = runtime.execute(RuntimeExecData::new(&tx, &state));
+        assert_eq!(res, not_found);
+    }
+
+    #[test]
+    fn test_execute_transfer_out_of_gas() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        state.set_account(&a, &Account { balance: 10 }).unwrap();
+        state.set_account(&b, &Account { balance: 0 }).unwrap();
+
+        let mut tx = transfer_tx(&a, &b, 5);
+        tx.gas_limit = 0;
+        let res = runtime.execute(RuntimeExecData::new(&tx, &state));
+
+        assert_eq!(
+            res,
+            Err(VmError::OutOfGas {
+                required: TRANSFER_GAS_COST,
+                limit: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_execute_invalid_and_unsupported_tx() {
+        let runtime = ValidatorRuntime::new();
+        let (a, b) = random_sender_receiver();
+        let state = StateManager::new_in_memory();
+
+        let tx =
+            Transaction::new_transfer(b.clone(), a.clone(), random_hash(), &[1, 2], 1).unwrap();
+        let res = runtime.execute(RuntimeExecData::new(&tx, &state));
+        assert!(matches!(res, Err(VmError::InvalidTxData(_))));
+
+        let data = SmartContractData {
+            contract_address: b.clone(),
+            method: "call".to_string(),
+            params: vec![],
+        }
+        .to_bytes()
+        .unwrap();
+        let tx = Transaction::new(TxType::SmartContract, random_hash(), b, a, &data, 1).unwrap();
+        let res = runtime.execute(RuntimeExecData::new(&tx, &state));
+        assert!(matches!(res, Err(VmError::UnsupportedTx(_))));
+    }
 }
@@ -1,9 +1,20 @@
-use crate::{core::transaction::Transaction, state::manager::StateManager};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{core::transaction::Transaction, crypto::hash::Hash, state::manager::StateManager};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExecutionMode {
+    // account writes go to state, or to the open state batch
+    Commit,
+    // account writes are buffered in a batch which is always
+    // discarded, state is never changed
+    DryRun,
+}
 
 pub struct RuntimeExecData<'a> {
     pub tx: &'a Transaction,
     pub state: &'a StateManager,
-    pub backup: bool,
+    pub mode: ExecutionMode,
 }
 
 impl<'a> RuntimeExecData<'a> {
@@ -11,15 +22,33 @@ impl<'a> RuntimeExecData<'a> {
         Self {
             tx,
             state,
-            backup: false,
+            mode: ExecutionMode::Commit,
         }
     }
 
-    pub fn new_with_backup(tx: &'a Transaction, state: &'a StateManager) -> Self {
+    pub fn new_dry_run(tx: &'a Transaction, state: &'a StateManager) -> Self {
         Self {
             tx,
             state,
-            backup: true,
+            mode: ExecutionMode::DryRun,
         }
     }
 }
+
+// outcome of dry running a single transaction, error is None when it executed
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct TxExecution {
+    pub hash: Option<Hash>,
+    pub gas_used: u64,
+    pub error: Option<String>,
+}
+
+// dry run of every transaction in a block against the current state,
+// roots are computed from the block txs and the resulting state
+#[derive(Debug, Clone, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct BlockExecution {
+    pub txs: Vec<TxExecution>,
+    pub state_root: Hash,
+    pub tx_root: Hash,
+    pub poh: Hash,
+}
@@ -1,17 +1,28 @@
 use core::time;
+use std::collections::HashSet;
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
+use borsh::{BorshDeserialize, BorshSerialize};
 use log::{error, info, warn};
+use rayon::{ThreadPool, ThreadPoolBuilder};
 
-use crate::core::blockchain::Blockchain;
-use crate::core::encoding::ByteEncoding;
+use crate::core::blockchain::{read_block_record, Blockchain};
+use crate::core::encoding::{ByteEncoding, HexEncoding};
 use crate::core::error::CoreError;
+use crate::core::height::BlockHeight;
 
 use crate::core::header::random_header;
 use crate::core::transaction::{BlockRewardData, TxType};
+use crate::crypto::address::Address;
 use crate::crypto::hash::Hash;
+use crate::crypto::public_key::PublicKey;
+use crate::crypto::scheme::SignatureScheme;
 use crate::lock;
+use crate::network::tx_pool::TxPool;
 use crate::network::types::ArcMut;
 use crate::{
     core::{block::Block, header::Header, transaction::Transaction},
@@ -20,28 +31,260 @@ use crate::{
 };
 
 use super::runtime::ValidatorRuntime;
-use super::types::RuntimeExecData;
+
+// default number of threads used to verify transaction signatures
+pub const DEFAULT_VERIFY_THREADS: usize = 4;
+
+// number of most recent blocks a transaction blockhash may reference
+pub const MAX_TX_BLOCKHASH_AGE: usize = 150;
+
+// how long after the chain tip was produced a competing block at the
+// same height may still replace it
+pub const DEFAULT_FORK_GRACE: Duration = Duration::from_secs(10);
+
+// where a block being validated came from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockSource {
+    Peer,
+    Sync(SocketAddr),
+}
+
+// blocks this validator proposed and how many of those were added
+// to or rejected by the chain
+#[derive(Debug, Clone, Copy, Default, PartialEq, BorshSerialize, BorshDeserialize)]
+pub struct BlockMetrics {
+    pub proposed: u64,
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+#[derive(Debug, Default)]
+struct BlockCounters {
+    proposed: AtomicU64,
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+// pick a validator from seed, the set is sorted first so every node
+// picks the same address whatever order it learned the validators in,
+// every validator is equally likely, there is no stake weighting, and
+// there is no fallback leader, the chain stalls at a height for as long
+// as its leader is offline
+pub fn select_leader(validators: &[Address], seed: &Hash) -> Option<Address> {
+    let mut sorted = validators.to_vec();
+    sorted.sort();
+
+    let mut index = [0_u8; 8];
+    index.copy_from_slice(&seed[..8]);
+    let index = u64::from_le_bytes(index) % sorted.len().max(1) as u64;
+
+    sorted.get(index as usize).cloned()
+}
+
+// fork choice between two valid blocks at the same height on the same
+// parent, the lower block hash is preferred so every node keeps the same
+// block whichever arrived first
+pub fn prefers_block(current: &Header, candidate: &Header) -> bool {
+    candidate.hash() < current.hash()
+}
 
 pub struct BlockValidator {
     private_key: PrivateKey,
     runtime: ValidatorRuntime,
+    // addresses allowed to produce blocks, always includes own address
+    validator_set: Vec<Address>,
+    // when set, blocks synced from this source skip transaction
+    // signature checks, never applies to BlockSource::Peer
+    trusted_sync_source: Option<SocketAddr>,
+    // dedicated pool for transaction signature checks so block
+    // verification does not compete with the global rayon pool
+    verify_pool: ThreadPool,
+    // fraction of collected gas fees which is not paid to the block
+    // producer, burned fees are removed from supply
+    burn_ratio: f64,
+    // signature schemes accepted for blocks and transactions
+    allowed_schemes: Vec<SignatureScheme>,
+    // counted in propose_block and add_proposed_block
+    counters: BlockCounters,
+    // competing blocks for the tip height are only considered while the
+    // tip is younger than this
+    fork_grace: Duration,
     pub pool_size: usize,
 }
 
 impl BlockValidator {
     pub fn new(private_key: PrivateKey, pool_size: usize) -> Self {
+        let validator_set = vec![private_key.address()];
         Self {
             private_key,
             pool_size,
+            validator_set,
+            trusted_sync_source: None,
+            verify_pool: build_verify_pool(DEFAULT_VERIFY_THREADS).unwrap(),
+            burn_ratio: 0.0,
+            allowed_schemes: vec![SignatureScheme::Secp256k1],
+            counters: BlockCounters::default(),
+            fork_grace: DEFAULT_FORK_GRACE,
             runtime: ValidatorRuntime::new(),
         }
     }
 
+    pub fn metrics(&self) -> BlockMetrics {
+        BlockMetrics {
+            proposed: self.counters.proposed.load(Ordering::Relaxed),
+            accepted: self.counters.accepted.load(Ordering::Relaxed),
+            rejected: self.counters.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    // every validator on the network must use the same ratio, blocks
+    // paying a different gas reward are rejected
+    pub fn set_burn_ratio(&mut self, ratio: f64) -> Result<(), CoreError> {
+        if !(0.0..=1.0).contains(&ratio) {
+            return Err(CoreError::Block(format!(
+                "burn ratio: {ratio} must be between 0 and 1"
+            )));
+        }
+        self.burn_ratio = ratio;
+        Ok(())
+    }
+
+    pub fn burn_ratio(&self) -> f64 {
+        self.burn_ratio
+    }
+
+    // pinned by the genesis config so every validator agrees
+    pub fn set_allowed_schemes(&mut self, schemes: Vec<SignatureScheme>) {
+        self.allowed_schemes = schemes;
+    }
+
+    // must match the cap of the chain blocks are committed to
+    pub fn set_spend_cap(&mut self, cap: Option<u64>) {
+        self.runtime.set_spend_cap(cap);
+    }
+
+    // block and transaction signatures must use an allowed scheme
+    pub fn validate_signature_schemes(&self, block: &Block) -> Result<(), CoreError> {
+        let block_schemes = block
+            .signer()
+            .map(|signer| signer.scheme())
+            .into_iter()
+            .chain(block.signature().map(|sig| sig.scheme()));
+        let tx_schemes = block.txs().iter().flat_map(|tx| {
+            tx.signer
+                .as_ref()
+                .map(|signer| signer.scheme())
+                .into_iter()
+                .chain(tx.signature.as_ref().map(|sig| sig.scheme()))
+        });
+
+        for scheme in block_schemes.chain(tx_schemes) {
+            if !self.allowed_schemes.contains(&scheme) {
+                return Err(CoreError::Block(format!(
+                    "signature scheme: {scheme:?} is not allowed on this chain"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn set_verify_threads(&mut self, threads: usize) -> Result<(), CoreError> {
+        self.verify_pool = build_verify_pool(threads)?;
+        Ok(())
+    }
+
+    pub fn verify_threads(&self) -> usize {
+        self.verify_pool.current_num_threads()
+    }
+
+    // verify block and transaction signatures on the verify pool
+    pub fn verify_block(&self, block: &Block) -> Result<(), CoreError> {
+        block.verify_parallel(&self.verify_pool)
+    }
+
+    // enable trust mode for a single sync source, None disables it
+    pub fn set_trust_mode(&mut self, source: Option<SocketAddr>) {
+        self.trusted_sync_source = source;
+    }
+
+    pub fn set_fork_grace(&mut self, grace: Duration) {
+        self.fork_grace = grace;
+    }
+
+    pub fn is_trusted(&self, source: BlockSource) -> bool {
+        match (source, self.trusted_sync_source) {
+            (BlockSource::Sync(addr), Some(trusted)) => addr == trusted,
+            _ => false,
+        }
+    }
+
+    pub fn add_validator(&mut self, address: Address) {
+        if !self.validator_set.contains(&address) {
+            self.validator_set.push(address);
+        }
+    }
+
+    // validator chosen to propose the block at height
+    pub fn leader_for(
+        &self,
+        chain: &Blockchain,
+        height: BlockHeight,
+    ) -> Result<Address, CoreError> {
+        let seed = chain.leader_seed(height)?;
+        select_leader(&self.validator_set, &seed)
+            .ok_or_else(|| CoreError::Block("validator set is empty".to_string()))
+    }
+
+    // true if this validator should propose the block after the tip
+    pub fn is_leader(&self, chain: &Blockchain) -> Result<bool, CoreError> {
+        Ok(self.leader_for(chain, chain.height().next())? == self.private_key.address())
+    }
+
+    pub fn is_validator(&self, address: &Address) -> bool {
+        self.validator_set.contains(address)
+    }
+
+    // check the block was signed by a member of the validator set
+    pub fn validate_block_signer(&self, block: &Block) -> Result<(), CoreError> {
+        let signer = block
+            .signer()
+            .ok_or_else(|| CoreError::Block("block has no signer".to_string()))?;
+        let address = PublicKey::from_bytes(&signer.to_bytes()?)?.address()?;
+
+        if !self.is_validator(&address) {
+            return Err(CoreError::Block(format!(
+                "block signer: {} is not in the validator set",
+                address.to_hex()?
+            )));
+        }
+
+        Ok(())
+    }
+
     pub fn validate_block(
         &self,
         chain: &MutexGuard<Blockchain>,
         block: &Block,
     ) -> Result<(), CoreError> {
+        self.validate_block_from(chain, block, BlockSource::Peer)
+    }
+
+    // roots, links and state are always checked, transaction signatures
+    // are skipped only for blocks from the trusted sync source
+    pub fn validate_block_from(
+        &self,
+        chain: &MutexGuard<Blockchain>,
+        block: &Block,
+        source: BlockSource,
+    ) -> Result<(), CoreError> {
+        // genesis is built from the genesis config, never received
+        if block.height() == BlockHeight::GENESIS {
+            return Err(CoreError::Block(
+                "genesis block comes from the genesis config and is not validated".to_string(),
+            ));
+        }
+
         // Check if the block is already in the blockchain
         if chain.has_block(block.height()) {
             return Err(CoreError::Block(
@@ -66,6 +309,77 @@ impl BlockValidator {
             ));
         }
 
+        self.validate_block_contents(chain, block, source)
+    }
+
+    // a block at the tip height on the same parent as the tip, received
+    // shortly after the tip was produced, is checked like a new block and
+    // must be preferred by fork choice to replace the tip
+    pub fn validate_competing_block(
+        &self,
+        chain: &MutexGuard<Blockchain>,
+        block: &Block,
+    ) -> Result<(), CoreError> {
+        let tip = chain.tip_header().ok_or(CoreError::Block(
+            "unable to get last block from chain".to_string(),
+        ))?;
+
+        if block.height() == BlockHeight::GENESIS || block.height() != tip.height() {
+            return Err(CoreError::Block(format!(
+                "competing block height: {} is not the chain tip height: {}",
+                block.height(),
+                tip.height()
+            )));
+        }
+
+        if block.hash() == &tip.hash() {
+            return Err(CoreError::Block(
+                "Blockchain already contains block".to_string(),
+            ));
+        }
+
+        if block.header().prev_hash() != tip.prev_hash() {
+            return Err(CoreError::Block(
+                "competing block does not share a parent with the chain tip".to_string(),
+            ));
+        }
+
+        // measured from local receipt, the tip timestamp is set by its proposer
+        let received = chain.tip_received().ok_or(CoreError::Block(
+            "chain tip was not received by this node".to_string(),
+        ))?;
+        if received.elapsed() > self.fork_grace {
+            return Err(CoreError::Block(format!(
+                "chain tip at height: {} was received longer ago than the fork grace period",
+                tip.height()
+            )));
+        }
+
+        if !prefers_block(&tip, block.header()) {
+            return Err(CoreError::Block(format!(
+                "competing block at height: {} is not preferred over the chain tip",
+                block.height()
+            )));
+        }
+
+        self.validate_block_contents(chain, block, BlockSource::Peer)
+    }
+
+    // checks which do not depend on where the block links into the chain
+    fn validate_block_contents(
+        &self,
+        chain: &MutexGuard<Blockchain>,
+        block: &Block,
+        source: BlockSource,
+    ) -> Result<(), CoreError> {
+        // produced blocks always hold the reward txs, empty roots are
+        // only valid for genesis
+        if block.txs().is_empty() {
+            return Err(CoreError::Block(
+                "block has no transactions, only the genesis block may be empty".to_string(),
+            ));
+        }
+
         // Verify the proof of history (PoH) if applicable
         if block.header().poh != Header::gen_poh(block.txs())? {
             return Err(CoreError::Block(
@@ -78,54 +392,134 @@ impl BlockValidator {
             return Err(CoreError::Block("Transaction root is invalid".to_string()));
         }
 
-        // Execute and validate all transactions in the block
-        let state = chain.state();
+        self.validate_signature_schemes(block)?;
+
+        // Reject blocks which contain the same transaction more than once
+        Block::check_duplicate_txs(block.txs())?;
+
+        // Transactions must be anchored to a recent block
+        let recent: HashSet<Hash> = chain
+            .recent_blockhashes(MAX_TX_BLOCKHASH_AGE)
+            .into_iter()
+            .collect();
         for tx in block.txs() {
-            let exec_data = RuntimeExecData::new_with_backup(tx, state);
-            self.runtime.execute(exec_data)?;
+            if !recent.contains(&tx.blockhash) {
+                return Err(CoreError::Block(format!(
+                    "transaction blockhash: {} is not a recent block",
+                    tx.blockhash
+                )));
+            }
         }
 
-        // Verify the state root after applying all transactions
-        let state_root = state.gen_state_root()?;
+        // Dry run all transactions and verify the resulting state root
+        let state_root = self.runtime.dry_run_block(block.txs(), chain.state())?;
         if block.header().state_root != state_root {
             return Err(CoreError::Block("State root is invalid".to_string()));
         }
 
-        // Revert the state after validation
-        state.rollback()?;
+        // rewards are applied like any other tx so a block paying them to
+        // another address can still have a matching state root
+        Self::validate_reward_recipients(block)?;
+        self.validate_fee_reward(block)?;
+
+        if self.is_trusted(source) {
+            return block.verify_signature();
+        }
+
+        self.verify_block(block)
+    }
+
+    // apply blocks written by Blockchain.export_to_writer to a chain which
+    // only holds genesis, the exported genesis must match ours and every
+    // following block is validated like a block from a peer before it is
+    // committed, returns the number of blocks imported after genesis
+    pub fn import_from_reader<R: Read>(
+        &self,
+        chain: &mut MutexGuard<Blockchain>,
+        r: &mut R,
+    ) -> Result<usize, CoreError> {
+        if chain.height() != BlockHeight::GENESIS {
+            return Err(CoreError::Block(
+                "blocks can only be imported into a fresh chain".to_string(),
+            ));
+        }
 
-        block.verify()
+        let genesis = match read_block_record(r)? {
+            Some(bytes) => Block::from_bytes(&bytes)?,
+            None => return Err(CoreError::Block("import stream is empty".to_string())),
+        };
+        let stored = chain
+            .get_block_by_height(BlockHeight::GENESIS)
+            .ok_or_else(|| CoreError::Block("chain has no genesis block".to_string()))?;
+        if genesis.hash() != stored.hash() {
+            return Err(CoreError::Block(format!(
+                "imported genesis: {} does not match chain genesis: {}",
+                genesis.hash(),
+                stored.hash()
+            )));
+        }
+
+        let mut imported = 0;
+        while let Some(bytes) = read_block_record(r)? {
+            let block = Block::from_bytes(&bytes)?;
+            self.validate_block_signer(&block)?;
+            self.validate_block(chain, &block)?;
+            chain.commit_block(block)?;
+            imported += 1;
+        }
+
+        Ok(imported)
     }
 
     pub fn propose_block(
         &self,
         chain: &MutexGuard<Blockchain>,
-        mut txs: Vec<Transaction>,
+        txs: Vec<Transaction>,
     ) -> Result<Block, CoreError> {
-        let last_block = chain.last_block().ok_or(CoreError::Block(
+        self.propose_block_checked(chain, txs, None)
+    }
+
+    // same as propose_block, txs not in mem_pool are also dropped
+    pub fn propose_block_from_pool(
+        &self,
+        chain: &MutexGuard<Blockchain>,
+        txs: Vec<Transaction>,
+        mem_pool: &TxPool,
+    ) -> Result<Block, CoreError> {
+        self.propose_block_checked(chain, txs, Some(mem_pool))
+    }
+
+    fn propose_block_checked(
+        &self,
+        chain: &MutexGuard<Blockchain>,
+        txs: Vec<Transaction>,
+        mem_pool: Option<&TxPool>,
+    ) -> Result<Block, CoreError> {
+        let txs = Self::proposal_txs(txs, mem_pool);
+
+        // capture tip once, the block is built on top of this tip
+        // and re-checked in add_proposed_block before being added
+        let tip = chain.tip_header().ok_or(CoreError::Block(
             "unable to get last block from chain".to_string(),
         ))?;
-        let last_header = last_block.header();
-        let prev_blockhash = last_header.hash();
+        let prev_blockhash = tip.hash().clone();
+
+        // one failing tx must not stop the rest from being included,
+        // failed txs are dropped
+        let (mut txs, failed) = self.runtime.split_executable(txs, chain.state())?;
+        for (tx, e) in failed {
+            let hash = tx.hash.map(|hash| hash.to_string()).unwrap_or_default();
+            warn!("excluding transaction: {hash} from proposed block, {e}");
+        }
 
         self.insert_reward_txs(prev_blockhash, &mut txs)?;
 
-        let height = last_header.height() + 1;
+        let height = tip.height() + 1;
         let poh = Header::gen_poh(&txs)?;
         let tx_root = Header::gen_tx_root(&txs)?;
 
-        // get state
-        let state = chain.state();
-        // execute each tx and backup each account
-        for tx in &txs {
-            // TODO: handle tx error case
-            let exec_data = RuntimeExecData::new_with_backup(tx, state);
-            self.runtime.execute(exec_data)?;
-        }
-        // calc new state_root after txs are applied
-        let state_root = state.gen_state_root()?;
-        // revert state after calculating state_root
-        state.rollback()?;
+        // state root after txs are applied, state is not changed
+        let state_root = self.runtime.dry_run_block(&txs, chain.state())?;
 
         let blockhash = Header::gen_blockhash(height, prev_blockhash, poh, tx_root, state_root)?;
 
@@ -144,9 +538,105 @@ impl BlockValidator {
             warn!("unable to sign block in miner: {e}")
         }
 
+        self.counters.proposed.fetch_add(1, Ordering::Relaxed);
+
         Ok(block)
     }
 
+    // take txs from the mem_pool, propose a block on the chain tip and
+    // add it, txs included in the block are confirmed in the mem_pool,
+    // txs are returned to the mem_pool if no block is added
+    pub fn produce_block(
+        &self,
+        chain: &mut MutexGuard<Blockchain>,
+        mem_pool: &mut TxPool,
+    ) -> Result<Block, CoreError> {
+        let txs = mem_pool.take(self.pool_size);
+        match self.produce_block_with(chain, txs.clone()) {
+            Ok(block) => {
+                mem_pool.confirm(block.txs());
+                Ok(block)
+            }
+            Err(e) => {
+                mem_pool.restore(txs);
+                Err(e)
+            }
+        }
+    }
+
+    // propose a block of txs on the chain tip and add it
+    pub fn produce_block_with(
+        &self,
+        chain: &mut MutexGuard<Blockchain>,
+        txs: Vec<Transaction>,
+    ) -> Result<Block, CoreError> {
+        let block = self.propose_block(chain, txs)?;
+        self.add_proposed_block(chain, block.clone())?;
+        Ok(block)
+    }
+
+    // add a block created by propose_block, the proposal is discarded
+    // if the chain tip has moved since the block was proposed
+    pub fn add_proposed_block(
+        &self,
+        chain: &mut MutexGuard<Blockchain>,
+        block: Block,
+    ) -> Result<(), CoreError> {
+        let res = Self::add_to_tip(chain, block);
+        match res {
+            Ok(()) => self.counters.accepted.fetch_add(1, Ordering::Relaxed),
+            Err(_) => self.counters.rejected.fetch_add(1, Ordering::Relaxed),
+        };
+        res
+    }
+
+    fn add_to_tip(chain: &mut MutexGuard<Blockchain>, block: Block) -> Result<(), CoreError> {
+        let tip = chain.tip().ok_or(CoreError::Block(
+            "unable to get last block from chain".to_string(),
+        ))?;
+
+        if tip.hash != block.header().prev_hash() || tip.height + 1 != block.height() {
+            return Err(CoreError::Block(format!(
+                "stale block proposal, chain tip moved to height: {} hash: {}",
+                tip.height, tip.hash
+            )));
+        }
+
+        chain.add_block(block)
+    }
+
+    // txs a caller may put in a proposal, reward txs are only ever
+    // created by insert_reward_txs, unsigned or badly signed txs and
+    // repeats of an earlier tx are dropped
+    fn proposal_txs(txs: Vec<Transaction>, mem_pool: Option<&TxPool>) -> Vec<Transaction> {
+        let mut seen = HashSet::new();
+
+        txs.into_iter()
+            .filter(|tx| {
+                let reason = if matches!(tx.tx_type, TxType::BlockReward | TxType::GasReward) {
+                    Some("reward transactions are created by the proposer".to_string())
+                } else if let Err(e) = tx.verify() {
+                    Some(e.to_string())
+                } else if !seen.insert(tx.hash) {
+                    Some("duplicate of an earlier transaction".to_string())
+                } else if mem_pool.is_some_and(|pool| !pool.has(tx)) {
+                    Some("transaction is not in the mem_pool".to_string())
+                } else {
+                    None
+                };
+
+                match reason {
+                    Some(reason) => {
+                        let hash = tx.hash.map(|hash| hash.to_string()).unwrap_or_default();
+                        warn!("dropping transaction: {hash} from proposed block, {reason}");
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
     fn insert_reward_txs(
         &self,
         prev_blockhash: Hash,
@@ -154,7 +644,7 @@ impl BlockValidator {
     ) -> Result<(), CoreError> {
         // Calculate the block reward and gas fees
         let block_reward = self.calculate_block_reward();
-        let gas_fees = self.collect_gas_fees(&txs);
+        let gas_fees = self.fees_after_burn(self.collect_gas_fees(&txs));
 
         // Create reward and fee transactions
         let reward_tx =
@@ -168,15 +658,75 @@ impl BlockValidator {
         Ok(())
     }
 
+    // reward and fee txs must pay the validator which signed the block
+    fn validate_reward_recipients(block: &Block) -> Result<(), CoreError> {
+        let rewards: Vec<&Transaction> = block
+            .txs()
+            .iter()
+            .filter(|tx| matches!(tx.tx_type, TxType::BlockReward | TxType::GasReward))
+            .collect();
+        if rewards.is_empty() {
+            return Ok(());
+        }
+
+        let signer = block
+            .signer()
+            .ok_or_else(|| CoreError::Block("block has no signer".to_string()))?;
+        let address = PublicKey::from_bytes(&signer.to_bytes()?)?.address()?;
+
+        for tx in rewards {
+            let data = BlockRewardData::from_bytes(&tx.data)?;
+            if data.to != address {
+                return Err(CoreError::Block(format!(
+                    "reward recipient: {} is not the block signer: {}",
+                    data.to.to_hex()?,
+                    address.to_hex()?
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // gas reward must pay the fees collected in the block less the burn
+    fn validate_fee_reward(&self, block: &Block) -> Result<(), CoreError> {
+        let expected = self.fees_after_burn(self.collect_gas_fees(block.txs()));
+
+        for tx in block
+            .txs()
+            .iter()
+            .filter(|tx| matches!(tx.tx_type, TxType::GasReward))
+        {
+            let data = BlockRewardData::from_bytes(&tx.data)?;
+            if data.amount != expected {
+                return Err(CoreError::Block(format!(
+                    "gas reward: {} does not match fees after burn: {expected}",
+                    data.amount
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // burned portion is rounded down so the producer never
+    // receives less than fees * (1 - burn_ratio)
+    fn fees_after_burn(&self, fees: u64) -> u64 {
+        let burned = (fees as f64 * self.burn_ratio) as u64;
+        fees - burned.min(fees)
+    }
+
     fn calculate_block_reward(&self) -> u64 {
         // Define how to calculate the block reward
         50 // Example reward value
     }
 
+    // only gas used is paid as fees at each tx's gas price, the fee for
+    // unused gas is refunded to the sender
     fn collect_gas_fees(&self, txs: &[Transaction]) -> u64 {
-        let mut total_fees = 0;
+        let mut total_fees: u64 = 0;
         for tx in txs {
-            total_fees += tx.gas_limit; // Assuming Transaction struct has a `gas_fee` field
+            total_fees = total_fees.saturating_add(tx.fee(self.runtime.gas_used(tx)));
         }
         total_fees
     }
@@ -206,18 +756,29 @@ impl BlockValidator {
     }
 }
 
+fn build_verify_pool(threads: usize) -> Result<ThreadPool, CoreError> {
+    ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("verify-{i}"))
+        .build()
+        .map_err(|e| CoreError::Block(format!("unable to build verify thread pool, {e}")))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::block::Block;
     use crate::core::blockchain::Blockchain;
+    use crate::core::genesis::GenesisConfig;
     use crate::core::header::Header;
     use crate::core::transaction::{random_signed_tx, Transaction, TransferData};
     use crate::crypto::address::Address;
     use crate::crypto::hash::Hash;
     use crate::crypto::private_key::{self, PrivateKey};
     use crate::crypto::utils::random_hash;
+    use crate::crypto::{public_key::PublicKeyBytes, signature::SignatureBytes};
     use crate::state::account::Account;
+    use crate::vm::runtime::TRANSFER_GAS_COST;
     use std::sync::{Arc, Mutex};
     use std::time::Duration;
 
@@ -229,7 +790,8 @@ mod tests {
     fn build_tx(pvt_key: &PrivateKey) -> Transaction {
         let receiver = PrivateKey::new().address();
         let sender = pvt_key.address();
-        let r_hash = random_hash();
+        // anchored to the genesis block of setup_blockchain
+        let r_hash = GenesisConfig::default().header().unwrap().blockhash;
         let bytes = TransferData {
             to: receiver.clone(),
             from: sender.clone(),
@@ -266,6 +828,391 @@ mod tests {
         assert!(result.is_ok(), "Block should be valid");
     }
 
+    #[test]
+    fn test_validate_signature_schemes() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let mut validator = BlockValidator::new(private_key.clone(), 10);
+
+        let chain = blockchain.lock().unwrap();
+        chain
+            .state()
+            .set_account(&private_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        // secp256k1 is allowed by default
+        let txs = vec![build_tx(&private_key)];
+        let block = validator.propose_block(&chain, txs).unwrap();
+        assert!(validator.validate_block(&chain, &block).is_ok());
+
+        validator.set_allowed_schemes(vec![SignatureScheme::Ed25519]);
+        match validator.validate_block(&chain, &block) {
+            Err(CoreError::Block(msg)) => assert!(msg.contains("is not allowed"), "{msg}"),
+            other => panic!("expected scheme error, got {other:?}"),
+        }
+    }
+
+    // build a block without Block::new verifying transaction signatures,
+    // as a block decoded from a sync source would be
+    fn unverified_block(
+        chain: &MutexGuard<Blockchain>,
+        txs: Vec<Transaction>,
+        private_key: &PrivateKey,
+    ) -> Block {
+        #[derive(borsh::BorshSerialize)]
+        struct RawBlock {
+            header: Header,
+            signer: Option<PublicKeyBytes>,
+            signature: Option<SignatureBytes>,
+            transactions: Vec<Transaction>,
+        }
+
+        let tip = chain.tip().unwrap();
+        let state_root = ValidatorRuntime::new()
+            .dry_run_block(&txs, chain.state())
+            .unwrap();
+
+        let height = tip.height + 1;
+        let poh = Header::gen_poh(&txs).unwrap();
+        let tx_root = Header::gen_tx_root(&txs).unwrap();
+        let blockhash = Header::gen_blockhash(height, tip.hash, poh, tx_root, state_root).unwrap();
+        let header = Header::new(height, blockhash, poh, tx_root, state_root, tip.hash);
+
+        let raw = RawBlock {
+            header,
+            signer: None,
+            signature: None,
+            transactions: txs,
+        };
+        let mut block = Block::from_bytes(&borsh::to_vec(&raw).unwrap()).unwrap();
+        block.sign(private_key).unwrap();
+        block
+    }
+
+    #[test]
+    fn test_validate_block_trust_mode() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let mut validator = BlockValidator::new(private_key.clone(), 10);
+
+        let chain = blockchain.lock().unwrap();
+        chain
+            .state()
+            .set_account(&private_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        // changing a signed field invalidates the tx signature
+        let mut tx = build_tx(&private_key);
+        tx.nonce = 9;
+        let block = unverified_block(&chain, vec![tx], &private_key);
+
+        let trusted: SocketAddr = "127.0.0.1:6000".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:6001".parse().unwrap();
+
+        // normal mode verifies tx signatures
+        assert!(validator
+            .validate_block_from(&chain, &block, BlockSource::Sync(trusted))
+            .is_err());
+
+        validator.set_trust_mode(Some(trusted));
+        assert!(validator
+            .validate_block_from(&chain, &block, BlockSource::Sync(trusted))
+            .is_ok());
+
+        // trust mode never applies to other sources or peer blocks
+        assert!(validator
+            .validate_block_from(&chain, &block, BlockSource::Sync(other))
+            .is_err());
+        assert!(validator.validate_block(&chain, &block).is_err());
+
+        // roots are still checked in trust mode
+        let mut tampered = validator
+            .propose_block(&chain, vec![build_tx(&private_key)])
+            .unwrap();
+        tampered.header.tx_root = random_hash();
+        assert!(validator
+            .validate_block_from(&chain, &tampered, BlockSource::Sync(trusted))
+            .is_err());
+    }
+
+    #[test]
+    fn test_validate_block_on_other_validator() {
+        let proposer_key = PrivateKey::new();
+        let proposer = BlockValidator::new(proposer_key.clone(), 10);
+        let mut other = BlockValidator::new(PrivateKey::new(), 10);
+        other.add_validator(proposer_key.address());
+
+        // both nodes start from the same genesis and state
+        let proposer_chain = setup_blockchain();
+        let other_chain = setup_blockchain();
+        for chain in [&proposer_chain, &other_chain] {
+            chain
+                .lock()
+                .unwrap()
+                .state()
+                .set_account(&proposer_key.address(), &Account { balance: 100 })
+                .unwrap();
+        }
+
+        let block = proposer
+            .propose_block(
+                &proposer_chain.lock().unwrap(),
+                vec![build_tx(&proposer_key)],
+            )
+            .unwrap();
+
+        // rewards are applied the same way on every node
+        let chain = other_chain.lock().unwrap();
+        assert!(other.validate_block(&chain, &block).is_ok());
+
+        // same validator address on another node
+        let same = BlockValidator::new(proposer_key.clone(), 10);
+        assert!(same.validate_block(&chain, &block).is_ok());
+    }
+
+    #[test]
+    fn test_validate_block_reward_recipient() {
+        let blockchain = setup_blockchain();
+        let proposer_key = PrivateKey::new();
+        let proposer = BlockValidator::new(proposer_key.clone(), 10);
+        let thief = BlockValidator::new(PrivateKey::new(), 10);
+
+        let chain = blockchain.lock().unwrap();
+        chain
+            .state()
+            .set_account(&proposer_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        let block = proposer
+            .propose_block(&chain, vec![build_tx(&proposer_key)])
+            .unwrap();
+
+        // block reward paid to another address
+        let mut txs = block.txs().to_vec();
+        txs[0] = thief
+            .create_reward_transaction(TxType::BlockReward, block.header().prev_hash(), 50)
+            .unwrap();
+
+        // state root from the original proposal no longer matches
+        let mut stale = unverified_block(&chain, txs.clone(), &proposer_key);
+        stale.header.state_root = block.header().state_root;
+        let err = proposer.validate_block(&chain, &stale).unwrap_err();
+        assert!(err.to_string().contains("State root is invalid"), "{err}");
+
+        // state root matches the rewards but they do not go to the signer
+        let block = unverified_block(&chain, txs, &proposer_key);
+        let err = proposer.validate_block(&chain, &block).unwrap_err();
+        assert!(err.to_string().contains("is not the block signer"), "{err}");
+    }
+
+    #[test]
+    fn test_verify_block_with_verify_threads() {
+        let private_key = PrivateKey::new();
+        let mut validator = BlockValidator::new(private_key.clone(), 10);
+        validator.set_verify_threads(2).unwrap();
+        assert_eq!(validator.verify_threads(), 2);
+
+        let txs: Vec<Transaction> = (0..32).map(|_| random_signed_tx()).collect();
+        let mut block = Block::new(random_header(1, random_hash()), txs).unwrap();
+        block.sign(&private_key).unwrap();
+
+        assert_eq!(block.num_txs(), 32);
+        assert!(validator.verify_block(&block).is_ok());
+    }
+
+    #[test]
+    fn test_gas_reward_only_used_gas() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let mut chain = blockchain.lock().unwrap();
+        let sender = PrivateKey::new();
+        let state = chain.state();
+        state
+            .set_account(&private_key.address(), &Account { balance: 0 })
+            .unwrap();
+        state
+            .set_account(&sender.address(), &Account { balance: 100 })
+            .unwrap();
+
+        // build_tx transfers 42 with a gas limit of 3
+        let tx = build_tx(&sender);
+        let receiver = TransferData::from_bytes(&tx.data).unwrap().to;
+        state
+            .set_account(&receiver, &Account { balance: 0 })
+            .unwrap();
+
+        let block = validator.propose_block(&chain, vec![tx]).unwrap();
+
+        let fee_tx = &block.txs()[1];
+        assert!(matches!(fee_tx.tx_type, TxType::GasReward));
+        let fee = BlockRewardData::from_bytes(&fee_tx.data).unwrap();
+        assert_eq!(fee.amount, TRANSFER_GAS_COST);
+
+        chain.commit_block(block).unwrap();
+
+        let state = chain.state();
+        let balance = |address: &Address| state.get_account(address).unwrap().balance;
+        assert_eq!(balance(&sender.address()), 100 - 42 - TRANSFER_GAS_COST);
+        assert_eq!(balance(&receiver), 42);
+        assert_eq!(balance(&private_key.address()), 50 + TRANSFER_GAS_COST);
+    }
+
+    #[test]
+    fn test_gas_fee_uses_gas_price() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let mut chain = blockchain.lock().unwrap();
+        let sender = PrivateKey::new();
+        let state = chain.state();
+        state
+            .set_account(&private_key.address(), &Account { balance: 0 })
+            .unwrap();
+        state
+            .set_account(&sender.address(), &Account { balance: 100 })
+            .unwrap();
+
+        // transfers 42 with a gas limit of 3 at 4 per unit of gas
+        let mut tx = build_tx(&sender);
+        tx.gas_price = 4;
+        tx.signature = None;
+        tx.signer = None;
+        tx.sign(&sender).unwrap();
+        let receiver = TransferData::from_bytes(&tx.data).unwrap().to;
+        state
+            .set_account(&receiver, &Account { balance: 0 })
+            .unwrap();
+
+        let block = validator.propose_block(&chain, vec![tx]).unwrap();
+
+        let fee = BlockRewardData::from_bytes(&block.txs()[1].data).unwrap();
+        assert_eq!(fee.amount, TRANSFER_GAS_COST * 4);
+
+        chain.commit_block(block).unwrap();
+
+        // only the fee for gas used is kept, the rest of the limit is refunded
+        let state = chain.state();
+        let balance = |address: &Address| state.get_account(address).unwrap().balance;
+        assert_eq!(balance(&sender.address()), 100 - 42 - TRANSFER_GAS_COST * 4);
+        assert_eq!(balance(&private_key.address()), 50 + TRANSFER_GAS_COST * 4);
+    }
+
+    #[test]
+    fn test_gas_fee_burn() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let mut validator = BlockValidator::new(private_key.clone(), 10);
+        validator.set_burn_ratio(0.5).unwrap();
+        assert!(validator.set_burn_ratio(1.5).is_err());
+
+        let mut chain = blockchain.lock().unwrap();
+        let senders: Vec<PrivateKey> = (0..4).map(|_| PrivateKey::new()).collect();
+        for sender in &senders {
+            chain
+                .state()
+                .set_account(&sender.address(), &Account { balance: 100 })
+                .unwrap();
+        }
+        let supply = |chain: &Blockchain| -> u64 {
+            chain
+                .state()
+                .accounts_page(0, usize::MAX)
+                .unwrap()
+                .iter()
+                .map(|(_, account)| account.balance)
+                .sum()
+        };
+        let txs: Vec<Transaction> = senders.iter().map(build_tx).collect();
+        let receivers = txs
+            .iter()
+            .map(|tx| TransferData::from_bytes(&tx.data).unwrap().to);
+        for address in receivers.chain([private_key.address()]) {
+            chain
+                .state()
+                .set_account(&address, &Account { balance: 0 })
+                .unwrap();
+        }
+        let supply_before = supply(&chain);
+
+        let block = validator.propose_block(&chain, txs).unwrap();
+
+        let fees = 4 * TRANSFER_GAS_COST;
+        let paid = BlockRewardData::from_bytes(&block.txs()[1].data)
+            .unwrap()
+            .amount;
+        assert_eq!(paid, (fees as f64 * (1.0 - 0.5)) as u64);
+
+        // validators with another burn ratio reject the gas reward
+        let other = BlockValidator::new(private_key.clone(), 10);
+        let err = other.validate_block(&chain, &block).unwrap_err();
+        assert!(err.to_string().contains("does not match fees after burn"));
+        assert!(validator.validate_block(&chain, &block).is_ok());
+
+        chain.commit_block(block).unwrap();
+
+        let burned = fees - paid;
+        assert_eq!(
+            chain
+                .state()
+                .get_account(&private_key.address())
+                .unwrap()
+                .balance,
+            50 + paid
+        );
+        assert_eq!(supply(&chain), supply_before + 50 - burned);
+    }
+
+    #[test]
+    fn test_validate_block_empty_txs() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+        let chain = blockchain.lock().unwrap();
+
+        // roots match an empty tx list but the reward txs are missing
+        let block = unverified_block(&chain, vec![], &private_key);
+        assert_eq!(block.header().poh, Header::empty_poh().unwrap());
+        assert_eq!(block.header().tx_root, Header::empty_tx_root().unwrap());
+        let err = validator.validate_block(&chain, &block).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("only the genesis block may be empty"));
+
+        let genesis = Block::genesis(&GenesisConfig::default()).unwrap();
+        let err = validator.validate_block(&chain, &genesis).unwrap_err();
+        assert!(err.to_string().contains("genesis config"), "{err}");
+
+        let block = validator.propose_block(&chain, vec![]).unwrap();
+        assert_eq!(block.num_txs(), 2);
+        assert!(validator.validate_block(&chain, &block).is_ok());
+    }
+
+    #[test]
+    fn test_validate_block_unknown_tx_blockhash() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let chain = blockchain.lock().unwrap();
+        chain
+            .state()
+            .set_account(&private_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        let mut tx = build_tx(&private_key);
+        tx.blockhash = random_hash();
+        tx.signature = None;
+        tx.signer = None;
+        tx.sign(&private_key).unwrap();
+
+        let block = validator.propose_block(&chain, vec![tx]).unwrap();
+        let err = validator.validate_block(&chain, &block).unwrap_err();
+        assert!(err.to_string().contains("is not a recent block"));
+    }
+
     #[test]
     fn test_validate_block_failure_duplicate() {
         let blockchain = setup_blockchain();
@@ -289,6 +1236,266 @@ mod tests {
         assert!(result.is_err(), "Block should be rejected as duplicate");
     }
 
+    #[test]
+    fn test_propose_block_drops_duplicate_tx() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let chain = blockchain.lock().unwrap();
+
+        let state = chain.state();
+        state
+            .set_account(&private_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        let tx = build_tx(&private_key);
+        let block = validator
+            .propose_block(&chain, vec![tx.clone(), tx.clone()])
+            .unwrap();
+        assert_eq!(block.num_txs(), 3);
+        assert!(Block::check_duplicate_txs(block.txs()).is_ok());
+        assert_eq!(block.txs()[2], tx);
+    }
+
+    #[test]
+    fn test_propose_block_excludes_failing_txs() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let mut chain = blockchain.lock().unwrap();
+
+        let rich = PrivateKey::new();
+        let poor = PrivateKey::new();
+        let state = chain.state();
+        state
+            .set_account(&rich.address(), &Account { balance: 50 })
+            .unwrap();
+        state
+            .set_account(&poor.address(), &Account { balance: 10 })
+            .unwrap();
+
+        // build_tx transfers 42 with a gas limit of 3, the second
+        // transfer from rich is over balance after the first
+        let valid = build_tx(&rich);
+        let txs = vec![
+            build_tx(&poor),
+            valid.clone(),
+            build_tx(&rich),
+            build_tx(&poor),
+            // sender has no account
+            build_tx(&PrivateKey::new()),
+        ];
+
+        let block = validator.propose_block(&chain, txs).unwrap();
+
+        // reward and fee txs then the only valid transfer
+        assert_eq!(block.num_txs(), 3);
+        assert_eq!(block.txs()[2], valid);
+        assert!(validator.validate_block(&chain, &block).is_ok());
+
+        // every tx kept also executes when the block is committed, the
+        // receiver of the transfer has no account before it
+        let receiver = TransferData::from_bytes(&valid.data).unwrap().to;
+        assert!(chain.state().get_account(&receiver).is_none());
+        chain.commit_block(block).unwrap();
+        assert_eq!(chain.height(), BlockHeight(1));
+        assert_eq!(chain.state().get_account(&receiver).unwrap().balance, 42);
+        assert_eq!(
+            chain.state().get_account(&rich.address()).unwrap().balance,
+            50 - 42 - TRANSFER_GAS_COST
+        );
+    }
+
+    #[test]
+    fn test_export_import_chain() {
+        let source = setup_blockchain();
+        let key = PrivateKey::new();
+        let validator = BlockValidator::new(key.clone(), 10);
+        let sender = PrivateKey::new();
+        let fund = |chain: &Blockchain| {
+            chain
+                .state()
+                .set_account(&sender.address(), &Account { balance: 1_000 })
+                .unwrap();
+        };
+
+        let mut chain = source.lock().unwrap();
+        fund(&chain);
+        for _ in 0..10 {
+            let block = validator
+                .propose_block(&chain, vec![build_tx(&sender)])
+                .unwrap();
+            chain.commit_block(block).unwrap();
+        }
+
+        let mut buf = vec![];
+        assert_eq!(chain.export_to_writer(&mut buf).unwrap(), 11);
+
+        let fresh = || {
+            let chain = setup_blockchain();
+            fund(&chain.lock().unwrap());
+            chain
+        };
+        let mut importer = BlockValidator::new(PrivateKey::new(), 10);
+
+        // blocks from a signer outside the validator set are rejected
+        let target = fresh();
+        let mut imported = target.lock().unwrap();
+        assert!(importer
+            .import_from_reader(&mut imported, &mut buf.as_slice())
+            .is_err());
+        assert_eq!(imported.height(), BlockHeight::GENESIS);
+
+        importer.add_validator(key.address());
+        assert_eq!(
+            importer
+                .import_from_reader(&mut imported, &mut buf.as_slice())
+                .unwrap(),
+            10
+        );
+        assert_eq!(imported.tip(), chain.tip());
+        assert_eq!(
+            imported.state().gen_state_root().unwrap(),
+            chain.state().gen_state_root().unwrap()
+        );
+
+        // only fresh chains accept an import
+        assert!(importer
+            .import_from_reader(&mut imported, &mut buf.as_slice())
+            .is_err());
+
+        // blocks are validated against the importing chain state
+        let unfunded = setup_blockchain();
+        let mut unfunded = unfunded.lock().unwrap();
+        let res = importer.import_from_reader(&mut unfunded, &mut buf.as_slice());
+        assert!(res.is_err());
+        assert_eq!(unfunded.height(), BlockHeight::GENESIS);
+
+        // a truncated stream is rejected
+        let target = fresh();
+        let res =
+            importer.import_from_reader(&mut target.lock().unwrap(), &mut &buf[..buf.len() - 1]);
+        assert!(matches!(res, Err(CoreError::Parsing(_))));
+
+        // a length prefix above the max block size is rejected
+        let genesis_len = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+        let mut oversized = buf[..4 + genesis_len].to_vec();
+        oversized.extend_from_slice(&u32::MAX.to_le_bytes());
+        let target = fresh();
+        let res =
+            importer.import_from_reader(&mut target.lock().unwrap(), &mut oversized.as_slice());
+        assert!(matches!(res, Err(CoreError::Parsing(_))));
+    }
+
+    #[test]
+    fn test_propose_block_unsigned_tx() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let chain = blockchain.lock().unwrap();
+        chain
+            .state()
+            .set_account(&private_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        let signed = build_tx(&private_key);
+        let mut unsigned = build_tx(&private_key);
+        unsigned.hash = None;
+        unsigned.signature = None;
+
+        // unsigned tx is dropped, reward txs come before the signed tx
+        let block = validator
+            .propose_block(&chain, vec![signed.clone(), unsigned])
+            .unwrap();
+        assert_eq!(block.num_txs(), 3);
+        assert_eq!(block.txs()[2], signed);
+    }
+
+    #[test]
+    fn test_propose_block_from_pool() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let chain = blockchain.lock().unwrap();
+        chain
+            .state()
+            .set_account(&private_key.address(), &Account { balance: 1_000 })
+            .unwrap();
+
+        let pooled = build_tx(&private_key);
+        let mut mem_pool = TxPool::new();
+        mem_pool.add(pooled.clone());
+
+        let foreign = build_tx(&private_key);
+        let mut unsigned = build_tx(&private_key);
+        unsigned.signature = None;
+        let mut tampered = build_tx(&private_key);
+        tampered.data = TransferData {
+            from: private_key.address(),
+            to: PrivateKey::new().address(),
+            amount: 1,
+        }
+        .to_bytes()
+        .unwrap();
+        let mut reward = build_tx(&private_key);
+        reward.tx_type = TxType::BlockReward;
+
+        let txs = vec![
+            pooled.clone(),
+            foreign.clone(),
+            unsigned.clone(),
+            tampered.clone(),
+            pooled.clone(),
+            reward.clone(),
+        ];
+        let block = validator
+            .propose_block_from_pool(&chain, txs.clone(), &mem_pool)
+            .unwrap();
+
+        // reward and fee txs then the pooled tx, once
+        assert_eq!(block.num_txs(), 3);
+        assert_eq!(block.txs()[2], pooled);
+
+        // without a mem_pool only signed, unique txs are kept
+        let block = validator.propose_block(&chain, txs).unwrap();
+        assert_eq!(block.num_txs(), 4);
+        assert_eq!(block.txs()[2..], [pooled, foreign]);
+        assert!(validator.validate_block(&chain, &block).is_ok());
+    }
+
+    #[test]
+    fn test_add_proposed_block_stale_tip() {
+        let blockchain = setup_blockchain();
+        let private_key = PrivateKey::new();
+        let validator = BlockValidator::new(private_key.clone(), 10);
+
+        let mut chain = blockchain.lock().unwrap();
+
+        let state = chain.state();
+        state
+            .set_account(&private_key.address(), &Account { balance: 100 })
+            .unwrap();
+
+        let stale = validator
+            .propose_block(&chain, vec![build_tx(&private_key)])
+            .unwrap();
+
+        // another block is added before the proposal is committed
+        let competing = validator.propose_block(&chain, vec![]).unwrap();
+        validator
+            .add_proposed_block(&mut chain, competing.clone())
+            .unwrap();
+
+        let result = validator.add_proposed_block(&mut chain, stale);
+        assert!(result.is_err(), "Stale proposal should be discarded");
+        assert_eq!(chain.height(), 1);
+        assert_eq!(chain.last_block().unwrap().hash(), competing.hash());
+    }
+
     #[test]
     fn test_propose_block_success() {
         let blockchain = setup_blockchain();
@@ -330,4 +1537,63 @@ mod tests {
         let block = result.unwrap();
         assert!(block.verify().is_ok(), "Block signature should be valid");
     }
+
+    #[test]
+    fn test_block_metrics() {
+        let blockchain = setup_blockchain();
+        let validator = BlockValidator::new(PrivateKey::new(), 10);
+        let mut chain = blockchain.lock().unwrap();
+
+        for _ in 0..3 {
+            let block = validator.propose_block(&chain, vec![]).unwrap();
+            validator.add_proposed_block(&mut chain, block).unwrap();
+        }
+
+        // both built on the same tip, only the first can be added
+        let first = validator.propose_block(&chain, vec![]).unwrap();
+        let second = validator.propose_block(&chain, vec![]).unwrap();
+        validator.add_proposed_block(&mut chain, first).unwrap();
+        assert!(validator.add_proposed_block(&mut chain, second).is_err());
+
+        // proposed but never added
+        validator.propose_block(&chain, vec![]).unwrap();
+
+        assert_eq!(
+            validator.metrics(),
+            BlockMetrics {
+                proposed: 6,
+                accepted: 4,
+                rejected: 1,
+            }
+        );
+        assert_eq!(chain.height(), 4);
+    }
+
+    #[test]
+    fn test_leader_selection() {
+        let blockchain = setup_blockchain();
+        let chain = blockchain.lock().unwrap();
+        let keys: Vec<PrivateKey> = (0..4).map(|_| PrivateKey::new()).collect();
+
+        // a lone validator always leads
+        let validator = BlockValidator::new(keys[0].clone(), 10);
+        assert!(validator.is_leader(&chain).unwrap());
+
+        // validators learning the set in a different order agree
+        let mut first = BlockValidator::new(keys[0].clone(), 10);
+        let mut second = BlockValidator::new(keys[3].clone(), 10);
+        for key in &keys {
+            first.add_validator(key.address());
+        }
+        for key in keys.iter().rev() {
+            second.add_validator(key.address());
+        }
+        let leader = first.leader_for(&chain, BlockHeight(1)).unwrap();
+        assert_eq!(second.leader_for(&chain, BlockHeight(1)).unwrap(), leader);
+        assert!(keys.iter().any(|key| key.address() == leader));
+        assert_eq!(
+            first.is_leader(&chain).unwrap(),
+            leader == keys[0].address()
+        );
+    }
 }